@@ -0,0 +1,25 @@
+//! Arbitrary text through the directive parsers, variable substitution and
+//! code fence validation, none of which may panic.
+//!
+//! Run with `cargo +nightly fuzz run directives` from the repository root.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use md2md::include_resolver::{
+    find_directives, parse_codesnippet_parameters, parse_include_parameters, process_variables,
+    validate_and_fix_code_fences,
+};
+use std::collections::HashMap;
+
+fuzz_target!(|text: &str| {
+    let _ = parse_include_parameters(text);
+    let _ = parse_codesnippet_parameters(text);
+    let _ = find_directives(text);
+
+    let variables = HashMap::from([("name".to_string(), text.to_string())]);
+    let _ = process_variables(text, &variables);
+
+    if let Ok(fixed) = validate_and_fix_code_fences(text, Some("text")) {
+        validate_and_fix_code_fences(&fixed, None).expect("Fixed code fences don't validate");
+    }
+});
@@ -0,0 +1,28 @@
+/*
+ * C interface to md2md, available when the library is built with `--features ffi`.
+ *
+ * Every function returns a NUL-terminated JSON document that must be released
+ * with md2md_free_string():
+ *   {"ok": true, "result": {...}}   or   {"ok": false, "error": "..."}
+ */
+#ifndef MD2MD_H
+#define MD2MD_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Processes a markdown file or directory. output_path may be NULL (defaults to "out"). */
+char *md2md_process_file(const char *source_path, const char *partials_path, const char *output_path);
+
+/* Resolves every directive in content, reading partials from partials_path. */
+char *md2md_process_string(const char *content, const char *partials_path);
+
+/* Releases a string returned by md2md. NULL is ignored. */
+void md2md_free_string(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* MD2MD_H */
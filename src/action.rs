@@ -1,5 +1,12 @@
-#[derive(Debug, Clone, PartialEq, Eq)]
+use crate::types::ProcessingEvent;
+use crossterm::event::KeyEvent;
+
+#[derive(Debug, Clone)]
 pub enum Action {
+    /// Progress of the background run, delivered as it happens so the UI redraws right away
+    Processing(ProcessingEvent),
+    /// A key press, mapped to an action by the app since it depends on its input mode
+    Key(KeyEvent),
     Tick,
     Render,
     Resize(u16, u16),
@@ -15,6 +22,16 @@ pub enum Action {
     PreviousTab,
     NextFile,
     PreviousFile,
+    PageUp,
+    PageDown,
+    ScrollToTop,
+    ScrollToBottom,
     ToggleErrorDetails,
-    GoToTab(u8), // For direct tab access with numbers 1-5
+    GoToTab(u8), // For direct tab access with numbers 1-8
+    StartFilter,
+    TogglePreview,
+    ToggleNode,
+    Retry,
+    OpenInEditor,
+    SaveReport,
 }
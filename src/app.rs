@@ -1,15 +1,28 @@
 use crate::action::Action;
-use crate::types::{ProcessingConfig, ProcessingSummary};
+use crate::error_code::ErrorCode;
+use crate::event::key_action;
+use crate::history::RunRecord;
+use crate::processor::{calculate_output_path, reprocess_file};
+use crate::report::{ReportFormat, default_report_path, write_report};
+use crate::types::{FileProcessResult, ProcessingConfig, ProcessingEvent, ProcessingSummary};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Instant, SystemTime};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ActiveTab {
     Progress,
     Files,
+    Tree,
     Analysis,
     Summary,
     ErrorSummary,
+    Warnings,
+    Watch,
 }
 
 impl ActiveTab {
@@ -17,13 +30,77 @@ impl ActiveTab {
         match self {
             ActiveTab::Progress => "Progress",
             ActiveTab::Files => "Files",
+            ActiveTab::Tree => "Tree",
             ActiveTab::Analysis => "Analysis",
             ActiveTab::Summary => "Summary",
             ActiveTab::ErrorSummary => "Error Summary",
+            ActiveTab::Warnings => "Warnings",
+            ActiveTab::Watch => "Watch",
         }
     }
 }
 
+/// Generated output of a file, shown in the Files tab
+#[derive(Debug, Clone)]
+pub struct Preview {
+    /// Path of the output file
+    pub path: String,
+    /// Its content, or why it can't be shown
+    pub content: String,
+}
+
+/// File to open in the editor, and the source file to process again afterwards
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditRequest {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+/// Batch of changed files seen in watch mode
+#[derive(Debug, Clone)]
+pub struct WatchedChange {
+    pub at: Instant,
+    pub run: usize,
+    pub paths: Vec<String>,
+    /// Source files processed again because of the change
+    pub affected: usize,
+}
+
+/// Watch mode state, shown in the Watch tab
+#[derive(Debug, Default)]
+pub struct WatchState {
+    /// Runs triggered by changes so far; the first run is run 0
+    pub runs: usize,
+    /// Is a run triggered by a change in progress?
+    pub rerunning: bool,
+    /// When the last run finished
+    pub last_run: Option<Instant>,
+    /// Run that last processed each source file
+    pub file_runs: HashMap<String, usize>,
+    /// Changes seen, most recent first
+    pub changes: VecDeque<WatchedChange>,
+}
+
+/// Changes kept in the feed of the Watch tab
+const MAX_WATCHED_CHANGES: usize = 100;
+
+/// Node of the Tree tab: a source file, or the include at an index of its results
+pub type TreeKey = (String, Option<usize>);
+
+/// Visible line of the Tree tab
+#[derive(Debug, Clone)]
+pub struct TreeRow<'a> {
+    pub key: TreeKey,
+    /// Indentation level; source files are at level 0
+    pub level: usize,
+    pub path: &'a str,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Does the node have nested includes?
+    pub expandable: bool,
+    pub expanded: bool,
+}
+
 /// Application state
 #[derive(Debug)]
 pub struct App {
@@ -41,14 +118,50 @@ pub struct App {
     pub start_time: Instant,
     /// Completion time of processing
     pub completion_time: Option<Instant>,
-    /// Selected file index for navigation
+    /// Selected file index for navigation, among the files matching `filter`
     pub selected_file_index: usize,
+    /// Selected include row of the selected file in the Files tab; `None` when the file row is selected
+    pub selected_include: Option<usize>,
+    /// Is the detail popup of the selected include open?
+    pub include_details: bool,
+    /// Fuzzy filter of the Files tab, matched against file and include paths
+    pub filter: String,
+    /// Is the filter input taking the key presses?
+    pub filter_editing: bool,
+    /// Output of the selected file, while its preview is open
+    pub preview: Option<Preview>,
+    /// Selected row of the Tree tab
+    pub tree_selected: usize,
+    /// First visible row of the Tree tab, kept between frames
+    pub tree_offset: Cell<usize>,
+    /// Tree nodes whose includes are hidden
+    pub collapsed: HashSet<TreeKey>,
+    /// File waiting to be opened in the editor by the event loop
+    pub edit_request: Option<EditRequest>,
+    /// First visible row of the Files list, kept between frames
+    pub files_offset: Cell<usize>,
+    /// Scroll offset of the text panes of the Analysis and Error Summary tabs
+    pub scroll_offset: usize,
+    /// Largest useful `scroll_offset`, as of the last frame
+    pub max_scroll: Cell<usize>,
+    /// Rows visible in the scrollable pane, as of the last frame
+    pub page_height: Cell<usize>,
     /// Are error details visible?
     pub error_details_visible: bool,
     /// Has the app switched to the final tab after completion?
     pub switched_to_final_tab: bool,
     /// Is help dialog visible?
     pub help_visible: bool,
+    /// Watch mode state; `None` when the app exits after one run
+    pub watch: Option<WatchState>,
+    /// Results of the previous run of the profile, compared against in the Summary tab
+    pub previous_run: Option<RunRecord>,
+    /// Path typed into the save-report prompt, while it is open
+    pub report_path: Option<String>,
+    /// Outcome of the last key action, shown in the footer until the next key press
+    pub message: Option<String>,
+    /// Archive the outputs went into, which can't take retried files once it is finished
+    pub archive: Option<PathBuf>,
 }
 
 impl App {
@@ -63,9 +176,27 @@ impl App {
             start_time: Instant::now(),
             completion_time: None,
             selected_file_index: 0,
+            selected_include: None,
+            include_details: false,
+            filter: String::new(),
+            filter_editing: false,
+            preview: None,
+            tree_selected: 0,
+            tree_offset: Cell::new(0),
+            collapsed: HashSet::new(),
+            edit_request: None,
+            files_offset: Cell::new(0),
+            scroll_offset: 0,
+            max_scroll: Cell::new(0),
+            page_height: Cell::new(1),
             error_details_visible: false,
             switched_to_final_tab: false,
             help_visible: false,
+            watch: None,
+            previous_run: None,
+            report_path: None,
+            message: None,
+            archive: None,
         }
     }
 
@@ -73,14 +204,11 @@ impl App {
     pub fn tick(&mut self) {
         // Check if processing is complete
         if !self.processing_complete {
-            let should_mark_complete = {
-                let summary_guard = self
-                    .summary
-                    .lock()
-                    .expect("Failed to acquire summary lock for completion check");
-                summary_guard.total_files > 0
-                    && summary_guard.processed_files >= summary_guard.total_files
-            };
+            let should_mark_complete = self
+                .summary
+                .lock()
+                .expect("Failed to acquire summary lock for completion check")
+                .complete;
 
             if should_mark_complete {
                 self.mark_processing_complete();
@@ -88,6 +216,47 @@ impl App {
         }
     }
 
+    /// Records an event from the background processing thread
+    pub fn handle_processing_event(&mut self, event: &ProcessingEvent) {
+        self.summary
+            .lock()
+            .expect("Failed to acquire summary lock for processing event")
+            .apply(event);
+
+        if matches!(event, ProcessingEvent::RunFinished { .. }) && !self.processing_complete {
+            self.mark_processing_complete();
+        }
+
+        if let Some(watch) = &mut self.watch {
+            match event {
+                ProcessingEvent::RunFinished { .. } => watch.last_run = Some(Instant::now()),
+                ProcessingEvent::FilesChanged {
+                    run,
+                    paths,
+                    affected,
+                } => {
+                    watch.rerunning = true;
+                    watch.changes.push_front(WatchedChange {
+                        at: Instant::now(),
+                        run: *run,
+                        paths: paths.clone(),
+                        affected: affected.len(),
+                    });
+                    watch.changes.truncate(MAX_WATCHED_CHANGES);
+                }
+                ProcessingEvent::Rerun { run, results, .. } => {
+                    watch.runs = *run;
+                    watch.rerunning = false;
+                    watch.last_run = Some(Instant::now());
+                    for result in results {
+                        watch.file_runs.insert(result.file_path.clone(), *run);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
         self.running = false;
@@ -105,7 +274,9 @@ impl App {
             .expect("Failed to acquire summary lock for error check");
         let has_errors = summary.get_failed_count() > 0 || summary.get_failed_includes() > 0;
 
-        if has_errors {
+        if self.watch.is_some() {
+            self.active_tab = ActiveTab::Watch;
+        } else if has_errors {
             self.active_tab = ActiveTab::ErrorSummary;
         } else {
             self.active_tab = ActiveTab::Summary;
@@ -119,20 +290,35 @@ impl App {
             .lock()
             .expect("Failed to acquire summary lock for next tab navigation");
         let has_errors = summary.get_failed_count() > 0 || summary.get_failed_includes() > 0;
+        let has_warnings = !summary.warnings.is_empty();
         drop(summary);
 
         self.active_tab = match self.active_tab {
             ActiveTab::Progress => ActiveTab::Files,
-            ActiveTab::Files => ActiveTab::Analysis,
+            ActiveTab::Files => ActiveTab::Tree,
+            ActiveTab::Tree => ActiveTab::Analysis,
             ActiveTab::Analysis => ActiveTab::Summary,
             ActiveTab::Summary => {
                 if self.processing_complete && has_errors {
                     ActiveTab::ErrorSummary
+                } else if has_warnings {
+                    ActiveTab::Warnings
                 } else {
-                    ActiveTab::Progress
+                    self.tab_after_summaries()
                 }
             }
-            ActiveTab::ErrorSummary => ActiveTab::Progress,
+            ActiveTab::ErrorSummary if has_warnings => ActiveTab::Warnings,
+            ActiveTab::ErrorSummary | ActiveTab::Warnings => self.tab_after_summaries(),
+            ActiveTab::Watch => ActiveTab::Progress,
+        }
+    }
+
+    /// The Watch tab in watch mode, otherwise the tabs wrap around to Progress
+    fn tab_after_summaries(&self) -> ActiveTab {
+        if self.watch.is_some() {
+            ActiveTab::Watch
+        } else {
+            ActiveTab::Progress
         }
     }
 
@@ -143,10 +329,13 @@ impl App {
             .lock()
             .expect("Failed to acquire summary lock for previous tab navigation");
         let has_errors = summary.get_failed_count() > 0 || summary.get_failed_includes() > 0;
+        let has_warnings = !summary.warnings.is_empty();
         drop(summary);
 
         self.active_tab = match self.active_tab {
-            ActiveTab::Progress => {
+            ActiveTab::Progress if self.watch.is_some() => ActiveTab::Watch,
+            ActiveTab::Progress | ActiveTab::Watch if has_warnings => ActiveTab::Warnings,
+            ActiveTab::Progress | ActiveTab::Watch | ActiveTab::Warnings => {
                 if self.processing_complete && has_errors {
                     ActiveTab::ErrorSummary
                 } else {
@@ -154,38 +343,446 @@ impl App {
                 }
             }
             ActiveTab::Files => ActiveTab::Progress,
-            ActiveTab::Analysis => ActiveTab::Files,
+            ActiveTab::Tree => ActiveTab::Files,
+            ActiveTab::Analysis => ActiveTab::Tree,
             ActiveTab::Summary => ActiveTab::Analysis,
             ActiveTab::ErrorSummary => ActiveTab::Summary,
         }
     }
 
-    /// Navigate to the next file
-    pub fn next_file(&mut self) {
+    /// Results shown in the Files tab: those matching the filter, in processing order
+    pub fn visible_files<'a>(&self, summary: &'a ProcessingSummary) -> Vec<&'a FileProcessResult> {
+        summary
+            .results
+            .iter()
+            .filter(|result| {
+                self.filter.is_empty()
+                    || fuzzy_match(&self.filter, &result.file_path)
+                    || result
+                        .includes
+                        .iter()
+                        .any(|include| fuzzy_match(&self.filter, &include.path))
+            })
+            .collect()
+    }
+
+    /// Number of files shown in the Files tab
+    fn visible_file_count(&self) -> usize {
         let summary = self
             .summary
             .lock()
-            .expect("Failed to acquire summary lock for next file navigation");
-        if !summary.results.is_empty() {
-            self.selected_file_index = (self.selected_file_index + 1) % summary.results.len();
-        }
+            .expect("Failed to acquire summary lock for file filtering");
+        self.visible_files(&summary).len()
     }
 
-    /// Navigate to the previous file
-    pub fn previous_file(&mut self) {
+    /// Number of includes of the selected file, listed under it in the Files tab
+    fn selected_include_count(&self) -> usize {
         let summary = self
             .summary
             .lock()
-            .expect("Failed to acquire summary lock for previous file navigation");
-        if !summary.results.is_empty() {
+            .expect("Failed to acquire summary lock for include navigation");
+        self.visible_files(&summary)
+            .get(self.selected_file_index)
+            .map_or(0, |result| result.includes.len())
+    }
+
+    /// Navigate to the next file; in the Files tab, through the includes of the selected file first
+    pub fn next_file(&mut self) {
+        if self.active_tab == ActiveTab::Files {
+            let includes = self.selected_include_count();
+            let next = self.selected_include.map_or(0, |index| index + 1);
+            if next < includes {
+                self.selected_include = Some(next);
+                return;
+            }
+            self.selected_include = None;
+        }
+        let count = self.visible_file_count();
+        if count > 0 {
+            self.selected_file_index = (self.selected_file_index + 1) % count;
+        }
+    }
+
+    /// Navigate to the previous file; in the Files tab, back up to the selected file from its includes
+    pub fn previous_file(&mut self) {
+        if self.active_tab == ActiveTab::Files
+            && let Some(index) = self.selected_include
+        {
+            self.selected_include = index.checked_sub(1);
+            return;
+        }
+        let count = self.visible_file_count();
+        if count > 0 {
             self.selected_file_index = if self.selected_file_index == 0 {
-                summary.results.len() - 1
+                count - 1
             } else {
                 self.selected_file_index - 1
             };
         }
     }
 
+    /// Opens the filter input of the Files tab
+    pub fn start_filter(&mut self) {
+        if self.active_tab == ActiveTab::Files {
+            self.filter_editing = true;
+        }
+    }
+
+    /// Applies a key press to the filter input: Enter keeps the filter, Esc clears it
+    fn edit_filter(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Enter => self.filter_editing = false,
+            KeyCode::Esc => {
+                self.filter_editing = false;
+                self.filter.clear();
+            }
+            KeyCode::Backspace => {
+                self.filter.pop();
+            }
+            KeyCode::Char(c) => self.filter.push(c),
+            _ => return,
+        }
+        self.selected_file_index = 0;
+        self.selected_include = None;
+        self.tree_selected = 0;
+        self.files_offset.set(0);
+    }
+
+    /// Opens the save-report prompt with a timestamped file name
+    pub fn start_report(&mut self) {
+        self.report_path = Some(default_report_path(SystemTime::now()));
+    }
+
+    /// Applies a key press to the save-report prompt: Enter saves, Esc cancels
+    fn edit_report_path(&mut self, key: KeyEvent) {
+        let Some(path) = &mut self.report_path else {
+            return;
+        };
+        match key.code {
+            KeyCode::Enter => {
+                let path = PathBuf::from(path.as_str());
+                self.report_path = None;
+                self.save_report(&path);
+            }
+            KeyCode::Esc => self.report_path = None,
+            KeyCode::Backspace => {
+                path.pop();
+            }
+            KeyCode::Char(c) => path.push(c),
+            _ => {}
+        }
+    }
+
+    /// Writes the current summary to `path`, in the format its extension asks for
+    pub fn save_report(&mut self, path: &Path) {
+        let elapsed = self
+            .completion_time
+            .unwrap_or_else(Instant::now)
+            .duration_since(self.start_time);
+        let summary = self
+            .summary
+            .lock()
+            .expect("Failed to acquire summary lock for report");
+        self.message = Some(
+            match write_report(path, ReportFormat::from_path(path), &summary, elapsed) {
+                Ok(()) => format!("Report saved to {}", path.display()),
+                Err(e) => format!("Failed to save report to {}: {e}", path.display()),
+            },
+        );
+    }
+
+    /// Does the active tab scroll a text pane rather than the file selection?
+    fn scrolls_text(&self) -> bool {
+        match self.active_tab {
+            ActiveTab::Analysis
+            | ActiveTab::ErrorSummary
+            | ActiveTab::Warnings
+            | ActiveTab::Watch => true,
+            ActiveTab::Files => self.preview.is_some(),
+            ActiveTab::Progress | ActiveTab::Tree | ActiveTab::Summary => false,
+        }
+    }
+
+    /// Opens the preview of the selected file's output, or closes an open one
+    pub fn toggle_preview(&mut self) {
+        if self.preview.is_some() {
+            self.preview = None;
+        } else if self.active_tab == ActiveTab::Files {
+            self.preview = self.load_preview();
+            self.scroll_offset = 0;
+        }
+    }
+
+    /// Reads the output written for the selected file
+    fn load_preview(&self) -> Option<Preview> {
+        let file_path = {
+            let summary = self
+                .summary
+                .lock()
+                .expect("Failed to acquire summary lock for preview");
+            self.visible_files(&summary)
+                .get(self.selected_file_index)?
+                .file_path
+                .clone()
+        };
+
+        let output_path = if self.config.batch {
+            match calculate_output_path(
+                Path::new(&file_path),
+                &self.config.source_path,
+                &self.config.output_path,
+                &self.config.naming,
+            ) {
+                Ok(output_path) => output_path,
+                Err(e) => {
+                    return Some(Preview {
+                        path: file_path,
+                        content: format!("Failed to calculate output path: {e}"),
+                    });
+                }
+            }
+        } else {
+            self.config.output_path.clone()
+        };
+        let output_path = if self.config.emit.markdown() {
+            output_path
+        } else {
+            output_path.with_extension("html")
+        };
+
+        let content = if self.config.diff {
+            "Nothing is written in diff mode; the changes are shown next to the file list."
+                .to_string()
+        } else {
+            fs::read_to_string(&output_path)
+                .unwrap_or_else(|e| format!("Failed to read output: {e}"))
+        };
+        Some(Preview {
+            path: output_path.to_string_lossy().to_string(),
+            content,
+        })
+    }
+
+    /// Moves the file selection, tree selection or text pane of the active tab by `rows`
+    pub fn scroll_by(&mut self, rows: isize) {
+        if self.scrolls_text() {
+            self.scroll_offset = self
+                .scroll_offset
+                .min(self.max_scroll.get())
+                .saturating_add_signed(rows)
+                .min(self.max_scroll.get());
+        } else if self.active_tab == ActiveTab::Tree {
+            let last = self.tree_row_count().saturating_sub(1);
+            self.tree_selected = self.tree_selected.saturating_add_signed(rows).min(last);
+        } else {
+            let last = self.visible_file_count().saturating_sub(1);
+            self.selected_include = None;
+            self.selected_file_index = self
+                .selected_file_index
+                .saturating_add_signed(rows)
+                .min(last);
+        }
+    }
+
+    /// Lines of the Tree tab: the files matching the filter with their includes,
+    /// nested under the file or partial that includes them
+    pub fn tree_rows<'a>(&self, summary: &'a ProcessingSummary) -> Vec<TreeRow<'a>> {
+        let mut rows = Vec::new();
+        for result in self.visible_files(summary) {
+            let key = (result.file_path.clone(), None);
+            let expanded = !self.collapsed.contains(&key);
+            rows.push(TreeRow {
+                level: 0,
+                path: &result.file_path,
+                success: result.success,
+                error: result.formatted_error(),
+                expandable: !result.includes.is_empty(),
+                expanded,
+                key,
+            });
+            if !expanded {
+                continue;
+            }
+
+            // Includes are recorded before the includes nested in them
+            let mut hidden_below: Option<usize> = None;
+            for (index, include) in result.includes.iter().enumerate() {
+                match hidden_below {
+                    Some(depth) if include.depth > depth => continue,
+                    _ => hidden_below = None,
+                }
+                let key = (result.file_path.clone(), Some(index));
+                let expandable = result
+                    .includes
+                    .get(index + 1)
+                    .is_some_and(|next| next.depth > include.depth);
+                let expanded = !self.collapsed.contains(&key);
+                if expandable && !expanded {
+                    hidden_below = Some(include.depth);
+                }
+                rows.push(TreeRow {
+                    level: include.depth + 1,
+                    path: &include.path,
+                    success: include.success,
+                    error: include.formatted_error(),
+                    expandable,
+                    expanded,
+                    key,
+                });
+            }
+        }
+        rows
+    }
+
+    fn tree_row_count(&self) -> usize {
+        let summary = self
+            .summary
+            .lock()
+            .expect("Failed to acquire summary lock for tree navigation");
+        self.tree_rows(&summary).len()
+    }
+
+    /// Processes failed files again and updates their results in place: the selected
+    /// file in the Files tab, every failed file in the Error Summary tab
+    pub fn retry(&mut self) {
+        let files: Vec<String> = {
+            let summary = self
+                .summary
+                .lock()
+                .expect("Failed to acquire summary lock for retry");
+            match self.active_tab {
+                ActiveTab::Files => self
+                    .visible_files(&summary)
+                    .get(self.selected_file_index)
+                    .map(|result| vec![result.file_path.clone()])
+                    .unwrap_or_default(),
+                ActiveTab::ErrorSummary => summary
+                    .results
+                    .iter()
+                    .filter(|result| !result.success)
+                    .map(|result| result.file_path.clone())
+                    .collect(),
+                _ => Vec::new(),
+            }
+        };
+
+        self.reprocess(files);
+    }
+
+    /// Processes `files` again, once the run is over so no output is written twice at once
+    pub fn reprocess(&mut self, files: Vec<String>) {
+        if !self.processing_complete {
+            return;
+        }
+        if let Some(archive) = &self.archive
+            && !files.is_empty()
+        {
+            self.message = Some(format!(
+                "Can't retry: {} is already written",
+                archive.display()
+            ));
+            return;
+        }
+        for file in files {
+            let _span = tracing::info_span!("retry", path = %file).entered();
+            match reprocess_file(&self.config, Path::new(&file)) {
+                Ok(rerun) => self
+                    .summary
+                    .lock()
+                    .expect("Failed to acquire summary lock for retry results")
+                    .replace_results(rerun),
+                Err(e) => tracing::warn!(error = %e, "Retry failed"),
+            }
+        }
+
+        // The Error Summary and Warnings tabs are gone once there is nothing left to show
+        if matches!(
+            self.active_tab,
+            ActiveTab::ErrorSummary | ActiveTab::Warnings
+        ) && !self.get_available_tabs().contains(&self.active_tab)
+        {
+            self.active_tab = ActiveTab::Summary;
+        }
+    }
+
+    /// Asks the event loop to open a file in the editor: the missing partial of the
+    /// selected file or the file itself in the Files tab, the selected node in the Tree tab
+    pub fn request_edit(&mut self) {
+        let summary = self
+            .summary
+            .lock()
+            .expect("Failed to acquire summary lock for editing");
+        let request = match self.active_tab {
+            ActiveTab::Files => self
+                .visible_files(&summary)
+                .get(self.selected_file_index)
+                .map(|result| {
+                    let missing = result
+                        .includes
+                        .iter()
+                        .find(|include| include.error_code == Some(ErrorCode::MissingInclude));
+                    EditRequest {
+                        path: PathBuf::from(missing.map_or(&result.file_path, |i| &i.path)),
+                        source: result.file_path.clone(),
+                    }
+                }),
+            ActiveTab::Tree => self
+                .tree_rows(&summary)
+                .into_iter()
+                .nth(self.tree_selected)
+                .map(|row| EditRequest {
+                    path: PathBuf::from(row.path),
+                    source: row.key.0,
+                }),
+            _ => None,
+        };
+        drop(summary);
+        self.edit_request = request;
+    }
+
+    /// Expands or collapses the selected node of the Tree tab
+    pub fn toggle_tree_node(&mut self) {
+        let key = {
+            let summary = self
+                .summary
+                .lock()
+                .expect("Failed to acquire summary lock for tree toggling");
+            self.tree_rows(&summary)
+                .into_iter()
+                .nth(self.tree_selected)
+                .filter(|row| row.expandable)
+                .map(|row| row.key)
+        };
+        if let Some(key) = key
+            && !self.collapsed.remove(&key)
+        {
+            self.collapsed.insert(key);
+        }
+    }
+
+    /// Moves one page up or down in the active tab
+    pub fn scroll_page(&mut self, down: bool) {
+        let page = self.page_height.get().max(1) as isize;
+        self.scroll_by(if down { page } else { -page });
+    }
+
+    /// Goes to the first file or the top of the text pane
+    pub fn scroll_to_top(&mut self) {
+        if self.scrolls_text() {
+            self.scroll_offset = 0;
+        } else if self.active_tab == ActiveTab::Tree {
+            self.tree_selected = 0;
+        } else {
+            self.selected_file_index = 0;
+            self.selected_include = None;
+        }
+    }
+
+    /// Goes to the last file or the bottom of the text pane
+    pub fn scroll_to_bottom(&mut self) {
+        self.scroll_by(isize::MAX);
+    }
+
     /// Toggle error details visibility
     pub fn toggle_error_details(&mut self) {
         self.error_details_visible = !self.error_details_visible;
@@ -214,6 +811,7 @@ impl App {
         let mut tabs = vec![
             ActiveTab::Progress,
             ActiveTab::Files,
+            ActiveTab::Tree,
             ActiveTab::Analysis,
             ActiveTab::Summary,
         ];
@@ -225,6 +823,15 @@ impl App {
             tabs.push(ActiveTab::ErrorSummary);
         }
 
+        // Likewise the Warnings tab
+        if !summary.warnings.is_empty() {
+            tabs.push(ActiveTab::Warnings);
+        }
+
+        if self.watch.is_some() {
+            tabs.push(ActiveTab::Watch);
+        }
+
         tabs
     }
 
@@ -260,7 +867,64 @@ impl App {
 
     /// Handle incoming actions
     pub fn handle_action(&mut self, action: Action) -> bool {
-        match action {
+        let tab = self.active_tab;
+        if matches!(action, Action::Key(_)) {
+            self.message = None;
+        }
+        let quit = match action {
+            Action::Processing(event) => {
+                self.handle_processing_event(&event);
+                false
+            }
+            Action::Retry => {
+                self.retry();
+                false
+            }
+            Action::OpenInEditor => {
+                self.request_edit();
+                false
+            }
+            Action::Key(key)
+                if key.code == KeyCode::Char('c')
+                    && key.modifiers.contains(KeyModifiers::CONTROL) =>
+            {
+                self.quit();
+                true
+            }
+            Action::Key(key) if self.report_path.is_some() => {
+                self.edit_report_path(key);
+                false
+            }
+            Action::Key(key) if self.filter_editing => {
+                self.edit_filter(key);
+                false
+            }
+            Action::Key(key) => match key_action(key) {
+                Some(action) => self.handle_action(action),
+                None => false,
+            },
+            Action::SaveReport => {
+                self.start_report();
+                false
+            }
+            Action::StartFilter => {
+                self.start_filter();
+                false
+            }
+            Action::TogglePreview | Action::ToggleNode if self.active_tab == ActiveTab::Tree => {
+                self.toggle_tree_node();
+                false
+            }
+            Action::TogglePreview
+                if self.active_tab == ActiveTab::Files && self.selected_include.is_some() =>
+            {
+                self.include_details = !self.include_details;
+                false
+            }
+            Action::TogglePreview => {
+                self.toggle_preview();
+                false
+            }
             Action::Quit => {
                 self.quit();
                 true
@@ -273,6 +937,14 @@ impl App {
                 self.previous_tab();
                 false
             }
+            Action::NextFile if self.scrolls_text() || self.active_tab == ActiveTab::Tree => {
+                self.scroll_by(1);
+                false
+            }
+            Action::PreviousFile if self.scrolls_text() || self.active_tab == ActiveTab::Tree => {
+                self.scroll_by(-1);
+                false
+            }
             Action::NextFile => {
                 self.next_file();
                 false
@@ -281,6 +953,22 @@ impl App {
                 self.previous_file();
                 false
             }
+            Action::PageUp => {
+                self.scroll_page(false);
+                false
+            }
+            Action::PageDown => {
+                self.scroll_page(true);
+                false
+            }
+            Action::ScrollToTop => {
+                self.scroll_to_top();
+                false
+            }
+            Action::ScrollToBottom => {
+                self.scroll_to_bottom();
+                false
+            }
             Action::Tick => {
                 self.tick();
                 false
@@ -294,7 +982,13 @@ impl App {
                 false
             }
             Action::HideHelp => {
-                self.help_visible = false;
+                if self.help_visible {
+                    self.help_visible = false;
+                } else if self.include_details {
+                    self.include_details = false;
+                } else {
+                    self.preview = None;
+                }
                 false
             }
             Action::ToggleHelp => {
@@ -313,9 +1007,10 @@ impl App {
                 match tab_num {
                     1 => self.active_tab = ActiveTab::Progress,
                     2 => self.active_tab = ActiveTab::Files,
-                    3 => self.active_tab = ActiveTab::Analysis,
-                    4 => self.active_tab = ActiveTab::Summary,
-                    5 => {
+                    3 => self.active_tab = ActiveTab::Tree,
+                    4 => self.active_tab = ActiveTab::Analysis,
+                    5 => self.active_tab = ActiveTab::Summary,
+                    6 => {
                         // Only allow access to Error Summary if there are errors
                         let summary = self
                             .summary
@@ -329,11 +1024,413 @@ impl App {
                             self.active_tab = ActiveTab::ErrorSummary;
                         }
                     }
+                    7 => {
+                        let has_warnings = !self
+                            .summary
+                            .lock()
+                            .expect("Failed to acquire summary lock for warnings tab access check")
+                            .warnings
+                            .is_empty();
+                        if has_warnings {
+                            self.active_tab = ActiveTab::Warnings;
+                        }
+                    }
+                    8 if self.watch.is_some() => self.active_tab = ActiveTab::Watch,
                     _ => {} // Invalid tab number
                 }
                 false
             }
             _ => false,
+        };
+
+        // Each tab starts at the top of its text
+        if self.active_tab != tab {
+            self.scroll_offset = 0;
+            self.preview = None;
+        }
+        // The include popup belongs to the selected include row
+        if self.selected_include.is_none() || self.active_tab != ActiveTab::Files {
+            self.include_details = false;
         }
+        quit
+    }
+}
+
+/// Whether the characters of `pattern` appear in order in `text`, ignoring case
+pub fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+        .all(|p| text.any(|c| c == p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::Processor;
+    use crate::types::{ContentWarning, DirectiveKind, IncludeResult, WarningKind};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_preview_shows_output_of_selected_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("intro.md"), "Intro").expect("Failed to write intro.md");
+        fs::write(source_dir.join("a.md"), "# A\n\n!include (intro.md)")
+            .expect("Failed to write a.md");
+        fs::write(source_dir.join("b.md"), "# B").expect("Failed to write b.md");
+
+        let processor = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .build()
+            .expect("Failed to build processor");
+        let summary = processor.run().expect("Failed to run processor");
+        let mut app = App::new(processor.config().clone(), Arc::new(Mutex::new(summary)));
+        app.active_tab = ActiveTab::Files;
+
+        app.selected_file_index = 1;
+        app.handle_action(Action::TogglePreview);
+        let preview = app.preview.clone().expect("Preview should be open");
+        assert!(preview.path.ends_with("b.md"));
+        assert_eq!(preview.content, "# B");
+
+        app.handle_action(Action::HideHelp);
+        assert!(app.preview.is_none());
+    }
+
+    #[test]
+    fn test_processing_actions_show_each_file_as_it_finishes() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::write(source_dir.join("a.md"), "# A").expect("Failed to write a.md");
+        fs::write(source_dir.join("b.md"), "# B").expect("Failed to write b.md");
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let processor = Processor::builder()
+            .source(&source_dir)
+            .partials(temp_dir.path())
+            .output(temp_dir.path().join("output"))
+            .events(sender)
+            .build()
+            .expect("Failed to build processor");
+        let config = processor.config().clone();
+        processor.run().expect("Failed to run processor");
+        drop(processor);
+
+        let mut app = App::new(config, Arc::new(Mutex::new(ProcessingSummary::new())));
+        let mut finished_counts = Vec::new();
+        for event in receiver {
+            let finished = matches!(event, ProcessingEvent::FileFinished(_));
+            app.handle_action(Action::Processing(event));
+            if finished {
+                let summary = app.summary.lock().expect("Failed to lock summary");
+                finished_counts.push((summary.results.len(), summary.total_files));
+            }
+        }
+
+        assert_eq!(finished_counts, vec![(1, 2), (2, 2)]);
+        assert!(app.is_processing_complete());
+    }
+
+    /// App showing `guide.md`, whose nested `badge.md` include is missing
+    fn guide_app(temp_dir: &TempDir) -> App {
+        let include = |path: &str, depth, success| IncludeResult {
+            path: path.to_string(),
+            success,
+            error_message: None,
+            error_code: (!success).then_some(ErrorCode::MissingInclude),
+            parent: String::new(),
+            depth,
+            title: None,
+            values: HashMap::new(),
+            duration: None,
+            bytes: None,
+            attempts: None,
+            kind: DirectiveKind::Include,
+        };
+        let mut summary = ProcessingSummary::new();
+        summary.results.push(FileProcessResult {
+            file_path: "guide.md".to_string(),
+            success: false,
+            includes: vec![
+                include("header.md", 0, true),
+                include("logo.md", 1, true),
+                include("badge.md", 2, false),
+                include("footer.md", 0, true),
+            ],
+            error_message: None,
+            error_code: None,
+            sizes: None,
+        });
+        let config = Processor::builder()
+            .source(temp_dir.path())
+            .partials(temp_dir.path())
+            .output(temp_dir.path())
+            .build()
+            .expect("Failed to build processor")
+            .config()
+            .clone();
+        App::new(config, Arc::new(Mutex::new(summary)))
+    }
+
+    #[test]
+    fn test_open_in_editor_picks_missing_partial_or_tree_node() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut app = guide_app(&temp_dir);
+
+        app.active_tab = ActiveTab::Files;
+        app.handle_action(Action::OpenInEditor);
+        assert_eq!(
+            app.edit_request.take(),
+            Some(EditRequest {
+                path: PathBuf::from("badge.md"),
+                source: "guide.md".to_string(),
+            })
+        );
+
+        app.active_tab = ActiveTab::Tree;
+        app.tree_selected = 2;
+        app.handle_action(Action::OpenInEditor);
+        assert_eq!(
+            app.edit_request.take(),
+            Some(EditRequest {
+                path: PathBuf::from("logo.md"),
+                source: "guide.md".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_tree_rows_nest_and_collapse_includes() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut app = guide_app(&temp_dir);
+        app.active_tab = ActiveTab::Tree;
+
+        let rows = |app: &App| {
+            let summary = app.summary.lock().expect("Failed to lock summary");
+            app.tree_rows(&summary)
+                .iter()
+                .map(|row| (row.level, row.path.to_string(), row.expandable))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rows(&app),
+            vec![
+                (0, "guide.md".to_string(), true),
+                (1, "header.md".to_string(), true),
+                (2, "logo.md".to_string(), true),
+                (3, "badge.md".to_string(), false),
+                (1, "footer.md".to_string(), false),
+            ]
+        );
+
+        app.handle_action(Action::NextFile);
+        app.handle_action(Action::ToggleNode);
+        assert_eq!(
+            rows(&app),
+            vec![
+                (0, "guide.md".to_string(), true),
+                (1, "header.md".to_string(), true),
+                (1, "footer.md".to_string(), false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watch_events_update_dashboard_and_results() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut app = guide_app(&temp_dir);
+        app.watch = Some(WatchState::default());
+        app.handle_action(Action::Processing(ProcessingEvent::RunFinished {
+            processed: 1,
+            failed: 1,
+            cancelled: false,
+        }));
+        assert_eq!(app.active_tab, ActiveTab::Watch);
+        assert_eq!(app.get_available_tabs().last(), Some(&ActiveTab::Watch));
+
+        app.handle_action(Action::Processing(ProcessingEvent::FilesChanged {
+            run: 1,
+            paths: vec!["badge.md".to_string()],
+            affected: vec!["guide.md".to_string()],
+        }));
+        let watch = app.watch.as_ref().expect("Watch state missing");
+        assert!(watch.rerunning);
+        assert_eq!(watch.changes.len(), 1);
+        assert_eq!(watch.changes[0].affected, 1);
+
+        app.handle_action(Action::Processing(ProcessingEvent::Rerun {
+            run: 1,
+            results: vec![FileProcessResult {
+                file_path: "guide.md".to_string(),
+                success: true,
+                includes: Vec::new(),
+                error_message: None,
+                error_code: None,
+                sizes: None,
+            }],
+            diffs: Vec::new(),
+            warnings: Vec::new(),
+        }));
+        let watch = app.watch.as_ref().expect("Watch state missing");
+        assert!(!watch.rerunning);
+        assert_eq!(watch.runs, 1);
+        assert_eq!(watch.file_runs.get("guide.md"), Some(&1));
+        let summary = app.summary.lock().expect("Failed to lock summary");
+        assert_eq!(summary.results.len(), 1);
+        assert!(summary.results[0].success);
+    }
+
+    #[test]
+    fn test_enter_on_include_row_opens_its_details() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::write(
+            temp_dir.path().join("intro.md"),
+            "Hi {% name %}\n\n!include(logo.md)",
+        )
+        .expect("Failed to write intro.md");
+        fs::write(temp_dir.path().join("logo.md"), "(logo)").expect("Failed to write logo.md");
+        fs::write(
+            source_dir.join("a.md"),
+            "!include(intro.md, title=\"Intro\", values=[name=\"Ada\"])",
+        )
+        .expect("Failed to write a.md");
+        fs::write(source_dir.join("b.md"), "# B").expect("Failed to write b.md");
+        let processor = Processor::builder()
+            .source(&source_dir)
+            .partials(temp_dir.path())
+            .output(temp_dir.path().join("output"))
+            .build()
+            .expect("Failed to build processor");
+        let summary = processor.run().expect("Failed to run processor");
+        let intro = &summary.results[0].includes[0];
+        assert_eq!(intro.title.as_deref(), Some("Intro"));
+        assert_eq!(intro.values.get("name").map(String::as_str), Some("Ada"));
+        assert!(intro.duration.is_some());
+
+        let mut app = App::new(processor.config().clone(), Arc::new(Mutex::new(summary)));
+        app.handle_action(Action::GoToTab(2));
+        app.handle_action(Action::NextFile);
+        app.handle_action(Action::NextFile);
+        assert_eq!(
+            (app.selected_file_index, app.selected_include),
+            (0, Some(1))
+        );
+        app.handle_action(Action::TogglePreview);
+        assert!(app.include_details);
+        assert!(app.preview.is_none());
+        app.handle_action(Action::HideHelp);
+        assert!(!app.include_details);
+
+        // Past the last include comes the next file, and back up the file row
+        app.handle_action(Action::NextFile);
+        assert_eq!((app.selected_file_index, app.selected_include), (1, None));
+        app.handle_action(Action::PreviousFile);
+        assert_eq!((app.selected_file_index, app.selected_include), (0, None));
+        app.handle_action(Action::TogglePreview);
+        assert!(!app.include_details);
+        assert!(app.preview.is_some());
+    }
+
+    #[test]
+    fn test_warnings_tab_is_shown_while_there_are_warnings() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut app = guide_app(&temp_dir);
+        app.mark_processing_complete();
+        assert!(!app.get_available_tabs().contains(&ActiveTab::Warnings));
+        app.handle_action(Action::GoToTab(7));
+        assert_eq!(app.active_tab, ActiveTab::ErrorSummary);
+
+        app.handle_action(Action::Processing(ProcessingEvent::ContentWarning(
+            ContentWarning {
+                source: "guide.md".to_string(),
+                file: "header.md".to_string(),
+                line: 3,
+                kind: WarningKind::DefaultValue,
+                message: "`name` is not set; using its default \"World\"".to_string(),
+            },
+        )));
+        assert_eq!(app.get_available_tabs().last(), Some(&ActiveTab::Warnings));
+        app.next_tab();
+        assert_eq!(app.active_tab, ActiveTab::Warnings);
+        app.next_tab();
+        assert_eq!(app.active_tab, ActiveTab::Progress);
+        app.previous_tab();
+        assert_eq!(app.active_tab, ActiveTab::Warnings);
+        app.previous_tab();
+        assert_eq!(app.active_tab, ActiveTab::ErrorSummary);
+        app.handle_action(Action::GoToTab(7));
+        assert_eq!(app.active_tab, ActiveTab::Warnings);
+
+        // Processing the file again without warnings hides the tab
+        app.summary
+            .lock()
+            .expect("Failed to lock summary")
+            .replace_results(ProcessingSummary {
+                results: vec![FileProcessResult {
+                    file_path: "guide.md".to_string(),
+                    success: true,
+                    includes: Vec::new(),
+                    error_message: None,
+                    error_code: None,
+                    sizes: None,
+                }],
+                ..ProcessingSummary::new()
+            });
+        assert!(!app.get_available_tabs().contains(&ActiveTab::Warnings));
+    }
+
+    #[test]
+    fn test_save_report_prompt_writes_the_summary() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut app = guide_app(&temp_dir);
+        let key = |code| Action::Key(KeyEvent::new(code, KeyModifiers::NONE));
+
+        app.handle_action(key(KeyCode::Char('s')));
+        let default_path = app.report_path.clone().expect("Prompt not open");
+        assert!(default_path.starts_with("md2md-report-") && default_path.ends_with(".json"));
+
+        // Keys go to the prompt while it is open
+        app.handle_action(key(KeyCode::Char('q')));
+        assert!(app.running);
+        app.handle_action(key(KeyCode::Esc));
+        assert!(app.report_path.is_none());
+
+        let path = temp_dir.path().join("reports/run.md");
+        app.handle_action(key(KeyCode::Char('s')));
+        app.report_path = Some(path.to_string_lossy().to_string());
+        app.handle_action(key(KeyCode::Enter));
+        assert!(app.report_path.is_none());
+        assert!(
+            app.message
+                .as_deref()
+                .is_some_and(|message| message.starts_with("Report saved to"))
+        );
+        let report = fs::read_to_string(&path).expect("Failed to read report");
+        assert!(report.contains("| ✗ | guide.md | 4 |"));
+
+        // The message is cleared by the next key press
+        app.handle_action(key(KeyCode::Down));
+        assert!(app.message.is_none());
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert!(fuzzy_match("gdin", "docs/guide/intro.md"));
+        assert!(fuzzy_match("GUIDE md", "docs/guide/intro.md"));
+        assert!(fuzzy_match("", "docs/guide/intro.md"));
+        assert!(!fuzzy_match("intro guide", "docs/guide/intro.md"));
+        assert!(!fuzzy_match("x", "docs/guide/intro.md"));
     }
 }
@@ -0,0 +1,348 @@
+//! Writing the outputs of a run into a single `.tar`, `.tar.gz` or `.zip` file
+
+use crate::sink::OutputSink;
+use crate::types::LineEndings;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Archive formats, told apart by the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    /// The format of an archive named `.tar`, `.tar.gz`, `.tgz` or `.zip`
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(feature = "archive")]
+pub use writer::ArchiveSink;
+
+#[cfg(feature = "archive")]
+mod writer {
+    use super::*;
+    use crate::banner::{build_time, utc_date_time};
+    use crate::file_handler::{ensure_output_directory, write_segments_to};
+    use crate::graph::normalize_path;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use std::fs::File;
+    use std::io::{BufWriter, Write};
+    use std::sync::Mutex;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, DateTime, ZipWriter};
+
+    /// Stores every output in an archive, under its path relative to `root`.
+    ///
+    /// The archive is only complete once [`ArchiveSink::finish`] is called.
+    pub struct ArchiveSink {
+        path: PathBuf,
+        root: PathBuf,
+        /// Modification time of the entries, from `SOURCE_DATE_EPOCH` when set
+        time: SystemTime,
+        writer: Mutex<ArchiveWriter>,
+    }
+
+    enum ArchiveWriter {
+        Tar(tar::Builder<BufWriter<File>>),
+        TarGz(tar::Builder<GzEncoder<BufWriter<File>>>),
+        Zip(Box<ZipWriter<BufWriter<File>>>),
+    }
+
+    impl ArchiveSink {
+        /// Creates the archive at `path`, in the format its extension names
+        pub fn create(
+            path: impl Into<PathBuf>,
+            root: impl AsRef<Path>,
+        ) -> Result<Self, Box<dyn std::error::Error>> {
+            let path = path.into();
+            let format = ArchiveFormat::from_path(&path).ok_or_else(|| {
+                format!(
+                    "{} is not a .tar, .tar.gz, .tgz or .zip file",
+                    path.display()
+                )
+            })?;
+            ensure_output_directory(&path)?;
+            let file = BufWriter::new(
+                File::create(&path)
+                    .map_err(|e| format!("Failed to create {}: {e}", path.display()))?,
+            );
+            let writer = match format {
+                ArchiveFormat::Tar => ArchiveWriter::Tar(tar::Builder::new(file)),
+                ArchiveFormat::TarGz => ArchiveWriter::TarGz(tar::Builder::new(GzEncoder::new(
+                    file,
+                    Compression::default(),
+                ))),
+                ArchiveFormat::Zip => ArchiveWriter::Zip(Box::new(ZipWriter::new(file))),
+            };
+            Ok(Self {
+                path,
+                root: normalize_path(root.as_ref()),
+                time: build_time(),
+                writer: Mutex::new(writer),
+            })
+        }
+
+        /// Writes the end of the archive, without which it is cut short
+        pub fn finish(self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+            let writer = self.writer.into_inner().unwrap_or_else(|e| e.into_inner());
+            let finish = || -> io::Result<()> {
+                match writer {
+                    ArchiveWriter::Tar(builder) => builder.into_inner()?.flush(),
+                    ArchiveWriter::TarGz(builder) => builder.into_inner()?.finish()?.flush(),
+                    ArchiveWriter::Zip(zip) => zip.finish().map_err(io::Error::other)?.flush(),
+                }
+            };
+            if let Err(e) = finish() {
+                remove_unfinished(&self.path);
+                return Err(format!("Failed to write {}: {e}", self.path.display()).into());
+            }
+            Ok(self.path)
+        }
+
+        /// Removes the archive without finishing it, e.g. when the run that was
+        /// writing it failed, so no truncated archive is left behind
+        pub fn discard(self) {
+            drop(self.writer);
+            remove_unfinished(&self.path);
+        }
+
+        /// The entry of the output at `path`: relative to the root, with `/` between directories
+        fn entry_name(&self, path: &Path) -> String {
+            let path = normalize_path(path);
+            let relative = match path.strip_prefix(&self.root) {
+                Ok(relative) if !relative.as_os_str().is_empty() => relative,
+                // A single output file, named after itself
+                _ => path.file_name().map_or(path.as_path(), Path::new),
+            };
+            relative
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/")
+        }
+
+        /// Adds `content` to a tar archive as `name`
+        fn append_tar<W: Write>(
+            &self,
+            builder: &mut tar::Builder<W>,
+            name: &str,
+            content: &[u8],
+        ) -> io::Result<()> {
+            let mut header = tar::Header::new_gnu();
+            header.set_entry_type(tar::EntryType::Regular);
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(
+                self.time
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_secs()),
+            );
+            builder.append_data(&mut header, name, content)
+        }
+    }
+
+    fn remove_unfinished(path: &Path) {
+        if let Err(e) = std::fs::remove_file(path) {
+            tracing::debug!(path = %path.display(), error = %e, "Failed to remove unfinished archive");
+        }
+    }
+
+    impl OutputSink for ArchiveSink {
+        fn write(
+            &self,
+            path: &Path,
+            segments: &[&str],
+            line_endings: LineEndings,
+        ) -> Result<(), Box<dyn std::error::Error>> {
+            // Tar entries start with their size
+            let mut content = Vec::new();
+            write_segments_to(&mut content, segments, line_endings)?;
+            let name = self.entry_name(path);
+            let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+            let appended = match &mut *writer {
+                ArchiveWriter::Tar(builder) => self.append_tar(builder, &name, &content),
+                ArchiveWriter::TarGz(builder) => self.append_tar(builder, &name, &content),
+                ArchiveWriter::Zip(zip) => {
+                    let (year, month, day, hours, minutes, seconds) = utc_date_time(self.time);
+                    // Zip dates start in 1980
+                    let modified = DateTime::from_date_and_time(
+                        u16::try_from(year).unwrap_or_default(),
+                        month as u8,
+                        day as u8,
+                        hours as u8,
+                        minutes as u8,
+                        seconds as u8,
+                    )
+                    .unwrap_or_default();
+                    let options = SimpleFileOptions::default()
+                        .compression_method(CompressionMethod::Deflated)
+                        .last_modified_time(modified)
+                        .unix_permissions(0o644);
+                    zip.start_file(name.as_str(), options)
+                        .map_err(io::Error::other)?;
+                    zip.write_all(&content)
+                }
+            };
+            appended
+                .map_err(|e| format!("Failed to add {name} to {}: {e}", self.path.display()))?;
+            Ok(())
+        }
+    }
+}
+
+/// Can't be created; md2md was built without the `archive` feature
+#[cfg(not(feature = "archive"))]
+pub enum ArchiveSink {}
+
+#[cfg(not(feature = "archive"))]
+impl ArchiveSink {
+    /// Fails; md2md was built without the `archive` feature
+    pub fn create(
+        path: impl Into<PathBuf>,
+        _root: impl AsRef<Path>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "Writing {} needs md2md built with the `archive` feature",
+                path.into().display()
+            ),
+        )
+        .into())
+    }
+
+    pub fn finish(self) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        match self {}
+    }
+
+    pub fn discard(self) {
+        match self {}
+    }
+}
+
+#[cfg(not(feature = "archive"))]
+impl OutputSink for ArchiveSink {
+    fn write(
+        &self,
+        _path: &Path,
+        _segments: &[&str],
+        _line_endings: LineEndings,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        match *self {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_format_from_path() {
+        for (name, format) in [
+            ("docs.tar.gz", Some(ArchiveFormat::TarGz)),
+            ("docs.TGZ", Some(ArchiveFormat::TarGz)),
+            ("out/docs.tar", Some(ArchiveFormat::Tar)),
+            ("docs.zip", Some(ArchiveFormat::Zip)),
+            ("docs.gz", None),
+            ("docs", None),
+        ] {
+            assert_eq!(ArchiveFormat::from_path(Path::new(name)), format, "{name}");
+        }
+    }
+
+    #[cfg(feature = "archive")]
+    #[test]
+    fn test_archives_hold_the_outputs() {
+        use std::collections::BTreeMap;
+        use std::fs::File;
+        use std::io::Read;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let out = temp_dir.path().join("out");
+        for name in ["docs.tar", "docs.tar.gz", "docs.zip"] {
+            let archive = temp_dir.path().join("dist").join(name);
+            let sink = ArchiveSink::create(&archive, &out).expect("Failed to create archive");
+            sink.write(&out.join("index.md"), &["# Home\n"], LineEndings::Crlf)
+                .expect("Failed to add index.md");
+            sink.write(
+                &out.join("guide/../guide/setup.md"),
+                &["Set", "up"],
+                LineEndings::Preserve,
+            )
+            .expect("Failed to add setup.md");
+            assert_eq!(sink.finish().expect("Failed to finish archive"), archive);
+            assert!(!out.exists());
+
+            let file = File::open(&archive).expect("Failed to open archive");
+            let mut entries = BTreeMap::new();
+            let mut read_tar = |reader: &mut dyn Read| {
+                let mut tar = tar::Archive::new(reader);
+                for entry in tar.entries().expect("Failed to read entries") {
+                    let mut entry = entry.expect("Failed to read entry");
+                    let mut content = String::new();
+                    entry
+                        .read_to_string(&mut content)
+                        .expect("Failed to read entry content");
+                    let path = entry.path().expect("Invalid entry path");
+                    entries.insert(path.to_string_lossy().to_string(), content);
+                }
+            };
+            match ArchiveFormat::from_path(&archive) {
+                Some(ArchiveFormat::Tar) => read_tar(&mut &file),
+                Some(ArchiveFormat::TarGz) => read_tar(&mut flate2::read::GzDecoder::new(file)),
+                _ => {
+                    let mut zip = zip::ZipArchive::new(file).expect("Failed to read zip");
+                    for index in 0..zip.len() {
+                        let mut entry = zip.by_index(index).expect("Failed to read entry");
+                        let mut content = String::new();
+                        entry
+                            .read_to_string(&mut content)
+                            .expect("Failed to read entry content");
+                        entries.insert(entry.name().to_string(), content);
+                    }
+                }
+            }
+            assert_eq!(
+                entries,
+                BTreeMap::from([
+                    ("guide/setup.md".to_string(), "Setup".to_string()),
+                    ("index.md".to_string(), "# Home\r\n".to_string()),
+                ]),
+                "{name}"
+            );
+        }
+
+        let archive = temp_dir.path().join("dist").join("failed.zip");
+        let sink = ArchiveSink::create(&archive, &out).expect("Failed to create archive");
+        sink.write(&out.join("index.md"), &["# Home\n"], LineEndings::Preserve)
+            .expect("Failed to add index.md");
+        sink.discard();
+        assert!(!archive.exists());
+    }
+
+    #[cfg(not(feature = "archive"))]
+    #[test]
+    fn test_archives_need_the_archive_feature() {
+        let error = ArchiveSink::create("docs.tar.gz", "out")
+            .err()
+            .expect("Creating an archive should fail without the archive feature");
+        assert!(error.to_string().contains("`archive` feature"));
+    }
+}
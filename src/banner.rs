@@ -0,0 +1,149 @@
+//! "Generated file" banner at the top of every output.
+
+use crate::mdx::frontmatter_end;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Fills `{source}`, `{time}` and `{version}` in a banner template.
+///
+/// `{time}` is `time` as a UTC timestamp, e.g. `2025-01-31T09:30:00Z`.
+pub fn render_banner(template: &str, source: &Path, time: SystemTime) -> String {
+    template
+        .replace("{source}", &source.to_string_lossy())
+        .replace("{time}", &utc_timestamp(time))
+        .replace("{version}", env!("CARGO_PKG_VERSION"))
+}
+
+/// Puts `banner` on the first line of `content`, after a leading `---` frontmatter block
+pub fn insert_banner(content: &str, banner: &str) -> String {
+    banner_segments(content, banner).concat()
+}
+
+/// The pieces of `content` with `banner` inserted, in order, for writing without copying `content`
+pub fn banner_segments<'a>(content: &'a str, banner: &'a str) -> [&'a str; 5] {
+    match frontmatter_end(content) {
+        Some(end) => {
+            let (frontmatter, body) = content.split_at(end);
+            let separator = if frontmatter.ends_with('\n') {
+                ""
+            } else {
+                "\n"
+            };
+            [frontmatter, separator, banner, "\n", body]
+        }
+        None => ["", "", banner, "\n", content],
+    }
+}
+
+/// Run time used for `{time}`; `SOURCE_DATE_EPOCH` is honoured for reproducible builds
+pub fn build_time() -> SystemTime {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .map(|seconds| UNIX_EPOCH + Duration::from_secs(seconds))
+        .unwrap_or_else(SystemTime::now)
+}
+
+pub(crate) fn utc_timestamp(time: SystemTime) -> String {
+    let (year, month, day, hours, minutes, seconds) = utc_date_time(time);
+    format!("{year:04}-{month:02}-{day:02}T{hours:02}:{minutes:02}:{seconds:02}Z")
+}
+
+/// Year, month, day, hours, minutes and seconds of `time` in UTC
+pub(crate) fn utc_date_time(time: SystemTime) -> (i64, i64, i64, u64, u64, u64) {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60,
+    )
+}
+
+/// Reads a timestamp written by [`utc_timestamp`], e.g. `2024-02-29T12:34:56Z`
+pub(crate) fn parse_utc_timestamp(text: &str) -> Option<SystemTime> {
+    let (date, time) = text.strip_suffix('Z')?.split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time.splitn(3, ':').map(str::parse::<u64>);
+    let (hours, minutes, seconds) = (time.next()?.ok()?, time.next()?.ok()?, time.next()?.ok()?);
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hours > 23
+        || minutes > 59
+        || seconds > 60
+    {
+        return None;
+    }
+
+    // Days since 1970-01-01 from a civil date, the inverse of the above
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = u64::try_from(era * 146_097 + day_of_era - 719_468).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86_400 + hours * 3_600 + minutes * 60 + seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_utc_timestamp() {
+        for seconds in [0, 951_782_400, 1_709_210_096, 4_102_444_799] {
+            let time = UNIX_EPOCH + Duration::from_secs(seconds);
+            assert_eq!(parse_utc_timestamp(&utc_timestamp(time)), Some(time));
+        }
+        assert_eq!(parse_utc_timestamp("2024-13-01T00:00:00Z"), None);
+        assert_eq!(parse_utc_timestamp("2024-02-29 12:34:56"), None);
+    }
+
+    #[test]
+    fn test_render_and_insert_banner() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        let banner = render_banner(
+            "<!-- {source} at {time} by md2md {version} -->",
+            Path::new("docs/guide.md"),
+            time,
+        );
+        assert_eq!(
+            banner,
+            format!(
+                "<!-- docs/guide.md at 2024-02-29T12:34:56Z by md2md {} -->",
+                env!("CARGO_PKG_VERSION")
+            )
+        );
+
+        assert_eq!(
+            insert_banner("# Guide", "<!-- b -->"),
+            "<!-- b -->\n# Guide"
+        );
+        assert_eq!(
+            insert_banner("---\ntitle: Guide\n---\n# Guide", "<!-- b -->"),
+            "---\ntitle: Guide\n---\n<!-- b -->\n# Guide"
+        );
+    }
+}
@@ -1,16 +1,157 @@
-use crate::types::ProcessingSummary;
+use crate::types::{ProcessingEvent, ProcessingSummary};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
 
-pub fn print_console_summary(summary: &ProcessingSummary, verbose: bool) {
+/// Width of the console progress bar, in characters
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/// How much console output and logging a run produces, from `-q` up to `-vv`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// One summary line and errors only
+    Quiet,
+    /// Progress, a short summary and warnings
+    #[default]
+    Normal,
+    /// Every file and include in the summary, and informational logs
+    Verbose,
+    /// Also logs every include resolution
+    Debug,
+}
+
+impl Verbosity {
+    /// Level of `--quiet` and the number of `-v` flags given
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        match (quiet, verbose) {
+            (true, _) => Self::Quiet,
+            (false, 0) => Self::Normal,
+            (false, 1) => Self::Verbose,
+            (false, _) => Self::Debug,
+        }
+    }
+
+    pub fn is_quiet(self) -> bool {
+        self == Self::Quiet
+    }
+
+    pub fn is_verbose(self) -> bool {
+        self >= Self::Verbose
+    }
+}
+
+/// Progress of a console run, drawn from its [`ProcessingEvent`]s.
+///
+/// On a terminal a single bar is redrawn in place; otherwise a plain line is
+/// printed at every tenth of the files so logs stay readable.
+pub struct ConsoleProgress<W: Write> {
+    out: W,
+    tty: bool,
+    done: usize,
+    total: usize,
+    /// Last tenth of the files a plain line was printed for
+    printed_step: Option<usize>,
+}
+
+impl<W: Write> ConsoleProgress<W> {
+    pub fn new(out: W, tty: bool) -> Self {
+        Self {
+            out,
+            tty,
+            done: 0,
+            total: 0,
+            printed_step: None,
+        }
+    }
+
+    pub fn update(&mut self, event: &ProcessingEvent) {
+        let _ = match event {
+            ProcessingEvent::FileStarted { path, total, .. } if self.tty => {
+                self.total = *total;
+                self.draw_bar(path)
+            }
+            ProcessingEvent::FileStarted { total, .. } => {
+                self.total = *total;
+                Ok(())
+            }
+            ProcessingEvent::FileFinished(result) => {
+                self.done += 1;
+                if self.tty {
+                    self.draw_bar(&result.file_path)
+                } else {
+                    self.print_step()
+                }
+            }
+            // The bar's line is cleared for the summary that follows
+            ProcessingEvent::RunFinished { .. } if self.tty && self.total > 0 => {
+                write!(self.out, "\r\x1b[2K").and_then(|_| self.out.flush())
+            }
+            _ => Ok(()),
+        };
+    }
+
+    fn draw_bar(&mut self, path: &str) -> std::io::Result<()> {
+        write!(
+            self.out,
+            "\r\x1b[2K{} {path}",
+            progress_bar(self.done, self.total, PROGRESS_BAR_WIDTH)
+        )?;
+        self.out.flush()
+    }
+
+    fn print_step(&mut self) -> std::io::Result<()> {
+        let step = (self.done * 10).checked_div(self.total).unwrap_or(10);
+        if self.printed_step.is_some_and(|printed| printed >= step) {
+            return Ok(());
+        }
+        self.printed_step = Some(step);
+        writeln!(
+            self.out,
+            "Processed {}/{} files ({}%)",
+            self.done,
+            self.total,
+            percent(self.done, self.total)
+        )
+    }
+}
+
+/// A bar like `[#######-------]  50% (5/10)`
+pub fn progress_bar(done: usize, total: usize, width: usize) -> String {
+    let filled = (done * width)
+        .checked_div(total)
+        .unwrap_or(width)
+        .min(width);
+    format!(
+        "[{}{}] {:>3}% ({done}/{total})",
+        "#".repeat(filled),
+        "-".repeat(width - filled),
+        percent(done, total)
+    )
+}
+
+fn percent(done: usize, total: usize) -> usize {
+    (done * 100).checked_div(total).unwrap_or(100)
+}
+
+/// Exit status of a finished run: 1 when a file failed, whatever the verbosity
+pub fn exit_code(summary: &ProcessingSummary) -> i32 {
+    if summary.get_failed_count() > 0 { 1 } else { 0 }
+}
+
+/// Prints the summary of a run
+pub fn print_run_summary(summary: &ProcessingSummary, verbosity: Verbosity) {
     let success_count = summary.get_success_count();
     let failed_count = summary.get_failed_count();
-    if verbose {
+    if verbosity.is_quiet() {
+        println!("{}", summary_line(summary));
+    } else if verbosity.is_verbose() {
         println!("\n=== PROCESSING SUMMARY ===\n");
 
         for result in &summary.results {
             let status_icon = if result.success { "✓" } else { "✗" };
             println!("{} File: {}", status_icon, result.file_path);
 
-            if let Some(error) = &result.error_message {
+            if let Some(error) = result.formatted_error() {
                 println!("  Error: {error}");
                 continue;
             }
@@ -23,8 +164,11 @@ pub fn print_console_summary(summary: &ProcessingSummary, verbose: bool) {
                     let include_icon = if include.success { "✓" } else { "✗" };
                     let status_text = if include.success { "OK" } else { "Error" };
                     println!("    {} {}: {}", include_icon, status_text, include.path);
+                    if let Some(attempts) = include.attempts {
+                        println!("      └─ read in {attempts} attempts");
+                    }
 
-                    if let Some(error) = &include.error_message {
+                    if let Some(error) = include.formatted_error() {
                         println!("      └─ {error}");
                     }
                 }
@@ -32,6 +176,13 @@ pub fn print_console_summary(summary: &ProcessingSummary, verbose: bool) {
             println!();
         }
 
+        for file in &summary.skipped {
+            println!("○ Skipped: {} ({})", file.path, file.reason.as_str());
+        }
+        if !summary.skipped.is_empty() {
+            println!();
+        }
+
         // Final statistics
         println!("=== FINAL SUMMARY ===");
         println!("┌─────────────────┬───────┬─────────┬────────┐");
@@ -50,6 +201,9 @@ pub fn print_console_summary(summary: &ProcessingSummary, verbose: bool) {
             summary.get_failed_includes()
         );
         println!("└─────────────────┴───────┴─────────┴────────┘");
+        if let Some(line) = skipped_line(summary) {
+            println!("{line}");
+        }
 
         if summary.get_failed_count() > 0 || summary.get_failed_includes() > 0 {
             println!("\nSome operations failed. Check the details above.");
@@ -63,12 +217,286 @@ pub fn print_console_summary(summary: &ProcessingSummary, verbose: bool) {
             success_count,
             failed_count
         );
+        if let Some(line) = skipped_line(summary) {
+            println!("{line}");
+        }
         if failed_count > 0 {
             println!("Some files failed to process.");
-            std::process::exit(1);
         } else {
             println!("All files processed successfully!");
-            std::process::exit(0);
         }
     }
 }
+
+/// The summary of a run on a single line, as printed with `--quiet`
+pub fn summary_line(summary: &ProcessingSummary) -> String {
+    let status = if summary.get_failed_count() > 0 || summary.get_failed_includes() > 0 {
+        "FAILED"
+    } else {
+        "ok"
+    };
+    format!(
+        "md2md: {status}. {} files ({} failed), {} includes ({} failed)",
+        summary.results.len(),
+        summary.get_failed_count(),
+        summary.get_total_includes(),
+        summary.get_failed_includes()
+    )
+}
+
+/// How many files were skipped and why, e.g. `Skipped 3 files: 2 ignored by
+/// .md2mdignore, 1 not Markdown.`; `None` when none were
+pub fn skipped_line(summary: &ProcessingSummary) -> Option<String> {
+    if summary.skipped.is_empty() {
+        return None;
+    }
+    let reasons: Vec<String> = summary
+        .get_skipped_by_reason()
+        .into_iter()
+        .map(|(reason, count)| format!("{count} {}", reason.as_str()))
+        .collect();
+    Some(format!(
+        "Skipped {} files: {}.",
+        summary.skipped.len(),
+        reasons.join(", ")
+    ))
+}
+
+/// Prints a GitHub Actions `::error` workflow command for every failed file and include
+pub fn print_github_annotations(summary: &ProcessingSummary) {
+    for annotation in format_github_annotations(summary) {
+        println!("{annotation}");
+    }
+}
+
+/// Builds the GitHub Actions annotations for all failures in the summary
+pub fn format_github_annotations(summary: &ProcessingSummary) -> Vec<String> {
+    let mut annotations = Vec::new();
+
+    for result in summary.results.iter().filter(|r| !r.success) {
+        let failed_includes: Vec<_> = result.includes.iter().filter(|i| !i.success).collect();
+
+        if failed_includes.is_empty() {
+            let message = result
+                .formatted_error()
+                .unwrap_or_else(|| "Processing failed".to_string());
+            annotations.push(format_github_error(&result.file_path, None, &message));
+            continue;
+        }
+
+        for include in failed_includes {
+            let line = find_directive_line(Path::new(&result.file_path), &include.path);
+            let message = match include.formatted_error() {
+                Some(error) => format!("Failed to include {}: {}", include.path, error),
+                None => format!("Failed to include {}", include.path),
+            };
+            annotations.push(format_github_error(&result.file_path, line, &message));
+        }
+    }
+
+    annotations
+}
+
+fn format_github_error(file: &str, line: Option<usize>, message: &str) -> String {
+    let mut properties = format!("file={}", escape_github_property(file));
+    if let Some(line) = line {
+        properties.push_str(&format!(",line={line}"));
+    }
+    format!("::error {}::{}", properties, escape_github_data(message))
+}
+
+/// Finds the 1-indexed line of the first directive in `source_file` that references `include_path`
+fn find_directive_line(source_file: &Path, include_path: &str) -> Option<usize> {
+    let content = fs::read_to_string(source_file).ok()?;
+    let file_name = Path::new(include_path).file_name()?.to_str()?;
+
+    content
+        .lines()
+        .position(|line| {
+            ["!include", "!codesnippet", "!docsnippet"]
+                .iter()
+                .any(|directive| line.contains(directive))
+                && line.contains(file_name)
+        })
+        .map(|index| index + 1)
+}
+
+fn escape_github_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_github_property(value: &str) -> String {
+    escape_github_data(value)
+        .replace(':', "%3A")
+        .replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DirectiveKind, FileProcessResult, IncludeResult, SkipReason, SkippedFile};
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_console_progress_without_a_terminal_prints_every_tenth() {
+        let mut output = Vec::new();
+        let mut progress = ConsoleProgress::new(&mut output, false);
+        for index in 0..20 {
+            let path = format!("doc{index}.md");
+            progress.update(&ProcessingEvent::FileStarted {
+                path: path.clone(),
+                index,
+                total: 20,
+            });
+            progress.update(&ProcessingEvent::FileFinished(FileProcessResult {
+                file_path: path,
+                success: true,
+                includes: Vec::new(),
+                error_message: None,
+                error_code: None,
+                sizes: None,
+            }));
+        }
+        let output = String::from_utf8(output).expect("Invalid UTF-8");
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[0], "Processed 1/20 files (5%)");
+        assert_eq!(lines[1], "Processed 2/20 files (10%)");
+        assert_eq!(lines[10], "Processed 20/20 files (100%)");
+        assert!(!output.contains('\r'));
+    }
+
+    #[test]
+    fn test_progress_bar() {
+        assert_eq!(progress_bar(5, 10, 10), "[#####-----]  50% (5/10)");
+        assert_eq!(progress_bar(0, 0, 4), "[####] 100% (0/0)");
+    }
+
+    #[test]
+    fn test_verbosity_flags_and_summary_line() {
+        assert_eq!(Verbosity::from_flags(false, 0), Verbosity::Normal);
+        assert_eq!(Verbosity::from_flags(false, 1), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(false, 3), Verbosity::Debug);
+        assert_eq!(Verbosity::from_flags(true, 0), Verbosity::Quiet);
+        assert!(Verbosity::Debug.is_verbose() && !Verbosity::Normal.is_verbose());
+
+        let mut summary = ProcessingSummary::new();
+        assert_eq!(
+            summary_line(&summary),
+            "md2md: ok. 0 files (0 failed), 0 includes (0 failed)"
+        );
+        summary.add_result(FileProcessResult {
+            file_path: "doc.md".to_string(),
+            success: false,
+            includes: Vec::new(),
+            error_message: Some("Missing partial".to_string()),
+            error_code: None,
+            sizes: None,
+        });
+        assert_eq!(
+            summary_line(&summary),
+            "md2md: FAILED. 1 files (1 failed), 0 includes (0 failed)"
+        );
+    }
+
+    #[test]
+    fn test_exit_code_fails_on_a_failed_file() {
+        let mut summary = ProcessingSummary::new();
+        assert_eq!(exit_code(&summary), 0);
+        summary.add_result(FileProcessResult {
+            file_path: "doc.md".to_string(),
+            success: false,
+            includes: Vec::new(),
+            error_message: Some("Warnings are errors with --strict".to_string()),
+            error_code: None,
+            sizes: None,
+        });
+        assert_eq!(exit_code(&summary), 1);
+    }
+
+    #[test]
+    fn test_skipped_line() {
+        let mut summary = ProcessingSummary::new();
+        assert_eq!(skipped_line(&summary), None);
+        summary.apply(&ProcessingEvent::FilesSkipped(
+            [
+                ("docs/logo.png", SkipReason::NotMarkdown),
+                ("docs/drafts/a.md", SkipReason::Ignored),
+                ("docs/drafts/b.md", SkipReason::Ignored),
+            ]
+            .map(|(path, reason)| SkippedFile {
+                path: path.to_string(),
+                reason,
+            })
+            .to_vec(),
+        ));
+        assert_eq!(
+            skipped_line(&summary).as_deref(),
+            Some("Skipped 3 files: 2 ignored by .md2mdignore, 1 not Markdown.")
+        );
+    }
+
+    #[test]
+    fn test_format_github_annotations_for_failed_include() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_file = temp_dir.path().join("doc.md");
+        fs::write(&source_file, "# Doc\n\n!include (missing.md)\n")
+            .expect("Failed to write doc.md");
+
+        let mut summary = ProcessingSummary::new();
+        summary.add_result(FileProcessResult {
+            file_path: source_file.to_string_lossy().to_string(),
+            success: false,
+            includes: vec![IncludeResult {
+                path: "partials/missing.md".to_string(),
+                success: false,
+                error_message: Some("No such file or directory".to_string()),
+                error_code: None,
+                parent: "docs/index.md".to_string(),
+                depth: 0,
+                title: None,
+                values: HashMap::new(),
+                duration: None,
+                bytes: None,
+                attempts: None,
+                kind: DirectiveKind::Include,
+            }],
+            error_message: Some("File contains failed includes".to_string()),
+            error_code: None,
+            sizes: None,
+        });
+
+        let annotations = format_github_annotations(&summary);
+        assert_eq!(annotations.len(), 1);
+        assert!(annotations[0].starts_with("::error file="));
+        assert!(annotations[0].contains(",line=3::"));
+        assert!(
+            annotations[0]
+                .ends_with("::Failed to include partials/missing.md: No such file or directory")
+        );
+    }
+
+    #[test]
+    fn test_format_github_annotations_escapes_message() {
+        let mut summary = ProcessingSummary::new();
+        summary.add_result(FileProcessResult {
+            file_path: "docs/a,b.md".to_string(),
+            success: false,
+            includes: vec![],
+            error_message: Some("50% done\nthen failed".to_string()),
+            error_code: None,
+            sizes: None,
+        });
+
+        let annotations = format_github_annotations(&summary);
+        assert_eq!(
+            annotations,
+            vec!["::error file=docs/a%2Cb.md::50%25 done%0Athen failed".to_string()]
+        );
+    }
+}
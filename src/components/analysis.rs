@@ -1,13 +1,19 @@
 use crate::app::App;
+use crate::components::scroll::render_scrollable;
+use crate::theme::Theme;
+use crate::usage::{heat_bar, partial_failures};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style, Stylize},
+    style::Stylize,
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph, Wrap},
 };
 
-pub fn render(f: &mut Frame, app: &App, area: Rect) {
+/// Offending paths listed under each error category
+const TOP_PATHS: usize = 5;
+
+pub fn render(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
     let summary = app
         .summary
         .lock()
@@ -16,7 +22,7 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
     if summary.results.is_empty() {
         let empty = Paragraph::new("No analysis available yet...")
             .block(Block::default().borders(Borders::ALL).title("Analysis"))
-            .style(Style::default().fg(Color::Gray));
+            .style(theme.muted);
         f.render_widget(empty, area);
         return;
     }
@@ -42,119 +48,124 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
         .count();
     let failed_includes = total_includes - successful_includes;
 
-    let stats = vec![
+    let mut stats = vec![
         Line::from(vec![
             Span::raw("Files: "),
-            Span::styled(
-                format!("{} total", summary.results.len()),
-                Style::default().fg(Color::Cyan),
-            ),
+            Span::styled(format!("{} total", summary.results.len()), theme.accent),
         ]),
         Line::from(vec![
             Span::raw("  ✓ "),
-            Span::styled(
-                format!("{successful_files} successful"),
-                Style::default().fg(Color::Green),
-            ),
+            Span::styled(format!("{successful_files} successful"), theme.success),
         ]),
         Line::from(vec![
             Span::raw("  ✗ "),
-            Span::styled(
-                format!("{failed_files} failed"),
-                Style::default().fg(Color::Red),
-            ),
+            Span::styled(format!("{failed_files} failed"), theme.error),
         ]),
         Line::from(""),
         Line::from(vec![
             Span::raw("Includes: "),
-            Span::styled(
-                format!("{total_includes} total"),
-                Style::default().fg(Color::Cyan),
-            ),
+            Span::styled(format!("{total_includes} total"), theme.accent),
         ]),
         Line::from(vec![
             Span::raw("  ✓ "),
-            Span::styled(
-                format!("{successful_includes} successful"),
-                Style::default().fg(Color::Green),
-            ),
+            Span::styled(format!("{successful_includes} successful"), theme.success),
         ]),
         Line::from(vec![
             Span::raw("  ✗ "),
-            Span::styled(
-                format!("{failed_includes} failed"),
-                Style::default().fg(Color::Red),
-            ),
+            Span::styled(format!("{failed_includes} failed"), theme.error),
         ]),
     ];
+    for count in summary.get_directives_by_kind() {
+        stats.push(Line::from(vec![
+            Span::raw(format!("  !{}: ", count.kind.as_str())),
+            Span::styled(format!("{} total", count.total), theme.accent),
+            Span::raw(", "),
+            Span::styled(format!("{} failed", count.failed), theme.error),
+        ]));
+    }
 
     let stats_widget = Paragraph::new(stats)
         .block(Block::default().borders(Borders::ALL).title("Statistics"))
         .wrap(Wrap { trim: true });
     f.render_widget(stats_widget, chunks[0]);
 
-    // Error Analysis
+    // Error Analysis, grouped by category so long runs of failures stay readable
     let mut error_analysis = Vec::new();
+    let categories = summary.get_error_categories();
 
-    let file_errors: Vec<_> = summary.results.iter().filter(|r| !r.success).collect();
-
-    let include_errors: Vec<_> = summary
-        .results
-        .iter()
-        .flat_map(|r| &r.includes)
-        .filter(|i| !i.success)
-        .collect();
-
-    if file_errors.is_empty() && include_errors.is_empty() {
+    if categories.is_empty() {
         error_analysis.push(Line::from(Span::styled(
             "No errors found ✓",
-            Style::default().fg(Color::Green).bold(),
+            theme.success.bold(),
         )));
-    } else {
-        if !file_errors.is_empty() {
-            error_analysis.push(Line::from(Span::styled(
-                "File Processing Errors:",
-                Style::default().fg(Color::Red).bold(),
-            )));
-            for error in file_errors {
-                error_analysis.push(Line::from(vec![
-                    Span::raw("  • "),
-                    Span::styled(&error.file_path, Style::default().fg(Color::Magenta)),
-                    Span::raw(": "),
-                    Span::styled(
-                        error.error_message.as_deref().unwrap_or("Unknown error"),
-                        Style::default().fg(Color::Yellow),
+    }
+
+    // The partials behind the most failed documents, worth fixing first
+    let failures = partial_failures(&summary);
+    if let Some(worst) = failures.first() {
+        let most = worst.documents.len();
+        error_analysis.push(Line::from(Span::styled(
+            "Failures by partial:",
+            theme.error.bold(),
+        )));
+        for partial in failures.iter().take(TOP_PATHS) {
+            let documents = partial.documents.len();
+            error_analysis.push(Line::from(vec![
+                Span::raw("  "),
+                Span::styled(
+                    format!("{:<10} ", heat_bar(documents, most, 10)),
+                    theme.error,
+                ),
+                Span::styled(partial.path.clone(), theme.path),
+                Span::styled(
+                    format!(
+                        " {documents} document{}",
+                        if documents == 1 { "" } else { "s" }
                     ),
-                ]));
-            }
-            error_analysis.push(Line::from(""));
+                    theme.muted,
+                ),
+            ]));
         }
-
-        if !include_errors.is_empty() {
+        error_analysis.push(Line::from(""));
+    }
+    for category in categories {
+        error_analysis.push(Line::from(vec![
+            Span::styled(capitalize(category.category.as_str()), theme.error.bold()),
+            Span::raw(": "),
+            Span::styled(format!("{}", category.count), theme.accent),
+        ]));
+        for (path, count) in category.paths.iter().take(TOP_PATHS) {
+            error_analysis.push(Line::from(vec![
+                Span::raw("  • "),
+                Span::styled(path.clone(), theme.path),
+                Span::styled(format!(" ×{count}"), theme.muted),
+            ]));
+        }
+        if category.paths.len() > TOP_PATHS {
             error_analysis.push(Line::from(Span::styled(
-                "Include Processing Errors:",
-                Style::default().fg(Color::Red).bold(),
+                format!("  … and {} more", category.paths.len() - TOP_PATHS),
+                theme.muted,
             )));
-            for error in include_errors {
-                error_analysis.push(Line::from(vec![
-                    Span::raw("  • "),
-                    Span::styled(&error.path, Style::default().fg(Color::Magenta)),
-                    Span::raw(": "),
-                    Span::styled(
-                        error.error_message.as_deref().unwrap_or("Unknown error"),
-                        Style::default().fg(Color::Yellow),
-                    ),
-                ]));
-            }
         }
+        error_analysis.push(Line::from(""));
     }
 
-    let error_widget = Paragraph::new(error_analysis)
-        .block(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Error Analysis"),
-        )
-        .wrap(Wrap { trim: true });
-    f.render_widget(error_widget, chunks[1]);
+    app.max_scroll.set(0);
+    render_scrollable(
+        f,
+        app,
+        error_analysis,
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Error Analysis"),
+        chunks[1],
+        true,
+    );
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
 }
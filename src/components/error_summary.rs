@@ -1,13 +1,15 @@
 use crate::app::App;
+use crate::components::scroll::render_scrollable;
+use crate::theme::Theme;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style, Stylize},
+    style::Stylize,
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph, Wrap},
 };
 
-pub fn render(f: &mut Frame, app: &App, area: Rect) {
+pub fn render(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
     let summary = app
         .summary
         .lock()
@@ -25,10 +27,7 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
 
     if file_errors.is_empty() && include_errors.is_empty() {
         let no_errors = Paragraph::new(vec![
-            Line::from(Span::styled(
-                "No errors found ✓",
-                Style::default().fg(Color::Green).bold(),
-            )),
+            Line::from(Span::styled("No errors found ✓", theme.success.bold())),
             Line::from(""),
             Line::from("All files and includes were processed successfully."),
         ])
@@ -57,37 +56,41 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
             },
         ])
         .split(area);
+    app.max_scroll.set(0);
 
     // File Errors Section
     if !file_errors.is_empty() {
         let mut error_lines = vec![
             Line::from(Span::styled(
                 format!("File Processing Errors ({}):", file_errors.len()),
-                Style::default().fg(Color::Red).bold(),
+                theme.error.bold(),
             )),
             Line::from(""),
         ];
 
         for error in &file_errors {
             error_lines.push(Line::from(vec![
-                Span::styled("✗ ", Style::default().fg(Color::Red)),
-                Span::styled(&error.file_path, Style::default().fg(Color::Magenta).bold()),
+                Span::styled("✗ ", theme.error),
+                Span::styled(&error.file_path, theme.path.bold()),
             ]));
 
-            if let Some(error_msg) = &error.error_message {
+            if let Some(error_msg) = error.formatted_error() {
                 error_lines.push(Line::from(vec![
                     Span::raw("  → "),
-                    Span::styled(error_msg, Style::default().fg(Color::Yellow)),
+                    Span::styled(error_msg, theme.warning),
                 ]));
             }
             error_lines.push(Line::from(""));
         }
 
-        let file_errors_widget = Paragraph::new(error_lines)
-            .block(Block::default().borders(Borders::ALL).title("File Errors"))
-            .wrap(Wrap { trim: true });
-
-        f.render_widget(file_errors_widget, chunks[0]);
+        render_scrollable(
+            f,
+            app,
+            error_lines,
+            Block::default().borders(Borders::ALL).title("File Errors"),
+            chunks[0],
+            true,
+        );
     }
 
     // Include Errors Section
@@ -95,35 +98,36 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
         let mut error_lines = vec![
             Line::from(Span::styled(
                 format!("Include Processing Errors ({}):", include_errors.len()),
-                Style::default().fg(Color::Red).bold(),
+                theme.error.bold(),
             )),
             Line::from(""),
         ];
 
         for error in &include_errors {
             error_lines.push(Line::from(vec![
-                Span::styled("✗ ", Style::default().fg(Color::Red)),
-                Span::styled(&error.path, Style::default().fg(Color::Magenta).bold()),
+                Span::styled("✗ ", theme.error),
+                Span::styled(&error.path, theme.path.bold()),
             ]));
 
-            if let Some(error_msg) = &error.error_message {
+            if let Some(error_msg) = error.formatted_error() {
                 error_lines.push(Line::from(vec![
                     Span::raw("  → "),
-                    Span::styled(error_msg, Style::default().fg(Color::Yellow)),
+                    Span::styled(error_msg, theme.warning),
                 ]));
             }
             error_lines.push(Line::from(""));
         }
 
-        let include_errors_widget = Paragraph::new(error_lines)
-            .block(
-                Block::default()
-                    .borders(Borders::ALL)
-                    .title("Include Errors"),
-            )
-            .wrap(Wrap { trim: true });
-
         let chunk_idx = if file_errors.is_empty() { 0 } else { 1 };
-        f.render_widget(include_errors_widget, chunks[chunk_idx]);
+        render_scrollable(
+            f,
+            app,
+            error_lines,
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Include Errors"),
+            chunks[chunk_idx],
+            true,
+        );
     }
 }
@@ -1,13 +1,18 @@
 use crate::app::App;
+use crate::components::scroll::{inner_height, render_scrollable, render_scrollbar};
+use crate::theme::Theme;
+use crate::types::{FileProcessResult, ProcessingSummary};
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Modifier, Style, Stylize},
+    style::{Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap},
 };
+use std::fs;
+use std::path::Path;
 
-pub fn render(f: &mut Frame, app: &App, area: Rect) {
+pub fn render(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
     let summary = app
         .summary
         .lock()
@@ -16,7 +21,7 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
     if summary.results.is_empty() {
         let empty = Paragraph::new("No files processed yet...")
             .block(Block::default().borders(Borders::ALL).title("Files"))
-            .style(Style::default().fg(Color::Gray));
+            .style(theme.muted);
         f.render_widget(empty, area);
         return;
     }
@@ -27,94 +32,165 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
         .split(area);
 
     // Files list
-    let items: Vec<ListItem> = summary
-        .results
-        .iter()
-        .enumerate()
-        .map(|(i, result)| {
-            let style = if i == app.selected_file_index {
-                if result.success {
-                    Style::default().bg(Color::Green).fg(Color::Black)
-                } else {
-                    Style::default().bg(Color::Red).fg(Color::White)
-                }
-            } else if result.success {
-                Style::default().fg(Color::Green)
+    let files = app.visible_files(&summary);
+    let selected = files.get(app.selected_file_index).copied();
+    let selected_include = app
+        .selected_include
+        .filter(|&index| selected.is_some_and(|result| index < result.includes.len()));
+    let mut items: Vec<ListItem> = Vec::new();
+    for (i, result) in files.iter().enumerate() {
+        let style = if i == app.selected_file_index && selected_include.is_none() {
+            if result.success {
+                theme.selected_success
             } else {
-                Style::default().fg(Color::Red)
-            };
+                theme.selected_error
+            }
+        } else {
+            theme.status(result.success)
+        };
 
-            let icon = if result.success { "✓" } else { "✗" };
-            let includes_info = if result.includes.is_empty() {
-                String::new()
-            } else {
-                format!(" ({} includes)", result.includes.len())
+        let icon = if result.success { "✓" } else { "✗" };
+        let includes_info = if result.includes.is_empty() {
+            String::new()
+        } else {
+            format!(" ({} includes)", result.includes.len())
+        };
+
+        items.push(
+            ListItem::new(format!("{} {}{}", icon, result.file_path, includes_info)).style(style),
+        );
+
+        // The includes of the selected file are listed under it
+        if i != app.selected_file_index {
+            continue;
+        }
+        for (index, include) in result.includes.iter().enumerate() {
+            let style = match (selected_include == Some(index), include.success) {
+                (true, true) => theme.selected_success,
+                (true, false) => theme.selected_error,
+                (false, success) => theme.status(success),
             };
+            let icon = if include.success { "✓" } else { "✗" };
+            items.push(
+                ListItem::new(format!(
+                    "{}{icon} {}",
+                    "  ".repeat(include.depth + 1),
+                    include.path
+                ))
+                .style(style),
+            );
+        }
+    }
+    let selected_row = app.selected_file_index + selected_include.map_or(0, |index| index + 1);
 
-            ListItem::new(format!("{} {}{}", icon, result.file_path, includes_info)).style(style)
-        })
-        .collect();
+    // An open preview takes the place of the list
+    if let Some(preview) = &app.preview {
+        app.max_scroll.set(0);
+        render_scrollable(
+            f,
+            app,
+            preview.content.lines().map(Line::raw).collect(),
+            Block::default()
+                .borders(Borders::ALL)
+                .title(format!("Preview: {} (p to close)", preview.path)),
+            chunks[0],
+            false,
+        );
+    } else {
+        // In diff mode the list shares its row with the selected file's diff
+        let list_area = if app.config.diff {
+            let columns = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+                .split(chunks[0]);
+            if let Some(selected_result) = selected {
+                render_diff(f, &summary, &selected_result.file_path, columns[1], theme);
+            }
+            columns[0]
+        } else {
+            chunks[0]
+        };
 
-    let files_list = List::new(items)
-        .block(Block::default().borders(Borders::ALL).title("Files"))
-        .highlight_style(Style::default().add_modifier(Modifier::BOLD));
-    f.render_widget(files_list, chunks[0]);
+        let title = if app.filter_editing || !app.filter.is_empty() {
+            let cursor = if app.filter_editing { "_" } else { "" };
+            format!(
+                "Files ({}/{}) /{}{cursor}",
+                files.len(),
+                summary.results.len(),
+                app.filter
+            )
+        } else {
+            "Files".to_string()
+        };
+        let rows = items.len();
+        let files_list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::BOLD));
+        let mut list_state = ListState::default()
+            .with_offset(app.files_offset.get())
+            .with_selected(Some(selected_row));
+        f.render_stateful_widget(files_list, list_area, &mut list_state);
+        app.files_offset.set(list_state.offset());
+        app.page_height.set(inner_height(list_area));
+        if rows > inner_height(list_area) {
+            render_scrollbar(f, list_area, rows, selected_row);
+        }
+    }
 
     // File details
-    if let Some(selected_result) = summary.results.get(app.selected_file_index) {
+    if let Some(selected_result) = selected {
         let mut details = vec![
             Line::from(vec![
                 Span::raw("File: "),
-                Span::styled(&selected_result.file_path, Style::default().fg(Color::Cyan)),
+                Span::styled(&selected_result.file_path, theme.accent),
             ]),
             Line::from(vec![
                 Span::raw("Status: "),
                 if selected_result.success {
-                    Span::styled("Success", Style::default().fg(Color::Green))
+                    Span::styled("Success", theme.success)
                 } else {
-                    Span::styled("Failed", Style::default().fg(Color::Red))
+                    Span::styled("Failed", theme.error)
                 },
             ]),
         ];
 
-        if let Some(error) = &selected_result.error_message {
+        if let Some(error) = selected_result.formatted_error() {
             details.push(Line::from(vec![
                 Span::raw("Error: "),
-                Span::styled(error, Style::default().fg(Color::Red)),
+                Span::styled(error, theme.error),
             ]));
         }
 
+        // Nesting is shown in the Tree tab; only the failed includes are listed here
         if !selected_result.includes.is_empty() {
-            details.push(Line::from(Span::styled(
-                format!("Includes ({}):", selected_result.includes.len()),
-                Style::default().fg(Color::Yellow),
-            )));
-
-            for include in &selected_result.includes {
-                let status = if include.success { "✓" } else { "✗" };
-                let style = if include.success {
-                    Style::default().fg(Color::Green)
-                } else {
-                    Style::default().fg(Color::Magenta).bold()
-                };
+            let failed: Vec<_> = selected_result
+                .includes
+                .iter()
+                .filter(|include| !include.success)
+                .collect();
+            details.push(Line::from(vec![
+                Span::styled(
+                    format!(
+                        "Includes: {} ({} failed)",
+                        selected_result.includes.len(),
+                        failed.len()
+                    ),
+                    theme.warning,
+                ),
+                Span::styled(" · see the Tree tab", theme.muted),
+            ]));
 
+            for include in failed {
                 let mut line_spans = vec![
                     Span::raw("  "),
-                    Span::styled(
-                        format!("{status} "),
-                        if include.success {
-                            Style::default().fg(Color::Green)
-                        } else {
-                            Style::default().fg(Color::Red)
-                        },
-                    ),
-                    Span::styled(&include.path, style),
+                    Span::styled("✗ ", theme.error),
+                    Span::styled(&include.path, theme.path.bold()),
                 ];
 
                 // Add error message inline if present
-                if let Some(error) = &include.error_message {
-                    line_spans.push(Span::styled(" → ", Style::default().fg(Color::Gray)));
-                    line_spans.push(Span::styled(error, Style::default().fg(Color::Yellow)));
+                if let Some(error) = include.formatted_error() {
+                    line_spans.push(Span::styled(" → ", theme.muted));
+                    line_spans.push(Span::styled(error, theme.warning));
                 }
 
                 details.push(Line::from(line_spans));
@@ -125,5 +201,156 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
             .block(Block::default().borders(Borders::ALL).title("File Details"))
             .wrap(Wrap { trim: true });
         f.render_widget(details_widget, chunks[1]);
+
+        if let (true, Some(index)) = (app.include_details, selected_include) {
+            render_include_details(f, selected_result, index, area, theme);
+        }
     }
 }
+
+/// Popup with everything known about the include at `index` of `result`
+fn render_include_details(
+    f: &mut Frame,
+    result: &FileProcessResult,
+    index: usize,
+    area: Rect,
+    theme: &Theme,
+) {
+    let include = &result.includes[index];
+    let path = Path::new(&include.path);
+    let absolute = fs::canonicalize(path)
+        .or_else(|_| std::path::absolute(path))
+        .map_or_else(|_| include.path.clone(), |p| p.display().to_string());
+
+    let mut lines = vec![
+        Line::from(vec![
+            Span::raw("Path: "),
+            Span::styled(absolute, theme.path.bold()),
+        ]),
+        Line::from(vec![
+            Span::raw("Status: "),
+            if include.success {
+                Span::styled("Included", theme.success)
+            } else {
+                Span::styled("Failed", theme.error)
+            },
+        ]),
+        Line::from(vec![
+            Span::raw("Resolution time: "),
+            Span::styled(
+                include.duration.map_or_else(
+                    || "not measured".to_string(),
+                    |duration| format!("{:.2}ms", duration.as_secs_f64() * 1000.0),
+                ),
+                theme.accent,
+            ),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled("Included from:", theme.text.bold())),
+    ];
+    for (level, step) in result.include_chain(index).into_iter().enumerate() {
+        let branch = if level == 0 { "" } else { "└ " };
+        lines.push(Line::from(vec![
+            Span::raw(format!(
+                "  {}{branch}",
+                "  ".repeat(level.saturating_sub(1))
+            )),
+            Span::styled(step.to_string(), theme.path),
+        ]));
+    }
+
+    lines.push(Line::from(""));
+    lines.push(Line::from(Span::styled("Parameters:", theme.text.bold())));
+    if include.title.is_none() && include.values.is_empty() {
+        lines.push(Line::from(Span::styled("  none", theme.muted)));
+    }
+    if let Some(title) = &include.title {
+        lines.push(Line::from(format!("  title = \"{title}\"")));
+    }
+    let mut values: Vec<_> = include.values.iter().collect();
+    values.sort();
+    for (name, value) in values {
+        lines.push(Line::from(format!("  values.{name} = \"{value}\"")));
+    }
+
+    if let Some(error) = include.formatted_error() {
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Error:", theme.error.bold())));
+        lines.extend(
+            error
+                .lines()
+                .map(|line| Line::from(Span::styled(format!("  {line}"), theme.error))),
+        );
+    }
+
+    let popup = centered(area, 80, 70);
+    f.render_widget(Clear, popup);
+    f.render_widget(
+        Paragraph::new(lines)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Include Details (Enter/Esc to close)")
+                    .border_style(theme.accent),
+            )
+            .wrap(Wrap { trim: false }),
+        popup,
+    );
+}
+
+/// Area of `percent_x` by `percent_y` percent in the middle of `area`
+fn centered(area: Rect, percent_x: u16, percent_y: u16) -> Rect {
+    let [_, middle, _] = Layout::vertical([
+        Constraint::Percentage((100 - percent_y) / 2),
+        Constraint::Percentage(percent_y),
+        Constraint::Percentage((100 - percent_y) / 2),
+    ])
+    .areas(area);
+    let [_, center, _] = Layout::horizontal([
+        Constraint::Percentage((100 - percent_x) / 2),
+        Constraint::Percentage(percent_x),
+        Constraint::Percentage((100 - percent_x) / 2),
+    ])
+    .areas(middle);
+    center
+}
+
+/// Unified diffs of the outputs of `file_path`, colored by line kind
+fn render_diff(
+    f: &mut Frame,
+    summary: &ProcessingSummary,
+    file_path: &str,
+    area: Rect,
+    theme: &Theme,
+) {
+    let lines: Vec<Line> = summary
+        .diffs
+        .iter()
+        .filter(|diff| diff.file_path == file_path)
+        .flat_map(|diff| diff.diff.lines())
+        .map(|line| {
+            let style = if line.starts_with("+++") || line.starts_with("---") {
+                theme.text
+            } else if line.starts_with('+') {
+                theme.success
+            } else if line.starts_with('-') {
+                theme.error
+            } else if line.starts_with("@@") {
+                theme.accent
+            } else {
+                theme.muted
+            };
+            Line::from(Span::styled(line, style))
+        })
+        .collect();
+
+    let diff_widget = if lines.is_empty() {
+        Paragraph::new("Outputs are up to date").style(theme.muted)
+    } else {
+        Paragraph::new(lines)
+    };
+    f.render_widget(
+        diff_widget.block(Block::default().borders(Borders::ALL).title("Diff")),
+        area,
+    );
+}
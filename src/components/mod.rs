@@ -2,10 +2,17 @@ pub mod analysis;
 pub mod error_summary;
 pub mod files;
 pub mod progress;
+pub mod scroll;
 pub mod summary;
+pub mod tree;
+pub mod warnings;
+pub mod watch;
 
 pub use analysis::render as render_analysis;
 pub use error_summary::render as render_error_summary;
 pub use files::render as render_files;
 pub use progress::render as render_progress;
 pub use summary::render as render_summary;
+pub use tree::render as render_tree;
+pub use warnings::render as render_warnings;
+pub use watch::render as render_watch;
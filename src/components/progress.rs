@@ -1,13 +1,13 @@
 use crate::app::App;
+use crate::theme::Theme;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Gauge, List, ListItem, Paragraph},
+    widgets::{Block, Borders, Gauge, LineGauge, List, ListItem, Paragraph},
 };
 
-pub fn render(f: &mut Frame, app: &App, area: Rect) {
+pub fn render(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
     let summary = app
         .summary
         .lock()
@@ -17,7 +17,7 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
         .direction(Direction::Vertical)
         .constraints([
             Constraint::Length(3),
-            Constraint::Length(3),
+            Constraint::Length(4),
             Constraint::Length(8),
             Constraint::Min(0),
         ])
@@ -31,7 +31,7 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
                 .borders(Borders::ALL)
                 .title("Overall Progress"),
         )
-        .gauge_style(Style::default().fg(Color::Blue))
+        .gauge_style(theme.progress)
         .percent(progress as u16)
         .label(format!(
             "{:.1}% ({}/{})",
@@ -39,11 +39,28 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
         ));
     f.render_widget(gauge, chunks[0]);
 
-    // Current file
+    // Current file, with the directive being resolved under it
+    let block = Block::default().borders(Borders::ALL).title("Processing");
+    let [file_row, include_row] = Layout::vertical([Constraint::Length(1), Constraint::Length(1)])
+        .areas(block.inner(chunks[1]));
+    f.render_widget(block, chunks[1]);
     let current_file = summary.current_file.as_deref().unwrap_or("None");
-    let current_info = Paragraph::new(format!("Current file: {current_file}"))
-        .block(Block::default().borders(Borders::ALL).title("Processing"));
-    f.render_widget(current_info, chunks[1]);
+    f.render_widget(
+        Paragraph::new(format!("Current file: {current_file}")),
+        file_row,
+    );
+    if let Some(include) = &summary.current_include {
+        let gauge = LineGauge::default()
+            .filled_style(theme.progress)
+            .ratio(include.index as f64 / include.total as f64)
+            .label(format!(
+                "resolving include {}/{}: {}",
+                include.index + 1,
+                include.total,
+                include.path
+            ));
+        f.render_widget(gauge, include_row);
+    }
 
     // Statistics
     let elapsed = if let Some(completion_time) = app.completion_time {
@@ -54,48 +71,30 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
     let stats_text = vec![
         Line::from(vec![
             Span::raw("Files processed: "),
-            Span::styled(
-                format!("{}", summary.processed_files),
-                Style::default().fg(Color::Green),
-            ),
+            Span::styled(format!("{}", summary.processed_files), theme.success),
             Span::raw(" / "),
-            Span::styled(
-                format!("{}", summary.total_files),
-                Style::default().fg(Color::Blue),
-            ),
+            Span::styled(format!("{}", summary.total_files), theme.progress),
         ]),
         Line::from(vec![
             Span::raw("Successful: "),
-            Span::styled(
-                format!("{}", summary.get_success_count()),
-                Style::default().fg(Color::Green),
-            ),
+            Span::styled(format!("{}", summary.get_success_count()), theme.success),
         ]),
         Line::from(vec![
             Span::raw("Failed: "),
-            Span::styled(
-                format!("{}", summary.get_failed_count()),
-                Style::default().fg(Color::Red),
-            ),
+            Span::styled(format!("{}", summary.get_failed_count()), theme.error),
         ]),
         Line::from(vec![
             Span::raw("Includes processed: "),
             Span::styled(
                 format!("{}", summary.get_successful_includes()),
-                Style::default().fg(Color::Green),
+                theme.success,
             ),
             Span::raw(" / "),
-            Span::styled(
-                format!("{}", summary.get_total_includes()),
-                Style::default().fg(Color::Blue),
-            ),
+            Span::styled(format!("{}", summary.get_total_includes()), theme.progress),
         ]),
         Line::from(vec![
             Span::raw("Elapsed time: "),
-            Span::styled(
-                format!("{:.1}s", elapsed.as_secs_f64()),
-                Style::default().fg(Color::Yellow),
-            ),
+            Span::styled(format!("{:.1}s", elapsed.as_secs_f64()), theme.warning),
         ]),
     ];
 
@@ -111,9 +110,9 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
         .take(10)
         .map(|result| {
             let style = if result.success {
-                Style::default().fg(Color::Green)
+                theme.success
             } else {
-                Style::default().fg(Color::Red)
+                theme.error
             };
             let icon = if result.success { "✓" } else { "✗" };
             ListItem::new(format!("{} {}", icon, result.file_path)).style(style)
@@ -0,0 +1,64 @@
+use crate::app::App;
+use ratatui::{
+    Frame,
+    layout::{Margin, Rect},
+    text::Line,
+    widgets::{Block, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap},
+};
+
+/// Renders wrapped `lines` in `block` at the app's scroll offset, with a scrollbar
+/// when they don't fit; `trim` drops the leading whitespace of wrapped lines.
+///
+/// The pane's limits are recorded in the app for paging; with several panes in a
+/// tab the longest one sets `max_scroll`, so reset it before rendering the first.
+pub fn render_scrollable(
+    f: &mut Frame,
+    app: &App,
+    lines: Vec<Line>,
+    block: Block,
+    area: Rect,
+    trim: bool,
+) {
+    let max_scroll = wrapped_height(&lines, area).saturating_sub(inner_height(area));
+    let offset = app.scroll_offset.min(max_scroll);
+    app.max_scroll.set(app.max_scroll.get().max(max_scroll));
+    app.page_height.set(inner_height(area));
+
+    let paragraph = Paragraph::new(lines)
+        .block(block)
+        .wrap(Wrap { trim })
+        .scroll((u16::try_from(offset).unwrap_or(u16::MAX), 0));
+    f.render_widget(paragraph, area);
+    render_scrollbar(f, area, max_scroll + 1, offset);
+}
+
+/// Rows `lines` take inside a bordered pane of `area` when wrapped
+pub fn wrapped_height(lines: &[Line], area: Rect) -> usize {
+    let width = usize::from(area.width.saturating_sub(2)).max(1);
+    lines
+        .iter()
+        .map(|line| line.width().div_ceil(width).max(1))
+        .sum()
+}
+
+/// Rows of content visible inside a bordered pane
+pub fn inner_height(area: Rect) -> usize {
+    usize::from(area.height.saturating_sub(2))
+}
+
+/// Draws a vertical scrollbar on the right border of `area` for `position` out of
+/// `positions` scroll positions; nothing is drawn when there is only one
+pub fn render_scrollbar(f: &mut Frame, area: Rect, positions: usize, position: usize) {
+    if positions <= 1 {
+        return;
+    }
+    let mut state = ScrollbarState::new(positions).position(position);
+    f.render_stateful_widget(
+        Scrollbar::new(ScrollbarOrientation::VerticalRight),
+        area.inner(Margin {
+            vertical: 1,
+            horizontal: 0,
+        }),
+        &mut state,
+    );
+}
@@ -1,14 +1,18 @@
 use crate::app::App;
+use crate::banner::utc_timestamp;
+use crate::history::RunRecord;
+use crate::theme::Theme;
+use crate::types::ProcessingSummary;
 use ratatui::{
     Frame,
     layout::{Constraint, Direction, Layout, Rect},
-    style::{Color, Style, Stylize},
+    style::Stylize,
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph, Wrap},
 };
 use std::time::Duration;
 
-pub fn render(f: &mut Frame, app: &App, area: Rect) {
+pub fn render(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
     let summary = app
         .summary
         .lock()
@@ -44,106 +48,121 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
 
     let status = if app.processing_complete {
         if failed_files > 0 || failed_includes > 0 {
-            ("COMPLETE WITH ERRORS", Color::Yellow)
+            ("COMPLETE WITH ERRORS", theme.warning)
         } else {
-            ("COMPLETE", Color::Green)
+            ("COMPLETE", theme.success)
         }
     } else {
-        ("PROCESSING", Color::Blue)
+        ("PROCESSING", theme.progress)
     };
 
     let mut content = vec![
         Line::from(vec![
             Span::raw("Status: "),
-            Span::styled(status.0, Style::default().fg(status.1).bold()),
+            Span::styled(status.0, status.1.bold()),
         ]),
         Line::from(vec![
             Span::raw("Elapsed Time: "),
-            Span::styled(format_duration(elapsed), Style::default().fg(Color::Cyan)),
+            Span::styled(format_duration(elapsed), theme.accent),
         ]),
-        Line::from(""),
-        Line::from(Span::styled(
-            "FILES",
-            Style::default().fg(Color::White).bold(),
-        )),
         Line::from(vec![
-            Span::raw("Total: "),
+            Span::raw("Started: "),
             Span::styled(
-                summary.results.len().to_string(),
-                Style::default().fg(Color::Cyan),
+                summary
+                    .started_at
+                    .map_or_else(|| "–".to_string(), utc_timestamp),
+                theme.muted,
             ),
+            Span::styled(format!(" (md2md {})", summary.version), theme.muted),
+        ]),
+        Line::from(""),
+        Line::from(Span::styled("FILES", theme.text.bold())),
+        Line::from(vec![
+            Span::raw("Total: "),
+            Span::styled(summary.results.len().to_string(), theme.accent),
         ]),
         Line::from(vec![
             Span::raw("Successful: "),
-            Span::styled(
-                successful_files.to_string(),
-                Style::default().fg(Color::Green),
-            ),
+            Span::styled(successful_files.to_string(), theme.success),
         ]),
         Line::from(vec![
             Span::raw("Failed: "),
-            Span::styled(
-                failed_files.to_string(),
-                Style::default().fg(if failed_files > 0 {
-                    Color::Red
-                } else {
-                    Color::Green
-                }),
-            ),
+            Span::styled(failed_files.to_string(), theme.status(failed_files == 0)),
         ]),
+        Line::from(vec![
+            Span::raw("Skipped: "),
+            Span::styled(summary.skipped.len().to_string(), theme.muted),
+        ]),
+    ];
+    // Why files weren't processed, e.g. `  ignored by .md2mdignore: 2`
+    for (reason, count) in summary.get_skipped_by_reason() {
+        content.push(Line::from(vec![
+            Span::raw(format!("  {}: ", reason.as_str())),
+            Span::styled(count.to_string(), theme.muted),
+        ]));
+    }
+    content.extend([
         Line::from(""),
-        Line::from(Span::styled(
-            "INCLUDES",
-            Style::default().fg(Color::White).bold(),
-        )),
+        Line::from(Span::styled("INCLUDES", theme.text.bold())),
         Line::from(vec![
             Span::raw("Total: "),
-            Span::styled(total_includes.to_string(), Style::default().fg(Color::Cyan)),
+            Span::styled(total_includes.to_string(), theme.accent),
         ]),
         Line::from(vec![
             Span::raw("Successful: "),
-            Span::styled(
-                successful_includes.to_string(),
-                Style::default().fg(Color::Green),
-            ),
+            Span::styled(successful_includes.to_string(), theme.success),
         ]),
         Line::from(vec![
             Span::raw("Failed: "),
             Span::styled(
                 failed_includes.to_string(),
-                Style::default().fg(if failed_includes > 0 {
-                    Color::Red
-                } else {
-                    Color::Green
-                }),
+                theme.status(failed_includes == 0),
             ),
         ]),
-    ];
+    ]);
+
+    let sizes = summary.get_size_stats();
+    if let Some((file, bytes)) = &sizes.largest_output {
+        content.extend([
+            Line::from(""),
+            Line::from(Span::styled("SIZES", theme.text.bold())),
+            Line::from(vec![
+                Span::raw("Read: "),
+                Span::styled(format!("{} bytes", sizes.bytes_read), theme.accent),
+            ]),
+            Line::from(vec![
+                Span::raw("Written: "),
+                Span::styled(format!("{} bytes", sizes.bytes_written), theme.accent),
+            ]),
+            Line::from(vec![
+                Span::raw("Largest output: "),
+                Span::styled(file.clone(), theme.path),
+                Span::styled(format!(" ({bytes} bytes)"), theme.muted),
+            ]),
+        ]);
+        if let Some(ratio) = sizes.average_expansion {
+            content.push(Line::from(vec![
+                Span::raw("Avg expansion: "),
+                Span::styled(format!("{ratio:.2}×"), theme.accent),
+            ]));
+        }
+    }
 
     // Add performance metrics if complete
     if app.processing_complete && !summary.results.is_empty() {
         let avg_time_per_file = elapsed.as_millis() as f64 / summary.results.len() as f64;
         content.push(Line::from(""));
-        content.push(Line::from(Span::styled(
-            "PERFORMANCE",
-            Style::default().fg(Color::White).bold(),
-        )));
+        content.push(Line::from(Span::styled("PERFORMANCE", theme.text.bold())));
         content.push(Line::from(vec![
             Span::raw("Avg time per file: "),
-            Span::styled(
-                format!("{avg_time_per_file:.2}ms"),
-                Style::default().fg(Color::Cyan),
-            ),
+            Span::styled(format!("{avg_time_per_file:.2}ms"), theme.accent),
         ]));
 
         if total_includes > 0 {
             let avg_time_per_include = elapsed.as_millis() as f64 / total_includes as f64;
             content.push(Line::from(vec![
                 Span::raw("Avg time per include: "),
-                Span::styled(
-                    format!("{avg_time_per_include:.2}ms"),
-                    Style::default().fg(Color::Cyan),
-                ),
+                Span::styled(format!("{avg_time_per_include:.2}ms"), theme.accent),
             ]));
         }
     }
@@ -155,7 +174,16 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
                 .title("Processing Summary"),
         )
         .wrap(Wrap { trim: true });
-    f.render_widget(summary_widget, chunks[0]);
+    match &app.previous_run {
+        Some(previous) => {
+            let [summary_area, comparison_area] =
+                Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                    .areas(chunks[0]);
+            f.render_widget(summary_widget, summary_area);
+            render_comparison(f, app, previous, &summary, comparison_area, theme);
+        }
+        None => f.render_widget(summary_widget, chunks[0]),
+    }
 
     // Recent Activity (last few processed files)
     let recent_activity: Vec<Line> = summary
@@ -166,9 +194,9 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
         .map(|result| {
             let icon = if result.success { "✓" } else { "✗" };
             let style = if result.success {
-                Style::default().fg(Color::Green)
+                theme.success
             } else {
-                Style::default().fg(Color::Red)
+                theme.error
             };
 
             let mut spans = vec![
@@ -179,7 +207,7 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
             if !result.includes.is_empty() {
                 spans.push(Span::styled(
                     format!(" ({} includes)", result.includes.len()),
-                    Style::default().fg(Color::Gray),
+                    theme.muted,
                 ));
             }
 
@@ -194,7 +222,7 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
                     .borders(Borders::ALL)
                     .title("Recent Activity"),
             )
-            .style(Style::default().fg(Color::Gray))
+            .style(theme.muted)
     } else {
         Paragraph::new(recent_activity)
             .block(
@@ -208,6 +236,69 @@ pub fn render(f: &mut Frame, app: &App, area: Rect) {
     f.render_widget(activity_widget, chunks[1]);
 }
 
+/// Files added, removed, newly failing and newly fixed since the previous run of the profile
+fn render_comparison(
+    f: &mut Frame,
+    app: &App,
+    previous: &RunRecord,
+    summary: &ProcessingSummary,
+    area: Rect,
+    theme: &Theme,
+) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title("Since Last Run");
+    if !app.processing_complete {
+        f.render_widget(
+            Paragraph::new("Compared once the run is complete...")
+                .block(block)
+                .style(theme.muted),
+            area,
+        );
+        return;
+    }
+
+    let delta = previous.compare(summary);
+    if delta.is_empty() {
+        f.render_widget(
+            Paragraph::new(Line::from(Span::styled(
+                "Same files and outcomes as the last run",
+                theme.success,
+            )))
+            .block(block),
+            area,
+        );
+        return;
+    }
+
+    // Regressions first, since they are what needs attention
+    let mut lines = Vec::new();
+    for (title, icon, files, style) in [
+        ("Newly failing", "✗", &delta.newly_failing, theme.error),
+        ("Newly fixed", "✓", &delta.newly_fixed, theme.success),
+        ("Added", "+", &delta.added, theme.accent),
+        ("Removed", "-", &delta.removed, theme.muted),
+    ] {
+        if files.is_empty() {
+            continue;
+        }
+        lines.push(Line::from(Span::styled(
+            format!("{title}: {}", files.len()),
+            style.bold(),
+        )));
+        lines.extend(files.iter().map(|file| {
+            Line::from(vec![
+                Span::styled(format!("  {icon} "), style),
+                Span::styled(file.as_str(), theme.path),
+            ])
+        }));
+    }
+    f.render_widget(
+        Paragraph::new(lines).block(block).wrap(Wrap { trim: true }),
+        area,
+    );
+}
+
 fn format_duration(duration: Duration) -> String {
     let total_seconds = duration.as_secs();
     let minutes = total_seconds / 60;
@@ -0,0 +1,68 @@
+use crate::app::App;
+use crate::components::scroll::{inner_height, render_scrollbar};
+use crate::theme::Theme;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+};
+
+pub fn render(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
+    let summary = app
+        .summary
+        .lock()
+        .expect("Failed to acquire summary lock for tree rendering");
+
+    let rows = app.tree_rows(&summary);
+    if rows.is_empty() {
+        let empty = Paragraph::new("No files processed yet...")
+            .block(Block::default().borders(Borders::ALL).title("Tree"))
+            .style(theme.muted);
+        f.render_widget(empty, area);
+        return;
+    }
+    let selected = app.tree_selected.min(rows.len() - 1);
+
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|row| {
+            let marker = match (row.expandable, row.expanded) {
+                (false, _) => "  ",
+                (true, true) => "▾ ",
+                (true, false) => "▸ ",
+            };
+            let icon = if row.success { "✓" } else { "✗" };
+            let style = theme.status(row.success);
+            let mut spans = vec![
+                Span::raw("  ".repeat(row.level)),
+                Span::raw(marker),
+                Span::styled(format!("{icon} "), style),
+                Span::styled(row.path, style),
+            ];
+            if let Some(error) = &row.error {
+                spans.push(Span::styled(" → ", theme.muted));
+                spans.push(Span::styled(error.clone(), theme.warning));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+
+    let tree = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Tree (Space/Enter to expand or collapse)"),
+        )
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    let mut state = ListState::default()
+        .with_offset(app.tree_offset.get())
+        .with_selected(Some(selected));
+    f.render_stateful_widget(tree, area, &mut state);
+    app.tree_offset.set(state.offset());
+    app.page_height.set(inner_height(area));
+    if rows.len() > inner_height(area) {
+        render_scrollbar(f, area, rows.len(), selected);
+    }
+}
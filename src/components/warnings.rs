@@ -0,0 +1,83 @@
+use crate::app::App;
+use crate::components::scroll::render_scrollable;
+use crate::theme::Theme;
+use crate::types::WarningKind;
+use ratatui::{
+    Frame,
+    layout::Rect,
+    style::Stylize,
+    text::{Line, Span},
+    widgets::{Block, Borders},
+};
+
+pub fn render(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
+    let summary = app
+        .summary
+        .lock()
+        .expect("Failed to acquire summary lock for warnings rendering");
+
+    let counts: Vec<String> = [
+        WarningKind::FenceFixed,
+        WarningKind::DefaultValue,
+        WarningKind::DeprecatedSyntax,
+        WarningKind::IncludesOutput,
+        WarningKind::LocaleFallback,
+        WarningKind::DuplicateTitle,
+        WarningKind::Lint,
+        WarningKind::Misspelling,
+    ]
+    .iter()
+    .filter_map(|kind| {
+        let count = summary.warnings.iter().filter(|w| w.kind == *kind).count();
+        (count > 0).then(|| format!("{count} {}", kind.as_str()))
+    })
+    .collect();
+    let mut lines = vec![
+        Line::from(vec![
+            Span::styled(
+                format!("Warnings ({})", summary.warnings.len()),
+                theme.warning.bold(),
+            ),
+            Span::styled(format!("  {}", counts.join(" · ")), theme.muted),
+        ]),
+        Line::from(""),
+    ];
+
+    // Warnings arrive file by file, so those of a source file are next to each other
+    let mut source = None;
+    for warning in &summary.warnings {
+        if source != Some(&warning.source) {
+            if source.is_some() {
+                lines.push(Line::from(""));
+            }
+            source = Some(&warning.source);
+            lines.push(Line::from(vec![
+                Span::styled("⚠ ", theme.warning),
+                Span::styled(&warning.source, theme.path.bold()),
+            ]));
+        }
+        let location = if warning.file == warning.source {
+            format!("line {}", warning.line)
+        } else {
+            format!("{}:{}", warning.file, warning.line)
+        };
+        lines.push(Line::from(vec![
+            Span::styled(format!("  {:<18}", warning.kind.as_str()), theme.accent),
+            Span::styled(location, theme.path),
+        ]));
+        lines.push(Line::from(vec![
+            Span::raw("    → "),
+            Span::styled(&warning.message, theme.text),
+        ]));
+    }
+
+    app.max_scroll.set(0);
+    render_scrollable(
+        f,
+        app,
+        lines,
+        Block::default().borders(Borders::ALL).title("Warnings"),
+        area,
+        true,
+    );
+}
@@ -0,0 +1,142 @@
+use crate::app::App;
+use crate::components::scroll::render_scrollable;
+use crate::theme::Theme;
+use ratatui::{
+    Frame,
+    layout::{Constraint, Direction, Layout, Rect},
+    style::Modifier,
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+};
+use std::time::Instant;
+
+pub fn render(f: &mut Frame, app: &App, area: Rect, theme: &Theme) {
+    let Some(watch) = &app.watch else {
+        return;
+    };
+    let summary = app
+        .summary
+        .lock()
+        .expect("Failed to acquire summary lock for watch rendering");
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(4), Constraint::Min(0)])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+        .split(chunks[1]);
+
+    // Status
+    let state = if watch.rerunning {
+        Span::styled("↻ Re-running", theme.warning.add_modifier(Modifier::BOLD))
+    } else if app.processing_complete {
+        Span::styled("● Watching", theme.success)
+    } else {
+        Span::styled("… First run", theme.warning)
+    };
+    let last_run = watch
+        .last_run
+        .map(|at| format!("last run {}", ago(at)))
+        .unwrap_or_else(|| "no run finished yet".to_string());
+    let status = vec![
+        Line::from(vec![
+            state,
+            Span::raw(format!(
+                "  {} and {}",
+                app.config.source_path.display(),
+                app.config.partials_path.display()
+            )),
+        ]),
+        Line::from(vec![
+            Span::raw(format!("Run #{} · {last_run} · ", watch.runs)),
+            Span::styled(format!("{} ok", summary.get_success_count()), theme.success),
+            Span::raw(" · "),
+            Span::styled(
+                format!("{} failed", summary.get_failed_count()),
+                theme.error,
+            ),
+        ]),
+    ];
+    f.render_widget(
+        Paragraph::new(status).block(Block::default().borders(Borders::ALL).title("Watch")),
+        chunks[0],
+    );
+
+    // Last-run status per file; files processed by the latest run are marked
+    let files: Vec<Line> = summary
+        .results
+        .iter()
+        .map(|result| {
+            let run = watch.file_runs.get(&result.file_path).copied().unwrap_or(0);
+            let icon = if result.success { "✓" } else { "✗" };
+            let marker = if run > 0 && run == watch.runs {
+                Span::styled("↻ ", theme.warning)
+            } else {
+                Span::raw("  ")
+            };
+            Line::from(vec![
+                marker,
+                Span::styled(
+                    format!("{icon} {}", result.file_path),
+                    theme.status(result.success),
+                ),
+                Span::styled(format!("  run #{run}"), theme.muted),
+            ])
+        })
+        .collect();
+    app.max_scroll.set(0);
+    render_scrollable(
+        f,
+        app,
+        files,
+        Block::default()
+            .borders(Borders::ALL)
+            .title("Files (last run)"),
+        columns[0],
+        true,
+    );
+
+    // Changed files feed
+    let changes: Vec<ListItem> = watch
+        .changes
+        .iter()
+        .flat_map(|change| {
+            let header = Line::from(vec![
+                Span::styled(format!("#{} ", change.run), theme.accent),
+                Span::styled(ago(change.at), theme.muted),
+                Span::raw(format!(" → {} file(s) re-run", change.affected)),
+            ]);
+            std::iter::once(ListItem::new(header)).chain(
+                change
+                    .paths
+                    .iter()
+                    .map(|path| ListItem::new(format!("  {path}"))),
+            )
+        })
+        .collect();
+    let feed = if changes.is_empty() {
+        List::new(vec![ListItem::new("No changes yet").style(theme.muted)])
+    } else {
+        List::new(changes)
+    };
+    f.render_widget(
+        feed.block(
+            Block::default()
+                .borders(Borders::ALL)
+                .title("Changed files"),
+        ),
+        columns[1],
+    );
+}
+
+/// How long ago `at` was, e.g. "12s ago"
+fn ago(at: Instant) -> String {
+    let seconds = at.elapsed().as_secs();
+    match seconds {
+        0..60 => format!("{seconds}s ago"),
+        60..3600 => format!("{}m ago", seconds / 60),
+        _ => format!("{}h ago", seconds / 3600),
+    }
+}
@@ -0,0 +1,197 @@
+//! Conditions of conditional includes, e.g. `!include(cloud.md, if="target == 'cloud'")`
+//!
+//! A condition compares variables with `==` and `!=`, tests a bare variable name
+//! for being set, and combines those with `!`, `&&`, `||` and parentheses.
+
+use std::collections::HashMap;
+
+/// Whether `condition` holds for `variables`.
+///
+/// A bare name holds when the variable is set to anything but an empty string
+/// or `false`; unset variables compare unequal to every value.
+pub fn evaluate(condition: &str, variables: &HashMap<String, String>) -> Result<bool, String> {
+    let tokens = tokenize(condition)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        position: 0,
+        variables,
+    };
+    let result = parser.or()?;
+    match parser.tokens.get(parser.position) {
+        None => Ok(result),
+        Some(token) => Err(format!("Unexpected {token:?} in condition `{condition}`")),
+    }
+}
+
+/// Checks that `condition` is well formed, without any variables
+pub fn validate(condition: &str) -> Result<(), String> {
+    evaluate(condition, &HashMap::new()).map(|_| ())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Name(String),
+    Text(String),
+    Equal,
+    NotEqual,
+    Not,
+    And,
+    Or,
+    Open,
+    Close,
+}
+
+fn tokenize(condition: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = condition.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::Equal,
+            '!' if chars.next_if(|&(_, c)| c == '=').is_some() => Token::NotEqual,
+            '!' => Token::Not,
+            '&' if chars.next_if(|&(_, c)| c == '&').is_some() => Token::And,
+            '|' if chars.next_if(|&(_, c)| c == '|').is_some() => Token::Or,
+            '\'' | '"' => {
+                let text: String = chars
+                    .by_ref()
+                    .map(|(_, next)| next)
+                    .take_while(|&next| next != c)
+                    .collect();
+                if !condition[start + 1..].contains(c) {
+                    return Err(format!("Unterminated string in condition `{condition}`"));
+                }
+                Token::Text(text)
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some((_, next)) =
+                    // Dots separate the parts of names like `pkg.version`
+                    chars.next_if(|&(_, next)| {
+                        next.is_alphanumeric() || matches!(next, '_' | '-' | '.')
+                    })
+                {
+                    name.push(next);
+                }
+                Token::Name(name)
+            }
+            other => return Err(format!("Unexpected `{other}` in condition `{condition}`")),
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens, `||` binding loosest and `!` tightest
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    variables: &'a HashMap<String, String>,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matches = self.tokens.get(self.position) == Some(token);
+        if matches {
+            self.position += 1;
+        }
+        matches
+    }
+
+    fn or(&mut self) -> Result<bool, String> {
+        let mut result = self.and()?;
+        while self.eat(&Token::Or) {
+            result |= self.and()?;
+        }
+        Ok(result)
+    }
+
+    fn and(&mut self) -> Result<bool, String> {
+        let mut result = self.unary()?;
+        while self.eat(&Token::And) {
+            result &= self.unary()?;
+        }
+        Ok(result)
+    }
+
+    fn unary(&mut self) -> Result<bool, String> {
+        match self.next().cloned() {
+            Some(Token::Not) => Ok(!self.unary()?),
+            Some(Token::Open) => {
+                let result = self.or()?;
+                if self.eat(&Token::Close) {
+                    Ok(result)
+                } else {
+                    Err("Missing `)` in condition".to_string())
+                }
+            }
+            Some(Token::Name(name)) => {
+                let value = self.variables.get(&name);
+                let equal = if self.eat(&Token::Equal) {
+                    true
+                } else if self.eat(&Token::NotEqual) {
+                    false
+                } else {
+                    return Ok(value.is_some_and(|value| !value.is_empty() && value != "false"));
+                };
+                let expected = match self.next() {
+                    Some(Token::Text(text) | Token::Name(text)) => text,
+                    _ => return Err(format!("Expected a value to compare `{name}` with")),
+                };
+                Ok((value == Some(expected)) == equal)
+            }
+            Some(token) => Err(format!("Unexpected {token:?} in condition")),
+            None => Err("Condition ends too early".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evaluate_conditions() {
+        let variables = HashMap::from([
+            ("target".to_string(), "cloud".to_string()),
+            ("beta".to_string(), "true".to_string()),
+            ("legacy".to_string(), "false".to_string()),
+        ]);
+        let holds = |condition: &str| {
+            evaluate(condition, &variables).expect("Failed to evaluate condition")
+        };
+
+        assert!(holds("target == 'cloud'"));
+        assert!(holds(r#"target == "cloud""#));
+        assert!(!holds("target != 'cloud'"));
+        assert!(holds("region != 'eu'"));
+        assert!(!holds("region == 'eu'"));
+        assert!(holds("beta"));
+        assert!(!holds("legacy"));
+        assert!(!holds("missing"));
+        assert!(holds("!missing && beta"));
+        assert!(holds("target == 'on-prem' || beta"));
+        assert!(!holds("!(target == 'cloud' || legacy)"));
+        assert!(holds("target == cloud"));
+        assert!(!holds("pkg.version == '1.0.0'"));
+
+        for malformed in [
+            "",
+            "target ==",
+            "target = 'cloud'",
+            "(beta",
+            "beta)",
+            "'open",
+        ] {
+            assert!(validate(malformed).is_err(), "{malformed}");
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use crate::lint::LintConfig;
+use crate::manifest::package_variables;
+use crate::processor::ProcessorBuilder;
+use crate::spellcheck::SpellcheckConfig;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Name of the project configuration file looked up in the working directory
+pub const CONFIG_FILE_NAME: &str = "md2md.toml";
+
+/// Project settings read from `md2md.toml`.
+///
+/// Every field is optional; command-line arguments always take precedence.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Source file or directory to process
+    pub source: Option<PathBuf>,
+    /// Directory containing the partials
+    pub partials: Option<PathBuf>,
+    /// Output file or directory
+    pub output: Option<PathBuf>,
+    /// Command run on every written output file, e.g. `prettier --write {}`
+    pub post_process: Option<String>,
+    /// Line put at the top of every output, e.g. `<!-- Generated from {source} -->`
+    pub banner: Option<String>,
+    /// Variables available to every partial, under `[variables]`; `--var` overrides them
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    /// Rules checked on every output, under `[lint]`; absent, nothing is linted
+    pub lint: Option<LintConfig>,
+    /// Checker and project dictionaries, under `[spellcheck]`; `--spellcheck` overrides the command
+    pub spellcheck: Option<SpellcheckConfig>,
+    /// Glossary file whose terms are linked in every output; `--glossary` overrides it
+    pub glossary: Option<PathBuf>,
+    /// Generated files built by `md2md build`, under `[targets.NAME]`
+    #[serde(default)]
+    pub targets: BTreeMap<String, Target>,
+}
+
+/// A single generated file, such as the README, built from one source document
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Target {
+    /// Source document
+    pub source: PathBuf,
+    /// File written
+    pub output: PathBuf,
+    /// Directory containing the partials. Default: the project's `partials`
+    pub partials: Option<PathBuf>,
+    /// Variables on top of the project's, under `[targets.NAME.variables]`
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+}
+
+impl Config {
+    /// Reads and parses a configuration file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read config file {path:?}: {e}"))?;
+        let config =
+            toml::from_str(&content).map_err(|e| format!("Invalid config file {path:?}: {e}"))?;
+        Ok(config)
+    }
+
+    /// Loads `path` if given, otherwise `md2md.toml` from the working directory when it exists.
+    ///
+    /// The `pkg.*` variables of the manifest next to the configuration file, or in
+    /// the working directory without one, are added under `[variables]`.
+    pub fn discover(path: Option<&Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut config = match path {
+            Some(path) => Self::load(path)?,
+            None if Path::new(CONFIG_FILE_NAME).is_file() => {
+                Self::load(Path::new(CONFIG_FILE_NAME))?
+            }
+            None => Self::default(),
+        };
+        let directory = path
+            .and_then(Path::parent)
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        config.add_package_variables(directory);
+        Ok(config)
+    }
+
+    /// Adds the `pkg.*` variables of the manifest in `directory`, unless `[variables]` sets them.
+    ///
+    /// A manifest that can't be read only logs a warning, as the variables may not be used.
+    pub fn add_package_variables(&mut self, directory: &Path) {
+        let variables = package_variables(directory).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Package variables left out");
+            HashMap::new()
+        });
+        for (name, value) in variables {
+            self.variables.entry(name).or_insert(value);
+        }
+    }
+
+    /// A processor for the target `name`, with the project's banner, post-processing,
+    /// glossary, lint rules and spellchecker, and its variables under the target's
+    pub fn target(&self, name: &str) -> Result<ProcessorBuilder, String> {
+        let Some(target) = self.targets.get(name) else {
+            let known: Vec<&str> = self.targets.keys().map(String::as_str).collect();
+            return Err(match known.as_slice() {
+                [] => {
+                    format!("No target `{name}`: {CONFIG_FILE_NAME} has no [targets.NAME] tables")
+                }
+                known => format!(
+                    "No target `{name}` in {CONFIG_FILE_NAME}; targets: {}",
+                    known.join(", ")
+                ),
+            });
+        };
+        let partials = target
+            .partials
+            .clone()
+            .or_else(|| self.partials.clone())
+            .unwrap_or_else(|| PathBuf::from("partials"));
+        // The target's variables are set last, overriding the project's
+        let mut builder = self.variables.iter().chain(&target.variables).fold(
+            ProcessorBuilder::default()
+                .source(&target.source)
+                .partials(partials)
+                .output(&target.output),
+            |builder, (name, value)| builder.var(name, value),
+        );
+        if let Some(command) = &self.post_process {
+            builder = builder.post_process(command);
+        }
+        if let Some(banner) = &self.banner {
+            builder = builder.banner(banner);
+        }
+        if let Some(glossary) = &self.glossary {
+            builder = builder.glossary(glossary);
+        }
+        if let Some(rules) = &self.lint {
+            builder = builder.lint(rules.clone());
+        }
+        if let Some(spellcheck) = &self.spellcheck {
+            builder = builder.spellcheck(spellcheck.clone());
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_config() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let config_path = temp_dir.path().join(CONFIG_FILE_NAME);
+        fs::write(
+            &config_path,
+            "source = \"docs\"\npartials = \"partials\"\noutput = \"out\"\n",
+        )
+        .expect("Failed to write config");
+
+        let config = Config::load(&config_path).expect("Failed to load config");
+        assert_eq!(config.source, Some(PathBuf::from("docs")));
+        assert_eq!(config.partials, Some(PathBuf::from("partials")));
+        assert_eq!(config.output, Some(PathBuf::from("out")));
+        assert!(config.variables.is_empty());
+        assert_eq!(config.lint, None);
+    }
+
+    #[test]
+    fn test_load_config_spellcheck() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let config_path = temp_dir.path().join(CONFIG_FILE_NAME);
+        fs::write(
+            &config_path,
+            "[spellcheck]\ncommand = \"hunspell -l\"\ndictionaries = [\"words.txt\"]\n",
+        )
+        .expect("Failed to write config");
+
+        let config = Config::load(&config_path).expect("Failed to load config");
+        assert_eq!(
+            config.spellcheck,
+            Some(SpellcheckConfig {
+                command: "hunspell -l".to_string(),
+                dictionaries: vec![PathBuf::from("words.txt")],
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_config_lint_rules() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let config_path = temp_dir.path().join(CONFIG_FILE_NAME);
+        fs::write(
+            &config_path,
+            "[lint]\nmax_line_length = 100\nbare_urls = false\n",
+        )
+        .expect("Failed to write config");
+
+        let config = Config::load(&config_path).expect("Failed to load config");
+        assert_eq!(
+            config.lint,
+            Some(LintConfig {
+                max_line_length: 100,
+                bare_urls: false,
+                ..LintConfig::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_load_config_variables() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let config_path = temp_dir.path().join(CONFIG_FILE_NAME);
+        fs::write(
+            &config_path,
+            "[variables]\ntarget = \"cloud\"\n\"pkg.name\" = \"Docs\"\n",
+        )
+        .expect("Failed to write config");
+        fs::write(
+            temp_dir.path().join("package.json"),
+            r#"{"name": "docs", "version": "1.2.0"}"#,
+        )
+        .expect("Failed to write package.json");
+
+        let mut config = Config::load(&config_path).expect("Failed to load config");
+        config.add_package_variables(temp_dir.path());
+        // [variables] wins over the manifest
+        assert_eq!(
+            config.variables,
+            HashMap::from([
+                ("target".to_string(), "cloud".to_string()),
+                ("pkg.name".to_string(), "Docs".to_string()),
+                ("pkg.version".to_string(), "1.2.0".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_discover_reads_the_manifest_next_to_the_config() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let config_path = temp_dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&config_path, "").expect("Failed to write config");
+        fs::write(temp_dir.path().join("package.json"), r#"{"name": "docs"}"#)
+            .expect("Failed to write package.json");
+
+        let config = Config::discover(Some(&config_path)).expect("Failed to discover config");
+        assert_eq!(config.variables["pkg.name"], "docs");
+
+        // A broken manifest only leaves its variables out
+        fs::write(temp_dir.path().join("package.json"), "{").expect("Failed to break package.json");
+        let config = Config::discover(Some(&config_path)).expect("Failed to discover config");
+        assert!(config.variables.is_empty());
+    }
+
+    #[test]
+    fn test_targets_build_one_file_each() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("docs")).expect("Failed to create docs directory");
+        fs::create_dir_all(root.join("partials")).expect("Failed to create partials directory");
+        fs::write(root.join("docs/README.md"), "# Readme").expect("Failed to write README.md");
+        let config_path = root.join(CONFIG_FILE_NAME);
+        fs::write(
+            &config_path,
+            format!(
+                "partials = {partials:?}\nbanner = \"<!-- generated -->\"\n\n[variables]\nwho = \"world\"\nlang = \"en\"\n\n[targets.readme]\nsource = {source:?}\noutput = {output:?}\nvariables = {{ who = \"users\" }}\n",
+                partials = root.join("partials"),
+                source = root.join("docs/README.md"),
+                output = root.join("README.md"),
+            ),
+        )
+        .expect("Failed to write config");
+
+        let config = Config::load(&config_path).expect("Failed to load config");
+        assert_eq!(config.targets.len(), 1);
+        let processing = config
+            .target("readme")
+            .expect("readme should be a target")
+            .build_config()
+            .expect("Failed to build target config");
+        assert_eq!(processing.source_path, root.join("docs/README.md"));
+        assert_eq!(processing.output_path, root.join("README.md"));
+        assert_eq!(processing.partials_path, root.join("partials"));
+        assert!(!processing.batch);
+        assert_eq!(processing.banner.as_deref(), Some("<!-- generated -->"));
+        assert_eq!(processing.variables["who"], "users");
+        assert_eq!(processing.variables["lang"], "en");
+
+        let Err(error) = config.target("contributing") else {
+            panic!("Unknown targets should be rejected");
+        };
+        assert!(error.contains("targets: readme"), "{error}");
+    }
+
+    #[test]
+    fn test_load_config_rejects_unknown_keys() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let config_path = temp_dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&config_path, "sauce = \"docs\"\n").expect("Failed to write config");
+
+        let error = Config::load(&config_path).expect_err("Unknown keys should be rejected");
+        assert!(error.to_string().contains("Invalid config file"));
+    }
+}
@@ -0,0 +1,447 @@
+//! `md2md daemon`: resolves documents sent over a Unix socket, keeping the
+//! partials it resolved cached between requests, e.g. for an editor that
+//! processes the open file on every keystroke.
+//!
+//! The protocol is one JSON object per line each way:
+//!
+//! ```text
+//! → {"method": "process", "path": "docs/guide.md", "content": "!include (header.md)\n…"}
+//! ← {"ok": true, "content": "# Header\n…", "includes": [{"path": "partials/header.md", …}]}
+//! → {"method": "shutdown"}
+//! ← {"ok": true}
+//! ```
+//!
+//! `content` is optional and read from `path` when missing, so unsaved buffers
+//! can be sent as they are. A request that can't be answered gets `"ok": false`
+//! and an `error`; includes that fail are reported in `includes`, as in a run.
+
+use crate::error_code::ErrorCode;
+use crate::include_resolver::{IncludeCache, ResolveOptions, resolve_includes};
+use crate::partial_source::{FileSystemSource, PartialSource, PrefetchSource};
+use crate::types::{Compat, IncludeResult, InputEncoding, ResolutionOrder, TemplateSyntax};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+/// How the daemon resolves documents; like the settings of a run, without the outputs
+#[derive(Debug, Clone, Default)]
+pub struct DaemonOptions {
+    pub partials_path: PathBuf,
+    /// Variables of every document; a request's own variables win
+    pub variables: HashMap<String, String>,
+    /// Language added to code fences that don't specify one; without it they fail
+    pub fix_code_fences: Option<String>,
+    pub compat: Compat,
+    pub passthrough: Option<TemplateSyntax>,
+    pub input_encoding: InputEncoding,
+    pub resolution_order: ResolutionOrder,
+    pub locale: Option<String>,
+}
+
+/// A line sent to the daemon
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "kebab-case")]
+pub enum DaemonRequest {
+    /// Resolves the document at `path`, or `content` as if it were there
+    Process {
+        path: PathBuf,
+        #[serde(default)]
+        content: Option<String>,
+        #[serde(default)]
+        variables: HashMap<String, String>,
+    },
+    Ping,
+    /// Answers, then stops the daemon
+    Shutdown,
+}
+
+/// The line answering a [`DaemonRequest`]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DaemonResponse {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<IncludeResult>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<ErrorCode>,
+}
+
+impl DaemonResponse {
+    fn ok() -> Self {
+        Self {
+            ok: true,
+            ..Self::default()
+        }
+    }
+
+    fn error(error_code: Option<ErrorCode>, message: String) -> Self {
+        Self {
+            error: Some(message),
+            error_code,
+            ..Self::default()
+        }
+    }
+}
+
+/// Answers [`DaemonRequest`]s, with one cache of resolved partials for all of them
+pub struct Daemon {
+    options: DaemonOptions,
+    source: PrefetchSource<FileSystemSource>,
+    cache: IncludeCache,
+}
+
+impl Daemon {
+    pub fn new(options: DaemonOptions) -> Self {
+        let source =
+            PrefetchSource::new(FileSystemSource::default().with_encoding(options.input_encoding));
+        Self {
+            options,
+            source,
+            cache: IncludeCache::new(),
+        }
+    }
+
+    pub fn options(&self) -> &DaemonOptions {
+        &self.options
+    }
+
+    /// How many includes were served from the cache so far
+    pub fn cache_hits(&self) -> usize {
+        self.cache.hits()
+    }
+
+    pub fn handle(&self, request: DaemonRequest) -> DaemonResponse {
+        match request {
+            DaemonRequest::Process {
+                path,
+                content,
+                variables,
+            } => self.process(&path, content, variables),
+            DaemonRequest::Ping | DaemonRequest::Shutdown => DaemonResponse::ok(),
+        }
+    }
+
+    fn process(
+        &self,
+        path: &Path,
+        content: Option<String>,
+        variables: HashMap<String, String>,
+    ) -> DaemonResponse {
+        let _span = tracing::info_span!("request", path = %path.display()).entered();
+        let content = match content {
+            Some(content) => content,
+            None => match self.source.read(path) {
+                Ok(content) => content,
+                Err(e) => {
+                    return DaemonResponse::error(
+                        Some(ErrorCode::UnreadableSource),
+                        format!("Failed to read {}: {e}", path.display()),
+                    );
+                }
+            },
+        };
+        let variables: HashMap<String, String> = self
+            .options
+            .variables
+            .clone()
+            .into_iter()
+            .chain(variables)
+            .collect();
+        let options = self.resolve_options(&variables);
+
+        let mut includes = Vec::new();
+        match resolve_includes(
+            &content,
+            path,
+            &mut includes,
+            self.options.fix_code_fences.as_deref(),
+            &options,
+        ) {
+            Ok(content) => DaemonResponse {
+                ok: true,
+                content: Some(content),
+                includes,
+                ..DaemonResponse::default()
+            },
+            Err(e) => DaemonResponse {
+                includes,
+                ..DaemonResponse::error(
+                    ErrorCode::of(e.as_ref()),
+                    format!("Failed to process includes: {e}"),
+                )
+            },
+        }
+    }
+
+    /// How documents are resolved, with `variables` in place of the daemon's
+    pub(crate) fn resolve_options<'a>(
+        &'a self,
+        variables: &'a HashMap<String, String>,
+    ) -> ResolveOptions<'a> {
+        ResolveOptions {
+            compat: self.options.compat,
+            passthrough: self.options.passthrough,
+            cache: Some(&self.cache),
+            resolution_order: self.options.resolution_order,
+            locale: self.options.locale.as_deref(),
+            ..ResolveOptions::new(&self.options.partials_path, variables, &self.source)
+        }
+    }
+
+    /// Answers every line of `reader` with a line to `writer`, until the reader
+    /// ends or asks for a shutdown; returns whether it did
+    pub fn serve_connection(
+        &self,
+        reader: impl BufRead,
+        mut writer: impl Write,
+    ) -> io::Result<bool> {
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (response, shutdown) = match serde_json::from_str::<DaemonRequest>(&line) {
+                Ok(request) => {
+                    let shutdown = request == DaemonRequest::Shutdown;
+                    (self.handle(request), shutdown)
+                }
+                Err(e) => (
+                    DaemonResponse::error(None, format!("Invalid request: {e}")),
+                    false,
+                ),
+            };
+            serde_json::to_writer(&mut writer, &response)?;
+            writer.write_all(b"\n")?;
+            writer.flush()?;
+            if shutdown {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Serves the connections to `listener` one at a time, until one asks for a shutdown
+    #[cfg(unix)]
+    pub fn serve(&self, listener: &std::os::unix::net::UnixListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = stream?;
+            let reader = io::BufReader::new(stream.try_clone()?);
+            match self.serve_connection(reader, &stream) {
+                Ok(true) => break,
+                Ok(false) => {}
+                // A client going away mid-request doesn't stop the daemon
+                Err(e) => tracing::warn!(error = %e, "Connection failed"),
+            }
+        }
+        tracing::debug!(hits = self.cache_hits(), "Includes served from the cache");
+        Ok(())
+    }
+}
+
+/// Listens at `socket`, replacing the file of a daemon that is no longer running
+#[cfg(unix)]
+pub fn bind(socket: &Path) -> io::Result<std::os::unix::net::UnixListener> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    if socket.exists() {
+        if UnixStream::connect(socket).is_ok() {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("A daemon is already listening at {}", socket.display()),
+            ));
+        }
+        std::fs::remove_file(socket)?;
+    }
+    UnixListener::bind(socket)
+}
+
+/// Fails; named pipes aren't supported yet
+#[cfg(not(unix))]
+pub fn bind(socket: &Path) -> io::Result<std::convert::Infallible> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "Listening at {} needs Unix sockets, which this platform doesn't have",
+            socket.display()
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn project() -> (TempDir, Daemon) {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials).expect("Failed to create partials directory");
+        fs::write(partials.join("header.md"), "# {% product %}").expect("Failed to write header");
+        fs::write(
+            temp_dir.path().join("doc.md"),
+            "!include (header.md)\n\nSaved",
+        )
+        .expect("Failed to write doc.md");
+        let daemon = Daemon::new(DaemonOptions {
+            partials_path: partials,
+            variables: HashMap::from([("product".to_string(), "md2md".to_string())]),
+            ..DaemonOptions::default()
+        });
+        (temp_dir, daemon)
+    }
+
+    #[test]
+    fn test_daemon_answers_each_line() {
+        let (temp_dir, daemon) = project();
+        let doc = temp_dir.path().join("doc.md");
+        let requests = [
+            serde_json::json!({"method": "process", "path": doc}).to_string(),
+            serde_json::json!({
+                "method": "process",
+                "path": doc,
+                "content": "!include (header.md)\n\nUnsaved",
+                "variables": {"product": "Docs"},
+            })
+            .to_string(),
+            serde_json::json!({"method": "process", "path": doc, "content": "!include (missing.md)"})
+                .to_string(),
+            serde_json::json!({"method": "process", "path": temp_dir.path().join("gone.md")})
+                .to_string(),
+            "not json".to_string(),
+            String::new(),
+            serde_json::json!({"method": "shutdown"}).to_string(),
+            serde_json::json!({"method": "ping"}).to_string(),
+        ]
+        .join("\n");
+
+        let mut output = Vec::new();
+        let shutdown = daemon
+            .serve_connection(requests.as_bytes(), &mut output)
+            .expect("Failed to serve connection");
+        assert!(shutdown);
+        let responses: Vec<DaemonResponse> = String::from_utf8(output)
+            .expect("Responses should be UTF-8")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("Failed to parse response"))
+            .collect();
+
+        // Nothing is answered after the shutdown
+        assert_eq!(responses.len(), 6);
+        assert_eq!(responses[0].content.as_deref(), Some("# md2md\n\nSaved"));
+        assert_eq!(responses[0].includes.len(), 1);
+        assert_eq!(responses[1].content.as_deref(), Some("# Docs\n\nUnsaved"));
+        assert!(responses[2].ok);
+        assert!(!responses[2].includes[0].success);
+        assert!(!responses[3].ok);
+        assert_eq!(responses[3].error_code, Some(ErrorCode::UnreadableSource));
+        assert!(!responses[4].ok);
+        assert!(
+            responses[4]
+                .error
+                .as_deref()
+                .is_some_and(|error| error.starts_with("Invalid request"))
+        );
+        assert!(responses[5].ok && responses[5].content.is_none());
+    }
+
+    #[test]
+    fn test_daemon_keeps_resolved_partials_between_requests() {
+        let (temp_dir, daemon) = project();
+        let request = DaemonRequest::Process {
+            path: temp_dir.path().join("doc.md"),
+            content: None,
+            variables: HashMap::new(),
+        };
+        daemon.handle(request.clone());
+        assert_eq!(daemon.cache_hits(), 0);
+        daemon.handle(request.clone());
+        assert_eq!(daemon.cache_hits(), 1);
+
+        // An edited partial is resolved again
+        fs::write(
+            temp_dir.path().join("partials/header.md"),
+            "# {% product %}!",
+        )
+        .expect("Failed to update header");
+        let response = daemon.handle(request);
+        assert_eq!(response.content.as_deref(), Some("# md2md!\n\nSaved"));
+        assert_eq!(daemon.cache_hits(), 1);
+    }
+
+    #[test]
+    fn test_daemon_resolves_again_when_a_nested_partial_changes() {
+        let (temp_dir, daemon) = project();
+        let partials = temp_dir.path().join("partials");
+        fs::write(
+            partials.join("header.md"),
+            "# {% product %}\n!include (inner.md)",
+        )
+        .expect("Failed to update header");
+        fs::write(partials.join("inner.md"), "Inner").expect("Failed to write inner");
+        let request = DaemonRequest::Process {
+            path: temp_dir.path().join("doc.md"),
+            content: None,
+            variables: HashMap::new(),
+        };
+        daemon.handle(request.clone());
+        let response = daemon.handle(request.clone());
+        assert_eq!(daemon.cache_hits(), 1);
+        assert_eq!(response.content.as_deref(), Some("# md2md\nInner\n\nSaved"));
+
+        fs::write(partials.join("inner.md"), "Edited").expect("Failed to update inner");
+        let response = daemon.handle(request);
+        assert_eq!(
+            response.content.as_deref(),
+            Some("# md2md\nEdited\n\nSaved")
+        );
+        assert_eq!(daemon.cache_hits(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_daemon_serves_a_unix_socket() {
+        use std::io::BufReader;
+        use std::os::unix::net::UnixStream;
+
+        let (temp_dir, daemon) = project();
+        let socket = temp_dir.path().join("md2md.sock");
+        // A socket file left behind by a daemon that is gone is replaced
+        drop(bind(&socket).expect("Failed to bind socket"));
+        let listener = bind(&socket).expect("Failed to bind socket again");
+        assert_eq!(
+            bind(&socket).expect_err("The socket is in use").kind(),
+            io::ErrorKind::AddrInUse
+        );
+
+        let doc = temp_dir.path().join("doc.md");
+        let client = std::thread::spawn(move || {
+            let mut stream = UnixStream::connect(&socket).expect("Failed to connect");
+            let mut reader = BufReader::new(stream.try_clone().expect("Failed to clone stream"));
+            let mut lines = Vec::new();
+            for request in [
+                serde_json::json!({"method": "process", "path": doc}),
+                serde_json::json!({"method": "shutdown"}),
+            ] {
+                writeln!(stream, "{request}").expect("Failed to send request");
+                let mut line = String::new();
+                reader
+                    .read_line(&mut line)
+                    .expect("Failed to read response");
+                lines.push(line);
+            }
+            lines
+        });
+        daemon.serve(&listener).expect("Failed to serve");
+
+        let lines = client.join().expect("Client panicked");
+        let response: DaemonResponse =
+            serde_json::from_str(&lines[0]).expect("Failed to parse response");
+        assert_eq!(response.content.as_deref(), Some("# md2md\n\nSaved"));
+        assert_eq!(lines[1].trim(), r#"{"ok":true}"#);
+    }
+}
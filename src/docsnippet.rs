@@ -0,0 +1,553 @@
+//! Doc comments of Rust items and docstrings of Python definitions, for `!docsnippet`.
+//!
+//! Items are found by name and nesting, such as `Processor::run` for a method in an
+//! `impl Processor` block or `Parser.parse` for a method of a Python class. Blocks
+//! are told apart by indentation, so the code is expected to be formatted the way
+//! rustfmt or a Python formatter leaves it.
+
+use crate::error_code::ErrorCode;
+use regex::Regex;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Languages doc comments can be read from, told apart by the file extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocLanguage {
+    Rust,
+    Python,
+}
+
+impl DocLanguage {
+    /// The language of a `.rs`, `.py` or `.pyi` file
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "rs" => Some(DocLanguage::Rust),
+            "py" | "pyi" => Some(DocLanguage::Python),
+            _ => None,
+        }
+    }
+}
+
+/// Documentation extracted from a code file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocComment {
+    /// The documentation as Markdown, without comment markers or indentation
+    pub text: String,
+    /// 1-based line of the code file the documentation starts on
+    pub line: usize,
+}
+
+/// The documentation of `item` in `code`, or of the module itself without one.
+///
+/// Rust items are separated by `::`, Python ones by `.` or `::`. Fenced Rust examples
+/// are marked as `rust` and lose their hidden `# ` lines, as rustdoc renders them.
+pub fn extract_docs(
+    code: &str,
+    language: DocLanguage,
+    item: Option<&str>,
+) -> Result<DocComment, Box<dyn std::error::Error>> {
+    let lines: Vec<&str> = code.lines().collect();
+    let path: Vec<&str> = item
+        .map(|item| {
+            item.split("::")
+                .flat_map(|part| match language {
+                    DocLanguage::Python => part.split('.').collect(),
+                    DocLanguage::Rust => vec![part],
+                })
+                .map(str::trim)
+                .collect()
+        })
+        .unwrap_or_default();
+    if path.iter().any(|part| part.is_empty()) {
+        return Err(ErrorCode::InvalidDocSnippetDirective
+            .error(format!("Invalid item `{}`", item.unwrap_or_default())));
+    }
+    let what = || match item {
+        Some(item) => format!("`{item}`"),
+        None => "The module".to_string(),
+    };
+
+    let docs = match language {
+        DocLanguage::Rust => {
+            let docs = if path.is_empty() {
+                rust_module_docs(&lines)
+            } else {
+                let line = find_rust_item(&lines, 0..lines.len(), &path, false)
+                    .ok_or_else(|| not_found(item))?;
+                rust_item_docs(&lines, line)
+            };
+            docs.map(|(text, line)| (rustdoc_markdown(&text), line))
+        }
+        DocLanguage::Python => {
+            let header_end = if path.is_empty() {
+                None
+            } else {
+                Some(
+                    find_python_item(&lines, 0..lines.len(), &path)
+                        .ok_or_else(|| not_found(item))?,
+                )
+            };
+            python_docstring(&lines, header_end)
+        }
+    };
+    let (text, line) = docs.ok_or_else(|| {
+        ErrorCode::MissingDocItem.error(format!("{} has no documentation", what()))
+    })?;
+    Ok(DocComment { text, line })
+}
+
+fn not_found(item: Option<&str>) -> Box<dyn std::error::Error> {
+    ErrorCode::MissingDocItem.error(format!(
+        "No item `{}` in the file",
+        item.unwrap_or_default()
+    ))
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start().len()
+}
+
+/// The `//!` lines at the top of a Rust file, and the line the first one is on
+fn rust_module_docs(lines: &[&str]) -> Option<(String, usize)> {
+    let mut docs = Vec::new();
+    let mut first = None;
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        if let Some(doc) = trimmed.strip_prefix("//!") {
+            first.get_or_insert(index + 1);
+            docs.push(doc.strip_prefix(' ').unwrap_or(doc));
+        } else if !(trimmed.is_empty() || trimmed.starts_with("#!")) {
+            break;
+        }
+    }
+    Some((docs.join("\n"), first?))
+}
+
+/// The line declaring the last item of `path` within `range` of `lines`, looking
+/// for the rest of the path in the blocks of the items the first part names.
+/// Fields and enum variants count as items `inside` a block.
+fn find_rust_item(
+    lines: &[&str],
+    range: std::ops::Range<usize>,
+    path: &[&str],
+    inside: bool,
+) -> Option<usize> {
+    let (name, rest) = path.split_first()?;
+    // Items nested deeper, e.g. in a function body, aren't those of the block
+    let indent = block_indentation(lines, &range);
+    let mut items = range.filter(|&index| indentation(lines[index]) == indent);
+    if rest.is_empty() {
+        return items.find(|&index| {
+            declares_rust_item(lines[index], name)
+                || inside && declares_rust_member(lines[index], name)
+        });
+    }
+    items
+        .filter(|&index| opens_rust_block(lines[index], name))
+        .find_map(|index| find_rust_item(lines, rust_block(lines, index), rest, true))
+}
+
+/// Indentation of the first line of `range` that isn't blank
+fn block_indentation(lines: &[&str], range: &std::ops::Range<usize>) -> usize {
+    lines[range.clone()]
+        .iter()
+        .find(|line| !line.trim().is_empty())
+        .map_or(0, |line| indentation(line))
+}
+
+/// Does `line` declare a function, type, constant, module or macro called `name`?
+fn declares_rust_item(line: &str, name: &str) -> bool {
+    static ITEM_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r#"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|default|extern\s+"[^"]*")\s+)*(?:fn|struct|enum|trait|type|const|static|mod|union|macro_rules!)\s+(?:mut\s+)?(\w+)"#,
+        )
+        .expect("Failed to compile Rust item regex")
+    });
+    ITEM_REGEX
+        .captures(line)
+        .is_some_and(|captures| &captures[1] == name)
+}
+
+/// Does `line` declare a field or an enum variant called `name`?
+fn declares_rust_member(line: &str, name: &str) -> bool {
+    static MEMBER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^\s*(?:pub(?:\([^)]*\))?\s+)?(\w+)\s*(?::[^:]|[({,]|=[^>=]|$)")
+            .expect("Failed to compile Rust member regex")
+    });
+    MEMBER_REGEX
+        .captures(line)
+        .is_some_and(|captures| &captures[1] == name)
+}
+
+/// Does `line` open a block with the items of `name`: its `impl`, or the struct,
+/// enum, trait or module itself?
+fn opens_rust_block(line: &str, name: &str) -> bool {
+    static CONTAINER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"^\s*(?:pub(?:\([^)]*\))?\s+)?(?:unsafe\s+)?(?:struct|enum|trait|union|mod)\s+(\w+)",
+        )
+        .expect("Failed to compile Rust container regex")
+    });
+    static IMPL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^\s*(?:unsafe\s+)?impl\b(?:\s*<[^{]*?>)?\s+([^{]+)")
+            .expect("Failed to compile Rust impl regex")
+    });
+    if let Some(captures) = CONTAINER_REGEX.captures(line) {
+        return &captures[1] == name;
+    }
+    let Some(captures) = IMPL_REGEX.captures(line) else {
+        return false;
+    };
+    // `impl Trait for Type` has the items of `Type`
+    let header = captures[1].split(" where").next().unwrap_or_default();
+    let ty = header.rsplit_once(" for ").map_or(header, |(_, ty)| ty);
+    let ty = ty.split('<').next().unwrap_or_default().trim();
+    ty.rsplit("::").next() == Some(name)
+}
+
+/// The lines inside the block opened on line `start`, up to its closing `}`
+fn rust_block(lines: &[&str], start: usize) -> std::ops::Range<usize> {
+    let header = lines[start].trim_end();
+    if header.ends_with("{}") || header.ends_with(';') {
+        return start + 1..start + 1;
+    }
+    let indent = indentation(lines[start]);
+    let end = (start + 1..lines.len())
+        .find(|&index| {
+            let line = lines[index];
+            indentation(line) <= indent && line.trim_start().starts_with('}')
+        })
+        .unwrap_or(lines.len());
+    start + 1..end
+}
+
+/// The `///` lines above the item on line `item`, past its attributes, and the line
+/// the first one is on
+fn rust_item_docs(lines: &[&str], item: usize) -> Option<(String, usize)> {
+    let mut docs = Vec::new();
+    let mut index = item;
+    while index > 0 {
+        index -= 1;
+        let trimmed = lines[index].trim();
+        if let Some(doc) = trimmed.strip_prefix("///") {
+            docs.push((doc.strip_prefix(' ').unwrap_or(doc), index + 1));
+        } else if trimmed.starts_with("#[") {
+            continue;
+        } else if trimmed.ends_with(']') {
+            // The last line of an attribute split over several lines
+            match (0..index)
+                .rev()
+                .find(|&i| lines[i].trim().starts_with("#["))
+            {
+                Some(attribute) => index = attribute,
+                None => break,
+            }
+        } else {
+            break;
+        }
+    }
+    let (_, first) = *docs.last()?;
+    docs.reverse();
+    let text: Vec<&str> = docs.into_iter().map(|(doc, _)| doc).collect();
+    Some((text.join("\n"), first))
+}
+
+/// Marks the fenced examples of rustdoc Markdown as `rust` and drops their hidden lines
+fn rustdoc_markdown(docs: &str) -> String {
+    const ATTRIBUTES: [&str; 6] = [
+        "rust",
+        "ignore",
+        "no_run",
+        "should_panic",
+        "compile_fail",
+        "test_harness",
+    ];
+    let mut lines = Vec::new();
+    // Whether a fence is open, and whether it holds Rust
+    let mut fence: Option<bool> = None;
+    for line in docs.split('\n') {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        if let Some(info) = trimmed.strip_prefix("```") {
+            fence = match fence {
+                Some(_) => None,
+                None => {
+                    let rust = info.split(',').map(str::trim).all(|attribute| {
+                        attribute.is_empty()
+                            || ATTRIBUTES.contains(&attribute)
+                            || attribute.starts_with("edition")
+                    });
+                    if rust {
+                        lines.push(format!("{indent}```rust"));
+                        fence = Some(true);
+                        continue;
+                    }
+                    Some(false)
+                }
+            };
+        } else if fence == Some(true) && (trimmed == "#" || trimmed.starts_with("# ")) {
+            continue;
+        }
+        lines.push(line.to_string());
+    }
+    lines.join("\n")
+}
+
+/// The line ending the header of the definition of the last item of `path` within
+/// `range` of `lines`, looking for the rest of the path in the bodies of the classes
+/// and functions the first part names
+fn find_python_item(lines: &[&str], range: std::ops::Range<usize>, path: &[&str]) -> Option<usize> {
+    static DEFINITION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^\s*(?:async\s+)?(?:def|class)\s+(\w+)")
+            .expect("Failed to compile Python definition regex")
+    });
+    let (name, rest) = path.split_first()?;
+    let indent = block_indentation(lines, &range);
+    range
+        .clone()
+        .filter(|&index| {
+            indentation(lines[index]) == indent
+                && DEFINITION_REGEX
+                    .captures(lines[index])
+                    .is_some_and(|captures| &captures[1] == *name)
+        })
+        .find_map(|index| {
+            // A signature can go over several lines, up to the `:` starting the body
+            let header_end = (index..range.end).find(|&i| {
+                let line = lines[i].split(" #").next().unwrap_or_default();
+                line.trim_end().ends_with(':')
+            })?;
+            if rest.is_empty() {
+                return Some(header_end);
+            }
+            let body_end = (header_end + 1..range.end)
+                .find(|&i| !lines[i].trim().is_empty() && indentation(lines[i]) <= indent)
+                .unwrap_or(range.end);
+            find_python_item(lines, header_end + 1..body_end, rest)
+        })
+}
+
+/// The docstring starting the body after line `header_end`, or the module's without
+/// one, cleaned up like `inspect.cleandoc`, and the line it starts on
+fn python_docstring(lines: &[&str], header_end: Option<usize>) -> Option<(String, usize)> {
+    let start = header_end.map_or(0, |header_end| header_end + 1);
+    let first = (start..lines.len()).find(|&index| {
+        let trimmed = lines[index].trim();
+        // Comments and a shebang can come before a module's docstring
+        let comment = header_end.is_none() && trimmed.starts_with('#');
+        !trimmed.is_empty() && !comment
+    })?;
+    let opening = lines[first]
+        .trim_start()
+        .trim_start_matches(['r', 'R', 'u', 'U']);
+    let quotes = ["\"\"\"", "'''"]
+        .into_iter()
+        .find(|quotes| opening.starts_with(quotes))?;
+    let opening = &opening[quotes.len()..];
+
+    let mut docstring = Vec::new();
+    if let Some((text, _)) = opening.split_once(quotes) {
+        docstring.push(text);
+    } else {
+        docstring.push(opening);
+        for line in &lines[first + 1..] {
+            match line.split_once(quotes) {
+                Some((text, _)) => {
+                    docstring.push(text);
+                    break;
+                }
+                None => docstring.push(line),
+            }
+        }
+    }
+
+    // The first line is trimmed; the others lose the indentation they share
+    let shared = docstring[1..]
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| indentation(line))
+        .min()
+        .unwrap_or(0);
+    let mut cleaned: Vec<&str> = std::iter::once(docstring[0].trim())
+        .chain(
+            docstring[1..]
+                .iter()
+                .map(|line| line.get(shared..).unwrap_or_default().trim_end()),
+        )
+        .collect();
+    let leading = cleaned.iter().take_while(|line| line.is_empty()).count();
+    while cleaned.last().is_some_and(|line| line.is_empty()) {
+        cleaned.pop();
+    }
+    let text = cleaned[leading.min(cleaned.len())..].join("\n");
+    (!text.is_empty()).then_some((text, first + 1 + leading))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RUST: &str = r#"//! Processing of documents
+//!
+//! Resolves every directive.
+
+use std::path::Path;
+
+/// What a run does
+#[derive(
+    Debug,
+    Clone,
+)]
+pub struct Processor {
+    /// Where the sources are
+    pub source: PathBuf,
+}
+
+impl Processor {
+    /// Processes every source.
+    ///
+    /// ```
+    /// # use md2md::processor::Processor;
+    /// let summary = Processor::builder().build()?.run()?;
+    /// ```
+    #[must_use]
+    pub fn run(&self) -> Result<(), Error> {
+        // Not a doc comment
+        Ok(())
+    }
+
+    pub fn undocumented(&self) {}
+}
+
+impl fmt::Display for Processor {
+    /// Writes the source
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+"#;
+
+    #[test]
+    fn test_rust_doc_comments() {
+        let docs = |item| extract_docs(RUST, DocLanguage::Rust, item);
+        assert_eq!(
+            docs(None).expect("Failed to extract module docs"),
+            DocComment {
+                text: "Processing of documents\n\nResolves every directive.".to_string(),
+                line: 1,
+            }
+        );
+        assert_eq!(
+            docs(Some("Processor::run")).expect("Failed to extract run docs"),
+            DocComment {
+                text: "Processes every source.\n\n```rust\nlet summary = Processor::builder().build()?.run()?;\n```"
+                    .to_string(),
+                line: 18,
+            }
+        );
+        assert_eq!(
+            docs(Some("Processor"))
+                .expect("Failed to extract struct docs")
+                .text,
+            "What a run does"
+        );
+        assert_eq!(
+            docs(Some("Processor::source"))
+                .expect("Failed to extract field docs")
+                .text,
+            "Where the sources are"
+        );
+        assert_eq!(
+            docs(Some("Processor::fmt"))
+                .expect("Failed to extract trait method docs")
+                .text,
+            "Writes the source"
+        );
+
+        for (item, message) in [
+            ("Processor::missing", "No item `Processor::missing`"),
+            ("Processor::undocumented", "has no documentation"),
+            ("run", "No item `run`"),
+        ] {
+            let error = docs(Some(item)).expect_err("Extracting docs should fail");
+            assert_eq!(
+                ErrorCode::of(error.as_ref()),
+                Some(ErrorCode::MissingDocItem)
+            );
+            assert!(error.to_string().contains(message), "{item}: {error}");
+        }
+    }
+
+    #[test]
+    fn test_python_docstrings() {
+        let code = r#"#!/usr/bin/env python3
+"""Parsing of configuration files."""
+
+class Parser:
+    '''Reads configuration.
+
+    Keys are case-sensitive.
+    '''
+
+    def parse(
+        self,
+        text: str,
+    ) -> dict:  # returns the settings
+        """
+        Parses `text`.
+
+            Indented stays indented.
+        """
+        return {}
+
+    def undocumented(self):
+        return None
+
+
+def parse():
+    r"""Module-level parse."""
+"#;
+        let docs = |item| extract_docs(code, DocLanguage::Python, item);
+        assert_eq!(
+            docs(None).expect("Failed to extract module docstring"),
+            DocComment {
+                text: "Parsing of configuration files.".to_string(),
+                line: 2,
+            }
+        );
+        assert_eq!(
+            docs(Some("Parser"))
+                .expect("Failed to extract class docstring")
+                .text,
+            "Reads configuration.\n\nKeys are case-sensitive."
+        );
+        assert_eq!(
+            docs(Some("Parser.parse")).expect("Failed to extract method docstring"),
+            DocComment {
+                text: "Parses `text`.\n\n    Indented stays indented.".to_string(),
+                line: 15,
+            }
+        );
+        assert_eq!(
+            docs(Some("parse"))
+                .expect("Failed to extract function docstring")
+                .text,
+            "Module-level parse."
+        );
+        assert!(docs(Some("Parser::undocumented")).is_err());
+        assert!(docs(Some("Parser.")).is_err());
+    }
+
+    #[test]
+    fn test_doc_language_from_path() {
+        assert_eq!(
+            DocLanguage::from_path(Path::new("src/lib.rs")),
+            Some(DocLanguage::Rust)
+        );
+        assert_eq!(
+            DocLanguage::from_path(Path::new("pkg/stubs.pyi")),
+            Some(DocLanguage::Python)
+        );
+        assert_eq!(DocLanguage::from_path(Path::new("index.js")), None);
+    }
+}
@@ -0,0 +1,105 @@
+//! Decoding of source documents and partials, which may not be UTF-8
+
+use crate::types::InputEncoding;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Reads `path` as text in `encoding`; text that doesn't decode is an
+/// [`io::ErrorKind::InvalidData`] error saying what is wrong with it
+pub fn read_text(path: &Path, encoding: InputEncoding) -> io::Result<String> {
+    decode(fs::read(path)?, encoding)
+        .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))
+}
+
+/// Decodes `bytes` in `encoding`. In UTF-8, a UTF-16 byte order mark is honoured and
+/// NUL bytes are taken for a binary file.
+pub fn decode(bytes: Vec<u8>, encoding: InputEncoding) -> Result<String, String> {
+    match encoding {
+        InputEncoding::Utf8 if has_utf16_bom(&bytes) => decode_utf16(&bytes),
+        InputEncoding::Utf8 if bytes.contains(&0) => {
+            Err("looks like a binary file (it contains NUL bytes)".to_string())
+        }
+        InputEncoding::Utf8 => String::from_utf8(bytes).map_err(|e| {
+            format!(
+                "is not valid UTF-8 (invalid byte at offset {}); set --input-encoding if it uses another encoding",
+                e.utf8_error().valid_up_to()
+            )
+        }),
+        InputEncoding::Latin1 => Ok(bytes.into_iter().map(char::from).collect()),
+        InputEncoding::Utf16 => decode_utf16(&bytes),
+    }
+}
+
+/// Whether `bytes` start with a UTF-16 byte order mark, in either byte order
+pub(crate) fn has_utf16_bom(bytes: &[u8]) -> bool {
+    bytes.starts_with(&[0xFF, 0xFE]) || bytes.starts_with(&[0xFE, 0xFF])
+}
+
+/// UTF-16, little-endian unless a byte order mark says otherwise
+fn decode_utf16(bytes: &[u8]) -> Result<String, String> {
+    let (bytes, big_endian) = match bytes {
+        [0xFE, 0xFF, rest @ ..] => (rest, true),
+        [0xFF, 0xFE, rest @ ..] => (rest, false),
+        _ => (bytes, false),
+    };
+    if bytes.len() % 2 != 0 {
+        return Err("is not valid UTF-16 (odd number of bytes)".to_string());
+    }
+    let units = bytes.chunks_exact(2).map(|pair| {
+        if big_endian {
+            u16::from_be_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_le_bytes([pair[0], pair[1]])
+        }
+    });
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(|e| format!("is not valid UTF-16 ({e})"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_encodings() {
+        assert_eq!(
+            decode("Café".as_bytes().to_vec(), InputEncoding::Utf8),
+            Ok("Café".to_string())
+        );
+        assert_eq!(
+            decode(b"Caf\xe9".to_vec(), InputEncoding::Latin1),
+            Ok("Café".to_string())
+        );
+
+        let utf16_le: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("Café".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let utf16_be: Vec<u8> = [0xFE, 0xFF]
+            .into_iter()
+            .chain("Café".encode_utf16().flat_map(u16::to_be_bytes))
+            .collect();
+        for bytes in [utf16_le, utf16_be] {
+            assert_eq!(
+                decode(bytes.clone(), InputEncoding::Utf16),
+                Ok("Café".to_string())
+            );
+            // The byte order mark gives UTF-16 away
+            assert_eq!(decode(bytes, InputEncoding::Utf8), Ok("Café".to_string()));
+        }
+    }
+
+    #[test]
+    fn test_decode_reports_invalid_text() {
+        let error = decode(b"Caf\xe9".to_vec(), InputEncoding::Utf8).expect_err("Latin-1 text");
+        assert!(error.contains("not valid UTF-8 (invalid byte at offset 3)"));
+
+        let error = decode(b"\x89PNG\0\0".to_vec(), InputEncoding::Utf8).expect_err("Binary");
+        assert!(error.contains("binary file"));
+
+        let error = decode(vec![0x41, 0x00, 0x42], InputEncoding::Utf16).expect_err("Odd length");
+        assert!(error.contains("odd number of bytes"));
+    }
+}
@@ -0,0 +1,249 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Stable identifiers for every kind of processing error.
+///
+/// Codes never change meaning once released, so scripts and editors can filter
+/// on them. They are grouped by decade: includes (E00x), recursion (E01x),
+/// variables (E02x), code fences (E03x), file I/O (E04x), links (E05x) and
+/// post-processing (E06x).
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+pub enum ErrorCode {
+    /// An included partial could not be read
+    #[serde(rename = "E001")]
+    MissingInclude,
+    /// An `!include` directive could not be parsed
+    #[serde(rename = "E002")]
+    InvalidIncludeDirective,
+    /// The file of a `!codesnippet` directive could not be read
+    #[serde(rename = "E003")]
+    MissingCodeSnippet,
+    /// The `start`/`end` lines of a `!codesnippet` are outside the file
+    #[serde(rename = "E004")]
+    InvalidSnippetRange,
+    /// A `!codesnippet` directive could not be parsed
+    #[serde(rename = "E005")]
+    InvalidCodeSnippetDirective,
+    /// The item of a `!docsnippet` isn't in its file, or has no documentation
+    #[serde(rename = "E006")]
+    MissingDocItem,
+    /// A `!docsnippet` directive could not be parsed, or names a file of another language
+    #[serde(rename = "E007")]
+    InvalidDocSnippetDirective,
+    /// Includes nest deeper than allowed, usually because they are circular
+    #[serde(rename = "E010")]
+    CircularInclude,
+    /// A `{% variable %}` has no value and no default
+    #[serde(rename = "E020")]
+    UndefinedVariable,
+    /// Variable values keep expanding into more variables
+    #[serde(rename = "E021")]
+    CircularVariable,
+    /// A code fence is never closed
+    #[serde(rename = "E030")]
+    UnclosedFence,
+    /// A code fence does not specify a language
+    #[serde(rename = "E031")]
+    MissingFenceLanguage,
+    /// A closing fence is indented differently from its opening fence
+    #[serde(rename = "E032")]
+    FenceIndentMismatch,
+    /// A code fence opens while another one is still open
+    #[serde(rename = "E033")]
+    NestedFence,
+    /// A source document could not be read
+    #[serde(rename = "E040")]
+    UnreadableSource,
+    /// Output could not be written
+    #[serde(rename = "E041")]
+    OutputWriteFailed,
+    /// No output path could be derived for a source document
+    #[serde(rename = "E042")]
+    InvalidOutputPath,
+    /// Another source document already writes to the same output path
+    #[serde(rename = "E043")]
+    OutputCollision,
+    /// An output would go over the size budget of a file or of the whole run
+    #[serde(rename = "E044")]
+    OutputTooLarge,
+    /// A relative link points to a file that does not exist
+    #[serde(rename = "E050")]
+    BrokenLink,
+    /// The post-processing command failed on an output file
+    #[serde(rename = "E060")]
+    PostProcessFailed,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::MissingInclude => "E001",
+            ErrorCode::InvalidIncludeDirective => "E002",
+            ErrorCode::MissingCodeSnippet => "E003",
+            ErrorCode::InvalidSnippetRange => "E004",
+            ErrorCode::InvalidCodeSnippetDirective => "E005",
+            ErrorCode::MissingDocItem => "E006",
+            ErrorCode::InvalidDocSnippetDirective => "E007",
+            ErrorCode::CircularInclude => "E010",
+            ErrorCode::UndefinedVariable => "E020",
+            ErrorCode::CircularVariable => "E021",
+            ErrorCode::UnclosedFence => "E030",
+            ErrorCode::MissingFenceLanguage => "E031",
+            ErrorCode::FenceIndentMismatch => "E032",
+            ErrorCode::NestedFence => "E033",
+            ErrorCode::UnreadableSource => "E040",
+            ErrorCode::OutputWriteFailed => "E041",
+            ErrorCode::InvalidOutputPath => "E042",
+            ErrorCode::OutputCollision => "E043",
+            ErrorCode::OutputTooLarge => "E044",
+            ErrorCode::BrokenLink => "E050",
+            ErrorCode::PostProcessFailed => "E060",
+        }
+    }
+
+    /// Creates an error carrying this code
+    pub fn error(self, message: impl Into<String>) -> Box<dyn std::error::Error> {
+        Box::new(CodedError {
+            code: self,
+            message: message.into(),
+        })
+    }
+
+    /// The category the code is grouped under in the Analysis tab
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            ErrorCode::MissingInclude
+            | ErrorCode::MissingCodeSnippet
+            | ErrorCode::MissingDocItem
+            | ErrorCode::UnreadableSource => ErrorCategory::FileNotFound,
+            ErrorCode::InvalidIncludeDirective
+            | ErrorCode::InvalidSnippetRange
+            | ErrorCode::InvalidCodeSnippetDirective
+            | ErrorCode::InvalidDocSnippetDirective => ErrorCategory::ParseError,
+            ErrorCode::CircularInclude => ErrorCategory::CircularInclude,
+            ErrorCode::UndefinedVariable | ErrorCode::CircularVariable => {
+                ErrorCategory::VariableMissing
+            }
+            ErrorCode::UnclosedFence
+            | ErrorCode::MissingFenceLanguage
+            | ErrorCode::FenceIndentMismatch
+            | ErrorCode::NestedFence => ErrorCategory::FenceInvalid,
+            ErrorCode::OutputWriteFailed
+            | ErrorCode::InvalidOutputPath
+            | ErrorCode::OutputCollision
+            | ErrorCode::OutputTooLarge => ErrorCategory::Output,
+            ErrorCode::BrokenLink => ErrorCategory::BrokenLink,
+            ErrorCode::PostProcessFailed => ErrorCategory::PostProcess,
+        }
+    }
+
+    /// The code attached to `error`, if it was created with [`ErrorCode::error`]
+    pub fn of(error: &(dyn std::error::Error + 'static)) -> Option<ErrorCode> {
+        error.downcast_ref::<CodedError>().map(|e| e.code)
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Broad kinds of errors, for counting failures of a run by what went wrong
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCategory {
+    FileNotFound,
+    ParseError,
+    CircularInclude,
+    VariableMissing,
+    FenceInvalid,
+    Output,
+    BrokenLink,
+    PostProcess,
+    /// Errors without a code
+    Other,
+}
+
+impl ErrorCategory {
+    /// The category of an error with `code`, if any
+    pub fn of(code: Option<ErrorCode>) -> Self {
+        code.map_or(ErrorCategory::Other, |code| code.category())
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::FileNotFound => "file not found",
+            ErrorCategory::ParseError => "parse error",
+            ErrorCategory::CircularInclude => "circular include",
+            ErrorCategory::VariableMissing => "variable missing",
+            ErrorCategory::FenceInvalid => "fence invalid",
+            ErrorCategory::Output => "output failed",
+            ErrorCategory::BrokenLink => "broken link",
+            ErrorCategory::PostProcess => "post-processing failed",
+            ErrorCategory::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An error message tagged with its [`ErrorCode`]
+#[derive(Debug, Clone)]
+pub struct CodedError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl fmt::Display for CodedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for CodedError {}
+
+/// Formats an error message for reporters, prefixed with its code when known
+pub fn format_error(code: Option<ErrorCode>, message: &str) -> String {
+    match code {
+        Some(code) => format!("[{code}] {message}"),
+        None => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_code_round_trip() {
+        let error = ErrorCode::MissingInclude.error("header.md not found");
+
+        assert_eq!(error.to_string(), "header.md not found");
+        assert_eq!(
+            ErrorCode::of(error.as_ref()),
+            Some(ErrorCode::MissingInclude)
+        );
+        assert_eq!(
+            ErrorCode::of(&*Box::<dyn std::error::Error>::from("plain")),
+            None
+        );
+        assert_eq!(
+            serde_json::to_string(&ErrorCode::CircularInclude).expect("Failed to serialize"),
+            "\"E010\""
+        );
+        assert_eq!(
+            format_error(Some(ErrorCode::UnclosedFence), "Fence never closed"),
+            "[E030] Fence never closed"
+        );
+    }
+}
@@ -2,13 +2,18 @@ use crate::action::Action;
 use crossterm::event::{
     self, Event as CrosstermEvent, KeyCode, KeyEvent, KeyEventKind, KeyModifiers,
 };
-use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
 use std::time::{Duration, Instant};
 
 /// Terminal event handler.
 #[derive(Debug)]
 pub struct EventHandler {
+    sender: mpsc::Sender<Action>,
     receiver: mpsc::Receiver<Action>,
+    /// While set, the terminal is left alone, e.g. for an editor
+    paused: Arc<AtomicBool>,
+    tick_rate: Duration,
     _handler: std::thread::JoinHandle<()>,
 }
 
@@ -18,9 +23,16 @@ impl EventHandler {
         let tick_rate = Duration::from_millis(tick_rate);
         let (sender, receiver) = mpsc::channel();
         let _sender = sender.clone();
+        let paused = Arc::new(AtomicBool::new(false));
+        let _paused = paused.clone();
         let _handler = std::thread::spawn(move || {
             let mut last_tick = Instant::now();
             loop {
+                if _paused.load(Ordering::Relaxed) {
+                    std::thread::sleep(Duration::from_millis(10));
+                    continue;
+                }
+
                 let timeout = tick_rate
                     .checked_sub(last_tick.elapsed())
                     .unwrap_or(Duration::from_secs(0));
@@ -28,8 +40,8 @@ impl EventHandler {
                 if event::poll(timeout).expect("no events available") {
                     match event::read().expect("unable to read event") {
                         CrosstermEvent::Key(key) => {
-                            if let Some(action) = handle_key_event(key)
-                                && _sender.send(action).is_err()
+                            if key.kind == KeyEventKind::Press
+                                && _sender.send(Action::Key(key)).is_err()
                             {
                                 return;
                             }
@@ -56,7 +68,30 @@ impl EventHandler {
                 }
             }
         });
-        Self { receiver, _handler }
+        Self {
+            sender,
+            receiver,
+            paused,
+            tick_rate,
+            _handler,
+        }
+    }
+
+    /// Stops reading the terminal, so a child process gets every key press
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::Relaxed);
+        // A poll in progress ends within one tick
+        std::thread::sleep(self.tick_rate);
+    }
+
+    /// Reads the terminal again after [`EventHandler::pause`]
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::Relaxed);
+    }
+
+    /// Sender for actions from other threads, delivered along with the terminal events
+    pub fn sender(&self) -> mpsc::Sender<Action> {
+        self.sender.clone()
     }
 
     /// Receive the next action from the handler thread.
@@ -65,11 +100,8 @@ impl EventHandler {
     }
 }
 
-fn handle_key_event(key: KeyEvent) -> Option<Action> {
-    if key.kind != KeyEventKind::Press {
-        return None;
-    }
-
+/// Action of a key press outside of text input
+pub fn key_action(key: KeyEvent) -> Option<Action> {
     match key.code {
         KeyCode::Char('q') => Some(Action::Quit),
         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => Some(Action::Quit),
@@ -77,15 +109,28 @@ fn handle_key_event(key: KeyEvent) -> Option<Action> {
         KeyCode::BackTab | KeyCode::Left => Some(Action::PreviousTab),
         KeyCode::Up | KeyCode::Char('k') => Some(Action::PreviousFile),
         KeyCode::Down | KeyCode::Char('j') => Some(Action::NextFile),
+        KeyCode::PageUp => Some(Action::PageUp),
+        KeyCode::PageDown => Some(Action::PageDown),
+        KeyCode::Home => Some(Action::ScrollToTop),
+        KeyCode::End => Some(Action::ScrollToBottom),
         KeyCode::Char('e') => Some(Action::ToggleErrorDetails),
         KeyCode::Char('1') => Some(Action::GoToTab(1)),
         KeyCode::Char('2') => Some(Action::GoToTab(2)),
         KeyCode::Char('3') => Some(Action::GoToTab(3)),
         KeyCode::Char('4') => Some(Action::GoToTab(4)),
         KeyCode::Char('5') => Some(Action::GoToTab(5)),
+        KeyCode::Char('6') => Some(Action::GoToTab(6)),
+        KeyCode::Char('7') => Some(Action::GoToTab(7)),
+        KeyCode::Char('8') => Some(Action::GoToTab(8)),
+        KeyCode::Char(' ') => Some(Action::ToggleNode),
         KeyCode::Char('?') => Some(Action::ToggleHelp),
+        KeyCode::Char('/') => Some(Action::StartFilter),
+        KeyCode::Char('p') | KeyCode::Enter => Some(Action::TogglePreview),
         KeyCode::Esc => Some(Action::HideHelp),
         KeyCode::Char('r') => Some(Action::Refresh),
+        KeyCode::Char('R') => Some(Action::Retry),
+        KeyCode::Char('o') => Some(Action::OpenInEditor),
+        KeyCode::Char('s') => Some(Action::SaveReport),
         _ => None,
     }
 }
@@ -0,0 +1,207 @@
+//! C ABI exports, built with `--features ffi`. See `include/md2md.h`.
+//!
+//! Every function returns a JSON document owned by the caller, which must be
+//! released with [`md2md_free_string`]:
+//! `{"ok": true, "result": {...}}` on success or `{"ok": false, "error": "..."}`.
+
+use crate::partial_source::FileSystemSource;
+use crate::processor::{Processor, process_string};
+use serde::Serialize;
+use std::ffi::{CStr, CString, c_char};
+use std::panic::{self, AssertUnwindSafe};
+
+/// Processes a markdown file or directory like the `md2md` command.
+///
+/// `output_path` may be null to use the default `out`. The result is the
+/// processing summary: `total_files`, `processed_files` and one entry in
+/// `results` per source file.
+///
+/// # Safety
+///
+/// Non-null arguments must be valid NUL-terminated UTF-8 strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn md2md_process_file(
+    source_path: *const c_char,
+    partials_path: *const c_char,
+    output_path: *const c_char,
+) -> *mut c_char {
+    respond(|| {
+        // SAFETY: the caller guarantees the pointers are valid C strings or null
+        let (source_path, partials_path, output_path) = unsafe {
+            (
+                required_str(source_path, "source_path")?,
+                required_str(partials_path, "partials_path")?,
+                optional_str(output_path, "output_path")?,
+            )
+        };
+
+        let mut builder = Processor::builder()
+            .source(source_path)
+            .partials(partials_path);
+        if let Some(output_path) = output_path {
+            builder = builder.output(output_path);
+        }
+        builder.build()?.run()
+    })
+}
+
+/// Resolves every directive in `content`, reading partials from `partials_path`.
+///
+/// The result holds the processed `content` and one entry in `includes` per
+/// directive.
+///
+/// # Safety
+///
+/// Both arguments must be valid NUL-terminated UTF-8 strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn md2md_process_string(
+    content: *const c_char,
+    partials_path: *const c_char,
+) -> *mut c_char {
+    respond(|| {
+        // SAFETY: the caller guarantees the pointers are valid C strings
+        let (content, partials_path) = unsafe {
+            (
+                required_str(content, "content")?,
+                required_str(partials_path, "partials_path")?,
+            )
+        };
+
+        process_string(content, &FileSystemSource::new(partials_path))
+    })
+}
+
+/// Releases a string returned by this library. Null is ignored.
+///
+/// # Safety
+///
+/// `value` must come from an md2md function and must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn md2md_free_string(value: *mut c_char) {
+    if !value.is_null() {
+        // SAFETY: the string was allocated by `CString::into_raw` in `respond`
+        drop(unsafe { CString::from_raw(value) });
+    }
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Response<T> {
+    Success { ok: bool, result: T },
+    Failure { ok: bool, error: String },
+}
+
+/// Runs `operation` and encodes its outcome, including panics, as a JSON C string
+fn respond<T: Serialize>(
+    operation: impl FnOnce() -> Result<T, Box<dyn std::error::Error>>,
+) -> *mut c_char {
+    let response = match panic::catch_unwind(AssertUnwindSafe(operation)) {
+        Ok(Ok(result)) => Response::Success { ok: true, result },
+        Ok(Err(e)) => Response::Failure {
+            ok: false,
+            error: e.to_string(),
+        },
+        Err(_) => Response::Failure {
+            ok: false,
+            error: "md2md panicked while processing".to_string(),
+        },
+    };
+
+    let json = serde_json::to_string(&response)
+        .unwrap_or_else(|e| format!(r#"{{"ok":false,"error":"Failed to serialize result: {e}"}}"#));
+    // JSON escapes control characters, so the output never contains a NUL byte
+    CString::new(json)
+        .map(CString::into_raw)
+        .unwrap_or(std::ptr::null_mut())
+}
+
+unsafe fn required_str<'a>(
+    value: *const c_char,
+    name: &str,
+) -> Result<&'a str, Box<dyn std::error::Error>> {
+    // SAFETY: forwarded from the caller
+    unsafe { optional_str(value, name) }?.ok_or_else(|| format!("{name} must not be null").into())
+}
+
+unsafe fn optional_str<'a>(
+    value: *const c_char,
+    name: &str,
+) -> Result<Option<&'a str>, Box<dyn std::error::Error>> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    // SAFETY: the caller guarantees a valid NUL-terminated string
+    let value = unsafe { CStr::from_ptr(value) };
+    value
+        .to_str()
+        .map(Some)
+        .map_err(|_| format!("{name} is not valid UTF-8").into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn take_json(value: *mut c_char) -> serde_json::Value {
+        assert!(!value.is_null());
+        // SAFETY: `value` was just returned by an md2md function
+        let json = unsafe { CStr::from_ptr(value) }
+            .to_str()
+            .expect("Result should be UTF-8")
+            .to_string();
+        unsafe { md2md_free_string(value) };
+        serde_json::from_str(&json).expect("Result should be valid JSON")
+    }
+
+    #[test]
+    fn test_process_string_and_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("header.md"), "# Hello").expect("Failed to write header.md");
+        fs::write(temp_dir.path().join("doc.md"), "!include (header.md)")
+            .expect("Failed to write doc.md");
+
+        let partials = CString::new(partials_dir.to_string_lossy().as_bytes())
+            .expect("Path should not contain NUL");
+        let content = CString::new("!include (header.md)\n\nBody").expect("Invalid content");
+        let response =
+            take_json(unsafe { md2md_process_string(content.as_ptr(), partials.as_ptr()) });
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["result"]["content"], "# Hello\n\nBody");
+
+        let source = CString::new(temp_dir.path().join("doc.md").to_string_lossy().as_bytes())
+            .expect("Path should not contain NUL");
+        let output = CString::new(temp_dir.path().join("out.md").to_string_lossy().as_bytes())
+            .expect("Path should not contain NUL");
+        let response = take_json(unsafe {
+            md2md_process_file(source.as_ptr(), partials.as_ptr(), output.as_ptr())
+        });
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["result"]["results"][0]["success"], true);
+        assert!(temp_dir.path().join("out.md").exists());
+    }
+
+    #[test]
+    fn test_errors_are_reported_as_json() {
+        let response = take_json(unsafe {
+            md2md_process_file(std::ptr::null(), std::ptr::null(), std::ptr::null())
+        });
+        assert_eq!(response["ok"], false);
+        assert_eq!(response["error"], "source_path must not be null");
+
+        let missing = CString::new("/definitely/not/here.md").expect("Invalid path");
+        let partials = CString::new(".").expect("Invalid path");
+        let response = take_json(unsafe {
+            md2md_process_file(missing.as_ptr(), partials.as_ptr(), std::ptr::null())
+        });
+        assert_eq!(response["ok"], false);
+        assert!(
+            response["error"]
+                .as_str()
+                .is_some_and(|e| e.contains("Source path does not exist"))
+        );
+    }
+}
@@ -1,63 +1,337 @@
+use crate::graph::normalize_path;
+use crate::ignore::{IGNORE_FILE_NAME, IgnoreRules};
+use crate::types::{FileOrder, LineEndings, SkipReason, SkippedFile};
+use std::borrow::Cow;
 use std::fs;
+use std::io::{BufWriter, Write};
 use std::path::{Path, PathBuf};
 
+/// Files found at a source path: those to process and those left out
+#[derive(Debug, Default)]
+pub struct SourceFiles {
+    pub files: Vec<PathBuf>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// Collects the markdown files to process, sorted by path; in a directory, paths
+/// matched by its `.md2mdignore` are skipped
 pub fn collect_markdown_files(
     source_path: &Path,
 ) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
-    let mut files = Vec::new();
+    Ok(collect_source_files(source_path)?.files)
+}
+
+/// [`collect_markdown_files`], also returning the files skipped and why, sorted by
+/// path. Hidden files and directories aren't reported unless they are Markdown.
+pub fn collect_source_files(source_path: &Path) -> Result<SourceFiles, Box<dyn std::error::Error>> {
+    let mut found = SourceFiles::default();
 
     if source_path.is_file() {
-        if source_path.extension().is_some_and(|ext| ext == "md") {
-            files.push(source_path.to_path_buf());
+        if is_markdown(source_path) {
+            found.files.push(source_path.to_path_buf());
+        } else {
+            found
+                .skipped
+                .push(skipped(source_path, SkipReason::NotMarkdown));
         }
     } else if source_path.is_dir() {
-        collect_files_recursive(source_path, &mut files)
-            .expect("Failed to collect files recursively from directory");
+        let mut others = Vec::new();
+        collect_files_recursive(source_path, &mut found.files, &mut others)?;
+
+        let ignore_rules = IgnoreRules::load(source_path);
+        let is_ignored = |file: &PathBuf| {
+            file.strip_prefix(source_path)
+                .is_ok_and(|relative| ignore_rules.is_ignored(relative))
+        };
+        let (ignored, files): (Vec<PathBuf>, Vec<PathBuf>) =
+            found.files.drain(..).partition(is_ignored);
+        found.files = files;
+        found.files.sort();
+
+        found.skipped = ignored
+            .iter()
+            .map(|file| skipped(file, SkipReason::Ignored))
+            .chain(
+                others
+                    .iter()
+                    .filter(|file| !file.strip_prefix(source_path).is_ok_and(is_hidden))
+                    .map(|file| {
+                        let reason = if is_ignored(file) {
+                            SkipReason::Ignored
+                        } else {
+                            SkipReason::NotMarkdown
+                        };
+                        skipped(file, reason)
+                    }),
+            )
+            .collect();
+        found.skipped.sort_by(|a, b| a.path.cmp(&b.path));
     }
 
-    Ok(files)
+    Ok(found)
+}
+
+/// Whether a component of `relative` starts with `.`, like `.git/config`
+fn is_hidden(relative: &Path) -> bool {
+    relative
+        .components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+}
+
+fn skipped(path: &Path, reason: SkipReason) -> SkippedFile {
+    SkippedFile {
+        path: path.to_string_lossy().to_string(),
+        reason,
+    }
+}
+
+/// Sorts files into processing order; unreadable modification times sort first
+pub fn sort_files(files: &mut [PathBuf], order: FileOrder) {
+    match order {
+        FileOrder::Name => files.sort(),
+        FileOrder::Mtime => files.sort_by_cached_key(|file| {
+            let modified = fs::metadata(file).and_then(|m| m.modified()).ok();
+            (modified, file.clone())
+        }),
+    }
 }
 
+/// Markdown files go in `files`, everything else but the ignore file in `others`
 fn collect_files_recursive(
     dir: &Path,
     files: &mut Vec<PathBuf>,
+    others: &mut Vec<PathBuf>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    for entry in fs::read_dir(dir).expect("Failed to read directory") {
-        let entry = entry.expect("Failed to read directory entry");
-        let path = entry.path();
+    let entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory {}: {e}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
 
         if path.is_dir() {
-            collect_files_recursive(&path, files)
-                .expect("Failed to collect files from subdirectory");
-        } else if path.extension().is_some_and(|ext| ext == "md") {
+            collect_files_recursive(&path, files, others)?;
+        } else if is_markdown(&path) {
             files.push(path);
+        } else if path.file_name().is_none_or(|name| name != IGNORE_FILE_NAME) {
+            others.push(path);
         }
     }
 
     Ok(())
 }
 
+/// Markdown (`.md`) and MDX (`.mdx`) files are processed
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "md" || ext == "mdx")
+}
+
 pub fn ensure_output_directory(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
     if let Some(parent) = path.parent()
         && !parent.exists()
     {
-        fs::create_dir_all(parent).expect("Failed to create output directory");
+        fs::create_dir_all(parent).map_err(|e| {
+            format!(
+                "Failed to create output directory {}: {e}",
+                parent.display()
+            )
+        })?;
     }
     Ok(())
 }
 
+/// Converts every line break of `content` to `line_endings`
+pub fn normalize_line_endings(content: &str, line_endings: LineEndings) -> Cow<'_, str> {
+    match line_endings {
+        LineEndings::Preserve => Cow::Borrowed(content),
+        LineEndings::Lf if !content.contains('\r') => Cow::Borrowed(content),
+        LineEndings::Lf => Cow::Owned(content.replace("\r\n", "\n")),
+        LineEndings::Crlf => Cow::Owned(content.replace("\r\n", "\n").replace('\n', "\r\n")),
+    }
+}
+
 pub fn write_file(path: &Path, content: &str) -> Result<(), Box<dyn std::error::Error>> {
-    ensure_output_directory(path).expect("Failed to ensure output directory exists");
-    fs::write(path, content).expect("Failed to write file content");
+    ensure_output_directory(path)?;
+    fs::write(path, content).map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
     Ok(())
 }
 
+/// Writes `segments` one after another through a buffer, converting line breaks on the way,
+/// so a large document is never copied whole in memory
+pub fn write_segments(
+    path: &Path,
+    segments: &[&str],
+    line_endings: LineEndings,
+) -> Result<(), Box<dyn std::error::Error>> {
+    ensure_output_directory(path)?;
+    let write = || -> std::io::Result<()> {
+        write_segments_to(
+            BufWriter::new(fs::File::create(path)?),
+            segments,
+            line_endings,
+        )
+    };
+    write().map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+    Ok(())
+}
+
+/// [`write_segments`] to any writer, e.g. stdout or an archive entry
+pub fn write_segments_to(
+    writer: impl Write,
+    segments: &[&str],
+    line_endings: LineEndings,
+) -> std::io::Result<()> {
+    let mut writer = LineEndingWriter {
+        inner: writer,
+        line_endings,
+        pending_cr: false,
+    };
+    for segment in segments {
+        writer.write_str(segment)?;
+    }
+    writer.finish()
+}
+
+/// Converts line breaks like [`normalize_line_endings`], across the boundaries of what it is given
+struct LineEndingWriter<W: Write> {
+    inner: W,
+    line_endings: LineEndings,
+    /// The last piece ended in `\r`, which is a line break if the next starts with `\n`
+    pending_cr: bool,
+}
+
+impl<W: Write> LineEndingWriter<W> {
+    fn write_str(&mut self, text: &str) -> std::io::Result<()> {
+        let newline = match self.line_endings {
+            LineEndings::Preserve => return self.inner.write_all(text.as_bytes()),
+            LineEndings::Lf => "\n",
+            LineEndings::Crlf => "\r\n",
+        };
+        for piece in text.split_inclusive('\n') {
+            if self.pending_cr && !piece.starts_with('\n') {
+                self.inner.write_all(b"\r")?;
+            }
+            self.pending_cr = false;
+            match piece.strip_suffix('\n') {
+                Some(line) => {
+                    let line = line.strip_suffix('\r').unwrap_or(line);
+                    self.inner.write_all(line.as_bytes())?;
+                    self.inner.write_all(newline.as_bytes())?;
+                }
+                None => match piece.strip_suffix('\r') {
+                    Some(line) => {
+                        self.inner.write_all(line.as_bytes())?;
+                        self.pending_cr = true;
+                    }
+                    None => self.inner.write_all(piece.as_bytes())?,
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> std::io::Result<()> {
+        if self.pending_cr {
+            self.inner.write_all(b"\r")?;
+        }
+        self.inner.flush()
+    }
+}
+
+/// Why writing to `output_path` would land in the source or partials tree, if it would
+///
+/// Paths are compared once resolved, so `-o .` and symlinks are caught; a part of a path
+/// that doesn't exist yet is compared as written. A single source file only conflicts with
+/// an output at its own path, so it can still be built next to its partials.
+pub fn output_overlap(
+    source_path: &Path,
+    partials_path: &Path,
+    output_path: &Path,
+) -> Option<String> {
+    let output = resolve_lenient(output_path);
+    if source_path.is_file() {
+        return (resolve_lenient(source_path) == output)
+            .then(|| format!("Output {output_path:?} is the source file"));
+    }
+    [("source", source_path), ("partials", partials_path)]
+        .into_iter()
+        .find_map(|(name, path)| {
+            let resolved = resolve_lenient(path);
+            if resolved == output {
+                Some(format!("Output {output_path:?} is the {name} path"))
+            } else if output.starts_with(&resolved) {
+                Some(format!(
+                    "Output {output_path:?} is inside the {name} path {path:?}"
+                ))
+            } else if resolved.starts_with(&output) {
+                Some(format!(
+                    "The {name} path {path:?} is inside output {output_path:?}"
+                ))
+            } else {
+                None
+            }
+        })
+}
+
+/// Canonicalizes the longest existing ancestor of `path` and appends the rest to it
+pub(crate) fn resolve_lenient(path: &Path) -> PathBuf {
+    let path = normalize_path(&std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()));
+    let mut missing = Vec::new();
+    let mut existing = path.as_path();
+    loop {
+        if let Ok(resolved) = fs::canonicalize(existing) {
+            return missing
+                .iter()
+                .rev()
+                .fold(resolved, |resolved, name| resolved.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name);
+                existing = parent;
+            }
+            _ => return path,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs;
     use tempfile::TempDir;
 
+    #[test]
+    fn test_normalize_line_endings() {
+        let mixed = "one\r\ntwo\nthree\r\n";
+
+        assert_eq!(normalize_line_endings(mixed, LineEndings::Preserve), mixed);
+        assert_eq!(
+            normalize_line_endings(mixed, LineEndings::Lf),
+            "one\ntwo\nthree\n"
+        );
+        assert_eq!(
+            normalize_line_endings(mixed, LineEndings::Crlf),
+            "one\r\ntwo\r\nthree\r\n"
+        );
+    }
+
+    #[test]
+    fn test_write_segments_matches_normalize_line_endings() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("out/doc.md");
+        // Line breaks split across segments, plus a lone `\r`
+        let segments = ["one\r", "\ntwo\n", "three\r\n\r", "x\r"];
+        let whole = segments.concat();
+
+        for line_endings in [LineEndings::Preserve, LineEndings::Lf, LineEndings::Crlf] {
+            write_segments(&path, &segments, line_endings).expect("Failed to write segments");
+            assert_eq!(
+                fs::read_to_string(&path).expect("Failed to read output"),
+                normalize_line_endings(&whole, line_endings)
+            );
+        }
+    }
+
     #[test]
     fn test_collect_markdown_files_single_file() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
@@ -116,6 +390,100 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_collect_markdown_files_honors_ignore_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path();
+
+        fs::write(
+            dir_path.join(".md2mdignore"),
+            "drafts/
+",
+        )
+        .expect("Failed to write ignore file");
+        fs::write(dir_path.join("index.md"), "# Index").expect("Failed to write index.md");
+        fs::create_dir(dir_path.join("drafts")).expect("Failed to create drafts directory");
+        fs::write(dir_path.join("drafts").join("wip.md"), "# WIP").expect("Failed to write wip.md");
+
+        let files = collect_markdown_files(dir_path).expect("Failed to collect markdown files");
+        assert_eq!(files, vec![dir_path.join("index.md")]);
+    }
+
+    #[test]
+    fn test_collect_source_files_reports_skipped_files() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path();
+
+        fs::write(dir_path.join(".md2mdignore"), "drafts/\n*.log\n")
+            .expect("Failed to write ignore file");
+        fs::write(dir_path.join("index.md"), "# Index").expect("Failed to write index.md");
+        fs::write(dir_path.join("logo.png"), "PNG").expect("Failed to write logo.png");
+        fs::write(dir_path.join("build.log"), "log").expect("Failed to write build.log");
+        fs::create_dir(dir_path.join("drafts")).expect("Failed to create drafts directory");
+        fs::write(dir_path.join("drafts").join("wip.md"), "# WIP").expect("Failed to write wip.md");
+        fs::create_dir(dir_path.join(".git")).expect("Failed to create .git directory");
+        fs::write(dir_path.join(".git").join("HEAD"), "ref").expect("Failed to write HEAD");
+
+        let found = collect_source_files(dir_path).expect("Failed to collect source files");
+        assert_eq!(found.files, vec![dir_path.join("index.md")]);
+        let skipped: Vec<(String, SkipReason)> = found
+            .skipped
+            .into_iter()
+            .map(|file| (file.path, file.reason))
+            .collect();
+        let path = |name: &str| dir_path.join(name).to_string_lossy().to_string();
+        assert_eq!(
+            skipped,
+            vec![
+                (path("build.log"), SkipReason::Ignored),
+                (path("drafts/wip.md"), SkipReason::Ignored),
+                (path("logo.png"), SkipReason::NotMarkdown),
+            ]
+        );
+
+        let single = collect_source_files(&dir_path.join("logo.png"))
+            .expect("Failed to collect source files");
+        assert!(single.files.is_empty());
+        assert_eq!(single.skipped[0].reason, SkipReason::NotMarkdown);
+    }
+
+    #[test]
+    fn test_files_are_sorted_by_name_or_mtime() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let dir_path = temp_dir.path();
+        let now = std::time::SystemTime::now();
+
+        for (name, age) in [("b.md", 10), ("a.md", 0), ("c.md", 20)] {
+            let path = dir_path.join(name);
+            fs::write(&path, "# Doc").expect("Failed to write markdown file");
+            fs::File::options()
+                .write(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(now - std::time::Duration::from_secs(age)))
+                .expect("Failed to set modification time");
+        }
+
+        let mut files = collect_markdown_files(dir_path).expect("Failed to collect markdown files");
+        assert_eq!(
+            files,
+            vec![
+                dir_path.join("a.md"),
+                dir_path.join("b.md"),
+                dir_path.join("c.md")
+            ]
+        );
+
+        sort_files(&mut files, FileOrder::Mtime);
+        assert_eq!(
+            files,
+            vec![
+                dir_path.join("c.md"),
+                dir_path.join("b.md"),
+                dir_path.join("a.md")
+            ]
+        );
+    }
+
     #[test]
     fn test_ensure_output_directory() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
@@ -181,4 +549,37 @@ mod tests {
             "New content"
         );
     }
+
+    #[test]
+    fn test_output_overlap() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let root = temp_dir.path();
+        let docs = root.join("docs");
+        let partials = root.join("partials");
+        fs::create_dir_all(&docs).expect("Failed to create docs");
+        fs::create_dir_all(&partials).expect("Failed to create partials");
+
+        assert_eq!(output_overlap(&docs, &partials, &root.join("out")), None);
+        assert_eq!(
+            output_overlap(&docs.join("a.md"), &partials, &docs.join("b.md")),
+            None
+        );
+        assert!(output_overlap(&docs, &partials, &docs).is_some());
+        assert!(output_overlap(&docs, &partials, &docs.join("out/new")).is_some());
+        assert!(output_overlap(&docs, &partials, &partials.join("../partials")).is_some());
+        assert!(output_overlap(&docs, &partials, root).is_some());
+        fs::write(docs.join("a.md"), "# A").expect("Failed to write source");
+        assert!(output_overlap(&docs.join("a.md"), &partials, &docs.join("a.md")).is_some());
+        assert_eq!(
+            output_overlap(&docs.join("a.md"), &docs, &docs.join("README.md")),
+            None
+        );
+
+        #[cfg(unix)]
+        {
+            let link = root.join("link");
+            std::os::unix::fs::symlink(&docs, &link).expect("Failed to create symlink");
+            assert!(output_overlap(&docs, &partials, &link.join("out")).is_some());
+        }
+    }
 }
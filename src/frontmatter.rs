@@ -0,0 +1,114 @@
+//! What becomes of a source document's own `---` frontmatter in its output
+
+use crate::banner::utc_timestamp;
+use crate::mdx::frontmatter_end;
+use crate::types::Frontmatter;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Keeps, strips or merges generated keys into the leading frontmatter of `content`.
+///
+/// Merging adds `generated` (`time` as a UTC timestamp), `source` and `generator`;
+/// keys the document already sets are left alone, and a document without
+/// frontmatter gets a block of just the generated keys.
+pub fn apply_frontmatter<'a>(
+    content: &'a str,
+    mode: Frontmatter,
+    source: &Path,
+    time: SystemTime,
+) -> Cow<'a, str> {
+    let end = frontmatter_end(content);
+    match mode {
+        Frontmatter::Keep => Cow::Borrowed(content),
+        Frontmatter::Strip => match end {
+            Some(end) => Cow::Borrowed(content[end..].trim_start_matches(['\r', '\n'])),
+            None => Cow::Borrowed(content),
+        },
+        Frontmatter::Merge => {
+            let generated = [
+                ("generated", utc_timestamp(time)),
+                ("source", source.to_string_lossy().to_string()),
+                ("generator", format!("md2md {}", env!("CARGO_PKG_VERSION"))),
+            ];
+            let Some(end) = end else {
+                let lines: String = generated
+                    .iter()
+                    .map(|(key, value)| yaml_line(key, value))
+                    .collect();
+                return Cow::Owned(format!("---\n{lines}---\n\n{content}"));
+            };
+            let (frontmatter, body) = content.split_at(end);
+            let existing = top_level_keys(frontmatter);
+            // The closing `---` line, which the generated keys go before
+            let closing = frontmatter[..frontmatter.trim_end().len()]
+                .rfind('\n')
+                .map_or(0, |newline| newline + 1);
+            let mut merged = frontmatter[..closing].to_string();
+            for (key, value) in &generated {
+                if !existing.contains(key) {
+                    merged.push_str(&yaml_line(key, value));
+                }
+            }
+            merged.push_str(&frontmatter[closing..]);
+            merged.push_str(body);
+            Cow::Owned(merged)
+        }
+    }
+}
+
+/// Keys set at the top level of a YAML frontmatter block
+fn top_level_keys(frontmatter: &str) -> HashSet<&str> {
+    frontmatter
+        .lines()
+        .filter(|line| !line.starts_with([' ', '\t', '#', '-']))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, _)| key.trim().trim_matches(['"', '\'']))
+        .collect()
+}
+
+/// `key: "value"`, quoted as JSON, which YAML reads as the same string
+fn yaml_line(key: &str, value: &str) -> String {
+    let value = serde_json::to_string(value).expect("Failed to quote frontmatter value");
+    format!("{key}: {value}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_apply_frontmatter() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_738_315_800);
+        let source = Path::new("docs/intro.md");
+        let document = "---\ntitle: Intro\nsource: custom\ntags:\n  - a\n---\n\n# Intro\n";
+        let generator = format!("generator: \"md2md {}\"\n", env!("CARGO_PKG_VERSION"));
+
+        assert_eq!(
+            apply_frontmatter(document, Frontmatter::Keep, source, time),
+            document
+        );
+        assert_eq!(
+            apply_frontmatter(document, Frontmatter::Strip, source, time),
+            "# Intro\n"
+        );
+        assert_eq!(
+            apply_frontmatter("# Intro\n", Frontmatter::Strip, source, time),
+            "# Intro\n"
+        );
+        assert_eq!(
+            apply_frontmatter(document, Frontmatter::Merge, source, time),
+            format!(
+                "---\ntitle: Intro\nsource: custom\ntags:\n  - a\ngenerated: \"2025-01-31T09:30:00Z\"\n{generator}---\n\n# Intro\n"
+            )
+        );
+        assert_eq!(
+            apply_frontmatter("# Intro\n", Frontmatter::Merge, source, time),
+            format!(
+                "---\ngenerated: \"2025-01-31T09:30:00Z\"\nsource: \"docs/intro.md\"\n{generator}---\n\n# Intro\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,179 @@
+//! Files as they were at a git revision, for `!include(CHANGELOG.md, rev="v1.2.0")`,
+//! and the files changed since one, for `--changed-since`
+//!
+//! Both need the `git` feature; without it such includes and runs fail.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The repository containing `path`, with its working tree, canonicalized
+#[cfg(feature = "git")]
+fn open_repository(path: &Path) -> io::Result<(git2::Repository, PathBuf)> {
+    let directory = path
+        .ancestors()
+        .find(|directory| directory.is_dir())
+        .unwrap_or(Path::new("."));
+    let repository = git2::Repository::discover(directory).map_err(|e| {
+        io::Error::other(format!(
+            "{} is not in a git repository: {}",
+            path.display(),
+            e.message()
+        ))
+    })?;
+    let workdir = repository
+        .workdir()
+        .and_then(|workdir| workdir.canonicalize().ok())
+        .ok_or_else(|| {
+            io::Error::other("Revisions can't be read from bare repositories".to_string())
+        })?;
+    Ok((repository, workdir))
+}
+
+#[cfg(feature = "git")]
+fn revision_tree<'r>(repository: &'r git2::Repository, rev: &str) -> io::Result<git2::Tree<'r>> {
+    repository
+        .revparse_single(rev)
+        .and_then(|object| object.peel_to_tree())
+        .map_err(|e| io::Error::other(format!("Unknown git revision `{rev}`: {}", e.message())))
+}
+
+/// The bytes of `path` as it was at `rev`, anything `git rev-parse` accepts, in the
+/// repository containing it. The file doesn't have to exist in the working tree.
+#[cfg(feature = "git")]
+pub fn read_at_revision(path: &Path, rev: &str) -> io::Result<Vec<u8>> {
+    let path = crate::file_handler::resolve_lenient(path);
+    let (repository, workdir) = open_repository(&path)?;
+    let relative = path.strip_prefix(&workdir).map_err(|_| {
+        io::Error::other(format!("{} is outside its git repository", path.display()))
+    })?;
+
+    let tree = revision_tree(&repository, rev)?;
+    let entry = tree.get_path(relative).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{} doesn't exist at revision `{rev}`", relative.display()),
+        )
+    })?;
+    let object = entry
+        .to_object(&repository)
+        .map_err(|e| io::Error::other(e.message().to_string()))?;
+    let blob = object.as_blob().ok_or_else(|| {
+        io::Error::other(format!(
+            "{} is not a file at revision `{rev}`",
+            relative.display()
+        ))
+    })?;
+    Ok(blob.content().to_vec())
+}
+
+/// Files of the repository containing `path` that differ from `rev`: changed,
+/// added, deleted or untracked, committed or not. Paths under the current directory
+/// are relative to it, like the paths md2md is usually given; others are absolute.
+#[cfg(feature = "git")]
+pub fn changed_files(path: &Path, rev: &str) -> io::Result<Vec<PathBuf>> {
+    let path = crate::file_handler::resolve_lenient(path);
+    let (repository, workdir) = open_repository(&path)?;
+    let tree = revision_tree(&repository, rev)?;
+    let mut options = git2::DiffOptions::new();
+    options.include_untracked(true).recurse_untracked_dirs(true);
+    let diff = repository
+        .diff_tree_to_workdir_with_index(Some(&tree), Some(&mut options))
+        .map_err(|e| io::Error::other(e.message().to_string()))?;
+
+    let current = std::env::current_dir().and_then(|directory| directory.canonicalize());
+    let mut files: Vec<PathBuf> = diff
+        .deltas()
+        .flat_map(|delta| [delta.old_file().path(), delta.new_file().path()])
+        .flatten()
+        .map(|relative| {
+            let file = workdir.join(relative);
+            match &current {
+                Ok(current) => file
+                    .strip_prefix(current)
+                    .map_or_else(|_| file.clone(), Path::to_path_buf),
+                Err(_) => file,
+            }
+        })
+        .collect();
+    files.sort();
+    files.dedup();
+    Ok(files)
+}
+
+/// Fails; md2md was built without the `git` feature
+#[cfg(not(feature = "git"))]
+pub fn changed_files(_path: &Path, rev: &str) -> io::Result<Vec<PathBuf>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Finding the files changed since `{rev}` needs md2md built with the `git` feature"),
+    ))
+}
+
+/// Fails; md2md was built without the `git` feature
+#[cfg(not(feature = "git"))]
+pub fn read_at_revision(_path: &Path, rev: &str) -> io::Result<Vec<u8>> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("Including from git revision `{rev}` needs md2md built with the `git` feature"),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_read_at_revision() {
+        use std::fs;
+        use tempfile::TempDir;
+
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let repository =
+            git2::Repository::init(temp_dir.path()).expect("Failed to init repository");
+        let changelog = temp_dir.path().join("CHANGELOG.md");
+        fs::write(&changelog, "## 1.2.0").expect("Failed to write CHANGELOG.md");
+
+        let mut index = repository.index().expect("Failed to open index");
+        index
+            .add_path(Path::new("CHANGELOG.md"))
+            .expect("Failed to stage CHANGELOG.md");
+        let tree_id = index.write_tree().expect("Failed to write tree");
+        let tree = repository.find_tree(tree_id).expect("Failed to find tree");
+        let signature =
+            git2::Signature::now("md2md", "md2md@example.com").expect("Failed to create signature");
+        let commit = repository
+            .commit(Some("HEAD"), &signature, &signature, "Release", &tree, &[])
+            .expect("Failed to commit");
+        repository
+            .tag_lightweight(
+                "v1.2.0",
+                &repository
+                    .find_object(commit, None)
+                    .expect("Failed to find commit"),
+                false,
+            )
+            .expect("Failed to tag");
+        fs::write(&changelog, "## 1.3.0\n\n## 1.2.0").expect("Failed to update CHANGELOG.md");
+
+        assert_eq!(
+            read_at_revision(&changelog, "v1.2.0").expect("Failed to read at revision"),
+            b"## 1.2.0"
+        );
+        let missing = read_at_revision(&temp_dir.path().join("gone.md"), "v1.2.0")
+            .expect_err("Missing file should fail");
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert!(read_at_revision(&changelog, "v9.9.9").is_err());
+    }
+
+    #[cfg(not(feature = "git"))]
+    #[test]
+    fn test_read_at_revision_needs_the_git_feature() {
+        let error = read_at_revision(Path::new("CHANGELOG.md"), "v1.2.0")
+            .expect_err("Reading a revision should fail without the git feature");
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+        let error = changed_files(Path::new("docs"), "main")
+            .expect_err("Diffing a revision should fail without the git feature");
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,370 @@
+//! Links from the first occurrence of each glossary term in an output to its entry.
+//!
+//! The glossary is a TOML file of terms under `[terms]`, each with the link of its
+//! entry and optionally a definition, written as the link's title:
+//!
+//! ```toml
+//! [terms]
+//! partial = { link = "/glossary/#partial", definition = "A file other documents include", aliases = ["partials"] }
+//! API = "https://en.wikipedia.org/wiki/API"
+//! ```
+//!
+//! Terms match whole words in any case, even ones starting or ending with punctuation
+//! like `C++` or `.NET`. Code, headings, links, HTML and frontmatter are left alone.
+
+use crate::include_resolver::CodeFences;
+use crate::mdx::frontmatter_end;
+use regex::{Regex, RegexBuilder};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GlossaryFile {
+    #[serde(default)]
+    terms: BTreeMap<String, EntryFile>,
+}
+
+/// A term's link, or its link with a definition and other names
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum EntryFile {
+    Link(String),
+    Entry {
+        link: String,
+        definition: Option<String>,
+        #[serde(default)]
+        aliases: Vec<String>,
+    },
+}
+
+/// A glossary entry that occurrences of its term link to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlossaryEntry {
+    pub term: String,
+    pub link: String,
+    pub definition: Option<String>,
+}
+
+/// Terms and the entries they link to
+#[derive(Debug, Clone)]
+pub struct Glossary {
+    entries: Vec<GlossaryEntry>,
+    /// Lowercase term or alias → index of its entry
+    names: HashMap<String, usize>,
+    /// Any of the names, longest first, so `code snippet` wins over `code`; whether a
+    /// match is a whole word is checked on its edges, as `\b` can't be next to `+`
+    pattern: Option<Regex>,
+}
+
+impl Glossary {
+    /// Reads the glossary file at `path`
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read glossary {}: {e}", path.display()))?;
+        let file: GlossaryFile = toml::from_str(&content)
+            .map_err(|e| format!("Invalid glossary {}: {e}", path.display()))?;
+        Self::new(file.terms.into_iter().map(|(term, entry)| match entry {
+            EntryFile::Link(link) => (
+                GlossaryEntry {
+                    term,
+                    link,
+                    definition: None,
+                },
+                Vec::new(),
+            ),
+            EntryFile::Entry {
+                link,
+                definition,
+                aliases,
+            } => (
+                GlossaryEntry {
+                    term,
+                    link,
+                    definition,
+                },
+                aliases,
+            ),
+        }))
+    }
+
+    /// A glossary of `entries`, each with the other names its term goes by
+    pub fn new(
+        entries: impl IntoIterator<Item = (GlossaryEntry, Vec<String>)>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut glossary = Self {
+            entries: Vec::new(),
+            names: HashMap::new(),
+            pattern: None,
+        };
+        for (entry, aliases) in entries {
+            let index = glossary.entries.len();
+            for name in std::iter::once(&entry.term).chain(&aliases) {
+                let name = name.trim();
+                if name.is_empty() {
+                    return Err(format!("Glossary term `{}` has an empty name", entry.term).into());
+                }
+                if let Some(other) = glossary.names.insert(name.to_lowercase(), index) {
+                    return Err(format!(
+                        "`{name}` names both glossary terms `{}` and `{}`",
+                        glossary.entries[other].term, entry.term
+                    )
+                    .into());
+                }
+            }
+            glossary.entries.push(entry);
+        }
+
+        let mut names: Vec<&String> = glossary.names.keys().collect();
+        names.sort_by_key(|name| std::cmp::Reverse(name.len()));
+        if !names.is_empty() {
+            let alternatives: Vec<String> = names.iter().map(|name| regex::escape(name)).collect();
+            glossary.pattern = Some(
+                RegexBuilder::new(&alternatives.join("|"))
+                    .case_insensitive(true)
+                    .build()?,
+            );
+        }
+        Ok(glossary)
+    }
+
+    /// The entries, in the order of their terms
+    pub fn entries(&self) -> &[GlossaryEntry] {
+        &self.entries
+    }
+
+    /// Links the first occurrence of every term in `content`, the Markdown of one
+    /// output, to its entry; later occurrences are left as they are
+    pub fn link_terms<'a>(&self, content: &'a str) -> Cow<'a, str> {
+        let Some(pattern) = &self.pattern else {
+            return Cow::Borrowed(content);
+        };
+        let fences = CodeFences::new(content);
+        let body_start = frontmatter_end(content).unwrap_or(0);
+        let mut linked: HashSet<usize> = HashSet::new();
+        let mut result = String::new();
+        let mut copied = 0;
+
+        let mut line_start = 0;
+        let mut blocks = Blocks::default();
+        for line in content.split_inclusive('\n') {
+            let start = line_start;
+            line_start += line.len();
+            let in_block = blocks.skip(line, fences.contain(content, start));
+            if start < body_start || in_block || !is_prose(line) {
+                continue;
+            }
+            let skipped = skipped_spans(line);
+            let mut at = 0;
+            while let Some(found) = pattern.find_at(line, at) {
+                if !is_whole_word(line, found.range()) {
+                    // A shorter name may still start inside it
+                    at = found.start()
+                        + line[found.start()..]
+                            .chars()
+                            .next()
+                            .map_or(1, char::len_utf8);
+                    continue;
+                }
+                at = found.end();
+                let Some(&index) = self.names.get(&found.as_str().to_lowercase()) else {
+                    continue;
+                };
+                if linked.contains(&index)
+                    || skipped.iter().any(|span| span.contains(&found.start()))
+                    || fences.contain(content, start + found.start())
+                {
+                    continue;
+                }
+                linked.insert(index);
+                let entry = &self.entries[index];
+                result.push_str(&content[copied..start + found.start()]);
+                result.push_str(&format!("[{}]({}", found.as_str(), entry.link));
+                if let Some(definition) = &entry.definition {
+                    result.push_str(&format!(" \"{}\"", definition.replace('"', "\\\"")));
+                }
+                result.push(')');
+                copied = start + found.end();
+            }
+            if linked.len() == self.entries.len() {
+                break;
+            }
+        }
+
+        if copied == 0 {
+            return Cow::Borrowed(content);
+        }
+        result.push_str(&content[copied..]);
+        Cow::Owned(result)
+    }
+}
+
+/// Whether terms on `line` may be linked: it isn't a heading or a link definition
+fn is_prose(line: &str) -> bool {
+    static DEFINITION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^\s*\[[^\]]+\]:").expect("Failed to compile link definition regex")
+    });
+    !line.trim_start().starts_with('#') && !DEFINITION_REGEX.is_match(line)
+}
+
+/// Whether `range` of `line` is a whole word, with no word character right before
+/// or after it
+fn is_whole_word(line: &str, range: Range<usize>) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    !line[..range.start].chars().next_back().is_some_and(is_word)
+        && !line[range.end..].chars().next().is_some_and(is_word)
+}
+
+/// Indented code blocks and HTML or JSX blocks, followed line by line
+#[derive(Default)]
+struct Blocks {
+    /// The previous line was blank; indented code can't interrupt a paragraph
+    after_blank: bool,
+    indented_code: bool,
+    /// An HTML block, which runs to the next blank line
+    html: bool,
+}
+
+impl Blocks {
+    /// Whether `line`, the next one, is in a block whose terms are left alone
+    fn skip(&mut self, line: &str, fenced: bool) -> bool {
+        static HTML_BLOCK_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^ {0,3}<(?:!--|/?[A-Za-z][A-Za-z0-9.-]*(?:\s|/?>|$))")
+                .expect("Failed to compile HTML block regex")
+        });
+        let blank = line.trim().is_empty();
+        let indented = line.starts_with("    ") || line.starts_with('\t');
+        self.indented_code =
+            !fenced && !blank && indented && (self.indented_code || self.after_blank);
+        if !fenced && !self.indented_code && HTML_BLOCK_REGEX.is_match(line) {
+            self.html = true;
+        }
+        let skip = self.indented_code || self.html;
+        if blank {
+            self.html = false;
+        }
+        self.after_blank = blank;
+        skip
+    }
+}
+
+/// Byte ranges of `line` that already are links, inline code, autolinks, HTML or URLs
+fn skipped_spans(line: &str) -> Vec<Range<usize>> {
+    static SKIPPED_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"`[^`]*`|!?\[[^\]]*\](?:\([^)]*\)|\[[^\]]*\])?|<[^>]*>|https?://\S+")
+            .expect("Failed to compile skipped span regex")
+    });
+    SKIPPED_REGEX
+        .find_iter(line)
+        .map(|span| span.range())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_first_occurrences_are_linked_outside_code_and_links() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("glossary.toml");
+        fs::write(
+            &path,
+            r#"[terms]
+partial = { link = "/glossary/#partial", definition = "A file other documents \"include\"", aliases = ["partials"] }
+"code snippet" = "/glossary/#code-snippet"
+code = "/glossary/#code"
+API = "https://en.wikipedia.org/wiki/API"
+"#,
+        )
+        .expect("Failed to write glossary");
+        let glossary = Glossary::load(&path).expect("Failed to load glossary");
+        assert_eq!(glossary.entries().len(), 4);
+
+        let content = [
+            "---",
+            "title: Partials and the API",
+            "---",
+            "# Partials",
+            "",
+            "Use `partial` files, see [partials](partials.md) or <img alt=\"partials\">.",
+            "",
+            "```md",
+            "A partial in code",
+            "```",
+            "",
+            "Partials hold a Code Snippet; a partial is reused. The api and the API.",
+            "",
+            "[api]: https://example.com/api",
+        ]
+        .join("\n");
+        let linked = glossary.link_terms(&content);
+        let expected = content.replace(
+            "Partials hold a Code Snippet; a partial is reused. The api and",
+            "[Partials](/glossary/#partial \"A file other documents \\\"include\\\"\") hold a [Code Snippet](/glossary/#code-snippet); a partial is reused. The [api](https://en.wikipedia.org/wiki/API) and",
+        );
+        assert_eq!(linked, expected);
+
+        // Without any of the terms, the content is borrowed as is
+        assert!(matches!(
+            glossary.link_terms("No terms here"),
+            Cow::Borrowed(_)
+        ));
+    }
+
+    #[test]
+    fn test_terms_with_punctuation_and_blocks_left_alone() {
+        let entry = |term: &str| {
+            (
+                GlossaryEntry {
+                    term: term.to_string(),
+                    link: format!("#{term}"),
+                    definition: None,
+                },
+                Vec::new(),
+            )
+        };
+        let glossary = Glossary::new([entry("C++"), entry(".NET"), entry("Node.js"), entry("API")])
+            .expect("Failed to create glossary");
+
+        let content = [
+            "Intro",
+            "",
+            "    API in indented code",
+            "",
+            "<div>",
+            "API in HTML",
+            "</div>",
+            "",
+            "Not ASP.NET or rapid; C++, .NET and Node.js. Then the API.",
+        ]
+        .join("\n");
+        let linked = glossary.link_terms(&content);
+        let expected = content.replace(
+            "C++, .NET and Node.js. Then the API.",
+            "[C++](#C++), [.NET](#.NET) and [Node.js](#Node.js). Then the [API](#API).",
+        );
+        assert_eq!(linked, expected);
+    }
+
+    #[test]
+    fn test_names_must_be_unique() {
+        let entry = |term: &str| GlossaryEntry {
+            term: term.to_string(),
+            link: format!("#{term}"),
+            definition: None,
+        };
+        let error = Glossary::new([
+            (entry("partial"), Vec::new()),
+            (entry("fragment"), vec!["Partial".to_string()]),
+        ])
+        .expect_err("A name shared by two terms should be rejected");
+        assert!(error.to_string().contains("names both glossary terms"));
+    }
+}
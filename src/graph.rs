@@ -0,0 +1,465 @@
+use crate::encoding;
+use crate::file_handler::collect_markdown_files;
+use crate::include_resolver::{
+    directive_path, find_directives, parse_codesnippet_parameters, parse_docsnippet_parameters,
+    parse_include_parameters, resolve_include_path,
+};
+use crate::types::InputEncoding;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// A single dependency from a document (or partial) to the file it pulls in
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct DependencyEdge {
+    pub from: String,
+    pub to: String,
+    /// Directive type that created the dependency: `include` or `codesnippet`
+    pub kind: String,
+}
+
+/// The file → partial dependency graph of a source tree
+#[derive(Debug, Default, Serialize)]
+pub struct DependencyGraph {
+    /// Top-level source documents
+    pub sources: BTreeSet<String>,
+    pub edges: BTreeSet<DependencyEdge>,
+    /// Files with an include read at a git revision, which can change while
+    /// the working tree doesn't
+    #[serde(skip)]
+    pub revision_includers: BTreeSet<String>,
+}
+
+impl DependencyGraph {
+    /// Walks every markdown file under `source_path`, read in `encoding`, and records
+    /// the partials and code snippets it depends on, following nested includes.
+    pub fn build(
+        source_path: &Path,
+        partials_path: &Path,
+        encoding: InputEncoding,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut graph = DependencyGraph::default();
+        let mut visited = HashSet::new();
+
+        for file in collect_markdown_files(source_path)? {
+            let file = normalize_path(&file);
+            graph.sources.insert(path_key(&file));
+            graph.scan_file(&file, partials_path, encoding, &mut visited);
+        }
+
+        Ok(graph)
+    }
+
+    fn scan_file(
+        &mut self,
+        file: &Path,
+        partials_path: &Path,
+        encoding: InputEncoding,
+        visited: &mut HashSet<PathBuf>,
+    ) {
+        if !visited.insert(file.to_path_buf()) {
+            return;
+        }
+
+        let Ok(content) = encoding::read_text(file, encoding) else {
+            return;
+        };
+
+        for (directive_type, directive) in find_directives(&content) {
+            let target = if directive_type == "include" {
+                parse_include_parameters(&directive)
+                    .ok()
+                    .and_then(|(path, params)| {
+                        if params.rev.is_some() {
+                            self.revision_includers.insert(path_key(file));
+                        }
+                        resolve_include_path(&path, file, partials_path).ok()
+                    })
+            } else if directive_type == "docsnippet" {
+                parse_docsnippet_parameters(&directive)
+                    .ok()
+                    .map(|(path, _)| resolve_snippet_path(&path, file))
+            } else {
+                parse_codesnippet_parameters(&directive)
+                    .ok()
+                    .map(|(path, _)| resolve_snippet_path(&path, file))
+            };
+
+            let Some(target) = target.map(|t| normalize_path(&t)) else {
+                continue;
+            };
+
+            self.edges.insert(DependencyEdge {
+                from: path_key(file),
+                to: path_key(&target),
+                kind: directive_type.clone(),
+            });
+
+            if directive_type == "include" {
+                self.scan_file(&target, partials_path, encoding, visited);
+            }
+        }
+    }
+
+    /// All nodes in the graph: sources plus every dependency target
+    pub fn nodes(&self) -> BTreeSet<&str> {
+        self.sources
+            .iter()
+            .map(String::as_str)
+            .chain(
+                self.edges
+                    .iter()
+                    .flat_map(|e| [e.from.as_str(), e.to.as_str()]),
+            )
+            .collect()
+    }
+
+    /// Whether `file` is a source document or a dependency of one
+    pub fn contains(&self, file: &Path) -> bool {
+        self.nodes()
+            .into_iter()
+            .any(|node| same_file(Path::new(node), file))
+    }
+
+    /// Every file that is the target of at least one directive
+    pub fn referenced_files(&self) -> BTreeSet<&str> {
+        self.edges.iter().map(|e| e.to.as_str()).collect()
+    }
+
+    /// Source documents that depend on `file`, directly or through other partials.
+    ///
+    /// A source document passed as `file` is affected by its own changes and is
+    /// included in the result.
+    pub fn affected_sources(&self, file: &Path) -> BTreeSet<String> {
+        let mut affected = BTreeSet::new();
+        let mut pending: Vec<String> = self
+            .nodes()
+            .into_iter()
+            .filter(|node| same_file(Path::new(node), file))
+            .map(str::to_string)
+            .collect();
+
+        while let Some(node) = pending.pop() {
+            if !affected.insert(node.clone()) {
+                continue;
+            }
+            pending.extend(
+                self.edges
+                    .iter()
+                    .filter(|e| e.to == node)
+                    .map(|e| e.from.clone()),
+            );
+        }
+
+        affected.retain(|node| self.sources.contains(node));
+        affected
+    }
+
+    /// Markdown files under `partials_path` that no source document reaches,
+    /// directly or through other partials
+    pub fn orphaned_partials(
+        &self,
+        partials_path: &Path,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let referenced = self.referenced_files();
+        let mut orphans: Vec<String> = collect_markdown_files(partials_path)?
+            .iter()
+            .map(|file| path_key(&normalize_path(file)))
+            .filter(|file| !referenced.contains(file.as_str()))
+            .collect();
+        orphans.sort();
+        Ok(orphans)
+    }
+
+    /// Renders the graph in Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let mut output = String::from("digraph md2md {\n    rankdir=LR;\n");
+        for source in &self.sources {
+            output.push_str(&format!("    \"{}\" [shape=box];\n", escape_label(source)));
+        }
+        for edge in &self.edges {
+            let style = if edge.kind != "include" {
+                " [style=dashed]"
+            } else {
+                ""
+            };
+            output.push_str(&format!(
+                "    \"{}\" -> \"{}\"{};\n",
+                escape_label(&edge.from),
+                escape_label(&edge.to),
+                style
+            ));
+        }
+        output.push_str("}\n");
+        output
+    }
+
+    /// Renders the graph as a Mermaid flowchart
+    pub fn to_mermaid(&self) -> String {
+        let nodes: Vec<&str> = self.nodes().into_iter().collect();
+        let id = |path: &str| nodes.iter().position(|n| *n == path).unwrap_or(0);
+
+        let mut output = String::from("graph LR\n");
+        for (index, node) in nodes.iter().enumerate() {
+            output.push_str(&format!("    n{index}[\"{}\"]\n", escape_label(node)));
+        }
+        for edge in &self.edges {
+            let arrow = if edge.kind != "include" {
+                "-.->"
+            } else {
+                "-->"
+            };
+            output.push_str(&format!(
+                "    n{} {} n{}\n",
+                id(&edge.from),
+                arrow,
+                id(&edge.to)
+            ));
+        }
+        output
+    }
+
+    /// Renders the graph as pretty-printed JSON
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Code snippets are resolved relative to the including file, like `process_code_snippet`
+fn resolve_snippet_path(snippet_path: &str, current_file: &Path) -> PathBuf {
+    let snippet_path = directive_path(snippet_path);
+    if snippet_path.has_root() {
+        snippet_path
+    } else {
+        current_file
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(snippet_path)
+    }
+}
+
+/// Lexically removes `.` and `..` components so the same file always maps to the same node
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Compares paths lexically first and falls back to the filesystem, so
+/// `partials/x.md` matches the absolute path of the same file
+fn same_file(a: &Path, b: &Path) -> bool {
+    if normalize_path(a) == normalize_path(b) {
+        return true;
+    }
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn path_key(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+fn escape_label(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup() -> (TempDir, PathBuf, PathBuf) {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        fs::write(
+            partials_dir.join("header.md"),
+            "# Header\n\n!include (logo.md)",
+        )
+        .expect("Failed to write header.md");
+        fs::write(partials_dir.join("logo.md"), "LOGO").expect("Failed to write logo.md");
+        fs::write(source_dir.join("code.rs"), "fn main() {}").expect("Failed to write code.rs");
+        fs::write(
+            source_dir.join("a.md"),
+            "!include (header.md)\n\n!codesnippet (code.rs, lang=\"rust\")",
+        )
+        .expect("Failed to write a.md");
+        fs::write(
+            source_dir.join("b.md"),
+            "!include (header.md)\n\n```md\n!include (ignored.md)\n```",
+        )
+        .expect("Failed to write b.md");
+
+        (temp_dir, source_dir, partials_dir)
+    }
+
+    #[test]
+    fn test_build_dependency_graph() {
+        let (_temp_dir, source_dir, partials_dir) = setup();
+        let graph = DependencyGraph::build(&source_dir, &partials_dir, InputEncoding::default())
+            .expect("Failed to build graph");
+
+        let a = path_key(&source_dir.join("a.md"));
+        let header = path_key(&partials_dir.join("header.md"));
+        let logo = path_key(&partials_dir.join("logo.md"));
+
+        assert_eq!(graph.sources.len(), 2);
+        assert_eq!(graph.edges.len(), 4);
+        assert!(graph.edges.iter().any(|e| e.from == a && e.to == header));
+        assert!(graph.edges.iter().any(|e| e.from == header && e.to == logo));
+        assert!(
+            graph
+                .edges
+                .iter()
+                .any(|e| e.from == a && e.kind == "codesnippet")
+        );
+        assert!(!graph.edges.iter().any(|e| e.to.ends_with("ignored.md")));
+    }
+
+    #[test]
+    fn test_docsnippets_depend_on_their_code_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("docs");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::write(source_dir.join("lib.rs"), "/// Entry\nfn main() {}")
+            .expect("Failed to write lib.rs");
+        fs::write(
+            source_dir.join("api.md"),
+            "!docsnippet (lib.rs, item=\"main\")",
+        )
+        .expect("Failed to write api.md");
+
+        let graph = DependencyGraph::build(
+            &source_dir,
+            &temp_dir.path().join("partials"),
+            InputEncoding::default(),
+        )
+        .expect("Failed to build graph");
+        assert_eq!(
+            graph.affected_sources(&source_dir.join("lib.rs")),
+            BTreeSet::from([path_key(&source_dir.join("api.md"))])
+        );
+        assert!(graph.to_mermaid().contains("-.->"));
+    }
+
+    #[test]
+    fn test_graph_renderers() {
+        let (_temp_dir, source_dir, partials_dir) = setup();
+        let graph = DependencyGraph::build(&source_dir, &partials_dir, InputEncoding::default())
+            .expect("Failed to build graph");
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph md2md {"));
+        assert!(dot.contains("header.md\" -> \""));
+        assert!(dot.contains("[style=dashed]"));
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.starts_with("graph LR\n"));
+        assert!(mermaid.contains(" --> "));
+        assert!(mermaid.contains(" -.-> "));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&graph.to_json().expect("Failed to serialize graph"))
+                .expect("Graph JSON should be valid");
+        assert_eq!(json["edges"].as_array().map(Vec::len), Some(4));
+    }
+
+    #[test]
+    fn test_orphaned_partials() {
+        let (_temp_dir, source_dir, partials_dir) = setup();
+        fs::write(partials_dir.join("unused.md"), "!include (also-unused.md)")
+            .expect("Failed to write unused.md");
+        fs::write(partials_dir.join("also-unused.md"), "Nobody reaches me")
+            .expect("Failed to write also-unused.md");
+
+        let graph = DependencyGraph::build(&source_dir, &partials_dir, InputEncoding::default())
+            .expect("Failed to build graph");
+        let orphans = graph
+            .orphaned_partials(&partials_dir)
+            .expect("Failed to list orphans");
+
+        assert_eq!(
+            orphans,
+            vec![
+                path_key(&partials_dir.join("also-unused.md")),
+                path_key(&partials_dir.join("unused.md")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_affected_sources() {
+        let (_temp_dir, source_dir, partials_dir) = setup();
+        fs::write(source_dir.join("c.md"), "No includes here").expect("Failed to write c.md");
+
+        let graph = DependencyGraph::build(&source_dir, &partials_dir, InputEncoding::default())
+            .expect("Failed to build graph");
+        let a = path_key(&source_dir.join("a.md"));
+        let b = path_key(&source_dir.join("b.md"));
+        let c = path_key(&source_dir.join("c.md"));
+
+        let through_nested = graph.affected_sources(&partials_dir.join("logo.md"));
+        assert_eq!(through_nested, BTreeSet::from([a.clone(), b]));
+
+        let snippet = graph.affected_sources(&source_dir.join("code.rs"));
+        assert_eq!(snippet, BTreeSet::from([a]));
+
+        assert_eq!(
+            graph.affected_sources(&source_dir.join("c.md")),
+            BTreeSet::from([c])
+        );
+        assert!(
+            graph
+                .affected_sources(&partials_dir.join("missing.md"))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        assert_eq!(
+            normalize_path(Path::new("docs/./guide/../partials/x.md")),
+            PathBuf::from("docs/partials/x.md")
+        );
+        assert_eq!(
+            normalize_path(Path::new("../x.md")),
+            PathBuf::from("../x.md")
+        );
+    }
+
+    #[test]
+    fn test_sources_are_read_in_their_encoding() {
+        let (_temp_dir, source_dir, partials_dir) = setup();
+        // "Café" in Latin-1 isn't valid UTF-8
+        let mut latin1 = b"Caf\xe9\n\n".to_vec();
+        latin1.extend_from_slice(b"!include(latin1.md)");
+        fs::write(source_dir.join("c.md"), latin1).expect("Failed to write c.md");
+        fs::write(partials_dir.join("latin1.md"), "Partial").expect("Failed to write latin1.md");
+        let c = path_key(&source_dir.join("c.md"));
+        let partial = partials_dir.join("latin1.md");
+
+        let graph = DependencyGraph::build(&source_dir, &partials_dir, InputEncoding::Utf8)
+            .expect("Failed to build graph");
+        assert!(graph.affected_sources(&partial).is_empty());
+
+        let graph = DependencyGraph::build(&source_dir, &partials_dir, InputEncoding::Latin1)
+            .expect("Failed to build graph");
+        assert_eq!(graph.affected_sources(&partial), BTreeSet::from([c]));
+    }
+}
@@ -0,0 +1,92 @@
+//! Duplicate heading anchors in a resolved document.
+//!
+//! Including the same partial twice repeats its headings, and with them their
+//! anchors. Duplicates can be reported, or given a unique `{#anchor-1}` id
+//! attribute so tables of contents and links can tell them apart.
+
+use crate::markedpp::{Heading, explicit_id, find_headings, heading_anchor};
+use std::collections::HashSet;
+
+/// A heading whose anchor is already used by an earlier heading
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateAnchor {
+    /// 1-indexed line of the repeated heading
+    pub line: usize,
+    pub anchor: String,
+    /// Unused anchor the heading gets when duplicates are deduplicated
+    pub replacement: String,
+}
+
+/// Finds every heading of `content` whose anchor repeats an earlier one
+pub fn find_duplicate_anchors(content: &str) -> Vec<DuplicateAnchor> {
+    let lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    duplicates(&find_headings(&lines))
+}
+
+/// Gives every repeated heading an explicit id with a `-1`, `-2`... suffix
+pub fn dedupe_anchors(content: &str) -> String {
+    let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    let headings = find_headings(&lines);
+    for duplicate in duplicates(&headings) {
+        let line = &mut lines[duplicate.line - 1];
+        let text = line.trim_end();
+        // An explicit id is replaced rather than followed by a second one
+        let text = match explicit_id(text) {
+            Some(id) => text[..text.len() - id.len() - 3].trim_end(),
+            None => text,
+        };
+        *line = format!("{text} {{#{}}}", duplicate.replacement);
+    }
+    lines.join("\n")
+}
+
+fn duplicates(headings: &[Heading]) -> Vec<DuplicateAnchor> {
+    let anchors: Vec<String> = headings
+        .iter()
+        .map(|heading| heading_anchor(&heading.text))
+        .collect();
+    let mut used: HashSet<String> = anchors.iter().cloned().collect();
+    let mut seen = HashSet::new();
+
+    let mut duplicates = Vec::new();
+    for (heading, anchor) in headings.iter().zip(anchors) {
+        if seen.insert(anchor.clone()) {
+            continue;
+        }
+        let replacement = (1..)
+            .map(|n| format!("{anchor}-{n}"))
+            .find(|candidate| !used.contains(candidate))
+            .expect("Failed to find an unused anchor");
+        used.insert(replacement.clone());
+        duplicates.push(DuplicateAnchor {
+            line: heading.line + 1,
+            anchor,
+            replacement,
+        });
+    }
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_duplicate_anchors_get_unused_suffixes() {
+        let content = "# Guide\n\n## Setup\n\n## Setup 1\n\n```md\n## Setup\n```\n\n## Setup\n\n## Setup {#setup}";
+
+        let duplicates = find_duplicate_anchors(content);
+        assert_eq!(
+            duplicates
+                .iter()
+                .map(|d| (d.line, d.replacement.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(11, "setup-2"), (13, "setup-3")]
+        );
+
+        assert_eq!(
+            dedupe_anchors(content),
+            "# Guide\n\n## Setup\n\n## Setup 1\n\n```md\n## Setup\n```\n\n## Setup {#setup-2}\n\n## Setup {#setup-3}"
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! Results of the previous run of a profile, kept to compare the next run against
+
+use crate::types::{ProcessingConfig, ProcessingSummary};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory, relative to the working directory, where the last run of every profile is kept
+pub const HISTORY_DIR: &str = ".md2md/runs";
+
+/// Outcome of every source file of a run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RunRecord {
+    /// Whether each file succeeded, by path
+    pub files: BTreeMap<String, bool>,
+}
+
+/// How a run differs from the previous one of its profile
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RunDelta {
+    /// Files that weren't part of the previous run
+    pub added: Vec<String>,
+    /// Files of the previous run that are gone
+    pub removed: Vec<String>,
+    /// Files that succeeded in the previous run and fail now
+    pub newly_failing: Vec<String>,
+    /// Files that failed in the previous run and succeed now
+    pub newly_fixed: Vec<String>,
+}
+
+impl RunDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.newly_failing.is_empty()
+            && self.newly_fixed.is_empty()
+    }
+}
+
+impl RunRecord {
+    pub fn from_summary(summary: &ProcessingSummary) -> Self {
+        Self {
+            files: summary
+                .results
+                .iter()
+                .map(|result| (result.file_path.clone(), result.success))
+                .collect(),
+        }
+    }
+
+    /// Reads a saved record; `None` when the profile has never run
+    pub fn load(path: &Path) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(path)?;
+        let record = serde_json::from_str(&content)
+            .map_err(|e| format!("Invalid run record {}: {e}", path.display()))?;
+        Ok(Some(record))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// Changes from this, the previous run, to `summary`
+    pub fn compare(&self, summary: &ProcessingSummary) -> RunDelta {
+        let current = Self::from_summary(summary);
+        let mut delta = RunDelta::default();
+        for (file, &success) in &current.files {
+            match self.files.get(file) {
+                None => delta.added.push(file.clone()),
+                Some(true) if !success => delta.newly_failing.push(file.clone()),
+                Some(false) if success => delta.newly_fixed.push(file.clone()),
+                Some(_) => {}
+            }
+        }
+        delta.removed = self
+            .files
+            .keys()
+            .filter(|file| !current.files.contains_key(*file))
+            .cloned()
+            .collect();
+        delta
+    }
+}
+
+/// Profile of a run when none is named: a hash of its source, partials and output paths
+pub fn default_profile(config: &ProcessingConfig) -> String {
+    // FNV-1a, which unlike the std hashers is stable across Rust versions
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for path in [
+        &config.source_path,
+        &config.partials_path,
+        &config.output_path,
+    ] {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        for byte in path.to_string_lossy().bytes().chain([0]) {
+            hash ^= u64::from(byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+    format!("{hash:016x}")
+}
+
+/// Where the last run of `profile` is kept; characters unsafe in file names become `_`
+pub fn record_path(profile: &str) -> PathBuf {
+    let name: String = profile
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    Path::new(HISTORY_DIR).join(format!("{name}.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::FileProcessResult;
+    use tempfile::TempDir;
+
+    fn summary(files: &[(&str, bool)]) -> ProcessingSummary {
+        let mut summary = ProcessingSummary::new();
+        for &(file, success) in files {
+            summary.add_result(FileProcessResult {
+                file_path: file.to_string(),
+                success,
+                includes: Vec::new(),
+                error_message: None,
+                error_code: None,
+                sizes: None,
+            });
+        }
+        summary
+    }
+
+    #[test]
+    fn test_compare_with_previous_run() {
+        let previous = RunRecord::from_summary(&summary(&[
+            ("a.md", true),
+            ("b.md", false),
+            ("c.md", true),
+            ("gone.md", true),
+        ]));
+        let delta = previous.compare(&summary(&[
+            ("a.md", false),
+            ("b.md", true),
+            ("c.md", true),
+            ("new.md", false),
+        ]));
+
+        assert_eq!(
+            delta,
+            RunDelta {
+                added: vec!["new.md".to_string()],
+                removed: vec!["gone.md".to_string()],
+                newly_failing: vec!["a.md".to_string()],
+                newly_fixed: vec!["b.md".to_string()],
+            }
+        );
+        assert!(previous.compare(&summary(&[])).added.is_empty());
+    }
+
+    #[test]
+    fn test_record_round_trip_and_paths() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("runs/docs.json");
+        assert_eq!(RunRecord::load(&path).expect("Failed to load"), None);
+
+        let record = RunRecord::from_summary(&summary(&[("a.md", true)]));
+        record.save(&path).expect("Failed to save run record");
+        assert_eq!(
+            RunRecord::load(&path).expect("Failed to load"),
+            Some(record)
+        );
+
+        assert_eq!(
+            record_path("docs/en"),
+            Path::new(HISTORY_DIR).join("docs_en.json")
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd, html};
+
+/// Page used when no `--html-template` is given
+pub const DEFAULT_TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">
+<title>{{ title }}</title>
+{{ css }}
+</head>
+<body>
+{{ content }}
+</body>
+</html>
+";
+
+/// Renders resolved Markdown into an HTML page.
+///
+/// `template` receives the rendered body as `{{ content }}`, the first level-1
+/// heading (or `fallback_title`) as `{{ title }}` and a stylesheet `<link>` for
+/// `css` as `{{ css }}`.
+pub fn render_page(
+    markdown: &str,
+    template: &str,
+    css: Option<&str>,
+    fallback_title: &str,
+) -> String {
+    let mut body = String::new();
+    html::push_html(&mut body, Parser::new_ext(markdown, options()));
+
+    let title = first_heading(markdown).unwrap_or_else(|| fallback_title.to_string());
+    let css_link = css
+        .map(|href| format!("<link rel=\"stylesheet\" href=\"{}\">", escape(href)))
+        .unwrap_or_default();
+
+    template
+        .replace("{{ title }}", &escape(&title))
+        .replace("{{ css }}", &css_link)
+        .replace("{{ content }}", &body)
+}
+
+/// CommonMark with tables, footnotes, strikethrough, task lists and `{#id}` heading attributes
+fn options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES
+}
+
+/// Plain text of the first level-1 heading
+fn first_heading(markdown: &str) -> Option<String> {
+    let mut title: Option<String> = None;
+    for event in Parser::new_ext(markdown, options()) {
+        match (event, title.as_mut()) {
+            (
+                Event::Start(Tag::Heading {
+                    level: HeadingLevel::H1,
+                    ..
+                }),
+                None,
+            ) => title = Some(String::new()),
+            (Event::Text(text) | Event::Code(text), Some(title)) => title.push_str(&text),
+            (Event::End(TagEnd::Heading(HeadingLevel::H1)), Some(_)) => break,
+            _ => {}
+        }
+    }
+    title.filter(|title| !title.is_empty())
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_page() {
+        let page = render_page(
+            "# Hello `md2md` & co\n\n| a |\n|---|\n| b |",
+            DEFAULT_TEMPLATE,
+            Some("style.css"),
+            "fallback",
+        );
+
+        assert!(page.contains("<title>Hello md2md &amp; co</title>"));
+        assert!(page.contains("<link rel=\"stylesheet\" href=\"style.css\">"));
+        assert!(page.contains("<h1>Hello <code>md2md</code> &amp; co</h1>"));
+        assert!(page.contains("<td>b</td>"));
+
+        let page = render_page(
+            "Just text",
+            "<t>{{ title }}</t>{{ css }}{{ content }}",
+            None,
+            "doc",
+        );
+        assert_eq!(page, "<t>doc</t><p>Just text</p>\n");
+    }
+}
@@ -0,0 +1,119 @@
+use regex::Regex;
+use std::fs;
+use std::path::Path;
+
+/// Name of the ignore file looked up at the root of a source directory
+pub const IGNORE_FILE_NAME: &str = ".md2mdignore";
+
+/// Gitignore-style patterns excluding source files from processing.
+///
+/// Supports `#` comments, `*`, `**`, `?`, a leading `/` to anchor a pattern to
+/// the source root, a trailing `/` to match directories only and `!` to
+/// re-include a previously ignored path. Later patterns win.
+#[derive(Debug, Default)]
+pub struct IgnoreRules {
+    rules: Vec<(Regex, bool)>,
+}
+
+impl IgnoreRules {
+    /// Parses ignore rules from the contents of an ignore file
+    pub fn parse(content: &str) -> Self {
+        let rules = content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let (pattern, negated) = match line.strip_prefix('!') {
+                    Some(rest) => (rest, true),
+                    None => (line, false),
+                };
+                pattern_to_regex(pattern).map(|regex| (regex, negated))
+            })
+            .collect();
+
+        Self { rules }
+    }
+
+    /// Loads `.md2mdignore` from `source_dir`, returning empty rules when there is none
+    pub fn load(source_dir: &Path) -> Self {
+        fs::read_to_string(source_dir.join(IGNORE_FILE_NAME))
+            .map(|content| Self::parse(&content))
+            .unwrap_or_default()
+    }
+
+    /// Whether a file path relative to the source root is ignored
+    pub fn is_ignored(&self, relative_path: &Path) -> bool {
+        let path = relative_path.to_string_lossy().replace('\\', "/");
+        let mut ignored = false;
+        for (regex, negated) in &self.rules {
+            if regex.is_match(&path) {
+                ignored = !negated;
+            }
+        }
+        ignored
+    }
+}
+
+fn pattern_to_regex(pattern: &str) -> Option<Regex> {
+    let directory_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    let anchored = pattern.starts_with('/') || pattern.contains('/');
+    let pattern = pattern.trim_start_matches('/');
+    if pattern.is_empty() {
+        return None;
+    }
+
+    let mut body = String::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    body.push_str("(?:.*/)?");
+                } else {
+                    body.push_str(".*");
+                }
+            }
+            '*' => body.push_str("[^/]*"),
+            '?' => body.push_str("[^/]"),
+            other => body.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+
+    let prefix = if anchored { "^" } else { "^(?:.*/)?" };
+    let suffix = if directory_only { "/.*$" } else { "(?:/.*)?$" };
+    Regex::new(&format!("{prefix}{body}{suffix}")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ignore_rules_patterns() {
+        let rules = IgnoreRules::parse(
+            "# Drafts are never published\ndrafts/\n*.draft.md\n/internal.md\ndocs/**/secret.md\n",
+        );
+
+        assert!(rules.is_ignored(Path::new("drafts/idea.md")));
+        assert!(rules.is_ignored(Path::new("guide/drafts/idea.md")));
+        assert!(rules.is_ignored(Path::new("guide/intro.draft.md")));
+        assert!(rules.is_ignored(Path::new("internal.md")));
+        assert!(rules.is_ignored(Path::new("docs/a/b/secret.md")));
+        assert!(rules.is_ignored(Path::new("docs/secret.md")));
+
+        assert!(!rules.is_ignored(Path::new("guide/internal.md")));
+        assert!(!rules.is_ignored(Path::new("drafts.md")));
+        assert!(!rules.is_ignored(Path::new("guide/intro.md")));
+    }
+
+    #[test]
+    fn test_ignore_rules_negation() {
+        let rules = IgnoreRules::parse("drafts/\n!drafts/keep.md\n");
+
+        assert!(rules.is_ignored(Path::new("drafts/idea.md")));
+        assert!(!rules.is_ignored(Path::new("drafts/keep.md")));
+    }
+}
@@ -1,8 +1,29 @@
-use crate::types::{CodeSnippetParameters, IncludeParameters, IncludeResult};
+use crate::condition;
+use crate::docsnippet::{DocComment, DocLanguage, extract_docs};
+use crate::error_code::ErrorCode;
+use crate::file_handler::is_markdown;
+use crate::headings;
+use crate::links::{BundledFile, rewrite_bundled_links};
+use crate::markedpp;
+use crate::mdx;
+use crate::mkdocs;
+use crate::partial_source::{FileSystemSource, PartialSource, RevisionSource};
+use crate::source_map::{self, LineOrigins};
+use crate::types::{
+    CodeSnippetParameters, Compat, DirectiveKind, DocSnippetParameters, DuplicateHeadings,
+    IncludeParameters, IncludeResult, ProcessingEvent, ResolutionOrder, RetryPolicy,
+    SourceComments, TemplateSyntax,
+};
+use crate::warnings::{Finding, WarningCollector};
 use regex::Regex;
-use std::collections::HashMap;
-use std::fs;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
 use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 
 /// Validates code fences in content and optionally fixes missing language definitions
 pub fn validate_and_fix_code_fences(
@@ -41,10 +62,10 @@ pub fn validate_and_fix_code_fences(
                             result_lines.push(fixed_line);
                             fence_stack.push((line_num, indent_level, true));
                         } else {
-                            return Err(format!(
+                            return Err(ErrorCode::MissingFenceLanguage.error(format!(
                                 "Code fence at line {} does not specify a language. Use --fix-code-fences to automatically fix this.",
                                 line_num + 1
-                            ).into());
+                            )));
                         }
                     } else {
                         // Opening fence with language is valid
@@ -60,16 +81,16 @@ pub fn validate_and_fix_code_fences(
                         fence_stack.pop();
                         result_lines.push(line.to_string());
                     } else if indent_level != open_indent {
-                        return Err(format!(
+                        return Err(ErrorCode::FenceIndentMismatch.error(format!(
                             "Code fence closing at line {} has different indentation than opening fence at line {}. Opening: {} spaces, Closing: {} spaces.",
                             line_num + 1, open_line + 1, open_indent, indent_level
-                        ).into());
+                        )));
                     } else if !lang_part.is_empty() {
                         // This looks like a new opening fence while another is still open
-                        return Err(format!(
+                        return Err(ErrorCode::NestedFence.error(format!(
                             "Found new code fence opening at line {} while previous fence from line {} is still open.",
                             line_num + 1, open_line + 1
-                        ).into());
+                        )));
                     } else {
                         result_lines.push(line.to_string());
                     }
@@ -85,11 +106,10 @@ pub fn validate_and_fix_code_fences(
     // Check if any fences are still open
     if !fence_stack.is_empty() {
         let (open_line, _, _) = fence_stack[0];
-        return Err(format!(
+        return Err(ErrorCode::UnclosedFence.error(format!(
             "Code fence opened at line {} was never closed.",
             open_line + 1
-        )
-        .into());
+        )));
     }
 
     // Preserve the original ending (newline or no newline)
@@ -101,45 +121,54 @@ pub fn validate_and_fix_code_fences(
     Ok(result)
 }
 
-/// Check if a position in the text is inside a code fence or inline code
-/// This function now requires valid code fences (validated by validate_and_fix_code_fences)
-fn is_inside_code_fence(content: &str, position: usize) -> bool {
-    let text_before = &content[..position];
-    let lines: Vec<&str> = text_before.lines().collect();
+/// Code fences of a text, found once so that any number of positions can be checked.
+/// This requires valid code fences (validated by validate_and_fix_code_fences)
+pub(crate) struct CodeFences {
+    ranges: Vec<std::ops::Range<usize>>,
+}
+
+impl CodeFences {
+    pub(crate) fn new(content: &str) -> Self {
+        Self {
+            ranges: code_fence_ranges(content),
+        }
+    }
 
-    let mut fence_stack = Vec::new(); // Stack to track open fences (indent_level)
+    /// Is `position` of `content`, the text these fences were found in, inside a code fence or inline code?
+    pub(crate) fn contain(&self, content: &str, position: usize) -> bool {
+        self.ranges.iter().any(|fence| fence.contains(&position))
+            || is_inside_inline_code(content, position)
+    }
+}
 
-    for line in lines.iter() {
+/// Byte ranges covered by code fences, from just after an opening fence's first three
+/// backticks to just after its closing fence's; an unclosed fence runs to the end.
+///
+/// A closing fence must have the opening one's indentation; other fences inside are ignored.
+fn code_fence_ranges(content: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut open: Option<(usize, usize)> = None; // (indent level, start of the range)
+    let mut line_start = 0;
+    for line in content.split_inclusive('\n') {
         let trimmed = line.trim_start();
         let indent_level = line.len() - trimmed.len();
-
-        // Check if this line contains a code fence
         if trimmed.starts_with("```") {
-            let fence_marker = trimmed.chars().take_while(|&c| c == '`').count();
-
-            if fence_marker >= 3 {
-                if fence_stack.is_empty() {
-                    // This is an opening fence
-                    fence_stack.push(indent_level);
-                } else {
-                    // Check if this is a closing fence
-                    let open_indent = fence_stack[fence_stack.len() - 1];
-
-                    // A closing fence should have the same indentation
-                    if indent_level == open_indent {
-                        fence_stack.pop();
-                    } else {
-                        // Ignored fence with wrong indentation
-                    }
+            let marker_end = line_start + indent_level + 3;
+            match open {
+                None => open = Some((indent_level, marker_end)),
+                Some((open_indent, start)) if open_indent == indent_level => {
+                    ranges.push(start..marker_end);
+                    open = None;
                 }
+                Some(_) => {}
             }
         }
+        line_start += line.len();
     }
-
-    let inside_fence = !fence_stack.is_empty();
-    let inside_inline = is_inside_inline_code(content, position);
-
-    inside_fence || inside_inline
+    if let Some((_, start)) = open {
+        ranges.push(start..content.len() + 1);
+    }
+    ranges
 }
 
 /// Check if a position is inside inline code (single backticks)
@@ -196,29 +225,81 @@ fn is_inside_inline_code(content: &str, position: usize) -> bool {
     single_backtick_count % 2 == 1
 }
 
+/// Returns the `(directive_type, directive)` pairs found in `content` outside code,
+/// e.g. `("include", "!include (header.md)")`
+pub fn find_directives(content: &str) -> Vec<(String, String)> {
+    directive_ranges(content)
+        .into_iter()
+        .map(|range| {
+            let directive = &content[range];
+            let directive_type = if directive.starts_with("!include") {
+                "include"
+            } else if directive.starts_with("!docsnippet") {
+                "docsnippet"
+            } else {
+                "codesnippet"
+            };
+            (directive_type.to_string(), directive.to_string())
+        })
+        .collect()
+}
+
+/// Byte ranges of the directives in `content` outside code, in order
+pub fn directive_ranges(content: &str) -> Vec<std::ops::Range<usize>> {
+    static DIRECTIVE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"!(include|codesnippet|docsnippet)\s*\((?:[^()]*|\([^()]*\))*\)")
+            .expect("Failed to compile directive regex pattern")
+    });
+
+    let fences = CodeFences::new(content);
+    DIRECTIVE_REGEX
+        .find_iter(content)
+        .filter(|directive| !fences.contain(content, directive.start()))
+        .map(|directive| directive.range())
+        .collect()
+}
+
 pub fn resolve_include_path(
     include_path_str: &str,
     current_file: &Path,
     partials_path: &Path,
 ) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let include_path = include_path_str.trim_matches(|c| c == '"' || c == '\'' || c == ' ');
+    let include_path = directive_path(include_path_str);
 
-    if include_path.starts_with("../") {
+    if include_path.starts_with(".") || include_path.starts_with("..") {
         // Relative to current file's directory
         let current_dir = current_file
             .parent()
-            .ok_or("Cannot determine parent directory of current file")
-            .expect("Failed to get parent directory of current file");
+            .ok_or("Cannot determine parent directory of current file")?;
         Ok(current_dir.join(include_path))
-    } else if include_path.starts_with('/') {
-        // Absolute path
-        Ok(PathBuf::from(include_path))
+    } else if include_path.has_root() {
+        // Absolute path: `/docs/x.md`, and on Windows `C:\docs\x.md` and `\\server\share\x.md`
+        Ok(include_path)
     } else {
         // Relative to partials directory
         Ok(partials_path.join(include_path))
     }
 }
 
+/// A path written in a directive, with `/` and `\` both taken as separators so that
+/// paths written on Windows resolve on every platform, and the other way round
+pub(crate) fn directive_path(path: &str) -> PathBuf {
+    let path = path.trim_matches(|c| c == '"' || c == '\'' || c == ' ');
+    PathBuf::from(path.replace(['/', '\\'], std::path::MAIN_SEPARATOR_STR))
+}
+
+/// A file argument in quotes or angle brackets, which may hold spaces, commas and parentheses
+const QUOTED_PATH: &str = r#""[^"]+"|'[^']+'|<[^>]+>"#;
+
+/// A directive's file argument without the quotes or angle brackets around it
+fn unquote_path(path: &str) -> &str {
+    let path = path.trim();
+    [('"', '"'), ('\'', '\''), ('<', '>')]
+        .iter()
+        .find_map(|&(open, close)| path.strip_prefix(open)?.strip_suffix(close))
+        .unwrap_or_else(|| path.trim_matches(|c| c == '"' || c == '\''))
+}
+
 pub fn parse_include_parameters(
     include_directive: &str,
 ) -> Result<(String, IncludeParameters), Box<dyn std::error::Error>> {
@@ -229,34 +310,63 @@ pub fn parse_include_parameters(
     // !include(file.md, title="Title", title-level=2)
     // !include(file.md, title="Title", title-level=2, values=[var1="val1", var2="val2"])
     // !include(file.md, values=[var1="val1", var2="val2"])
-
-    let main_regex = Regex::new(r"!include\s*\(\s*([^,\s]+)(?:,\s*(.+))?\s*\)")
-        .expect("Failed to compile main include regex");
-
-    let captures = main_regex
-        .captures(include_directive)
-        .ok_or(format!(
-            "Invalid include directive format '{include_directive}'"
+    // !include(file.md, if="target == 'cloud'")
+    // !include(CHANGELOG.md, rev="v1.2.0")  [as it was at a git revision]
+    // !include("release notes, 2024.md")  [quoted, or in <>, for spaces, commas and parentheses]
+    // Parameters may also be split over several lines, up to a closing `)` of its own
+
+    static MAIN_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(
+            r"(?s)!include\s*\(\s*({QUOTED_PATH}|[^,\s]+)(?:\s*,\s*(.+))?\s*\)"
         ))
-        .expect("Failed to capture include directive");
-
-    let file_path = captures
-        .get(1)
-        .ok_or("Missing file path in include directive")
-        .expect("Failed to get file path from include directive")
-        .as_str()
-        .trim()
-        .trim_matches(|c| c == '"' || c == '\'');
+        .expect("Failed to compile main include regex")
+    });
+    static TITLE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"title\s*=\s*"([^"]+)""#).expect("Failed to compile title regex")
+    });
+    static LEVEL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"title-level\s*=\s*(\d+)").expect("Failed to compile title-level regex")
+    });
+    static VALUES_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"values\s*=\s*\[([^\]]+)\]").expect("Failed to compile values regex")
+    });
+    static IF_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"\bif\s*=\s*(?:"([^"]*)"|'([^']*)')"#).expect("Failed to compile if regex")
+    });
+    static REV_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"\brev\s*=\s*"([^"]+)""#).expect("Failed to compile rev regex")
+    });
+    static PAIR_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"(\w+)\s*=\s*"([^"]+)""#).expect("Failed to compile values pair regex")
+    });
+
+    let captures = MAIN_REGEX.captures(include_directive).ok_or(format!(
+        "Invalid include directive format '{include_directive}'"
+    ))?;
+
+    let file_path = unquote_path(
+        captures
+            .get(1)
+            .ok_or("Missing file path in include directive")?
+            .as_str(),
+    );
 
     let mut params = IncludeParameters::default();
 
     if let Some(params_str) = captures.get(2) {
         let params_content = params_str.as_str();
+        // Values may be named like the other parameters, so those are looked for around them
+        let outside_values = match VALUES_REGEX.find(params_content) {
+            Some(values) => format!(
+                "{}{}",
+                &params_content[..values.start()],
+                &params_content[values.end()..]
+            ),
+            None => params_content.to_string(),
+        };
 
         // Parse title parameter
-        if let Ok(title_regex) = Regex::new(r#"title\s*=\s*"([^"]+)""#)
-            && let Some(title_capture) = title_regex.captures(params_content)
-        {
+        if let Some(title_capture) = TITLE_REGEX.captures(&outside_values) {
             params.title = Some(
                 title_capture
                     .get(1)
@@ -267,15 +377,13 @@ pub fn parse_include_parameters(
         }
 
         // Parse title-level parameter
-        if let Ok(level_regex) = Regex::new(r"title-level\s*=\s*(\d+)")
-            && let Some(level_capture) = level_regex.captures(params_content)
-        {
+        if let Some(level_capture) = LEVEL_REGEX.captures(&outside_values) {
             let level = level_capture
                 .get(1)
                 .expect("Failed to get title-level from include parameters")
                 .as_str()
                 .parse::<u8>()
-                .expect("Failed to parse title-level");
+                .map_err(|e| format!("Invalid title-level: {e}"))?;
             if (1..=6).contains(&level) {
                 params.title_level = Some(level);
             } else {
@@ -283,20 +391,37 @@ pub fn parse_include_parameters(
             }
         }
 
+        // Parse if parameter, rejecting conditions that can't be evaluated
+        if let Some(if_capture) = IF_REGEX.captures(&outside_values) {
+            let condition = if_capture
+                .get(1)
+                .or_else(|| if_capture.get(2))
+                .expect("Failed to get condition from include parameters")
+                .as_str();
+            condition::validate(condition)?;
+            params.condition = Some(condition.to_string());
+        }
+
+        // Parse rev parameter
+        if let Some(rev_capture) = REV_REGEX.captures(&outside_values) {
+            params.rev = Some(
+                rev_capture
+                    .get(1)
+                    .expect("Failed to get rev from include parameters")
+                    .as_str()
+                    .to_string(),
+            );
+        }
+
         // Parse values parameter - now using square brackets instead of parentheses
-        if let Ok(values_regex) = Regex::new(r"values\s*=\s*\[([^\]]+)\]")
-            && let Some(values_capture) = values_regex.captures(params_content)
-        {
+        if let Some(values_capture) = VALUES_REGEX.captures(params_content) {
             let values_str = values_capture
                 .get(1)
                 .expect("Failed to get values string from include parameters")
                 .as_str();
 
             // Parse individual key="value" pairs
-            let pair_regex = Regex::new(r#"(\w+)\s*=\s*"([^"]+)""#)
-                .expect("Failed to compile values pair regex");
-
-            for pair_capture in pair_regex.captures_iter(values_str) {
+            for pair_capture in PAIR_REGEX.captures_iter(values_str) {
                 let key = pair_capture
                     .get(1)
                     .expect("Failed to get key from values")
@@ -324,19 +449,30 @@ pub fn parse_codesnippet_parameters(
     // !codesnippet (path/to/file.py, lang="python", start=3)
     // !codesnippet (path/to/file.py, lang="python", start=3, end=10)
 
-    let main_regex = Regex::new(r"!codesnippet\s*\(\s*([^,)]+)(?:,\s*(.+))?\s*\)")
-        .expect("Failed to compile main codesnippet regex");
-
-    let captures = main_regex
+    static MAIN_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(
+            r"(?s)!codesnippet\s*\(\s*({QUOTED_PATH}|[^,)]+)(?:\s*,\s*(.+))?\s*\)"
+        ))
+        .expect("Failed to compile main codesnippet regex")
+    });
+    static LANG_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"lang\s*=\s*"([^"]+)""#).expect("Failed to compile lang regex")
+    });
+    static START_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"start\s*=\s*(\d+)").expect("Failed to compile start regex"));
+    static END_REGEX: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"end\s*=\s*(\d+)").expect("Failed to compile end regex"));
+
+    let captures = MAIN_REGEX
         .captures(codesnippet_directive)
         .ok_or("Invalid codesnippet directive format")?;
 
-    let file_path = captures
-        .get(1)
-        .ok_or("Missing file path in codesnippet directive")?
-        .as_str()
-        .trim()
-        .trim_matches(|c| c == '"' || c == '\'');
+    let file_path = unquote_path(
+        captures
+            .get(1)
+            .ok_or("Missing file path in codesnippet directive")?
+            .as_str(),
+    );
 
     let mut params = CodeSnippetParameters::default();
 
@@ -344,16 +480,12 @@ pub fn parse_codesnippet_parameters(
         let params_content = params_str.as_str();
 
         // Parse lang parameter
-        if let Ok(lang_regex) = Regex::new(r#"lang\s*=\s*"([^"]+)""#)
-            && let Some(lang_capture) = lang_regex.captures(params_content)
-        {
+        if let Some(lang_capture) = LANG_REGEX.captures(params_content) {
             params.lang = Some(lang_capture.get(1).unwrap().as_str().to_string());
         }
 
         // Parse start parameter
-        if let Ok(start_regex) = Regex::new(r"start\s*=\s*(\d+)")
-            && let Some(start_capture) = start_regex.captures(params_content)
-        {
+        if let Some(start_capture) = START_REGEX.captures(params_content) {
             let start = start_capture.get(1).unwrap().as_str().parse::<usize>()?;
             if start > 0 {
                 params.start = Some(start);
@@ -363,9 +495,7 @@ pub fn parse_codesnippet_parameters(
         }
 
         // Parse end parameter
-        if let Ok(end_regex) = Regex::new(r"end\s*=\s*(\d+)")
-            && let Some(end_capture) = end_regex.captures(params_content)
-        {
+        if let Some(end_capture) = END_REGEX.captures(params_content) {
             let end = end_capture.get(1).unwrap().as_str().parse::<usize>()?;
             if end > 0 {
                 params.end = Some(end);
@@ -383,45 +513,54 @@ pub fn process_code_snippet(
     current_file: &Path,
     params: &CodeSnippetParameters,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    // Resolve path relative to current file's directory (not partials)
-    let resolved_path = if file_path.is_absolute() {
-        file_path.to_path_buf()
-    } else {
-        current_file
-            .parent()
-            .ok_or("Cannot determine parent directory of current file")?
-            .join(file_path)
-    };
+    process_code_snippet_from(
+        file_path,
+        current_file,
+        params,
+        &FileSystemSource::default(),
+    )
+}
+
+/// Like [`process_code_snippet`], reading the code file from `source`
+pub fn process_code_snippet_from(
+    file_path: &Path,
+    current_file: &Path,
+    params: &CodeSnippetParameters,
+    source: &dyn PartialSource,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let resolved_path = code_snippet_path(&file_path.to_string_lossy(), current_file)?;
+
+    // Determine start and end lines (1-indexed in params, 0-indexed for array access)
+    let start_line = params.start.unwrap_or(1).saturating_sub(1);
 
-    // Read the file
-    let content = fs::read_to_string(&resolved_path).map_err(|e| {
+    // Read no further than needed; past `start_line` too, so a file shorter than that is
+    // read whole and its length can be reported
+    let limit = params.end.map(|end| end.max(start_line + 1));
+    let lines = source.read_lines(&resolved_path, limit).map_err(|e| {
         format!(
             "Failed to read code file '{}': {}",
             resolved_path.display(),
             e
         )
     })?;
-
-    let lines: Vec<&str> = content.lines().collect();
     if lines.is_empty() {
         return Ok(String::new());
     }
 
-    // Determine start and end lines (1-indexed in params, 0-indexed for array access)
-    let start_line = params.start.unwrap_or(1).saturating_sub(1);
     let end_line = params.end.unwrap_or(lines.len()).min(lines.len());
 
     if start_line >= lines.len() {
-        return Err(format!(
+        return Err(ErrorCode::InvalidSnippetRange.error(format!(
             "Start line {} is beyond the file length ({})",
             start_line + 1,
             lines.len()
-        )
-        .into());
+        )));
     }
 
     if params.end.is_some() && end_line <= start_line {
-        return Err("End line must be greater than start line".into());
+        return Err(
+            ErrorCode::InvalidSnippetRange.error("End line must be greater than start line")
+        );
     }
 
     // Extract the requested lines
@@ -433,16 +572,130 @@ pub fn process_code_snippet(
     Ok(format!("```{lang}\n{code_content}\n```"))
 }
 
+pub fn parse_docsnippet_parameters(
+    docsnippet_directive: &str,
+) -> Result<(String, DocSnippetParameters), Box<dyn std::error::Error>> {
+    // Match patterns like:
+    // !docsnippet (src/lib.rs)  [the module's own documentation]
+    // !docsnippet (src/lib.rs, item="Processor::run")
+    // !docsnippet (parser.py, item="Parser.parse")
+    static MAIN_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(
+            r"(?s)!docsnippet\s*\(\s*({QUOTED_PATH}|[^,)]+)(?:\s*,\s*(.+))?\s*\)"
+        ))
+        .expect("Failed to compile main docsnippet regex")
+    });
+    static ITEM_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"item\s*=\s*"([^"]+)""#).expect("Failed to compile item regex")
+    });
+
+    let captures = MAIN_REGEX
+        .captures(docsnippet_directive)
+        .ok_or("Invalid docsnippet directive format")?;
+    let file_path = unquote_path(
+        captures
+            .get(1)
+            .ok_or("Missing file path in docsnippet directive")?
+            .as_str(),
+    );
+
+    let mut params = DocSnippetParameters::default();
+    if let Some(params_str) = captures.get(2) {
+        let item = ITEM_REGEX
+            .captures(params_str.as_str())
+            .ok_or("Expected item=\"...\" in docsnippet directive")?;
+        params.item = Some(item[1].trim().to_string());
+    }
+
+    Ok((file_path.to_string(), params))
+}
+
+/// The documentation of the item `params` names in the Rust or Python file at
+/// `file_path`, relative to `current_file`, read from `source`
+pub fn process_doc_snippet_from(
+    file_path: &Path,
+    current_file: &Path,
+    params: &DocSnippetParameters,
+    source: &dyn PartialSource,
+) -> Result<DocComment, Box<dyn std::error::Error>> {
+    let resolved_path = code_snippet_path(&file_path.to_string_lossy(), current_file)?;
+    let language = DocLanguage::from_path(&resolved_path).ok_or_else(|| {
+        ErrorCode::InvalidDocSnippetDirective.error(format!(
+            "Documentation can only be read from Rust or Python files, not '{}'",
+            resolved_path.display()
+        ))
+    })?;
+    let code = source.read(&resolved_path).map_err(|e| {
+        ErrorCode::MissingCodeSnippet.error(format!(
+            "Failed to read code file '{}': {e}",
+            resolved_path.display()
+        ))
+    })?;
+    extract_docs(&code, language, params.item.as_deref())
+}
+
+/// Where the code file of a `!codesnippet` in `current_file` is: relative to the
+/// directory of `current_file`, not to the partials
+pub(crate) fn code_snippet_path(
+    path: &str,
+    current_file: &Path,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = directive_path(path);
+    if path.has_root() {
+        return Ok(path);
+    }
+    Ok(current_file
+        .parent()
+        .ok_or("Cannot determine parent directory of current file")?
+        .join(path))
+}
+
+/// Defaults a partial declares for its variables in a leading
+/// `<!-- md2md:vars product="md2md" version -->` block, and where the rest of it starts.
+///
+/// Names without a value only document that the partial expects them.
+pub fn declared_defaults(content: &str) -> (HashMap<String, String>, usize) {
+    static BLOCK_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"\A\s*<!--\s*md2md:vars\b((?s:.*?))-->[ \t]*(?:\r?\n)?")
+            .expect("Failed to compile vars block regex")
+    });
+    static PAIR_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"(\w+)(?:\s*=\s*"([^"]*)")?"#).expect("Failed to compile vars pair regex")
+    });
+    let Some(block) = BLOCK_REGEX.captures(content) else {
+        return (HashMap::new(), 0);
+    };
+    let defaults = PAIR_REGEX
+        .captures_iter(&block[1])
+        .filter_map(|pair| Some((pair[1].to_string(), pair.get(2)?.as_str().to_string())))
+        .collect();
+    (defaults, block.get(0).map_or(0, |m| m.end()))
+}
+
 pub fn process_variables(
     content: &str,
     variables: &HashMap<String, String>,
+) -> Result<String, Box<dyn std::error::Error>> {
+    process_variables_with_passthrough(content, variables, None)
+}
+
+/// Like [`process_variables`], leaving the tags of another template engine intact.
+///
+/// With `passthrough`, a `{% name %}` that is neither defined nor has a default is
+/// kept verbatim instead of failing, and the engine's raw and comment blocks are
+/// never touched.
+pub fn process_variables_with_passthrough(
+    content: &str,
+    variables: &HashMap<String, String>,
+    passthrough: Option<TemplateSyntax>,
 ) -> Result<String, Box<dyn std::error::Error>> {
     let mut result = content.to_string();
 
     // Process variables in format {% variable_name %} or {% variable_name || "default_value" %}
-    // Use a simple pattern that works with rust string literals
-    let var_pattern = r#"\{%\s*(\w+)(?:\s*\|\|\s*\"([^\"]+)\")?\s*%\}"#;
-    let var_regex = Regex::new(var_pattern).expect("Failed to compile variable regex");
+    static VAR_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"\{%\s*(\w+(?:\.\w+)*)(?:\s*\|\|\s*\"([^\"]+)\")?\s*%\}"#)
+            .expect("Failed to compile variable regex")
+    });
 
     const MAX_ITERATIONS: usize = 100; // Prevent infinite loops
     let mut iterations = 0;
@@ -450,16 +703,22 @@ pub fn process_variables(
     loop {
         iterations += 1;
         if iterations > MAX_ITERATIONS {
-            return Err("Maximum variable processing iterations exceeded. Possible circular variable references.".into());
+            return Err(ErrorCode::CircularVariable.error("Maximum variable processing iterations exceeded. Possible circular variable references."));
         }
 
         let mut found_variable = false;
         let mut new_result = String::new();
         let mut last_end = 0;
 
-        for capture in var_regex.captures_iter(&result) {
-            found_variable = true;
+        let opaque = passthrough.map_or_else(Vec::new, |syntax| opaque_ranges(&result, syntax));
+        for capture in VAR_REGEX.captures_iter(&result) {
             let full_match = capture.get(0).expect("Failed to get full match");
+            if opaque
+                .iter()
+                .any(|range| range.contains(&full_match.start()))
+            {
+                continue;
+            }
             let var_name = capture
                 .get(1)
                 .expect("Failed to get variable name")
@@ -474,13 +733,18 @@ pub fn process_variables(
                 new_result.push_str(value);
             } else if let Some(default) = default_value {
                 new_result.push_str(default);
+            } else if passthrough.is_some() {
+                tracing::debug!(tag = full_match.as_str(), "Passing template tag through");
+                new_result.push_str(full_match.as_str());
+                last_end = full_match.end();
+                continue;
             } else {
-                return Err(format!(
+                return Err(ErrorCode::UndefinedVariable.error(format!(
                     "Variable '{var_name}' not found and no default value provided"
-                )
-                .into());
+                )));
             }
 
+            found_variable = true;
             last_end = full_match.end();
         }
 
@@ -496,6 +760,23 @@ pub fn process_variables(
     Ok(result)
 }
 
+/// Byte ranges of `content` that belong to the template engine: raw and comment blocks
+pub(crate) fn opaque_ranges(content: &str, syntax: TemplateSyntax) -> Vec<std::ops::Range<usize>> {
+    static JINJA_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?s)\{%-?\s*raw\s*-?%\}.*?\{%-?\s*endraw\s*-?%\}|\{#.*?#\}")
+            .expect("Failed to compile passthrough regex")
+    });
+    static LIQUID_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?s)\{%-?\s*(raw|comment)\s*-?%\}.*?\{%-?\s*end(?:raw|comment)\s*-?%\}")
+            .expect("Failed to compile passthrough regex")
+    });
+    let regex = match syntax {
+        TemplateSyntax::Jinja => &JINJA_REGEX,
+        TemplateSyntax::Liquid => &LIQUID_REGEX,
+    };
+    regex.find_iter(content).map(|m| m.range()).collect()
+}
+
 pub fn add_title_to_content(content: &str, title: &str, level: u8) -> String {
     let title_prefix = "#".repeat(level as usize);
     format!("{title_prefix} {title}\n\n{content}")
@@ -507,13 +788,17 @@ pub fn process_includes(
     partials_path: &Path,
     includes_tracker: &mut Vec<IncludeResult>,
 ) -> Result<String, Box<dyn std::error::Error>> {
+    let source = FileSystemSource::default();
+    let variables = HashMap::new();
+    let options = ResolveOptions::new(partials_path, &variables, &source);
     process_includes_with_depth(
         content,
         current_file,
-        partials_path,
         includes_tracker,
         0,
-        None,
+        &options,
+        1,
+        false,
     )
 }
 
@@ -524,298 +809,1478 @@ pub fn process_includes_with_validation(
     includes_tracker: &mut Vec<IncludeResult>,
     fix_code_fences: Option<&str>,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    // First validate and optionally fix code fences
-    let validated_content = validate_and_fix_code_fences(content, fix_code_fences)?;
-    process_includes_with_depth(
-        &validated_content,
+    process_includes_with_variables(
+        content,
         current_file,
         partials_path,
         includes_tracker,
-        0,
         fix_code_fences,
+        &HashMap::new(),
+        &FileSystemSource::default(),
     )
 }
 
-fn process_includes_with_depth(
+/// Like [`process_includes_with_validation`], reading partials and code snippets
+/// from `source`, with `variables` available to every included partial. Values
+/// given in an include directive take precedence.
+pub fn process_includes_with_variables(
     content: &str,
     current_file: &Path,
     partials_path: &Path,
     includes_tracker: &mut Vec<IncludeResult>,
-    depth: usize,
     fix_code_fences: Option<&str>,
+    variables: &HashMap<String, String>,
+    source: &dyn PartialSource,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    const MAX_DEPTH: usize = 5;
-
-    let fix_code_fences_with_lang = fix_code_fences.map(|lang| lang.to_string());
-
-    if depth > MAX_DEPTH {
-        return Err(format!(
-            "Maximum include depth ({MAX_DEPTH}) exceeded. Possible circular includes."
-        )
-        .into());
-    }
-    // Match both !include and !codesnippet statements
-    let directive_regex =
-        Regex::new(r"(?s)(\n*?)(!(include|codesnippet)\s*\((?:[^()]*|\([^()]*\))*\))(\n*)")
-            .expect("Failed to compile directive regex pattern");
-    let mut result = content.to_string();
+    let options = ResolveOptions::new(partials_path, variables, source);
+    resolve_includes(
+        content,
+        current_file,
+        includes_tracker,
+        fix_code_fences,
+        &options,
+    )
+}
 
-    // Keep processing until no more includes are found (for nested includes)
-    const MAX_INCLUDE_ITERATIONS: usize = 50; // Prevent infinite loops
-    let mut iterations = 0;
+/// Settings shared by every level of a nested include resolution
+pub struct ResolveOptions<'a> {
+    pub partials_path: &'a Path,
+    /// Variables available to every included partial. Partials nested in an include
+    /// get these merged with its `values`, the nearer include winning
+    pub variables: &'a HashMap<String, String>,
+    pub source: &'a dyn PartialSource,
+    pub compat: Compat,
+    /// Template engine whose tags are kept when substituting variables
+    pub passthrough: Option<TemplateSyntax>,
+    /// Rewrites links to bundled files as in-document anchors once resolution is done
+    pub bundle_links: bool,
+    pub duplicate_headings: Option<DuplicateHeadings>,
+    pub source_comments: Option<SourceComments>,
+    /// Receives a [`ProcessingEvent::IncludeStarted`] before each directive of the document is resolved
+    pub events: Option<&'a Sender<ProcessingEvent>>,
+    /// Partials already resolved in this run, shared by every document of the run
+    pub cache: Option<&'a IncludeCache>,
+    pub resolution_order: ResolutionOrder,
+    /// Locale whose translations of partials, e.g. `header.de.md` for `de`, are read when present
+    pub locale: Option<&'a str>,
+    /// Written in place of a directive that fails, with `{path}`, `{error}` and `{code}`
+    /// filled in; an HTML comment describing the failure when unset
+    pub error_template: Option<&'a str>,
+    /// Retries of include reads that fail transiently
+    pub retry: RetryPolicy,
+    /// Receives the warnings of the document as its includes are resolved
+    pub warnings: Option<&'a WarningCollector>,
+}
 
-    loop {
-        iterations += 1;
-        if iterations > MAX_INCLUDE_ITERATIONS {
-            return Err("Maximum include processing iterations exceeded. Possible circular includes or malformed directives.".into());
+impl<'a> ResolveOptions<'a> {
+    /// Options reading partials from `partials_path` through `source`, with `variables`
+    /// and every other setting at its default
+    pub fn new(
+        partials_path: &'a Path,
+        variables: &'a HashMap<String, String>,
+        source: &'a dyn PartialSource,
+    ) -> Self {
+        Self {
+            partials_path,
+            variables,
+            source,
+            compat: Compat::default(),
+            passthrough: None,
+            bundle_links: false,
+            duplicate_headings: None,
+            source_comments: None,
+            events: None,
+            cache: None,
+            resolution_order: ResolutionOrder::default(),
+            locale: None,
+            error_template: None,
+            retry: RetryPolicy::default(),
+            warnings: None,
         }
+    }
+}
 
-        let mut found_include = false;
-        let mut new_result = String::new();
-        let mut last_end = 0;
-
-        for capture in directive_regex.captures_iter(&result) {
-            let full_match = capture.get(0).expect("Failed to get full regex match");
-            let before_newlines = capture
-                .get(1)
-                .expect("Failed to get before newlines from regex match")
-                .as_str();
-            let directive = capture
-                .get(2)
-                .expect("Failed to get directive from regex match")
-                .as_str();
-            let directive_type = capture
-                .get(3)
-                .expect("Failed to get directive type from regex match")
-                .as_str();
-            let after_newlines = capture
-                .get(4)
-                .expect("Failed to get after newlines from regex match")
-                .as_str();
-
-            // Check if this directive is inside a code fence
-            if is_inside_code_fence(&result, full_match.start()) {
-                // Skip processing this directive as it's inside a code block
-                // But still add the content up to this point
-                new_result.push_str(&result[last_end..full_match.end()]);
-                last_end = full_match.end();
-                continue;
-            }
-
-            found_include = true;
-
-            // Add content before the directive
-            new_result.push_str(&result[last_end..full_match.start()]);
-
-            // Handle different directive types
-            if directive_type == "include" {
-                // Parse the include directive with parameters
-                match parse_include_parameters(directive) {
-                    Ok((include_path_str, params)) => {
-                        // Resolve the include path
-                        let include_path =
-                            resolve_include_path(&include_path_str, current_file, partials_path)
-                                .expect("Failed to resolve include path");
-
-                        // Read and process the included file
-                        match fs::read_to_string(&include_path) {
-                            Ok(mut included_content) => {
-                                // Track successful include
-                                includes_tracker.push(IncludeResult {
-                                    path: include_path.to_string_lossy().to_string(),
-                                    success: true,
-                                    error_message: None,
-                                });
+/// Fully resolved partials of a run, so a partial included by many documents is
+/// only resolved once.
+///
+/// Entries are keyed by the partial's path and a hash of its content, the
+/// variables and title it is included with, and its depth. An entry is only
+/// served while everything read resolving it, nested partials and code snippets
+/// included, still reads the same, so edits between runs of watch mode or daemon
+/// requests are never served stale. Failed resolutions aren't kept.
+#[derive(Debug, Default)]
+pub struct IncludeCache {
+    entries: RefCell<HashMap<CacheKey, CachedInclude>>,
+    hits: Cell<usize>,
+}
 
-                                // Process variables in the included content
-                                if !params.values.is_empty() {
-                                    match process_variables(&included_content, &params.values) {
-                                        Ok(processed_content) => {
-                                            included_content = processed_content
-                                        }
-                                        Err(e) => {
-                                            // Track variable processing error
-                                            includes_tracker.push(IncludeResult {
-                                                path: include_path.to_string_lossy().to_string(),
-                                                success: false,
-                                                error_message: Some(format!(
-                                                    "Variable processing failed: {e}"
-                                                )),
-                                            });
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    path: PathBuf,
+    content_hash: u64,
+    values: BTreeMap<String, String>,
+    title: Option<String>,
+    title_level: Option<u8>,
+    depth: usize,
+    /// Resolved with source map markers
+    mapped: bool,
+}
 
-                                            // Keep the original include directive as a comment
-                                            new_result.push_str(before_newlines);
-                                            new_result.push_str(&format!("<!-- Failed to process variables in include: {include_path_str} (Error: {e}) -->"));
-                                            new_result.push_str(after_newlines);
+#[derive(Debug, Clone)]
+struct CachedInclude {
+    content: String,
+    /// Includes nested in the partial
+    includes: Vec<IncludeResult>,
+    /// Its title, with variables substituted
+    title: Option<String>,
+    /// What resolving it found for [`ResolveOptions::warnings`]
+    findings: Vec<Finding>,
+    /// Everything read while resolving it
+    dependencies: Vec<Dependency>,
+}
 
-                                            last_end = full_match.end();
-                                            continue;
-                                        }
-                                    }
-                                }
+impl IncludeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-                                // Add title if specified
-                                if let Some(title) = &params.title {
-                                    let level = params.title_level.unwrap_or(1);
-                                    included_content =
-                                        add_title_to_content(&included_content, title, level);
-                                }
+    /// How many includes were served from the cache
+    pub fn hits(&self) -> usize {
+        self.hits.get()
+    }
 
-                                // Recursively process includes in the included file
-                                let mut nested_includes = Vec::new();
-                                let processed_included = process_includes_with_depth(
-                                    &included_content,
-                                    &include_path,
-                                    partials_path,
-                                    &mut nested_includes,
-                                    depth + 1,
-                                    fix_code_fences_with_lang.as_deref(),
-                                )
-                                .expect("Failed to process nested includes");
+    /// The entry for `key`, unless something it was resolved from reads
+    /// differently from `source` now
+    fn get(&self, key: &CacheKey, source: &dyn PartialSource) -> Option<CachedInclude> {
+        let cached = self
+            .entries
+            .borrow()
+            .get(key)
+            .filter(|cached| {
+                cached
+                    .dependencies
+                    .iter()
+                    .all(|dependency| dependency.unchanged(source))
+            })
+            .cloned();
+        if cached.is_some() {
+            self.hits.set(self.hits.get() + 1);
+        }
+        cached
+    }
 
-                                // Add nested includes to the main tracker
-                                includes_tracker.extend(nested_includes);
+    fn insert(&self, key: CacheKey, cached: CachedInclude) {
+        self.entries.borrow_mut().insert(key, cached);
+    }
+}
 
-                                // Preserve the exact spacing around the include
-                                new_result.push_str(before_newlines);
+impl CacheKey {
+    fn new(
+        path: &Path,
+        content: &str,
+        values: &HashMap<String, String>,
+        params: &IncludeParameters,
+        depth: usize,
+        mapped: bool,
+    ) -> Self {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        Self {
+            path: path.to_path_buf(),
+            content_hash: hasher.finish(),
+            values: values
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            title: params.title.clone(),
+            title_level: params.title_level,
+            depth,
+            mapped,
+        }
+    }
+}
 
-                                // Add the processed content exactly as-is to preserve document structure
-                                new_result.push_str(&processed_included);
+fn hash_of(value: &impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
 
-                                // Add the preserved after newlines
-                                new_result.push_str(after_newlines);
-                            }
-                            Err(e) => {
-                                // Track failed include
-                                let error_msg = format!("{e}");
-                                includes_tracker.push(IncludeResult {
-                                    path: include_path.to_string_lossy().to_string(),
-                                    success: false,
-                                    error_message: Some(error_msg.clone()),
-                                });
+/// A read made while resolving a cached include
+#[derive(Debug, Clone, PartialEq)]
+struct Dependency {
+    path: PathBuf,
+    read: DependencyRead,
+    /// Hash of what was read, `None` when it couldn't be
+    hash: Option<u64>,
+}
 
-                                // Keep the original include directive as a comment with preserved formatting
-                                new_result.push_str(before_newlines);
-                                new_result.push_str(&format!(
-                                    "<!-- Failed to include: {include_path_str} (Error: {error_msg}) -->"
-                                ));
-                                new_result.push_str(after_newlines);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        // Track failed include with parse error
-                        includes_tracker.push(IncludeResult {
-                            path: directive.to_string(),
-                            success: false,
-                            error_message: Some(format!("Failed to parse include directive: {e}")),
-                        });
-
-                        // Add content before the include and keep the original directive as a comment
-                        new_result.push_str(before_newlines);
-                        new_result.push_str(&format!(
-                            "<!-- Failed to parse include directive: {directive} (Error: {e}) -->"
-                        ));
-                        new_result.push_str(after_newlines);
-                    }
-                }
-            } else if directive_type == "codesnippet" {
-                // Handle codesnippet directive
-                match parse_codesnippet_parameters(directive) {
-                    Ok((file_path_str, params)) => {
-                        let file_path = PathBuf::from(&file_path_str);
-
-                        match process_code_snippet(&file_path, current_file, &params) {
-                            Ok(code_block) => {
-                                // Track successful codesnippet
-                                includes_tracker.push(IncludeResult {
-                                    path: file_path_str.clone(),
-                                    success: true,
-                                    error_message: None,
-                                });
+#[derive(Debug, Clone, PartialEq)]
+enum DependencyRead {
+    Whole,
+    Lines(Option<usize>),
+    Revision(String),
+}
 
-                                // Add the code block with preserved formatting
-                                new_result.push_str(before_newlines);
-                                new_result.push_str(&code_block);
-                                new_result.push_str(after_newlines);
-                            }
-                            Err(e) => {
-                                // Track failed codesnippet
-                                let error_msg = format!("{e}");
-                                includes_tracker.push(IncludeResult {
-                                    path: file_path_str.clone(),
-                                    success: false,
-                                    error_message: Some(error_msg.clone()),
-                                });
+impl Dependency {
+    /// Whether reading it again from `source` gives what it gave before
+    fn unchanged(&self, source: &dyn PartialSource) -> bool {
+        let hash = match &self.read {
+            DependencyRead::Whole => source.read(&self.path).ok().map(|read| hash_of(&read)),
+            DependencyRead::Lines(limit) => source
+                .read_lines(&self.path, *limit)
+                .ok()
+                .map(|read| hash_of(&read)),
+            DependencyRead::Revision(rev) => source
+                .read_at_revision(&self.path, rev)
+                .ok()
+                .map(|read| hash_of(&read)),
+        };
+        hash == self.hash
+    }
+}
 
-                                // Keep the original directive as a comment with preserved formatting
-                                new_result.push_str(before_newlines);
-                                new_result.push_str(&format!(
-                                    "<!-- Failed to process codesnippet: {file_path_str} (Error: {error_msg}) -->"
-                                ));
-                                new_result.push_str(after_newlines);
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        // Track failed codesnippet with parse error
-                        includes_tracker.push(IncludeResult {
-                            path: directive.to_string(),
-                            success: false,
-                            error_message: Some(format!(
-                                "Failed to parse codesnippet directive: {e}"
-                            )),
-                        });
-
-                        // Add content before the directive and keep the original directive as a comment
-                        new_result.push_str(before_newlines);
-                        new_result.push_str(&format!(
-                            "<!-- Failed to parse codesnippet directive: {directive} (Error: {e}) -->"
-                        ));
-                        new_result.push_str(after_newlines);
-                    }
-                }
-            }
+/// Reads through `inner`, keeping what each read returned as a [`Dependency`]
+struct RecordingSource<'a> {
+    inner: &'a dyn PartialSource,
+    reads: RefCell<Vec<Dependency>>,
+}
 
-            last_end = full_match.end();
+impl<'a> RecordingSource<'a> {
+    fn new(inner: &'a dyn PartialSource) -> Self {
+        Self {
+            inner,
+            reads: RefCell::default(),
         }
+    }
 
-        if !found_include {
-            break;
+    fn record<T: Hash>(
+        &self,
+        path: &Path,
+        read: DependencyRead,
+        result: std::io::Result<T>,
+    ) -> std::io::Result<T> {
+        let dependency = Dependency {
+            path: path.to_path_buf(),
+            read,
+            hash: result.as_ref().ok().map(hash_of),
+        };
+        let mut reads = self.reads.borrow_mut();
+        if !reads.contains(&dependency) {
+            reads.push(dependency);
         }
-
-        // Add remaining content
-        new_result.push_str(&result[last_end..]);
-        result = new_result;
+        result
     }
-
-    Ok(result)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use tempfile::TempDir;
+impl PartialSource for RecordingSource<'_> {
+    fn read(&self, path: &Path) -> std::io::Result<String> {
+        self.record(path, DependencyRead::Whole, self.inner.read(path))
+    }
 
-    #[test]
-    fn test_resolve_include_path_relative_to_partials() {
-        let temp_dir = TempDir::new().expect("Failed to create temp directory");
-        let current_file = temp_dir.path().join("current.md");
-        let partials_path = temp_dir.path().join("partials");
+    fn read_lines(&self, path: &Path, limit: Option<usize>) -> std::io::Result<Vec<String>> {
+        let lines = self.inner.read_lines(path, limit);
+        self.record(path, DependencyRead::Lines(limit), lines)
+    }
 
-        let resolved = resolve_include_path("header.md", &current_file, &partials_path)
-            .expect("Failed to resolve include path");
-        assert_eq!(resolved, partials_path.join("header.md"));
+    fn prefetch(&self, paths: &[PathBuf]) {
+        self.inner.prefetch(paths)
     }
 
-    #[test]
-    fn test_resolve_include_path_relative_to_current() {
-        let temp_dir = TempDir::new().expect("Failed to create temp directory");
-        let current_file = temp_dir.path().join("docs").join("current.md");
-        let partials_path = temp_dir.path().join("partials");
+    fn read_at_revision(&self, path: &Path, rev: &str) -> std::io::Result<String> {
+        let read = self.inner.read_at_revision(path, rev);
+        self.record(path, DependencyRead::Revision(rev.to_string()), read)
+    }
+}
 
-        let resolved = resolve_include_path("../header.md", &current_file, &partials_path)
+/// Validates code fences, then resolves every directive in `content` with `options`.
+///
+/// Once all includes are resolved, source-map comments are stripped when
+/// [`ResolveOptions::source_comments`] asks for it and repeated heading anchors
+/// are handled as set by [`ResolveOptions::duplicate_headings`]. In
+/// [`Compat::Markedpp`] mode the document-level `!toc` and `!numberedheadings`
+/// directives are applied next, followed by the link rewriting of
+/// [`ResolveOptions::bundle_links`].
+pub fn resolve_includes(
+    content: &str,
+    current_file: &Path,
+    includes_tracker: &mut Vec<IncludeResult>,
+    fix_code_fences: Option<&str>,
+    options: &ResolveOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    resolve_document(
+        content,
+        current_file,
+        includes_tracker,
+        fix_code_fences,
+        options,
+        false,
+    )
+    .map(|(resolved, _)| resolved)
+}
+
+/// Like [`resolve_includes`], also returning the file and line each line of the
+/// result comes from, for a [`source_map::SourceMap`]
+pub fn resolve_includes_with_origins(
+    content: &str,
+    current_file: &Path,
+    includes_tracker: &mut Vec<IncludeResult>,
+    fix_code_fences: Option<&str>,
+    options: &ResolveOptions,
+) -> Result<(String, LineOrigins), Box<dyn std::error::Error>> {
+    resolve_document(
+        content,
+        current_file,
+        includes_tracker,
+        fix_code_fences,
+        options,
+        true,
+    )
+}
+
+fn resolve_document(
+    content: &str,
+    current_file: &Path,
+    includes_tracker: &mut Vec<IncludeResult>,
+    fix_code_fences: Option<&str>,
+    options: &ResolveOptions,
+    with_origins: bool,
+) -> Result<(String, LineOrigins), Box<dyn std::error::Error>> {
+    // First validate and optionally fix code fences
+    if let (Some(warnings), Some(language)) = (options.warnings, fix_code_fences) {
+        warnings.fixed_fences(current_file, content, language);
+    }
+    let validated_content = validate_and_fix_code_fences(content, fix_code_fences)?;
+    let resolved = process_includes_with_depth(
+        &validated_content,
+        current_file,
+        includes_tracker,
+        0,
+        options,
+        1,
+        with_origins,
+    )?;
+    let (resolved, origins) = if with_origins {
+        source_map::extract_origins(&resolved, current_file)
+    } else {
+        (resolved, Vec::new())
+    };
+    // The steps below can move lines; the origins follow them once they are done
+    let included = with_origins.then(|| resolved.clone());
+
+    let resolved = match options.source_comments {
+        Some(SourceComments::Strip) => strip_source_markers(&resolved),
+        Some(SourceComments::Insert) | None => resolved,
+    };
+    let resolved = match options.duplicate_headings {
+        Some(DuplicateHeadings::Dedupe) => headings::dedupe_anchors(&resolved),
+        Some(DuplicateHeadings::Warn) => {
+            for duplicate in headings::find_duplicate_anchors(&resolved) {
+                tracing::warn!(
+                    file = %current_file.display(),
+                    line = duplicate.line,
+                    anchor = %duplicate.anchor,
+                    "Duplicate heading anchor"
+                );
+            }
+            resolved
+        }
+        None => resolved,
+    };
+    let resolved = match options.compat {
+        Compat::Markedpp => markedpp::apply_document_directives(&resolved),
+        Compat::Md2md | Compat::Mkdocs => resolved,
+    };
+    let resolved = if options.bundle_links {
+        bundle(content, &resolved, current_file, includes_tracker, options)
+    } else {
+        resolved
+    };
+    let origins = match included {
+        Some(included) => source_map::carry_origins(&included, &origins, &resolved),
+        None => origins,
+    };
+    Ok((resolved, origins))
+}
+
+/// Rewrites the links of `resolved` to the files bundled into it as in-document anchors
+fn bundle(
+    content: &str,
+    resolved: &str,
+    current_file: &Path,
+    includes_tracker: &[IncludeResult],
+    options: &ResolveOptions,
+) -> String {
+    let mut bundled = vec![BundledFile {
+        path: current_file.to_path_buf(),
+        anchor: markedpp::first_heading_anchor(content),
+    }];
+    for include in includes_tracker.iter().filter(|inc| inc.success) {
+        let path = PathBuf::from(&include.path);
+        if is_markdown(&path) && !bundled.iter().any(|file| file.path == path) {
+            let anchor = options
+                .source
+                .read(&path)
+                .ok()
+                .and_then(|partial| markedpp::first_heading_anchor(&partial));
+            bundled.push(BundledFile { path, anchor });
+        }
+    }
+    rewrite_bundled_links(resolved, &bundled)
+}
+
+/// A directive resolved on its own by [`expand_directive_at`]
+#[derive(Debug, Clone)]
+pub struct DirectiveExpansion {
+    /// Byte range of the directive in the document
+    pub range: std::ops::Range<usize>,
+    /// What the directive is replaced with: its partial or code snippet, resolved
+    pub content: String,
+    /// Files the directive depends on, nested includes included, failed ones too
+    pub includes: Vec<IncludeResult>,
+}
+
+/// Resolves only the directive of `content` at the byte `offset`, e.g. under an
+/// editor's cursor, the way [`resolve_includes`] would in `current_file`.
+///
+/// Returns `None` when there is no directive at `offset`, including inside code.
+/// Document-level steps such as code fence validation and link bundling don't apply.
+pub fn expand_directive_at(
+    content: &str,
+    offset: usize,
+    current_file: &Path,
+    options: &ResolveOptions,
+) -> Option<Result<DirectiveExpansion, Box<dyn std::error::Error>>> {
+    let range = directive_ranges(content)
+        .into_iter()
+        .find(|range| range.contains(&offset))?;
+    let mut includes = Vec::new();
+    Some(
+        process_includes_with_depth(
+            &content[range.clone()],
+            current_file,
+            &mut includes,
+            0,
+            options,
+            1,
+            false,
+        )
+        .map(|content| DirectiveExpansion {
+            range,
+            content,
+            includes,
+        }),
+    )
+}
+
+/// Begin and end comments around an included partial; MDX has no HTML comments
+fn source_markers(path: &str, mdx: bool) -> (String, String) {
+    if mdx {
+        (
+            format!("{{/* md2md: begin {path} */}}"),
+            format!("{{/* md2md: end {path} */}}"),
+        )
+    } else {
+        (
+            format!("<!-- md2md: begin {path} -->"),
+            format!("<!-- md2md: end {path} -->"),
+        )
+    }
+}
+
+/// Removes every line holding only a source-map comment
+fn strip_source_markers(content: &str) -> String {
+    static MARKER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"^\s*(?:<!--\s*md2md: (?:begin|end) .*-->|\{/\*\s*md2md: (?:begin|end) .*\*/\})\s*$",
+        )
+        .expect("Failed to compile source marker regex")
+    });
+    content
+        .split('\n')
+        .filter(|line| !MARKER_REGEX.is_match(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The file a directive points at, e.g. `header.md` for `!include(header.md, title="Header")`
+fn directive_target(directive: &str) -> &str {
+    static TARGET_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(&format!(r"\(\s*({QUOTED_PATH}|[^,)]+)"))
+            .expect("Failed to compile directive target regex")
+    });
+    TARGET_REGEX
+        .captures(directive)
+        .and_then(|captures| captures.get(1))
+        .map_or(directive, |target| unquote_path(target.as_str()))
+}
+
+/// Starts timing a directive; wasm32 has no clock, so nothing is timed there
+fn start_timer() -> Option<Instant> {
+    if cfg!(target_arch = "wasm32") {
+        None
+    } else {
+        Some(Instant::now())
+    }
+}
+
+/// Reads an included partial, returning the path it was resolved to, its content
+/// or the error reading it, and how many reads that took.
+///
+/// markedpp resolves relative paths against the including file first, so in that
+/// mode the partials directory is only a fallback. With a locale, a translation
+/// in any location is preferred over the untranslated file. With a `rev`, files
+/// are read as they were at that git revision instead.
+pub(crate) fn read_include(
+    include_path_str: &str,
+    current_file: &Path,
+    rev: Option<&str>,
+    options: &ResolveOptions,
+) -> Result<(PathBuf, std::io::Result<String>, u32), Box<dyn std::error::Error>> {
+    let candidates = include_candidates(include_path_str, current_file, options)?;
+    let revision = rev.map(|rev| RevisionSource::new(options.source, rev));
+    let source: &dyn PartialSource = match &revision {
+        Some(revision) => revision,
+        None => options.source,
+    };
+    let read = |path: &Path| retrying(options.retry, || source.read(path));
+
+    // Looked up in order; when none can be read, the error is the last one's
+    let (last, others) = candidates
+        .split_last()
+        .expect("Include candidates are never empty");
+    for candidate in others {
+        if let (Ok(content), attempts) = read(candidate) {
+            return Ok((candidate.clone(), Ok(content), attempts));
+        }
+    }
+    let (content, attempts) = read(last);
+    Ok((last.clone(), content, attempts))
+}
+
+/// Runs `read` until it succeeds, fails for good or `policy` allows no more retries,
+/// waiting longer before each retry; returns the last result and the number of reads
+fn retrying(
+    policy: RetryPolicy,
+    mut read: impl FnMut() -> std::io::Result<String>,
+) -> (std::io::Result<String>, u32) {
+    let mut attempts = 1;
+    let mut delay = Duration::from_millis(policy.delay_ms);
+    loop {
+        match read() {
+            Err(e) if attempts <= policy.retries && is_transient(&e) => {
+                tracing::debug!(error = %e, attempts, "Retrying include read");
+                std::thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+                attempts += 1;
+            }
+            result => return (result, attempts),
+        }
+    }
+}
+
+/// Errors a second read may not run into, unlike a missing file
+fn is_transient(error: &std::io::Error) -> bool {
+    use std::io::ErrorKind;
+    matches!(
+        error.kind(),
+        ErrorKind::Interrupted
+            | ErrorKind::TimedOut
+            | ErrorKind::WouldBlock
+            | ErrorKind::ResourceBusy
+            | ErrorKind::StaleNetworkFileHandle
+            | ErrorKind::ConnectionRefused
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected
+            | ErrorKind::BrokenPipe
+            | ErrorKind::HostUnreachable
+            | ErrorKind::NetworkUnreachable
+            | ErrorKind::NetworkDown
+    )
+}
+
+/// Where an include of `include_path_str` in `current_file` is looked for, in order
+pub(crate) fn include_candidates(
+    include_path_str: &str,
+    current_file: &Path,
+    options: &ResolveOptions,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let path = directive_path(include_path_str);
+    let order = match options.resolution_order {
+        ResolutionOrder::Partials if options.compat == Compat::Markedpp => {
+            ResolutionOrder::CurrentThenPartials
+        }
+        order => order,
+    };
+    let plain = !path.has_root() && !path.starts_with(".") && !path.starts_with("..");
+    let mut candidates = match current_file.parent() {
+        Some(current_dir) if plain && order != ResolutionOrder::Partials => {
+            let current = current_dir.join(&path);
+            let partials = options.partials_path.join(&path);
+            match order {
+                ResolutionOrder::PartialsThenCurrent => vec![partials, current],
+                _ => vec![current, partials],
+            }
+        }
+        _ => vec![resolve_include_path(
+            include_path_str,
+            current_file,
+            options.partials_path,
+        )?],
+    };
+    if let Some(locale) = options.locale {
+        let translations = candidates.iter().map(|path| localized_path(path, locale));
+        candidates = translations.chain(candidates.clone()).collect();
+    }
+    Ok(candidates)
+}
+
+/// Lets `options.source` read every partial `content` includes at once, before they
+/// are resolved one after another. Partials included by partials aren't prefetched.
+fn prefetch_includes(content: &str, current_file: &Path, options: &ResolveOptions) {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for (directive_type, directive) in find_directives(content) {
+        if directive_type != "include" {
+            continue;
+        }
+        let Ok((path, params)) = parse_include_parameters(&directive) else {
+            continue;
+        };
+        if params.rev.is_some() {
+            continue;
+        }
+        for candidate in include_candidates(&path, current_file, options).unwrap_or_default() {
+            if !paths.contains(&candidate) {
+                paths.push(candidate);
+            }
+        }
+    }
+    options.source.prefetch(&paths);
+}
+
+/// The translation of `path` into `locale`, e.g. `header.de.md` for `header.md` and `de`
+pub(crate) fn localized_path(path: &Path, locale: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{stem}.{locale}.{}", extension.to_string_lossy()),
+        None => format!("{stem}.{locale}"),
+    };
+    path.with_file_name(name)
+}
+
+/// What replaces a directive that failed: the `error_template` of `options` with
+/// the directive's `path`, `error` and `code` filled in, or else `message` as an HTML comment
+fn failure_placeholder(
+    options: &ResolveOptions,
+    message: &str,
+    path: &str,
+    error: &str,
+    code: ErrorCode,
+) -> String {
+    static PLACEHOLDER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"\{(path|error|code)\}").expect("Failed to compile placeholder regex")
+    });
+    let Some(template) = options.error_template else {
+        return format!("<!-- {message} -->");
+    };
+    // One pass, so a path or error containing `{code}` is kept as it is
+    PLACEHOLDER_REGEX
+        .replace_all(template, |capture: &regex::Captures| match &capture[1] {
+            "path" => path.to_string(),
+            "error" => error.to_string(),
+            _ => code.as_str().to_string(),
+        })
+        .into_owned()
+}
+
+/// Resolves the directives of `content`, read from `current_file`.
+///
+/// `content` starts at `first_line` of `current_file`. When `mapped`, included
+/// blocks are wrapped in [`source_map`] markers.
+fn process_includes_with_depth(
+    content: &str,
+    current_file: &Path,
+    includes_tracker: &mut Vec<IncludeResult>,
+    depth: usize,
+    options: &ResolveOptions,
+    first_line: i64,
+    mapped: bool,
+) -> Result<String, Box<dyn std::error::Error>> {
+    const MAX_DEPTH: usize = 5;
+
+    if depth > MAX_DEPTH {
+        return Err(ErrorCode::CircularInclude.error(format!(
+            "Maximum include depth ({MAX_DEPTH}) exceeded. Possible circular includes."
+        )));
+    }
+    if depth == 0 {
+        prefetch_includes(content, current_file, options);
+    }
+    // Match !include, !codesnippet and !docsnippet statements
+    static DIRECTIVE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(
+            r"(?s)(\n*?)(!(include|codesnippet|docsnippet)\s*\((?:[^()]*|\([^()]*\))*\))(\n*)",
+        )
+        .expect("Failed to compile directive regex pattern")
+    });
+    let first_snippet = includes_tracker.len();
+    let result = match options.compat {
+        Compat::Mkdocs => {
+            mkdocs::expand_snippets(content, current_file, includes_tracker, options)?
+        }
+        Compat::Md2md | Compat::Markedpp => content.to_string(),
+    };
+    for snippet in &mut includes_tracker[first_snippet..] {
+        snippet.depth += depth;
+    }
+    // Nested includes resolve as part of the document directive that pulls them in
+    let progress = options.events.filter(|_| depth == 0);
+    let total = progress.map_or(0, |_| find_directives(&result).len());
+    let mut resolving = 0;
+
+    // Included content comes back fully resolved, so one pass over the text expands every directive
+    let mut new_result = String::with_capacity(result.len());
+    let mut last_end = 0;
+    // Line of `current_file` each directive starts on, counted as far as the last one
+    let mut counted = (0, first_line);
+    let fences = CodeFences::new(&result);
+    // H1s are told to `options.warnings` in document order, between the includes around them
+    let mut titles = match options.warnings {
+        Some(_) => {
+            let lines: Vec<String> = result.split('\n').map(str::to_string).collect();
+            markedpp::find_headings(&lines)
+                .into_iter()
+                .filter(|heading| heading.level == 1)
+                .collect()
+        }
+        None => Vec::new(),
+    }
+    .into_iter()
+    .peekable();
+    let title = |heading: markedpp::Heading| {
+        if let Some(warnings) = options.warnings {
+            let text = process_variables_with_passthrough(
+                &heading.text,
+                options.variables,
+                options.passthrough,
+            )
+            .unwrap_or(heading.text);
+            warnings.title(
+                current_file,
+                (first_line + heading.line as i64) as usize,
+                &text,
+            );
+        }
+    };
+    let mdx_ranges = if mdx::is_mdx(current_file) {
+        mdx::protected_ranges(&result)
+    } else {
+        Vec::new()
+    };
+
+    for capture in DIRECTIVE_REGEX.captures_iter(&result) {
+        let full_match = capture.get(0).expect("Failed to get full regex match");
+        let before_newlines = capture
+            .get(1)
+            .expect("Failed to get before newlines from regex match")
+            .as_str();
+        let directive = capture
+            .get(2)
+            .expect("Failed to get directive from regex match")
+            .as_str();
+        let directive_type = capture
+            .get(3)
+            .expect("Failed to get directive type from regex match")
+            .as_str();
+        let after_newlines = capture
+            .get(4)
+            .expect("Failed to get after newlines from regex match")
+            .as_str();
+
+        // Check if this directive is inside a code fence or MDX syntax
+        let directive_start = capture
+            .get(2)
+            .expect("Failed to get directive from regex match")
+            .start();
+        let start = full_match.start();
+        if fences.contain(&result, start)
+            || mdx_ranges
+                .iter()
+                .any(|range| range.contains(&directive_start))
+        {
+            // Skip processing this directive as it's inside a code block
+            // But still add the content up to this point
+            new_result.push_str(&result[last_end..full_match.end()]);
+            last_end = full_match.end();
+            continue;
+        }
+
+        counted = (
+            directive_start,
+            counted.1 + result[counted.0..directive_start].matches('\n').count() as i64,
+        );
+        let line = counted.1;
+        let end_line = mapped.then(|| line + directive.matches('\n').count() as i64);
+        while let Some(heading) =
+            titles.next_if(|heading| first_line + (heading.line as i64) < line)
+        {
+            title(heading);
+        }
+        if let Some(warnings) = options.warnings {
+            warnings.directive_syntax(current_file, line as usize, directive, options.compat);
+        }
+
+        let _span = tracing::debug_span!("include", directive = directive_type, depth).entered();
+        let started = start_timer();
+        if let Some(sender) = progress {
+            let _ = sender.send(ProcessingEvent::IncludeStarted {
+                file: current_file.to_string_lossy().to_string(),
+                path: directive_target(directive).to_string(),
+                index: resolving,
+                total: total.max(resolving + 1),
+            });
+            resolving += 1;
+        }
+
+        // Add content before the directive
+        new_result.push_str(&result[last_end..full_match.start()]);
+
+        // Handle different directive types
+        if directive_type == "include" {
+            // Parse the include directive with parameters
+            match parse_include_parameters(directive) {
+                Ok((_, params))
+                    if params.condition.as_deref().is_some_and(|condition| {
+                        condition::evaluate(condition, options.variables) == Ok(false)
+                    }) =>
+                {
+                    tracing::debug!(directive, "Include left out; its condition doesn't hold");
+                    // A directive on a line of its own takes the line breaks after it along
+                    new_result.push_str(before_newlines);
+                    if !(new_result.is_empty() || new_result.ends_with('\n')) {
+                        new_result.push_str(after_newlines);
+                    }
+                }
+                Ok((include_path_str, params)) => {
+                    // Resolve the include path and read the included file
+                    let (include_path, included, attempts) = read_include(
+                        &include_path_str,
+                        current_file,
+                        params.rev.as_deref(),
+                        options,
+                    )?;
+
+                    match included {
+                        Ok(mut included_content) => {
+                            tracing::debug!(path = %include_path.display(), "Included partial");
+                            if let Some(warnings) = options.warnings {
+                                warnings.include(
+                                    current_file,
+                                    line as usize,
+                                    &include_path_str,
+                                    &include_path,
+                                    options.locale,
+                                );
+                            }
+
+                            // Track successful include, timed once its nested includes are resolved
+                            let tracked = includes_tracker.len();
+                            includes_tracker.push(IncludeResult {
+                                path: include_path.to_string_lossy().to_string(),
+                                success: true,
+                                error_message: None,
+                                error_code: None,
+                                parent: current_file.to_string_lossy().to_string(),
+                                depth,
+                                title: params.title.clone(),
+                                values: params.values.clone(),
+                                duration: None,
+                                bytes: None,
+                                attempts: (attempts > 1).then_some(attempts),
+                                kind: DirectiveKind::Include,
+                            });
+
+                            let mut values = options.variables.clone();
+                            values.extend(params.values.clone());
+                            let cache_key = options.cache.map(|_| {
+                                CacheKey::new(
+                                    &include_path,
+                                    &included_content,
+                                    &values,
+                                    &params,
+                                    depth,
+                                    mapped,
+                                )
+                            });
+                            // Line of the partial its body starts on, past its defaults
+                            let body_line = {
+                                let (_, body_start) = declared_defaults(&included_content);
+                                1 + included_content[..body_start].matches('\n').count() as i64
+                            };
+                            // The title, when there is one, comes before the body
+                            let body_first_line =
+                                body_line - if params.title.is_some() { 2 } else { 0 };
+                            let title_level = params.title_level.unwrap_or(1);
+                            let cached = options
+                                .cache
+                                .zip(cache_key.as_ref())
+                                .and_then(|(cache, key)| cache.get(key, options.source));
+
+                            let processed_included = if let Some(cached) = cached {
+                                tracing::debug!(path = %include_path.display(), "Resolved partial served from cache");
+                                includes_tracker.extend(cached.includes);
+                                if let Some(warnings) = options.warnings {
+                                    if let Some(title) = &cached.title
+                                        && title_level == 1
+                                    {
+                                        warnings.include_title(current_file, line as usize, title);
+                                    }
+                                    warnings.replay(&cached.findings);
+                                }
+                                cached.content
+                            } else {
+                                let mut title = params.title.clone();
+                                // Defaults the partial declares yield to any value it is given
+                                let (defaults, body_start) = declared_defaults(&included_content);
+                                let variables: HashMap<String, String> =
+                                    defaults.into_iter().chain(values.clone()).collect();
+                                let mark = options.warnings.map(|warnings| {
+                                    let mark = warnings.mark();
+                                    if !variables.is_empty() {
+                                        warnings.default_values(
+                                            &include_path,
+                                            &included_content,
+                                            &variables,
+                                            options.passthrough,
+                                        );
+                                    }
+                                    mark
+                                });
+                                included_content.drain(..body_start);
+                                // Process variables in the included content and its title
+                                if !variables.is_empty() {
+                                    let substitute = |text: &str| {
+                                        process_variables_with_passthrough(
+                                            text,
+                                            &variables,
+                                            options.passthrough,
+                                        )
+                                    };
+                                    match substitute(&included_content).and_then(|content| {
+                                        Ok((content, title.as_deref().map(substitute).transpose()?))
+                                    }) {
+                                        Ok((processed_content, processed_title)) => {
+                                            included_content = processed_content;
+                                            title = processed_title;
+                                        }
+                                        Err(e) => {
+                                            tracing::warn!(path = %include_path.display(), error = %e, "Variable processing failed");
+
+                                            // Track variable processing error
+                                            includes_tracker.push(IncludeResult {
+                                                path: include_path.to_string_lossy().to_string(),
+                                                success: false,
+                                                error_message: Some(format!(
+                                                    "Variable processing failed: {e}"
+                                                )),
+                                                error_code: Some(
+                                                    ErrorCode::of(e.as_ref())
+                                                        .unwrap_or(ErrorCode::UndefinedVariable),
+                                                ),
+                                                parent: current_file.to_string_lossy().to_string(),
+                                                depth,
+                                                title: params.title.clone(),
+                                                values: params.values.clone(),
+                                                duration: started.map(|started| started.elapsed()),
+                                                bytes: None,
+                                                attempts: None,
+                                                kind: DirectiveKind::Include,
+                                            });
+
+                                            // Keep the original include directive as a comment
+                                            new_result.push_str(before_newlines);
+                                            new_result.push_str(&failure_placeholder(
+                                                options,
+                                                &format!("Failed to process variables in include: {include_path_str} (Error: {e})"),
+                                                &include_path_str,
+                                                &e.to_string(),
+                                                ErrorCode::of(e.as_ref())
+                                                    .unwrap_or(ErrorCode::UndefinedVariable),
+                                            ));
+                                            new_result.push_str(after_newlines);
+
+                                            last_end = full_match.end();
+                                            continue;
+                                        }
+                                    }
+                                }
+
+                                if let (Some(warnings), Some(title)) = (options.warnings, &title)
+                                    && title_level == 1
+                                {
+                                    warnings.include_title(current_file, line as usize, title);
+                                }
+
+                                // Recursively process includes in the included file, in its variable scope,
+                                // noting what it reads for the cache
+                                let recorded = RecordingSource::new(options.source);
+                                let mut nested_includes = Vec::new();
+                                let mut processed_included = process_includes_with_depth(
+                                    &included_content,
+                                    &include_path,
+                                    &mut nested_includes,
+                                    depth + 1,
+                                    &ResolveOptions {
+                                        variables: &values,
+                                        source: if options.cache.is_some() {
+                                            &recorded
+                                        } else {
+                                            options.source
+                                        },
+                                        ..*options
+                                    },
+                                    body_line,
+                                    mapped,
+                                )?;
+
+                                // Add title if specified
+                                if let Some(title) = &title {
+                                    processed_included = add_title_to_content(
+                                        &processed_included,
+                                        title,
+                                        title_level,
+                                    );
+                                }
+
+                                if let (Some(cache), Some(key)) = (options.cache, cache_key) {
+                                    cache.insert(
+                                        key,
+                                        CachedInclude {
+                                            content: processed_included.clone(),
+                                            includes: nested_includes.clone(),
+                                            title,
+                                            findings: options
+                                                .warnings
+                                                .zip(mark)
+                                                .map(|(warnings, mark)| warnings.since(mark))
+                                                .unwrap_or_default(),
+                                            dependencies: recorded.reads.into_inner(),
+                                        },
+                                    );
+                                }
+
+                                // Add nested includes to the main tracker
+                                includes_tracker.extend(nested_includes);
+                                processed_included
+                            };
+                            includes_tracker[tracked].duration =
+                                started.map(|started| started.elapsed());
+                            includes_tracker[tracked].bytes = Some(processed_included.len());
+
+                            // Markers only wrap includes on a line of their own, so inline ones stay inline
+                            let on_own_line = (full_match.start() == 0
+                                || !before_newlines.is_empty()
+                                || result[..full_match.start()].ends_with('\n'))
+                                && (full_match.end() == result.len() || !after_newlines.is_empty());
+                            let commented = options.source_comments == Some(SourceComments::Insert)
+                                && on_own_line;
+                            // The source comments count as the lines of the directive
+                            let (begin_marker, end_marker) = match end_line {
+                                Some(end_line) => (
+                                    source_map::begin_marker(
+                                        &include_path,
+                                        body_first_line - commented as i64,
+                                    ),
+                                    source_map::end_marker(end_line),
+                                ),
+                                _ => Default::default(),
+                            };
+                            let processed_included = if commented {
+                                let path = include_path.to_string_lossy();
+                                let (begin, end) = source_markers(&path, mdx::is_mdx(current_file));
+                                format!(
+                                    "{begin_marker}{begin}\n{processed_included}\n{end_marker}{end}"
+                                )
+                            } else {
+                                format!("{begin_marker}{processed_included}{end_marker}")
+                            };
+
+                            // Preserve the exact spacing around the include
+                            new_result.push_str(before_newlines);
+
+                            // Add the processed content exactly as-is to preserve document structure
+                            new_result.push_str(&processed_included);
+
+                            // Add the preserved after newlines
+                            new_result.push_str(after_newlines);
+                        }
+                        Err(e) => {
+                            tracing::warn!(path = %include_path.display(), error = %e, "Failed to include partial");
+
+                            // Track failed include
+                            let error_msg = format!("{e}");
+                            includes_tracker.push(IncludeResult {
+                                path: include_path.to_string_lossy().to_string(),
+                                success: false,
+                                error_message: Some(error_msg.clone()),
+                                error_code: Some(ErrorCode::MissingInclude),
+                                parent: current_file.to_string_lossy().to_string(),
+                                depth,
+                                title: params.title.clone(),
+                                values: params.values.clone(),
+                                duration: started.map(|started| started.elapsed()),
+                                bytes: None,
+                                attempts: (attempts > 1).then_some(attempts),
+                                kind: DirectiveKind::Include,
+                            });
+
+                            // Keep the original include directive as a comment with preserved formatting
+                            new_result.push_str(before_newlines);
+                            new_result.push_str(&failure_placeholder(
+                                options,
+                                &format!(
+                                    "Failed to include: {include_path_str} (Error: {error_msg})"
+                                ),
+                                &include_path_str,
+                                &error_msg,
+                                ErrorCode::MissingInclude,
+                            ));
+                            new_result.push_str(after_newlines);
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(directive, error = %e, "Failed to parse include directive");
+
+                    // Track failed include with parse error
+                    includes_tracker.push(IncludeResult {
+                        path: directive.to_string(),
+                        success: false,
+                        error_message: Some(format!("Failed to parse include directive: {e}")),
+                        error_code: Some(ErrorCode::InvalidIncludeDirective),
+                        parent: current_file.to_string_lossy().to_string(),
+                        depth,
+                        title: None,
+                        values: HashMap::new(),
+                        duration: started.map(|started| started.elapsed()),
+                        bytes: None,
+                        attempts: None,
+                        kind: DirectiveKind::Include,
+                    });
+
+                    // Add content before the include and keep the original directive as a comment
+                    new_result.push_str(before_newlines);
+                    new_result.push_str(&failure_placeholder(
+                        options,
+                        &format!("Failed to parse include directive: {directive} (Error: {e})"),
+                        directive,
+                        &e.to_string(),
+                        ErrorCode::InvalidIncludeDirective,
+                    ));
+                    new_result.push_str(after_newlines);
+                }
+            }
+        } else if directive_type == "codesnippet" {
+            // Handle codesnippet directive
+            match parse_codesnippet_parameters(directive) {
+                Ok((file_path_str, params)) => {
+                    let file_path = PathBuf::from(&file_path_str);
+
+                    match process_code_snippet_from(
+                        &file_path,
+                        current_file,
+                        &params,
+                        options.source,
+                    ) {
+                        Ok(code_block) => {
+                            tracing::debug!(path = %file_path_str, "Included code snippet");
+
+                            // Track successful codesnippet
+                            includes_tracker.push(IncludeResult {
+                                path: file_path_str.clone(),
+                                success: true,
+                                error_message: None,
+                                error_code: None,
+                                parent: current_file.to_string_lossy().to_string(),
+                                depth,
+                                title: None,
+                                values: HashMap::new(),
+                                duration: started.map(|started| started.elapsed()),
+                                bytes: Some(code_block.len()),
+                                attempts: None,
+                                kind: DirectiveKind::CodeSnippet,
+                            });
+
+                            // The fences count as the directive's lines, the code as the code file's
+                            let code_block = match (end_line, code_block.split_once('\n')) {
+                                (Some(end_line), Some((open, rest)))
+                                    if let Some((code, close)) = rest.rsplit_once('\n')
+                                        && let Ok(path) =
+                                            code_snippet_path(&file_path_str, current_file) =>
+                                {
+                                    let start = params.start.unwrap_or(1) as i64;
+                                    format!(
+                                        "{open}\n{}{code}{}\n{close}",
+                                        source_map::begin_marker(&path, start),
+                                        source_map::end_marker(end_line - 1),
+                                    )
+                                }
+                                _ => code_block,
+                            };
+
+                            // Add the code block with preserved formatting
+                            new_result.push_str(before_newlines);
+                            new_result.push_str(&code_block);
+                            new_result.push_str(after_newlines);
+                        }
+                        Err(e) => {
+                            tracing::warn!(path = %file_path_str, error = %e, "Failed to process code snippet");
+
+                            // Track failed codesnippet
+                            let error_msg = format!("{e}");
+                            includes_tracker.push(IncludeResult {
+                                path: file_path_str.clone(),
+                                success: false,
+                                error_message: Some(error_msg.clone()),
+                                error_code: Some(
+                                    ErrorCode::of(e.as_ref())
+                                        .unwrap_or(ErrorCode::MissingCodeSnippet),
+                                ),
+                                parent: current_file.to_string_lossy().to_string(),
+                                depth,
+                                title: None,
+                                values: HashMap::new(),
+                                duration: started.map(|started| started.elapsed()),
+                                bytes: None,
+                                attempts: None,
+                                kind: DirectiveKind::CodeSnippet,
+                            });
+
+                            // Keep the original directive as a comment with preserved formatting
+                            new_result.push_str(before_newlines);
+                            new_result.push_str(&failure_placeholder(
+                                options,
+                                &format!(
+                                    "Failed to process codesnippet: {file_path_str} (Error: {error_msg})"
+                                ),
+                                &file_path_str,
+                                &error_msg,
+                                ErrorCode::of(e.as_ref()).unwrap_or(ErrorCode::MissingCodeSnippet),
+                            ));
+                            new_result.push_str(after_newlines);
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(directive, error = %e, "Failed to parse codesnippet directive");
+
+                    // Track failed codesnippet with parse error
+                    includes_tracker.push(IncludeResult {
+                        path: directive.to_string(),
+                        success: false,
+                        error_message: Some(format!("Failed to parse codesnippet directive: {e}")),
+                        error_code: Some(ErrorCode::InvalidCodeSnippetDirective),
+                        parent: current_file.to_string_lossy().to_string(),
+                        depth,
+                        title: None,
+                        values: HashMap::new(),
+                        duration: started.map(|started| started.elapsed()),
+                        bytes: None,
+                        attempts: None,
+                        kind: DirectiveKind::CodeSnippet,
+                    });
+
+                    // Add content before the directive and keep the original directive as a comment
+                    new_result.push_str(before_newlines);
+                    new_result.push_str(&failure_placeholder(
+                        options,
+                        &format!("Failed to parse codesnippet directive: {directive} (Error: {e})"),
+                        directive,
+                        &e.to_string(),
+                        ErrorCode::InvalidCodeSnippetDirective,
+                    ));
+                    new_result.push_str(after_newlines);
+                }
+            }
+        } else if directive_type == "docsnippet" {
+            let resolved = parse_docsnippet_parameters(directive)
+                .map_err(|e| {
+                    ErrorCode::InvalidDocSnippetDirective
+                        .error(format!("Failed to parse docsnippet directive: {e}"))
+                })
+                .and_then(|(file_path_str, params)| {
+                    let docs = process_doc_snippet_from(
+                        Path::new(&file_path_str),
+                        current_file,
+                        &params,
+                        options.source,
+                    )?;
+                    Ok((file_path_str, docs))
+                });
+            match resolved {
+                Ok((file_path_str, docs)) => {
+                    tracing::debug!(path = %file_path_str, "Included doc snippet");
+                    includes_tracker.push(IncludeResult {
+                        path: file_path_str.clone(),
+                        success: true,
+                        error_message: None,
+                        error_code: None,
+                        parent: current_file.to_string_lossy().to_string(),
+                        depth,
+                        title: None,
+                        values: HashMap::new(),
+                        duration: started.map(|started| started.elapsed()),
+                        bytes: Some(docs.text.len()),
+                        attempts: None,
+                        kind: DirectiveKind::DocSnippet,
+                    });
+
+                    new_result.push_str(before_newlines);
+                    match (end_line, code_snippet_path(&file_path_str, current_file)) {
+                        (Some(end_line), Ok(path)) => {
+                            new_result.push_str(&source_map::begin_marker(&path, docs.line as i64));
+                            new_result.push_str(&docs.text);
+                            new_result.push_str(&source_map::end_marker(end_line));
+                        }
+                        _ => new_result.push_str(&docs.text),
+                    }
+                    new_result.push_str(after_newlines);
+                }
+                Err(e) => {
+                    tracing::warn!(directive, error = %e, "Failed to process doc snippet");
+                    let code = ErrorCode::of(e.as_ref()).unwrap_or(ErrorCode::MissingDocItem);
+                    let target = directive_target(directive);
+                    includes_tracker.push(IncludeResult {
+                        path: target.to_string(),
+                        success: false,
+                        error_message: Some(e.to_string()),
+                        error_code: Some(code),
+                        parent: current_file.to_string_lossy().to_string(),
+                        depth,
+                        title: None,
+                        values: HashMap::new(),
+                        duration: started.map(|started| started.elapsed()),
+                        bytes: None,
+                        attempts: None,
+                        kind: DirectiveKind::DocSnippet,
+                    });
+
+                    new_result.push_str(before_newlines);
+                    new_result.push_str(&failure_placeholder(
+                        options,
+                        &format!("Failed to process docsnippet: {directive} (Error: {e})"),
+                        target,
+                        &e.to_string(),
+                        code,
+                    ));
+                    new_result.push_str(after_newlines);
+                }
+            }
+        }
+
+        last_end = full_match.end();
+    }
+
+    // Add remaining content
+    new_result.push_str(&result[last_end..]);
+    titles.for_each(title);
+    Ok(new_result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partial_source::MemorySource;
+    use proptest::prelude::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// Text made mostly of the characters directives and fences are built from
+    const DIRECTIVE_TEXT: &str = r#"[ !a-z0-9_.,=()"'\[\]{}%|`#\n-]{0,80}"#;
+
+    proptest! {
+        #[test]
+        fn prop_parse_directives_never_panic(args in DIRECTIVE_TEXT, spacing in " ?") {
+            let _ = parse_include_parameters(&format!("!include{spacing}({args})"));
+            let _ = parse_include_parameters(&args);
+            let _ = parse_codesnippet_parameters(&format!("!codesnippet{spacing}({args})"));
+            let _ = parse_codesnippet_parameters(&args);
+        }
+
+        #[test]
+        fn prop_parse_include_parameters_round_trip(
+            path in "[a-z][a-z0-9_/-]{0,20}\\.md",
+            title in proptest::option::of("[A-Za-z0-9 ]{1,20}"),
+            level in proptest::option::of(1u8..=6),
+            values in proptest::collection::hash_map("[a-z]{1,8}", "[A-Za-z0-9 .]{1,12}", 0..4),
+        ) {
+            let mut args = vec![path.clone()];
+            if let Some(title) = &title {
+                args.push(format!("title=\"{title}\""));
+            }
+            if let Some(level) = level {
+                args.push(format!("title-level={level}"));
+            }
+            if !values.is_empty() {
+                let pairs: Vec<String> =
+                    values.iter().map(|(key, value)| format!("{key}=\"{value}\"")).collect();
+                args.push(format!("values=[{}]", pairs.join(", ")));
+            }
+
+            let (parsed_path, params) = parse_include_parameters(&format!("!include({})", args.join(", ")))
+                .expect("Failed to parse generated include directive");
+            prop_assert_eq!(parsed_path, path);
+            prop_assert_eq!(params.title, title);
+            prop_assert_eq!(params.title_level, level.or(Some(1)));
+            prop_assert_eq!(params.values, values);
+        }
+
+        #[test]
+        fn prop_process_variables_never_panics(
+            content in DIRECTIVE_TEXT,
+            variables in proptest::collection::hash_map("[a-z]{1,3}", DIRECTIVE_TEXT, 0..4),
+        ) {
+            let _ = process_variables(&content, &variables);
+            let _ = process_variables_with_passthrough(&content, &variables, Some(TemplateSyntax::Jinja));
+            let _ = process_variables_with_passthrough(&content, &variables, Some(TemplateSyntax::Liquid));
+        }
+
+        #[test]
+        fn prop_fixed_code_fences_validate(
+            lines in proptest::collection::vec(" {0,2}(`{3,4}[a-z]{0,3}|[a-z `]{0,10})", 0..12),
+            final_newline in any::<bool>(),
+        ) {
+            let mut content = lines.join("\n");
+            if final_newline {
+                content.push('\n');
+            }
+            if let Ok(fixed) = validate_and_fix_code_fences(&content, Some("text")) {
+                let revalidated = validate_and_fix_code_fences(&fixed, None)
+                    .expect("Failed to validate fixed code fences");
+                prop_assert_eq!(revalidated, fixed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_resolve_include_path_relative_to_partials() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let current_file = temp_dir.path().join("current.md");
+        let partials_path = temp_dir.path().join("partials");
+
+        let resolved = resolve_include_path("header.md", &current_file, &partials_path)
+            .expect("Failed to resolve include path");
+        assert_eq!(resolved, partials_path.join("header.md"));
+    }
+
+    #[test]
+    fn test_resolve_include_path_relative_to_current() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let current_file = temp_dir.path().join("docs").join("current.md");
+        let partials_path = temp_dir.path().join("partials");
+
+        let resolved = resolve_include_path("../header.md", &current_file, &partials_path)
             .expect("Failed to resolve include path");
         assert_eq!(resolved, temp_dir.path().join("docs").join("../header.md"));
     }
@@ -832,6 +2297,78 @@ mod tests {
         assert_eq!(resolved, PathBuf::from(absolute_path));
     }
 
+    #[test]
+    fn test_resolve_include_path_with_windows_separators() {
+        let current_file = Path::new("docs").join("guide").join("current.md");
+        let partials_path = Path::new("partials");
+        let resolve = |path| {
+            resolve_include_path(path, &current_file, partials_path)
+                .expect("Failed to resolve include path")
+        };
+
+        assert_eq!(
+            resolve(r"..\shared\note.md"),
+            Path::new("docs")
+                .join("guide")
+                .join("..")
+                .join("shared")
+                .join("note.md")
+        );
+        assert_eq!(
+            resolve(r"common\note.md"),
+            partials_path.join("common").join("note.md")
+        );
+        assert!(resolve(r"\\server\share\note.md").has_root());
+        #[cfg(windows)]
+        assert_eq!(resolve(r"C:\docs\x.md"), PathBuf::from(r"C:\docs\x.md"));
+    }
+
+    #[test]
+    fn test_dot_slash_and_resolution_order() {
+        let source = MemorySource::new()
+            .with_file("docs/sibling.md", "sibling")
+            .with_file("docs/both.md", "docs")
+            .with_file("partials/both.md", "partials")
+            .with_file("partials/shared.md", "shared");
+        let current_file = Path::new("docs/guide.md");
+        let read = |path: &str, resolution_order| {
+            let variables = HashMap::new();
+            let options = ResolveOptions {
+                resolution_order,
+                ..ResolveOptions::new(Path::new("partials"), &variables, &source)
+            };
+            let (_, content, _) = read_include(path, current_file, None, &options)
+                .expect("Failed to resolve include");
+            content.ok()
+        };
+
+        assert_eq!(
+            read("./sibling.md", ResolutionOrder::Partials).as_deref(),
+            Some("sibling")
+        );
+        assert_eq!(read("sibling.md", ResolutionOrder::Partials), None);
+        assert_eq!(
+            read("both.md", ResolutionOrder::Partials).as_deref(),
+            Some("partials")
+        );
+        assert_eq!(
+            read("both.md", ResolutionOrder::PartialsThenCurrent).as_deref(),
+            Some("partials")
+        );
+        assert_eq!(
+            read("sibling.md", ResolutionOrder::PartialsThenCurrent).as_deref(),
+            Some("sibling")
+        );
+        assert_eq!(
+            read("both.md", ResolutionOrder::CurrentThenPartials).as_deref(),
+            Some("docs")
+        );
+        assert_eq!(
+            read("shared.md", ResolutionOrder::CurrentThenPartials).as_deref(),
+            Some("shared")
+        );
+    }
+
     #[test]
     fn test_parse_include_parameters_simple() {
         let directive = "!include (readme-terminology.md)";
@@ -869,6 +2406,371 @@ mod tests {
         assert!(params.values.is_empty());
     }
 
+    #[test]
+    fn test_quoted_paths_with_spaces_commas_and_parentheses() {
+        for (directive, expected) in [
+            (
+                r#"!include("release notes 2024.md")"#,
+                "release notes 2024.md",
+            ),
+            (
+                r#"!include ("notes, 2024.md", title="Notes")"#,
+                "notes, 2024.md",
+            ),
+            (
+                r#"!include('notes (draft).md' , title="Notes")"#,
+                "notes (draft).md",
+            ),
+            (
+                r#"!include(<notes, (draft).md>, title="Notes")"#,
+                "notes, (draft).md",
+            ),
+            ("!include(plain.md, title=\"Notes\")", "plain.md"),
+        ] {
+            let (path, params) =
+                parse_include_parameters(directive).expect("Failed to parse include parameters");
+            assert_eq!(path, expected, "{directive}");
+            assert_eq!(directive_target(directive), expected, "{directive}");
+            if directive.contains("title") {
+                assert_eq!(params.title.as_deref(), Some("Notes"), "{directive}");
+            }
+        }
+        let (path, params) =
+            parse_codesnippet_parameters(r#"!codesnippet("my code, v2 (old).py", lang="python")"#)
+                .expect("Failed to parse codesnippet parameters");
+        assert_eq!(path, "my code, v2 (old).py");
+        assert_eq!(params.lang.as_deref(), Some("python"));
+
+        let source = MemorySource::new()
+            .with_file("partials/release notes, 2024 (draft).md", "Notes")
+            .with_file("docs/my code.py", "print()");
+        let variables = HashMap::new();
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let content = "!include(\"release notes, 2024 (draft).md\")\n\n!codesnippet(<my code.py>)";
+        let mut includes = Vec::new();
+        let result = resolve_includes(
+            content,
+            Path::new("docs/doc.md"),
+            &mut includes,
+            None,
+            &options,
+        )
+        .expect("Failed to process includes");
+        assert_eq!(result, "Notes\n\n```\nprint()\n```");
+        assert!(includes.iter().all(|include| include.success));
+    }
+
+    #[test]
+    fn test_docsnippets_embed_documentation() {
+        let source = MemorySource::new()
+            .with_file(
+                "src/lib.rs",
+                "//! The crate\n\npub struct Processor;\n\nimpl Processor {\n    /// Runs **everything**\n    pub fn run(&self) {}\n}\n",
+            )
+            .with_file("src/notes.txt", "Notes");
+        let variables = HashMap::new();
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let content = "# API\n\n!docsnippet(../src/lib.rs)\n\n!docsnippet (../src/lib.rs, item=\"Processor::run\")\n\n!docsnippet(../src/lib.rs, item=\"Processor::stop\")\n\n!docsnippet(../src/notes.txt)\n\n!docsnippet(../src/lib.rs, lang=\"rust\")";
+        let mut includes = Vec::new();
+        let result = resolve_includes(
+            content,
+            Path::new("docs/api.md"),
+            &mut includes,
+            None,
+            &options,
+        )
+        .expect("Failed to process includes");
+
+        assert!(result.starts_with("# API\n\nThe crate\n\nRuns **everything**\n\n"));
+        let outcomes: Vec<_> = includes
+            .iter()
+            .map(|include| (include.kind, include.error_code))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                (DirectiveKind::DocSnippet, None),
+                (DirectiveKind::DocSnippet, None),
+                (DirectiveKind::DocSnippet, Some(ErrorCode::MissingDocItem)),
+                (
+                    DirectiveKind::DocSnippet,
+                    Some(ErrorCode::InvalidDocSnippetDirective)
+                ),
+                (
+                    DirectiveKind::DocSnippet,
+                    Some(ErrorCode::InvalidDocSnippetDirective)
+                ),
+            ]
+        );
+        assert_eq!(includes[2].path, "../src/lib.rs");
+    }
+
+    #[test]
+    fn test_expand_directive_at_resolves_the_directive_under_the_offset() {
+        let source = MemorySource::new()
+            .with_file(
+                "partials/intro.md",
+                "Intro to {% product %}\n\n!include(nested.md)",
+            )
+            .with_file("partials/nested.md", "Nested")
+            .with_file("docs/code.rs", "fn main() {}");
+        let variables = HashMap::from([("product".to_string(), "md2md".to_string())]);
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let content = "# Doc\n\n!include(intro.md)\n\n```\n!include(fenced.md)\n```\n!codesnippet(code.rs, lang=\"rust\")";
+        let current_file = Path::new("docs/doc.md");
+        let expand = |offset| expand_directive_at(content, offset, current_file, &options);
+
+        let include = expand(content.find("intro").expect("Missing include"))
+            .expect("Missing directive")
+            .expect("Failed to expand include");
+        assert_eq!(&content[include.range.clone()], "!include(intro.md)");
+        assert_eq!(include.content, "Intro to md2md\n\nNested");
+        let dependencies: Vec<_> = include.includes.iter().map(|i| i.path.as_str()).collect();
+        assert_eq!(
+            dependencies,
+            vec![
+                Path::new("partials").join("intro.md").to_string_lossy(),
+                Path::new("partials").join("nested.md").to_string_lossy(),
+            ]
+        );
+
+        let snippet = expand(content.len() - 1)
+            .expect("Missing directive")
+            .expect("Failed to expand snippet");
+        assert_eq!(snippet.content, "```rust\nfn main() {}\n```");
+
+        assert!(expand(0).is_none());
+        assert!(expand(content.find("fenced").expect("Missing fenced include")).is_none());
+    }
+
+    #[test]
+    fn test_conditional_includes() {
+        let source = MemorySource::new()
+            .with_file("partials/cloud.md", "Cloud setup")
+            .with_file("partials/on-prem.md", "On-prem setup");
+        let variables = HashMap::from([("target".to_string(), "cloud".to_string())]);
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let content = "# Setup\n\n!include(cloud.md, if=\"target == 'cloud'\")\n\n!include(on-prem.md, if=\"target == 'on-prem'\")\n\nDone";
+        let mut includes = Vec::new();
+
+        let result = resolve_includes(content, Path::new("doc.md"), &mut includes, None, &options)
+            .expect("Failed to process includes");
+
+        assert_eq!(result, "# Setup\n\nCloud setup\n\nDone");
+        assert_eq!(includes.len(), 1);
+        assert!(includes[0].success);
+
+        let (_, params) = parse_include_parameters(r#"!include(a.md, if="target == 'cloud'")"#)
+            .expect("Failed to parse include parameters");
+        assert_eq!(params.condition.as_deref(), Some("target == 'cloud'"));
+        assert!(parse_include_parameters(r#"!include(a.md, if="target = 'cloud'")"#).is_err());
+
+        // A value named `if` is a value, not a condition
+        let (_, params) = parse_include_parameters(r#"!include(a.md, values=[if="P.  Eo"])"#)
+            .expect("Failed to parse include parameters");
+        assert_eq!(params.condition, None);
+        assert_eq!(params.values["if"], "P.  Eo");
+    }
+
+    #[test]
+    fn test_locale_prefers_translations() {
+        let source = MemorySource::new()
+            .with_file("partials/header.md", "Welcome")
+            .with_file("partials/header.de.md", "Willkommen")
+            .with_file("partials/footer.md", "Goodbye");
+        let variables = HashMap::new();
+        let options = ResolveOptions {
+            locale: Some("de"),
+            ..ResolveOptions::new(Path::new("partials"), &variables, &source)
+        };
+        let content = "!include(header.md)\n\n!include(footer.md)";
+        let mut includes = Vec::new();
+
+        let result = resolve_includes(content, Path::new("doc.md"), &mut includes, None, &options)
+            .expect("Failed to process includes");
+        assert_eq!(result, "Willkommen\n\nGoodbye");
+
+        let untranslated = resolve_includes(
+            content,
+            Path::new("doc.md"),
+            &mut Vec::new(),
+            None,
+            &ResolveOptions {
+                locale: None,
+                error_template: None,
+                retry: RetryPolicy::default(),
+                ..options
+            },
+        )
+        .expect("Failed to process includes");
+        assert_eq!(untranslated, "Welcome\n\nGoodbye");
+
+        assert_eq!(
+            localized_path(Path::new("partials/header.md"), "de"),
+            Path::new("partials/header.de.md")
+        );
+    }
+
+    #[test]
+    fn test_include_from_git_revision() {
+        let (path, params) = parse_include_parameters(r#"!include(CHANGELOG.md, rev="v1.2.0")"#)
+            .expect("Failed to parse include parameters");
+        assert_eq!(path, "CHANGELOG.md");
+        assert_eq!(params.rev.as_deref(), Some("v1.2.0"));
+
+        // The working copy is never read for a revision, and memory has no history
+        let source = MemorySource::new().with_file("partials/CHANGELOG.md", "## Unreleased");
+        let variables = HashMap::new();
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let mut includes = Vec::new();
+        let result = resolve_includes(
+            r#"!include(CHANGELOG.md, rev="v1.2.0")"#,
+            Path::new("doc.md"),
+            &mut includes,
+            None,
+            &options,
+        )
+        .expect("Failed to process includes");
+
+        assert!(result.starts_with("<!-- Failed to include: CHANGELOG.md"));
+        assert!(!includes[0].success);
+        assert_eq!(includes[0].error_code, Some(ErrorCode::MissingInclude));
+    }
+
+    #[test]
+    fn test_error_template_replaces_failed_directives() {
+        let source = MemorySource::new();
+        let variables = HashMap::new();
+        let resolve = |template| {
+            let options = ResolveOptions {
+                error_template: Some(template),
+                ..ResolveOptions::new(Path::new("partials"), &variables, &source)
+            };
+            let mut includes = Vec::new();
+            let result = resolve_includes(
+                "# Doc\n\n!include(missing.md)\n\n!codesnippet(main.rs)",
+                Path::new("doc.md"),
+                &mut includes,
+                None,
+                &options,
+            )
+            .expect("Failed to process includes");
+            assert_eq!(
+                includes.iter().filter(|include| !include.success).count(),
+                2
+            );
+            result
+        };
+
+        assert_eq!(
+            resolve("> Missing content: {path} [{code}]"),
+            "# Doc\n\n> Missing content: missing.md [E001]\n\n> Missing content: main.rs [E003]"
+        );
+        assert_eq!(resolve(""), "# Doc\n\n\n\n");
+        assert!(resolve("{error}").contains("main.rs"));
+    }
+
+    #[test]
+    fn test_include_reads_are_retried_after_transient_failures() {
+        /// Times out on the first `failures` reads of `flaky.md`
+        struct FlakySource {
+            inner: MemorySource,
+            failures: usize,
+            reads: Cell<usize>,
+        }
+
+        impl PartialSource for FlakySource {
+            fn read(&self, path: &Path) -> std::io::Result<String> {
+                if path.ends_with("flaky.md") {
+                    self.reads.set(self.reads.get() + 1);
+                    if self.reads.get() <= self.failures {
+                        return Err(std::io::ErrorKind::TimedOut.into());
+                    }
+                }
+                self.inner.read(path)
+            }
+        }
+
+        let resolve = |failures, retries| {
+            let source = FlakySource {
+                inner: MemorySource::new().with_file("partials/flaky.md", "Flaky"),
+                failures,
+                reads: Cell::new(0),
+            };
+            let variables = HashMap::new();
+            let options = ResolveOptions {
+                error_template: Some(""),
+                retry: RetryPolicy {
+                    retries,
+                    delay_ms: 0,
+                },
+                ..ResolveOptions::new(Path::new("partials"), &variables, &source)
+            };
+            let mut includes = Vec::new();
+            let result = resolve_includes(
+                "!include(flaky.md)\n!include(missing.md)",
+                Path::new("doc.md"),
+                &mut includes,
+                None,
+                &options,
+            )
+            .expect("Failed to process includes");
+            let attempts: Vec<_> = includes
+                .iter()
+                .map(|include| (include.success, include.attempts))
+                .collect();
+            (result, attempts, source.reads.get())
+        };
+
+        // Missing files are never read again
+        assert_eq!(
+            resolve(2, 3),
+            (
+                "Flaky\n".to_string(),
+                vec![(true, Some(3)), (false, None)],
+                3
+            )
+        );
+        assert_eq!(
+            resolve(3, 1),
+            ("\n".to_string(), vec![(false, Some(2)), (false, None)], 2)
+        );
+        assert_eq!(resolve(0, 3).1, vec![(true, None), (false, None)]);
+    }
+
+    #[test]
+    fn test_multi_line_directives() {
+        let source = MemorySource::new()
+            .with_file("partials/intro.md", "Hello {% name %}")
+            .with_file("docs/main.rs", "one\ntwo\nthree");
+        let variables = HashMap::new();
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let content = "!include(\n    intro.md,\n    title=\"Intro\",\n    title-level=2,\n    values=[\n        name=\"World\",\n    ]\n)\n\n!codesnippet(\n    main.rs,\n    lang=\"rust\",\n    start=2\n)";
+        let mut includes = Vec::new();
+
+        let result = resolve_includes(
+            content,
+            Path::new("docs/doc.md"),
+            &mut includes,
+            None,
+            &options,
+        )
+        .expect("Failed to process includes");
+
+        assert_eq!(
+            result,
+            "## Intro\n\nHello World\n\n```rust\ntwo\nthree\n```"
+        );
+        assert!(includes.iter().all(|include| include.success));
+    }
+
+    #[test]
+    fn test_parse_include_parameters_malformed_is_an_error() {
+        for directive in ["!include()", "!include (", "!include ( , title=\"T\")"] {
+            assert!(parse_include_parameters(directive).is_err(), "{directive}");
+        }
+    }
+
     #[test]
     fn test_parse_include_parameters_with_values() {
         let directive = r#"!include (readme-terminology.md, values=[variable_name_1="Value 1", variable_name_2="Value 2"])"#;
@@ -926,6 +2828,15 @@ mod tests {
         assert_eq!(result, "Hello Guest!");
     }
 
+    #[test]
+    fn test_process_variables_dotted_names() {
+        let content = r#"v{% pkg.version %} of {% pkg.name || "md2md" %}"#;
+        let variables = HashMap::from([("pkg.version".to_string(), "1.4.0".to_string())]);
+
+        let result = process_variables(content, &variables).expect("Failed to process variables");
+        assert_eq!(result, "v1.4.0 of md2md");
+    }
+
     #[test]
     fn test_process_variables_missing_no_default() {
         let content = "Hello {% name %}!";
@@ -941,6 +2852,206 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_process_variables_passes_template_tags_through() {
+        let variables = HashMap::from([("name".to_string(), "md2md".to_string())]);
+
+        let jinja = "{% if user %}Hi {% name %}{% endif %} {{ page.title }} {# {% name %} #} {% raw %}{% name %}{% endraw %}";
+        let result =
+            process_variables_with_passthrough(jinja, &variables, Some(TemplateSyntax::Jinja))
+                .expect("Failed to process variables");
+        assert_eq!(
+            result,
+            "{% if user %}Hi md2md{% endif %} {{ page.title }} {# {% name %} #} {% raw %}{% name %}{% endraw %}"
+        );
+
+        let liquid = "{% seo %} {% name %} {% comment %}{% name %}{% endcomment %}";
+        let result =
+            process_variables_with_passthrough(liquid, &variables, Some(TemplateSyntax::Liquid))
+                .expect("Failed to process variables");
+        assert_eq!(
+            result,
+            "{% seo %} md2md {% comment %}{% name %}{% endcomment %}"
+        );
+    }
+
+    #[test]
+    fn test_source_comments_wrap_and_strip_includes() {
+        let source = MemorySource::new()
+            .with_file("partials/header.md", "# Header\n!include (inner.md)")
+            .with_file("partials/inner.md", "Inner")
+            .with_file("partials/version.md", "1.0");
+        let resolve = |content: &str, file: &str, mode| {
+            let variables = HashMap::new();
+            let options = ResolveOptions {
+                source_comments: Some(mode),
+                ..ResolveOptions::new(Path::new("partials"), &variables, &source)
+            };
+            resolve_includes(content, Path::new(file), &mut Vec::new(), None, &options)
+                .expect("Failed to resolve includes")
+        };
+
+        let content = "!include (header.md)\n\nVersion !include (version.md)";
+        let inserted = resolve(content, "guide.md", SourceComments::Insert);
+        assert_eq!(
+            inserted,
+            "<!-- md2md: begin partials/header.md -->\n# Header\n<!-- md2md: begin partials/inner.md -->\nInner\n<!-- md2md: end partials/inner.md -->\n<!-- md2md: end partials/header.md -->\n\nVersion 1.0"
+        );
+        assert_eq!(
+            resolve(&inserted, "guide.md", SourceComments::Strip),
+            "# Header\nInner\n\nVersion 1.0"
+        );
+
+        assert!(
+            resolve("!include (inner.md)", "guide.mdx", SourceComments::Insert)
+                .starts_with("{/* md2md: begin partials/inner.md */}\nInner")
+        );
+    }
+
+    #[test]
+    fn test_includes_record_parent_and_depth() {
+        let source = MemorySource::new()
+            .with_file("partials/header.md", "# Header\n!include (inner.md)")
+            .with_file("partials/inner.md", "Inner");
+        let variables = HashMap::new();
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let mut includes = Vec::new();
+        resolve_includes(
+            "!include (header.md)\n!include (missing.md)",
+            Path::new("guide.md"),
+            &mut includes,
+            None,
+            &options,
+        )
+        .expect("Failed to resolve includes");
+
+        let tree: Vec<_> = includes
+            .iter()
+            .map(|i| (i.path.as_str(), i.parent.as_str(), i.depth, i.success))
+            .collect();
+        assert_eq!(
+            tree,
+            vec![
+                ("partials/header.md", "guide.md", 0, true),
+                ("partials/inner.md", "partials/header.md", 1, true),
+                ("partials/missing.md", "guide.md", 0, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_include_cache_reuses_resolved_partials_across_documents() {
+        let source = MemorySource::new()
+            .with_file(
+                "partials/header.md",
+                "# {% name || \"Header\" %}\n!include (inner.md)",
+            )
+            .with_file("partials/inner.md", "Inner");
+        let cache = IncludeCache::new();
+        let variables = HashMap::new();
+        let options = ResolveOptions {
+            cache: Some(&cache),
+            ..ResolveOptions::new(Path::new("partials"), &variables, &source)
+        };
+        let resolve = |document: &str, content: &str| {
+            let mut includes = Vec::new();
+            let resolved =
+                resolve_includes(content, Path::new(document), &mut includes, None, &options)
+                    .expect("Failed to resolve includes");
+            let tree: Vec<_> = includes
+                .iter()
+                .map(|i| (i.path.clone(), i.parent.clone(), i.depth))
+                .collect();
+            (resolved, tree)
+        };
+
+        let first = resolve("a.md", "!include (header.md)");
+        assert_eq!(cache.hits(), 0);
+        let second = resolve("b.md", "!include (header.md)");
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(second.0, first.0);
+        assert_eq!(second.1[1..], first.1[1..]);
+        assert_eq!(second.1[0].1, "b.md");
+
+        // Other values make another entry, as does its nested include, which inherits them
+        let (resolved, _) = resolve("c.md", "!include (header.md, values=[name=\"Top\"])");
+        assert_eq!(cache.hits(), 1);
+        assert_eq!(resolved, "# Top\nInner");
+    }
+
+    #[test]
+    fn test_code_fences() {
+        let content =
+            "text\n```rust\n!include(a.md)\n  ```\n```\nafter `!include(b.md)`\n  ```\nopen";
+        let position = |needle: &str| content.find(needle).expect("Missing needle");
+        let fences = CodeFences::new(content);
+
+        assert!(!fences.contain(content, 2));
+        assert!(fences.contain(content, position("!include(a.md)")));
+        // The indented fence doesn't close the unindented one, the next one does
+        assert!(fences.contain(content, position("```\n```") + 2));
+        assert!(!fences.contain(content, position("\nafter")));
+        assert!(fences.contain(content, position("!include(b.md)")));
+        // An unclosed fence runs to the end
+        assert!(fences.contain(content, position("open")));
+        assert!(fences.contain(content, content.len()));
+    }
+
+    #[test]
+    fn test_directives_in_included_content_are_expanded_once() {
+        // The snippet's own fences would make its directive look like document text on a rescan
+        let source = MemorySource::new()
+            .with_file("code/example.md", "```\n!include(inner.md)\n```")
+            .with_file("partials/inner.md", "Inner");
+        let variables = HashMap::new();
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let mut includes = Vec::new();
+        let resolved = resolve_includes(
+            "!codesnippet(code/example.md, lang=\"markdown\")",
+            Path::new("guide.md"),
+            &mut includes,
+            None,
+            &options,
+        )
+        .expect("Failed to resolve includes");
+
+        assert_eq!(resolved, "```markdown\n```\n!include(inner.md)\n```\n```");
+        assert_eq!(includes.len(), 1);
+    }
+
+    #[test]
+    fn test_process_code_snippet_ranges() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let code: String = (1..=1000).map(|n| format!("line {n}\n")).collect();
+        fs::write(temp_dir.path().join("code.rs"), code).expect("Failed to write code.rs");
+        let current_file = temp_dir.path().join("doc.md");
+        let snippet = |start, end| {
+            process_code_snippet(
+                Path::new("code.rs"),
+                &current_file,
+                &CodeSnippetParameters {
+                    lang: Some("rust".to_string()),
+                    start,
+                    end,
+                },
+            )
+        };
+
+        assert_eq!(
+            snippet(Some(2), Some(3)).expect("Failed to read snippet"),
+            "```rust\nline 2\nline 3\n```"
+        );
+        assert!(
+            snippet(Some(999), None)
+                .expect("Failed to read snippet")
+                .ends_with("line 1000\n```")
+        );
+        let error = snippet(Some(1001), Some(1002)).expect_err("Start beyond the file");
+        assert!(error.to_string().contains("file length (1000)"));
+        let error = snippet(Some(5), Some(4)).expect_err("End before start");
+        assert!(error.to_string().contains("End line must be greater"));
+    }
+
     #[test]
     fn test_add_title_to_content() {
         let content = "This is the content.";
@@ -1009,6 +3120,89 @@ Main content continues here."#;
         assert!(includes[0].success);
     }
 
+    #[test]
+    fn test_nested_includes_inherit_variables() {
+        let source = MemorySource::new()
+            .with_file(
+                "partials/outer.md",
+                "{% product %} {% version %}\n!include(inner.md, values=[version=\"2\"])",
+            )
+            .with_file(
+                "partials/inner.md",
+                "{% product %} {% version %} {% site %}",
+            );
+        let variables = HashMap::from([("site".to_string(), "docs".to_string())]);
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let content = r#"!include(outer.md, values=[product="md2md", version="1"])"#;
+        let mut includes = Vec::new();
+
+        let result = resolve_includes(content, Path::new("doc.md"), &mut includes, None, &options)
+            .expect("Failed to process includes");
+
+        assert_eq!(result, "md2md 1\nmd2md 2 docs");
+        assert!(includes.iter().all(|include| include.success));
+    }
+
+    #[test]
+    fn test_declared_defaults() {
+        let partial =
+            "<!-- md2md:vars product=\"md2md\"\n  audience version=\"\" -->\n# {% product %}";
+        let (defaults, body_start) = declared_defaults(partial);
+        assert_eq!(
+            defaults,
+            HashMap::from([
+                ("product".to_string(), "md2md".to_string()),
+                ("version".to_string(), String::new()),
+            ])
+        );
+        assert_eq!(&partial[body_start..], "# {% product %}");
+        assert_eq!(
+            declared_defaults("# Title\n<!-- md2md:vars a=\"b\" -->"),
+            (HashMap::new(), 0)
+        );
+
+        let source = MemorySource::new().with_file("partials/intro.md", partial);
+        let variables = HashMap::new();
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let resolve = |content: &str| {
+            resolve_includes(
+                content,
+                Path::new("doc.md"),
+                &mut Vec::new(),
+                None,
+                &options,
+            )
+            .expect("Failed to process includes")
+        };
+        assert_eq!(resolve("!include(intro.md)"), "# md2md");
+        assert_eq!(
+            resolve(r#"!include(intro.md, values=[product="Other"])"#),
+            "# Other"
+        );
+    }
+
+    #[test]
+    fn test_include_title_with_variables() {
+        let source = MemorySource::new().with_file("partials/guide.md", "Read on.");
+        let variables = HashMap::from([("product".to_string(), "md2md".to_string())]);
+        let options = ResolveOptions::new(Path::new("partials"), &variables, &source);
+        let content = r#"!include(guide.md, title="{% kind %} for {% product %}", title-level=2, values=[kind="Guide"])"#;
+        let mut includes = Vec::new();
+
+        let result = resolve_includes(content, Path::new("doc.md"), &mut includes, None, &options)
+            .expect("Failed to process includes");
+
+        assert_eq!(result, "## Guide for md2md\n\nRead on.");
+        assert!(includes[0].success);
+
+        let content = r#"!include(guide.md, title="For {% audience %}")"#;
+        let mut includes = Vec::new();
+        let result = resolve_includes(content, Path::new("doc.md"), &mut includes, None, &options)
+            .expect("Failed to process includes");
+        assert!(includes.iter().any(|include| !include.success));
+        assert!(result.contains("Failed to process variables"));
+    }
+
     #[test]
     fn test_validate_and_fix_code_fences_valid() {
         let content = r#"# Test
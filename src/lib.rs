@@ -1,20 +1,75 @@
+#[cfg(feature = "cli")]
 pub mod action;
+#[cfg(feature = "cli")]
 pub mod app;
+pub mod archive;
+pub mod banner;
 pub mod cli_messages;
+#[cfg(feature = "cli")]
 pub mod components;
+pub mod condition;
+pub mod config;
+pub mod daemon;
+pub mod docsnippet;
+pub mod encoding;
+pub mod error_code;
+#[cfg(feature = "cli")]
 pub mod event;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 pub mod file_handler;
+pub mod frontmatter;
+pub mod git;
+pub mod glossary;
+pub mod graph;
+pub mod headings;
+pub mod history;
+pub mod html;
+pub mod ignore;
 pub mod include_resolver;
+pub mod links;
+pub mod lint;
+pub mod lock;
+pub mod lsp;
+pub mod manifest;
+pub mod markedpp;
+pub mod mdx;
+pub mod mkdocs;
+pub mod normalize;
+pub mod partial_source;
+pub mod post_process;
 pub mod processor;
+pub mod report;
+pub mod scaffold;
+pub mod schema;
+pub mod sink;
+pub mod source_map;
+pub mod spellcheck;
+#[cfg(feature = "cli")]
+pub mod theme;
+#[cfg(feature = "cli")]
 pub mod tui;
 pub mod types;
+pub mod usage;
+pub mod validation;
+pub mod warnings;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watch;
+
+pub use processor::{Processor, ProcessorBuilder};
 
 #[cfg(test)]
 mod integration_tests {
     use super::*;
+    use std::collections::HashMap;
     use std::fs;
     use tempfile::TempDir;
-    use types::{ProcessingConfig, ProcessingSummary};
+    use types::{
+        Compat, Emit, FileOrder, Frontmatter, HtmlOptions, InputEncoding, LineEndings,
+        Normalization, OutputBudget, OutputNaming, ProcessingConfig, ProcessingSummary,
+        ResolutionOrder, RetryPolicy,
+    };
 
     #[test]
     fn test_end_to_end_processing_with_includes() {
@@ -67,10 +122,41 @@ mod integration_tests {
             batch: true,
             verbose: false,
             fix_code_fences: None,
+            variables: HashMap::new(),
+            order: FileOrder::default(),
+            post_process: None,
+            naming: OutputNaming::default(),
+            compat: Compat::default(),
+            passthrough: None,
+            emit: Emit::default(),
+            html: HtmlOptions::default(),
+            bundle_links: false,
+            check_links: false,
+            duplicate_headings: None,
+            line_endings: LineEndings::default(),
+            normalization: Normalization::default(),
+            source_comments: None,
+            banner: None,
+            diff: false,
+            input_encoding: InputEncoding::default(),
+            restrict_includes: false,
+            resolution_order: ResolutionOrder::default(),
+            frontmatter: Frontmatter::default(),
+            locale: None,
+            budget: OutputBudget::default(),
+            lint: None,
+            spellcheck: None,
+            source_map: false,
+            strict: false,
+            error_template: None,
+            retry: RetryPolicy::default(),
+            changed_since: None,
+            glossary: None,
         };
 
         let mut summary = ProcessingSummary::new();
-        processor::process_files(&config, &mut summary, |_| {}).expect("Failed to process files");
+        processor::process_files(&config, &mut summary, None, None)
+            .expect("Failed to process files");
 
         // Verify processing results
         assert_eq!(summary.results.len(), 2);
@@ -142,10 +228,40 @@ mod integration_tests {
             batch: false,
             verbose: false,
             fix_code_fences: None,
+            variables: HashMap::new(),
+            order: FileOrder::default(),
+            post_process: None,
+            naming: OutputNaming::default(),
+            compat: Compat::default(),
+            passthrough: None,
+            emit: Emit::default(),
+            html: HtmlOptions::default(),
+            bundle_links: false,
+            check_links: false,
+            duplicate_headings: None,
+            line_endings: LineEndings::default(),
+            normalization: Normalization::default(),
+            source_comments: None,
+            banner: None,
+            diff: false,
+            input_encoding: InputEncoding::default(),
+            restrict_includes: false,
+            resolution_order: ResolutionOrder::default(),
+            frontmatter: Frontmatter::default(),
+            locale: None,
+            budget: OutputBudget::default(),
+            lint: None,
+            spellcheck: None,
+            source_map: false,
+            strict: false,
+            error_template: None,
+            retry: RetryPolicy::default(),
+            changed_since: None,
+            glossary: None,
         };
 
         let mut summary = ProcessingSummary::new();
-        processor::process_files(&config, &mut summary, |_| {})
+        processor::process_files(&config, &mut summary, None, None)
             .expect("Failed to process files with missing includes");
 
         // Processing should complete but file should be marked as failed
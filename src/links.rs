@@ -0,0 +1,287 @@
+use crate::file_handler::is_markdown;
+use crate::graph::normalize_path;
+use crate::markedpp::heading_anchors;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// A Markdown link or image, capturing its target
+static LINK_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"!?\[[^\]]*\]\(([^)\s]+)(?:\s+[^)]*)?\)").expect("Failed to compile link regex")
+});
+
+/// A relative link or image reference whose target does not exist
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrokenLink {
+    /// 1-indexed line of the link in the checked content
+    pub line: usize,
+    pub target: String,
+}
+
+/// Finds relative links and image references in `content` whose target file
+/// does not exist relative to `base_dir`.
+///
+/// External URLs, pure anchors (`#section`) and root-relative paths are skipped,
+/// as are links inside code fences and inline code.
+pub fn find_broken_links(content: &str, base_dir: &Path) -> Vec<BrokenLink> {
+    link_targets(content)
+        .into_iter()
+        .filter(|link| {
+            local_link_path(&link.target).is_some_and(|path| !base_dir.join(path).exists())
+        })
+        .collect()
+}
+
+/// Finds links in a written output file whose target file does not exist
+/// next to it, or whose `#anchor` is not generated by any heading of the target.
+///
+/// Anchors are only checked in Markdown targets and in the output itself.
+pub fn find_broken_output_links(content: &str, output_file: &Path) -> Vec<BrokenLink> {
+    let base_dir = output_file.parent().unwrap_or(Path::new(""));
+    let own_anchors = heading_anchors(content);
+
+    link_targets(content)
+        .into_iter()
+        .filter(|link| {
+            let fragment = link.target.split_once('#').map(|(_, fragment)| fragment);
+            let anchor_missing = |anchors: &[String]| {
+                fragment.is_some_and(|f| !f.is_empty() && !anchors.iter().any(|a| a == f))
+            };
+            match local_link_path(&link.target) {
+                Some(path) => {
+                    let target = base_dir.join(path);
+                    if !target.exists() {
+                        true
+                    } else if is_markdown(&target) {
+                        let anchors = fs::read_to_string(&target)
+                            .map(|target| heading_anchors(&target))
+                            .unwrap_or_default();
+                        anchor_missing(&anchors)
+                    } else {
+                        false
+                    }
+                }
+                None if link.target.starts_with('#') => anchor_missing(&own_anchors),
+                None => false,
+            }
+        })
+        .collect()
+}
+
+/// Every link and image target in `content`, outside code fences and inline code.
+/// The caller keeps the ones that are broken.
+fn link_targets(content: &str) -> Vec<BrokenLink> {
+    let mut links = Vec::new();
+    let mut in_fence = false;
+
+    for (index, line) in content.lines().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        let line_without_code = strip_inline_code(line);
+        for capture in LINK_REGEX.captures_iter(&line_without_code) {
+            links.push(BrokenLink {
+                line: index + 1,
+                target: capture[1].to_string(),
+            });
+        }
+    }
+
+    links
+}
+
+/// A file whose content ends up in a bundled document
+#[derive(Debug, Clone, PartialEq)]
+pub struct BundledFile {
+    pub path: PathBuf,
+    /// Anchor of the file's first heading, for links without a fragment
+    pub anchor: Option<String>,
+}
+
+/// Rewrites relative links to files bundled into `content` as in-document anchors.
+///
+/// A link is resolved from the directory of every bundled file, so links written
+/// in the including document and in its partials are both found: with `guide.md`
+/// bundling `setup.md`, `[Install](./setup.md#install)` becomes `[Install](#install)`.
+pub fn rewrite_bundled_links(content: &str, bundled: &[BundledFile]) -> String {
+    let mut lines = Vec::new();
+    let mut in_fence = false;
+
+    for line in content.split('\n') {
+        let is_fence = line.trim_start().starts_with("```");
+        if is_fence {
+            in_fence = !in_fence;
+        }
+        if is_fence || in_fence {
+            lines.push(line.to_string());
+            continue;
+        }
+
+        let line_without_code = strip_inline_code(line);
+        let mut rewritten = String::with_capacity(line.len());
+        let mut last_end = 0;
+        for capture in LINK_REGEX.captures_iter(&line_without_code) {
+            let target = capture.get(1).expect("Failed to get link target");
+            if let Some(anchor) = bundled_anchor(target.as_str(), bundled) {
+                rewritten.push_str(&line[last_end..target.start()]);
+                rewritten.push_str(&anchor);
+                last_end = target.end();
+            }
+        }
+        rewritten.push_str(&line[last_end..]);
+        lines.push(rewritten);
+    }
+
+    lines.join("\n")
+}
+
+/// The in-document anchor replacing `target`, when it points to a bundled file
+fn bundled_anchor(target: &str, bundled: &[BundledFile]) -> Option<String> {
+    let path = local_link_path(target)?;
+    let fragment = target.split_once('#').map(|(_, fragment)| fragment);
+    let file = bundled.iter().find(|file| {
+        bundled.iter().any(|base| {
+            let base_dir = base.path.parent().unwrap_or(Path::new(""));
+            normalize_path(&base_dir.join(&path)) == normalize_path(&file.path)
+        })
+    })?;
+
+    match fragment {
+        Some(fragment) if !fragment.is_empty() => Some(format!("#{fragment}")),
+        _ => file.anchor.as_ref().map(|anchor| format!("#{anchor}")),
+    }
+}
+
+/// Returns the file part of a link target when it points to a local relative path
+fn local_link_path(target: &str) -> Option<String> {
+    let target = target.trim_matches(|c| c == '<' || c == '>');
+    if target.is_empty()
+        || target.starts_with('#')
+        || target.starts_with('/')
+        || target.contains("://")
+        || target.starts_with("mailto:")
+        || target.starts_with("tel:")
+    {
+        return None;
+    }
+
+    let path = target.split(['#', '?']).next().unwrap_or("");
+    if path.is_empty() {
+        return None;
+    }
+
+    Some(path.replace("%20", " "))
+}
+
+/// Blanks out inline code spans so links inside them are ignored, keeping byte offsets
+fn strip_inline_code(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut in_code = false;
+
+    for ch in line.chars() {
+        if ch == '`' {
+            in_code = !in_code;
+            result.push(ch);
+        } else if in_code {
+            result.push_str(&" ".repeat(ch.len_utf8()));
+        } else {
+            result.push(ch);
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_broken_links_reports_missing_files() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        fs::write(temp_dir.path().join("exists.md"), "# Exists").expect("Failed to write file");
+
+        let content =
+            "See [ok](exists.md#intro) and [missing](missing.md).\n\n![img](images/logo.png)";
+        let broken = find_broken_links(content, temp_dir.path());
+
+        assert_eq!(
+            broken,
+            vec![
+                BrokenLink {
+                    line: 1,
+                    target: "missing.md".to_string()
+                },
+                BrokenLink {
+                    line: 3,
+                    target: "images/logo.png".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_broken_output_links_checks_anchors() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        fs::write(temp_dir.path().join("setup.md"), "# Setup\n\n## Linux")
+            .expect("Failed to write file");
+        fs::write(temp_dir.path().join("logo.png"), "").expect("Failed to write file");
+
+        let content = "# Guide\n\n## Usage\n\n[a](#usage) [b](#missing) [c](setup.md#linux)\n\
+                       [d](setup.md#windows) [e](gone.md) ![f](logo.png#x)";
+        let broken = find_broken_output_links(content, &temp_dir.path().join("guide.md"));
+
+        let targets: Vec<&str> = broken.iter().map(|link| link.target.as_str()).collect();
+        assert_eq!(targets, vec!["#missing", "setup.md#windows", "gone.md"]);
+        assert_eq!(broken[0].line, 5);
+    }
+
+    #[test]
+    fn test_rewrite_bundled_links() {
+        let bundled = vec![
+            BundledFile {
+                path: PathBuf::from("docs/guide.md"),
+                anchor: Some("guide".to_string()),
+            },
+            BundledFile {
+                path: PathBuf::from("partials/setup/install.md"),
+                anchor: Some("installation".to_string()),
+            },
+            BundledFile {
+                path: PathBuf::from("partials/faq.md"),
+                anchor: None,
+            },
+        ];
+
+        let content = "[a](../partials/setup/install.md#linux) [b](./guide.md)\n\
+                       [c](setup/install.md) [d](faq.md) [e](other.md#x) `[f](guide.md)`\n\
+                       ```md\n[g](guide.md)\n```";
+
+        assert_eq!(
+            rewrite_bundled_links(content, &bundled),
+            "[a](#linux) [b](#guide)\n\
+             [c](#installation) [d](faq.md) [e](other.md#x) `[f](guide.md)`\n\
+             ```md\n[g](guide.md)\n```"
+        );
+    }
+
+    #[test]
+    fn test_find_broken_links_skips_external_and_code() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+
+        let content = "[web](https://example.com) [anchor](#top) [mail](mailto:a@b.c)\n\
+                       `[code](missing.md)`\n\
+                       ```md\n[fenced](missing.md)\n```";
+        let broken = find_broken_links(content, temp_dir.path());
+
+        assert!(broken.is_empty());
+    }
+}
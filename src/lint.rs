@@ -0,0 +1,227 @@
+//! Markdownlint-style rules checked on every assembled output.
+//!
+//! Linting is turned on by a `[lint]` table in `md2md.toml`, where each rule
+//! can be turned off, e.g. `bare_urls = false`. Problems are reported as warnings.
+
+use crate::include_resolver::CodeFences;
+use crate::markedpp::find_headings;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+/// Which rules are checked; all of them unless turned off
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct LintConfig {
+    /// Lines longer than `max_line_length` characters, outside code and tables
+    pub line_length: bool,
+    pub max_line_length: usize,
+    /// Headings more than one level deeper than the one before, e.g. `#` then `###`
+    pub heading_increment: bool,
+    /// Headings ending in `.`, `,`, `;`, `:` or `!`
+    pub heading_punctuation: bool,
+    /// URLs that aren't in a link or `<>`
+    pub bare_urls: bool,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        Self {
+            line_length: true,
+            max_line_length: 80,
+            heading_increment: true,
+            heading_punctuation: true,
+            bare_urls: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    LineLength,
+    HeadingIncrement,
+    HeadingPunctuation,
+    BareUrl,
+}
+
+impl Rule {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rule::LineLength => "line-length",
+            Rule::HeadingIncrement => "heading-increment",
+            Rule::HeadingPunctuation => "heading-punctuation",
+            Rule::BareUrl => "bare-url",
+        }
+    }
+}
+
+/// A problem one rule found in a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintIssue {
+    /// 1-based line
+    pub line: usize,
+    pub rule: Rule,
+    pub message: String,
+}
+
+/// Checks `content` against the enabled rules, returning the issues in line order
+pub fn lint(content: &str, config: &LintConfig) -> Vec<LintIssue> {
+    let fences = CodeFences::new(content);
+    let mut issues = Vec::new();
+
+    let mut line_start = 0;
+    for (index, line) in content.split('\n').enumerate() {
+        let line_end = line_start + line.len();
+        let in_code = fences.contain(content, line_end);
+        let length = line.trim_end().chars().count();
+        // Tables and lines without spaces, like long URLs, can't be wrapped
+        if config.line_length
+            && !in_code
+            && length > config.max_line_length
+            && !line.trim_start().starts_with('|')
+            && line.trim().contains(' ')
+        {
+            issues.push(LintIssue {
+                line: index + 1,
+                rule: Rule::LineLength,
+                message: format!(
+                    "Line is {length} characters, over {}",
+                    config.max_line_length
+                ),
+            });
+        }
+        if config.bare_urls {
+            bare_urls(content, line, line_start, index, &fences, &mut issues);
+        }
+        line_start = line_end + 1;
+    }
+
+    let lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    let mut previous_level = None;
+    for heading in find_headings(&lines) {
+        if config.heading_increment
+            && let Some(previous) = previous_level
+            && heading.level > previous + 1
+        {
+            issues.push(LintIssue {
+                line: heading.line + 1,
+                rule: Rule::HeadingIncrement,
+                message: format!("Heading jumps from H{previous} to H{}", heading.level),
+            });
+        }
+        previous_level = Some(heading.level);
+        if config.heading_punctuation
+            && let Some(last) = heading.text.chars().last()
+            && ".,;:!".contains(last)
+        {
+            issues.push(LintIssue {
+                line: heading.line + 1,
+                rule: Rule::HeadingPunctuation,
+                message: format!("Heading `{}` ends with `{last}`", heading.text),
+            });
+        }
+    }
+
+    issues.sort_by_key(|issue| issue.line);
+    issues
+}
+
+/// URLs of `line`, which starts at byte `line_start` of `content`, outside code, links and `<>`
+fn bare_urls(
+    content: &str,
+    line: &str,
+    line_start: usize,
+    index: usize,
+    fences: &CodeFences,
+    issues: &mut Vec<LintIssue>,
+) {
+    static URL_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"https?://[^\s<>()\[\]]+").expect("Failed to compile URL regex")
+    });
+    static DEFINITION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^\s*\[[^\]]+\]:").expect("Failed to compile link definition regex")
+    });
+    if DEFINITION_REGEX.is_match(line) {
+        return;
+    }
+    for url in URL_REGEX.find_iter(line) {
+        // Link targets, autolinks and HTML attributes come right after one of these
+        let wrapped = line[..url.start()]
+            .chars()
+            .last()
+            .is_some_and(|before| "<([\"'=".contains(before));
+        if wrapped || fences.contain(content, line_start + url.start()) {
+            continue;
+        }
+        // Punctuation ending the sentence isn't part of the URL
+        let url = url
+            .as_str()
+            .trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        issues.push(LintIssue {
+            line: index + 1,
+            rule: Rule::BareUrl,
+            message: format!("Bare URL {url}; write <{url}> or a link"),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_rules() {
+        let content = [
+            "# Guide",
+            "",
+            "### Setup:",
+            "",
+            "See https://example.com, <https://example.com> or [docs](https://example.com).",
+            "",
+            "A line that goes on and on, well past the forty characters this test allows.",
+            "",
+            "| A table row that is longer than forty characters is fine |",
+            "",
+            "```text",
+            "## Not a heading! https://example.com and a long line of code that is never wrapped",
+            "```",
+            "",
+            "[docs]: https://example.com",
+        ]
+        .join("\n");
+        let config = LintConfig {
+            max_line_length: 40,
+            ..LintConfig::default()
+        };
+
+        let found: Vec<(usize, Rule)> = lint(&content, &config)
+            .into_iter()
+            .map(|issue| (issue.line, issue.rule))
+            .collect();
+        let issues = lint(&content, &config);
+        assert_eq!(
+            issues[3].message,
+            "Bare URL https://example.com; write <https://example.com> or a link"
+        );
+        assert_eq!(
+            found,
+            vec![
+                (3, Rule::HeadingIncrement),
+                (3, Rule::HeadingPunctuation),
+                (5, Rule::LineLength),
+                (5, Rule::BareUrl),
+                (7, Rule::LineLength),
+            ]
+        );
+
+        let quiet = LintConfig {
+            line_length: false,
+            heading_increment: false,
+            heading_punctuation: false,
+            bare_urls: false,
+            ..LintConfig::default()
+        };
+        assert!(lint(&content, &quiet).is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+//! Advisory lock on an output directory, so two runs never write into it at once
+
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the lock file in the output directory
+pub const LOCK_FILE: &str = ".md2md.lock";
+
+/// Lock on an output directory, released when dropped or when the process ends.
+///
+/// The lock is taken by the operating system on the lock file, so a run that is
+/// killed never leaves a stale lock, only the file. The file is removed when the
+/// lock is dropped.
+#[derive(Debug)]
+pub struct OutputLock {
+    /// Holds the lock until it is closed
+    _file: File,
+    path: PathBuf,
+}
+
+impl OutputLock {
+    /// Locks `dir`, failing straight away when another run holds it
+    pub fn acquire(dir: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create output directory {}: {e}", dir.display()))?;
+        let path = dir.join(LOCK_FILE);
+        let mut file = loop {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&path)
+                .map_err(|e| format!("Failed to open lock file {}: {e}", path.display()))?;
+            match file.try_lock() {
+                // The run that held it may have removed the file before it was locked here
+                Ok(()) if is_same_file(&file, &path) => break file,
+                Ok(()) => continue,
+                Err(TryLockError::WouldBlock) => {
+                    let mut holder = String::new();
+                    let holder = match file.read_to_string(&mut holder) {
+                        Ok(_) if !holder.trim().is_empty() => {
+                            format!(" (process {})", holder.trim())
+                        }
+                        _ => String::new(),
+                    };
+                    return Err(format!(
+                        "Output directory {} is in use by another md2md run{holder}",
+                        dir.display()
+                    )
+                    .into());
+                }
+                Err(TryLockError::Error(e)) => {
+                    return Err(format!("Failed to lock {}: {e}", path.display()).into());
+                }
+            }
+        };
+        file.set_len(0)?;
+        writeln!(file, "{}", std::process::id())?;
+        Ok(Self { _file: file, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for OutputLock {
+    /// Removes the lock file while it is still locked, so no other run locks it in between
+    fn drop(&mut self) {
+        if let Err(e) = fs::remove_file(&self.path) {
+            tracing::debug!(path = %self.path.display(), error = %e, "Failed to remove lock file");
+        }
+    }
+}
+
+/// Whether `file` is still the one at `path`
+#[cfg(unix)]
+fn is_same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), fs::metadata(path)) {
+        (Ok(open), Ok(current)) => open.dev() == current.dev() && open.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+/// Whether `file` is still the one at `path`; an open file can't be replaced here
+#[cfg(not(unix))]
+fn is_same_file(_file: &File, path: &Path) -> bool {
+    path.exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_output_lock_is_exclusive_until_dropped() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let output = temp_dir.path().join("out");
+
+        let lock = OutputLock::acquire(&output).expect("Failed to acquire lock");
+        assert_eq!(
+            fs::read_to_string(lock.path()).expect("Failed to read lock file"),
+            format!("{}\n", std::process::id())
+        );
+        let error = OutputLock::acquire(&output).expect_err("Lock should be held");
+        assert!(error.to_string().contains("in use by another md2md run"));
+
+        let path = lock.path().to_path_buf();
+        drop(lock);
+        assert!(!path.exists(), "The lock file should be removed");
+        OutputLock::acquire(&output).expect("Failed to acquire released lock");
+    }
+}
@@ -0,0 +1,594 @@
+//! `md2md lsp`: a language server for documents with md2md directives, speaking
+//! the Language Server Protocol over stdio.
+//!
+//! Open documents get diagnostics for directives that don't resolve, including
+//! partials with variables that aren't set. On a directive, go to definition opens
+//! the partial or code file it reads and hover previews what it resolves to; paths
+//! of partials are completed inside `!include(`. Documents are resolved by a
+//! [`Daemon`](crate::daemon::Daemon), so partials are cached between keystrokes.
+
+use crate::daemon::DaemonOptions;
+
+#[cfg(feature = "lsp")]
+pub use server::LanguageServer;
+
+/// Serves editors over stdin and stdout until they shut the server down
+#[cfg(feature = "lsp")]
+pub fn serve_stdio(options: DaemonOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let (connection, io_threads) = lsp_server::Connection::stdio();
+    let capabilities = serde_json::to_value(LanguageServer::capabilities())?;
+    connection.initialize(capabilities)?;
+    LanguageServer::new(options).serve(&connection)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Fails; md2md was built without the `lsp` feature
+#[cfg(not(feature = "lsp"))]
+pub fn serve_stdio(_options: DaemonOptions) -> Result<(), Box<dyn std::error::Error>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "The language server needs md2md built with the `lsp` feature",
+    )
+    .into())
+}
+
+#[cfg(feature = "lsp")]
+mod server {
+    use super::*;
+    use crate::daemon::Daemon;
+    use crate::error_code::ErrorCode;
+    use crate::file_handler::collect_markdown_files;
+    use crate::include_resolver::{
+        DirectiveExpansion, code_snippet_path, directive_ranges, expand_directive_at,
+        include_candidates, parse_codesnippet_parameters, parse_docsnippet_parameters,
+        parse_include_parameters, process_doc_snippet_from,
+    };
+    use crate::partial_source::FileSystemSource;
+    use lsp_server::{Connection, Message, Notification, Request, Response};
+    use lsp_types::notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    };
+    use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as _};
+    use lsp_types::{
+        CompletionItem, CompletionItemKind, CompletionOptions, CompletionTextEdit, Diagnostic,
+        DiagnosticSeverity, Hover, HoverContents, HoverProviderCapability, Location, MarkupContent,
+        MarkupKind, NumberOrString, OneOf, Position, PublishDiagnosticsParams, Range,
+        ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind, TextEdit, Url,
+    };
+    use regex::Regex;
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::LazyLock;
+
+    /// Lines of resolved content a hover shows
+    const HOVER_LINES: usize = 40;
+
+    /// Answers an editor about the documents it has open
+    pub struct LanguageServer {
+        daemon: Daemon,
+        /// Text of the open documents, which may be ahead of their files
+        documents: HashMap<Url, String>,
+    }
+
+    impl LanguageServer {
+        pub fn new(options: DaemonOptions) -> Self {
+            Self {
+                daemon: Daemon::new(options),
+                documents: HashMap::new(),
+            }
+        }
+
+        pub fn capabilities() -> ServerCapabilities {
+            ServerCapabilities {
+                text_document_sync: Some(TextDocumentSyncCapability::Kind(
+                    TextDocumentSyncKind::FULL,
+                )),
+                hover_provider: Some(HoverProviderCapability::Simple(true)),
+                definition_provider: Some(OneOf::Left(true)),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec!["(".to_string(), "/".to_string()]),
+                    ..CompletionOptions::default()
+                }),
+                ..ServerCapabilities::default()
+            }
+        }
+
+        /// Keeps `text` as the content of `uri`, opened or edited
+        pub fn open(&mut self, uri: Url, text: String) {
+            self.documents.insert(uri, text);
+        }
+
+        pub fn close(&mut self, uri: &Url) {
+            self.documents.remove(uri);
+        }
+
+        /// Errors of the directives of `uri` that don't resolve, each at its directive
+        pub fn diagnostics(&self, uri: &Url) -> Vec<Diagnostic> {
+            let Some((text, path)) = self.document(uri) else {
+                return Vec::new();
+            };
+            let mut diagnostics = Vec::new();
+            for directive in directive_ranges(text) {
+                let range = range_of(text, &directive);
+                let expansion = match self.expand(text, &path, directive.start) {
+                    Some(Ok(expansion)) => expansion,
+                    Some(Err(e)) => {
+                        diagnostics.push(diagnostic(
+                            range,
+                            ErrorCode::of(e.as_ref()),
+                            e.to_string(),
+                        ));
+                        continue;
+                    }
+                    None => continue,
+                };
+                let directive = &text[directive];
+                let failed = expansion.includes.iter().filter(|include| !include.success);
+                for include in failed {
+                    let message = include.error_message.as_deref().unwrap_or("Failed");
+                    let message = if include.path == directive {
+                        message.to_string()
+                    } else if include.depth == 0 {
+                        format!("{}: {message}", include.path)
+                    } else {
+                        format!(
+                            "{}, included by {}: {message}",
+                            include.path, include.parent
+                        )
+                    };
+                    diagnostics.push(diagnostic(range, include.error_code, message));
+                }
+            }
+            diagnostics
+        }
+
+        /// The partial or code file read by the directive at `position`
+        pub fn definition(&self, uri: &Url, position: Position) -> Option<Location> {
+            let (text, path) = self.document(uri)?;
+            let directive = &text[directive_at(text, offset_at(text, position)?)?];
+            let (target, line) = if directive.starts_with("!include") {
+                let (include, _) = parse_include_parameters(directive).ok()?;
+                let variables = HashMap::new();
+                let options = self.daemon.resolve_options(&variables);
+                // The first that exists, as when it is read
+                let candidates = include_candidates(&include, &path, &options).ok()?;
+                (candidates.into_iter().find(|path| path.is_file())?, 0)
+            } else if directive.starts_with("!docsnippet") {
+                let (snippet, params) = parse_docsnippet_parameters(directive).ok()?;
+                let target = code_snippet_path(&snippet, &path).ok()?;
+                // The documentation of the item
+                let docs = process_doc_snippet_from(
+                    Path::new(&snippet),
+                    &path,
+                    &params,
+                    &FileSystemSource::default(),
+                );
+                let line = docs.map_or(0, |docs| docs.line - 1);
+                (target, line as u32)
+            } else {
+                let (snippet, params) = parse_codesnippet_parameters(directive).ok()?;
+                let line = params.start.map_or(0, |start| start.saturating_sub(1));
+                (code_snippet_path(&snippet, &path).ok()?, line as u32)
+            };
+            let uri = Url::from_file_path(std::path::absolute(target).ok()?).ok()?;
+            let start = Position::new(line, 0);
+            Some(Location::new(uri, Range::new(start, start)))
+        }
+
+        /// Partials whose path starts with what is typed after `!include(` at `position`
+        pub fn completion(&self, uri: &Url, position: Position) -> Vec<CompletionItem> {
+            static TYPED_PATH_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+                Regex::new(r#"!include\s*\(\s*["'<]?([^,()"'<>]*)$"#)
+                    .expect("Failed to compile typed path regex")
+            });
+            let Some((text, offset)) = self
+                .documents
+                .get(uri)
+                .and_then(|text| Some((text, offset_at(text, position)?)))
+            else {
+                return Vec::new();
+            };
+            let line_start = text[..offset].rfind('\n').map_or(0, |index| index + 1);
+            let Some(typed) = TYPED_PATH_REGEX
+                .captures(&text[line_start..offset])
+                .and_then(|capture| capture.get(1))
+            else {
+                return Vec::new();
+            };
+            let replaced = Range::new(position_at(text, line_start + typed.start()), position);
+
+            let partials_path = &self.daemon.options().partials_path;
+            let partials = collect_markdown_files(partials_path).unwrap_or_default();
+            partials
+                .iter()
+                .filter_map(|partial| {
+                    let relative = partial.strip_prefix(partials_path).ok()?;
+                    let name = relative
+                        .components()
+                        .map(|component| component.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    name.starts_with(typed.as_str()).then(|| CompletionItem {
+                        label: name.clone(),
+                        kind: Some(CompletionItemKind::FILE),
+                        text_edit: Some(CompletionTextEdit::Edit(TextEdit::new(replaced, name))),
+                        ..CompletionItem::default()
+                    })
+                })
+                .collect()
+        }
+
+        /// What the directive at `position` resolves to, or why it doesn't
+        pub fn hover(&self, uri: &Url, position: Position) -> Option<Hover> {
+            let (text, path) = self.document(uri)?;
+            let (value, range) = match self.expand(text, &path, offset_at(text, position)?)? {
+                Ok(expansion) => {
+                    let lines: Vec<&str> = expansion.content.lines().collect();
+                    let mut value = lines[..lines.len().min(HOVER_LINES)].join("\n");
+                    if lines.len() > HOVER_LINES {
+                        value
+                            .push_str(&format!("\n\n*… {} more lines*", lines.len() - HOVER_LINES));
+                    }
+                    if !expansion.includes.is_empty() {
+                        let files: Vec<String> = expansion
+                            .includes
+                            .iter()
+                            .map(|include| format!("`{}`", include.path))
+                            .collect();
+                        value.push_str(&format!("\n\n---\n\nFrom {}", files.join(", ")));
+                    }
+                    (value, Some(range_of(text, &expansion.range)))
+                }
+                Err(e) => (e.to_string(), None),
+            };
+            Some(Hover {
+                contents: HoverContents::Markup(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value,
+                }),
+                range,
+            })
+        }
+
+        /// Answers the requests and notifications of `connection` until it shuts down
+        pub fn serve(&mut self, connection: &Connection) -> Result<(), Box<dyn std::error::Error>> {
+            for message in &connection.receiver {
+                match message {
+                    Message::Request(request) => {
+                        if connection.handle_shutdown(&request)? {
+                            return Ok(());
+                        }
+                        connection.sender.send(self.respond(request).into())?;
+                    }
+                    Message::Notification(notification) => {
+                        for uri in self.notify(notification) {
+                            let params = PublishDiagnosticsParams {
+                                diagnostics: self.diagnostics(&uri),
+                                uri,
+                                version: None,
+                            };
+                            let notification =
+                                Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+                            connection.sender.send(notification.into())?;
+                        }
+                    }
+                    Message::Response(_) => {}
+                }
+            }
+            Ok(())
+        }
+
+        fn respond(&self, request: Request) -> Response {
+            fn params<R: lsp_types::request::Request>(
+                request: &Request,
+            ) -> Result<R::Params, serde_json::Error> {
+                serde_json::from_value(request.params.clone())
+            }
+
+            let result = match request.method.as_str() {
+                HoverRequest::METHOD => params::<HoverRequest>(&request).map(|params| {
+                    let at = params.text_document_position_params;
+                    serde_json::to_value(self.hover(&at.text_document.uri, at.position))
+                }),
+                GotoDefinition::METHOD => params::<GotoDefinition>(&request).map(|params| {
+                    let at = params.text_document_position_params;
+                    serde_json::to_value(self.definition(&at.text_document.uri, at.position))
+                }),
+                Completion::METHOD => params::<Completion>(&request).map(|params| {
+                    let at = params.text_document_position;
+                    serde_json::to_value(self.completion(&at.text_document.uri, at.position))
+                }),
+                method => {
+                    return Response::new_err(
+                        request.id,
+                        lsp_server::ErrorCode::MethodNotFound as i32,
+                        format!("Unsupported request: {method}"),
+                    );
+                }
+            };
+            match result.and_then(|value| value) {
+                Ok(value) => Response::new_ok(request.id, value),
+                Err(e) => Response::new_err(
+                    request.id,
+                    lsp_server::ErrorCode::InvalidParams as i32,
+                    e.to_string(),
+                ),
+            }
+        }
+
+        /// Applies `notification`, returning the documents whose diagnostics changed
+        fn notify(&mut self, notification: Notification) -> Vec<Url> {
+            match notification.method.as_str() {
+                DidOpenTextDocument::METHOD => {
+                    let Ok(params) = notification.extract(DidOpenTextDocument::METHOD) else {
+                        return Vec::new();
+                    };
+                    let params: lsp_types::DidOpenTextDocumentParams = params;
+                    let uri = params.text_document.uri;
+                    self.open(uri.clone(), params.text_document.text);
+                    vec![uri]
+                }
+                DidChangeTextDocument::METHOD => {
+                    let Ok(params) = notification.extract(DidChangeTextDocument::METHOD) else {
+                        return Vec::new();
+                    };
+                    let params: lsp_types::DidChangeTextDocumentParams = params;
+                    // Changes come as whole documents, so the last one is the text
+                    let Some(change) = params.content_changes.into_iter().last() else {
+                        return Vec::new();
+                    };
+                    let uri = params.text_document.uri;
+                    self.open(uri.clone(), change.text);
+                    vec![uri]
+                }
+                DidCloseTextDocument::METHOD => {
+                    let Ok(params) = notification.extract(DidCloseTextDocument::METHOD) else {
+                        return Vec::new();
+                    };
+                    let params: lsp_types::DidCloseTextDocumentParams = params;
+                    self.close(&params.text_document.uri);
+                    // Clears the diagnostics of the closed document
+                    vec![params.text_document.uri]
+                }
+                // A saved partial can fix or break any open document
+                DidSaveTextDocument::METHOD => self.documents.keys().cloned().collect(),
+                _ => Vec::new(),
+            }
+        }
+
+        fn document(&self, uri: &Url) -> Option<(&str, PathBuf)> {
+            let text = self.documents.get(uri)?;
+            Some((text, uri.to_file_path().ok()?))
+        }
+
+        /// The directive of `text` at the byte `offset`, resolved with the partials cached so far
+        fn expand(
+            &self,
+            text: &str,
+            path: &Path,
+            offset: usize,
+        ) -> Option<Result<DirectiveExpansion, Box<dyn std::error::Error>>> {
+            let variables = &self.daemon.options().variables;
+            expand_directive_at(text, offset, path, &self.daemon.resolve_options(variables))
+        }
+    }
+
+    fn diagnostic(range: Range, code: Option<ErrorCode>, message: String) -> Diagnostic {
+        Diagnostic {
+            range,
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: code.map(|code| NumberOrString::String(code.as_str().to_string())),
+            source: Some("md2md".to_string()),
+            message,
+            ..Diagnostic::default()
+        }
+    }
+
+    /// Byte range of the directive of `text` at the byte `offset`
+    fn directive_at(text: &str, offset: usize) -> Option<std::ops::Range<usize>> {
+        directive_ranges(text)
+            .into_iter()
+            .find(|directive| directive.contains(&offset))
+    }
+
+    fn range_of(text: &str, range: &std::ops::Range<usize>) -> Range {
+        Range::new(position_at(text, range.start), position_at(text, range.end))
+    }
+
+    /// The position of the byte `offset` in `text`; columns count UTF-16 code units
+    fn position_at(text: &str, offset: usize) -> Position {
+        let before = &text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        Position::new(
+            before.matches('\n').count() as u32,
+            before[line_start..].encode_utf16().count() as u32,
+        )
+    }
+
+    /// The byte offset of `position` in `text`, at most the end of its line
+    fn offset_at(text: &str, position: Position) -> Option<usize> {
+        let mut line_start = 0;
+        for _ in 0..position.line {
+            line_start += text[line_start..].find('\n')? + 1;
+        }
+        let line = text[line_start..].split('\n').next().unwrap_or_default();
+        let mut units = 0;
+        for (index, c) in line.char_indices() {
+            if units >= position.character as usize {
+                return Some(line_start + index);
+            }
+            units += c.len_utf16();
+        }
+        Some(line_start + line.len())
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::fs;
+        use tempfile::TempDir;
+
+        fn project() -> (TempDir, LanguageServer, Url) {
+            let temp_dir = TempDir::new().expect("Failed to create temp directory");
+            let partials = temp_dir.path().join("partials");
+            fs::create_dir_all(partials.join("shared")).expect("Failed to create partials");
+            fs::write(partials.join("header.md"), "# {% product %}").expect("Failed to write");
+            fs::write(partials.join("shared/footer.md"), "Bye {% who %}")
+                .expect("Failed to write footer");
+            fs::write(
+                temp_dir.path().join("code.rs"),
+                "fn one() {}\nfn two() {}\n",
+            )
+            .expect("Failed to write code.rs");
+            let server = LanguageServer::new(DaemonOptions {
+                partials_path: partials,
+                variables: HashMap::from([("product".to_string(), "md2md".to_string())]),
+                fix_code_fences: Some("text".to_string()),
+                ..DaemonOptions::default()
+            });
+            let uri =
+                Url::from_file_path(temp_dir.path().join("doc.md")).expect("Failed to make a URI");
+            (temp_dir, server, uri)
+        }
+
+        #[test]
+        fn test_language_server_diagnoses_directives_that_dont_resolve() {
+            let (_temp_dir, mut server, uri) = project();
+            server.open(
+                uri.clone(),
+                "!include(header.md)\né !include(missing.md)\n\n```\n!include(fenced.md)\n```\n!include(shared/footer.md)"
+                    .to_string(),
+            );
+
+            let diagnostics = server.diagnostics(&uri);
+            let found: Vec<_> = diagnostics
+                .iter()
+                .map(|d| (d.range.start, d.range.end, d.code.clone()))
+                .collect();
+            let code = |code: &str| Some(NumberOrString::String(code.to_string()));
+            assert_eq!(
+                found,
+                vec![
+                    (Position::new(1, 2), Position::new(1, 22), code("E001")),
+                    (Position::new(6, 0), Position::new(6, 26), code("E020")),
+                ]
+            );
+            assert!(diagnostics[0].message.contains("missing.md"));
+            assert!(diagnostics[1].message.contains("'who'"));
+
+            server.close(&uri);
+            assert!(server.diagnostics(&uri).is_empty());
+        }
+
+        #[test]
+        fn test_language_server_diagnoses_edited_nested_partials_again() {
+            let (temp_dir, mut server, uri) = project();
+            let partials = temp_dir.path().join("partials");
+            fs::write(partials.join("outer.md"), "!include(shared/footer.md)")
+                .expect("Failed to write outer.md");
+            server.open(uri.clone(), "!include(outer.md)".to_string());
+            assert_eq!(server.diagnostics(&uri).len(), 1);
+
+            fs::write(partials.join("shared/footer.md"), "Bye").expect("Failed to fix footer");
+            assert!(server.diagnostics(&uri).is_empty());
+        }
+
+        #[test]
+        fn test_language_server_goes_to_hovers_and_completes() {
+            let (temp_dir, mut server, uri) = project();
+            server.open(
+                uri.clone(),
+                "!include(header.md)\n!codesnippet(code.rs, start=2)\n!include(\"sh\n!codesnippet(code.rs, start=0)"
+                    .to_string(),
+            );
+
+            let header = server
+                .definition(&uri, Position::new(0, 12))
+                .expect("Missing definition of the include");
+            assert_eq!(
+                header.uri.to_file_path().ok(),
+                Some(temp_dir.path().join("partials/header.md"))
+            );
+            let code = server
+                .definition(&uri, Position::new(1, 3))
+                .expect("Missing definition of the snippet");
+            assert_eq!(code.range.start, Position::new(1, 0));
+            // There is no line 0 to go to
+            assert!(server.definition(&uri, Position::new(3, 3)).is_none());
+            assert!(server.definition(&uri, Position::new(2, 0)).is_none());
+
+            let hover = server
+                .hover(&uri, Position::new(0, 0))
+                .expect("Missing hover");
+            let HoverContents::Markup(markup) = hover.contents else {
+                panic!("Hover should be Markdown");
+            };
+            assert!(markup.value.starts_with("# md2md\n\n---\n\nFrom `"));
+            assert!(markup.value.contains("header.md`"));
+
+            let completions = server.completion(&uri, Position::new(2, 12));
+            let labels: Vec<_> = completions.iter().map(|c| c.label.as_str()).collect();
+            assert_eq!(labels, vec!["shared/footer.md"]);
+            let Some(CompletionTextEdit::Edit(edit)) = &completions[0].text_edit else {
+                panic!("Completion should replace the typed path");
+            };
+            assert_eq!(edit.range.start, Position::new(2, 10));
+            assert!(server.completion(&uri, Position::new(0, 5)).is_empty());
+        }
+
+        #[test]
+        fn test_language_server_publishes_diagnostics_and_answers_requests() {
+            let (_temp_dir, mut server, uri) = project();
+            let (connection, client) = Connection::memory();
+            let send = |message: Message| client.sender.send(message).expect("Failed to send");
+            send(
+                Notification::new(
+                    DidOpenTextDocument::METHOD.to_string(),
+                    serde_json::json!({"textDocument": {
+                        "uri": uri, "languageId": "markdown", "version": 1,
+                        "text": "!include(missing.md)",
+                    }}),
+                )
+                .into(),
+            );
+            send(
+                Request::new(
+                    1.into(),
+                    HoverRequest::METHOD.to_string(),
+                    serde_json::json!({"textDocument": {"uri": uri}, "position": {"line": 0, "character": 2}}),
+                )
+                .into(),
+            );
+            send(Request::new(2.into(), "md2md/unknown".to_string(), serde_json::json!({})).into());
+            send(Request::new(3.into(), "shutdown".to_string(), serde_json::Value::Null).into());
+            send(Notification::new("exit".to_string(), serde_json::Value::Null).into());
+
+            server.serve(&connection).expect("Failed to serve");
+            let messages: Vec<Message> = client.receiver.try_iter().collect();
+            assert_eq!(messages.len(), 4);
+            let Message::Notification(published) = &messages[0] else {
+                panic!("Diagnostics should be published first");
+            };
+            let published: PublishDiagnosticsParams =
+                serde_json::from_value(published.params.clone()).expect("Invalid diagnostics");
+            assert_eq!(published.uri, uri);
+            assert_eq!(published.diagnostics.len(), 1);
+            let Message::Response(hover) = &messages[1] else {
+                panic!("Hover should be answered");
+            };
+            assert!(
+                hover
+                    .result
+                    .as_ref()
+                    .is_some_and(|result| !result.is_null())
+            );
+            let Message::Response(unknown) = &messages[2] else {
+                panic!("Unknown request should be answered");
+            };
+            assert!(unknown.error.is_some());
+        }
+    }
+}
@@ -1,14 +1,353 @@
-use clap::{Parser, crate_version};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum, crate_version};
 use md2md::{
-    app::App,
-    cli_messages,
+    action::Action,
+    app::{ActiveTab, App, WatchState},
+    archive::{ArchiveFormat, ArchiveSink},
+    cli_messages::{self, Verbosity},
+    config::{self, Config},
+    daemon::{Daemon, DaemonOptions},
     event::EventHandler,
+    graph::{DependencyGraph, normalize_path},
+    history::{self, RunRecord},
+    lock::OutputLock,
+    processor::{CheckOptions, calculate_output_path},
+    report::{self, ReportFormat, parse_report_target},
+    scaffold,
+    schema::{self, SchemaKind},
+    spellcheck::SpellcheckConfig,
+    theme::Theme,
     tui::Tui,
-    types::{ProcessingConfig, ProcessingSummary},
+    types::{
+        CancellationToken, Compat, DuplicateHeadings, Emit, FileOrder, Frontmatter, HtmlOptions,
+        InputEncoding, LineEndings, Normalization, OutputBudget, OutputNaming, ProcessingConfig,
+        ProcessingEvent, ProcessingSummary, ResolutionOrder, RetryPolicy, SourceComments,
+        TemplateSyntax,
+    },
+    usage::partial_usage,
+    validation::{
+        ConfigError, check_directory_output, check_file_output, check_inputs, check_output,
+        check_settings, names_directory,
+    },
+    watch::{POLL_INTERVAL, Watcher},
 };
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
 use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, mpsc};
+use std::time::{Duration, Instant};
+use tracing::level_filters::LevelFilter;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ListFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SchemaType {
+    /// Settings of a run, as the library's `ProcessingConfig`
+    Config,
+    /// Outcome of a run, as the library's `ProcessingSummary`
+    Summary,
+    /// JSON reports written with `--report`
+    Report,
+    /// Source maps written with `--source-map`
+    SourceMap,
+}
+
+impl From<SchemaType> for SchemaKind {
+    fn from(schema: SchemaType) -> Self {
+        match schema {
+            SchemaType::Config => SchemaKind::Config,
+            SchemaType::Summary => SchemaKind::Summary,
+            SchemaType::Report => SchemaKind::Report,
+            SchemaType::SourceMap => SchemaKind::SourceMap,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Order {
+    Name,
+    Mtime,
+}
+
+impl From<Order> for FileOrder {
+    fn from(order: Order) -> Self {
+        match order {
+            Order::Name => FileOrder::Name,
+            Order::Mtime => FileOrder::Mtime,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ThemeName {
+    Default,
+    Light,
+    HighContrast,
+    Monochrome,
+}
+
+impl From<ThemeName> for Theme {
+    fn from(name: ThemeName) -> Self {
+        match name {
+            ThemeName::Default => Theme::default(),
+            ThemeName::Light => Theme::light(),
+            ThemeName::HighContrast => Theme::high_contrast(),
+            ThemeName::Monochrome => Theme::monochrome(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum CompatMode {
+    Md2md,
+    Markedpp,
+    Mkdocs,
+}
+
+impl From<CompatMode> for Compat {
+    fn from(mode: CompatMode) -> Self {
+        match mode {
+            CompatMode::Md2md => Compat::Md2md,
+            CompatMode::Markedpp => Compat::Markedpp,
+            CompatMode::Mkdocs => Compat::Mkdocs,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Passthrough {
+    Jinja,
+    Liquid,
+}
+
+impl From<Passthrough> for TemplateSyntax {
+    fn from(passthrough: Passthrough) -> Self {
+        match passthrough {
+            Passthrough::Jinja => TemplateSyntax::Jinja,
+            Passthrough::Liquid => TemplateSyntax::Liquid,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum EmitFormat {
+    Md,
+    Html,
+    Both,
+}
+
+impl From<EmitFormat> for Emit {
+    fn from(format: EmitFormat) -> Self {
+        match format {
+            EmitFormat::Md => Emit::Markdown,
+            EmitFormat::Html => Emit::Html,
+            EmitFormat::Both => Emit::Both,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum DuplicateHeadingsMode {
+    Warn,
+    Dedupe,
+}
+
+impl From<DuplicateHeadingsMode> for DuplicateHeadings {
+    fn from(mode: DuplicateHeadingsMode) -> Self {
+        match mode {
+            DuplicateHeadingsMode::Warn => DuplicateHeadings::Warn,
+            DuplicateHeadingsMode::Dedupe => DuplicateHeadings::Dedupe,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum LineEndingsMode {
+    Lf,
+    Crlf,
+    Preserve,
+}
+
+impl From<LineEndingsMode> for LineEndings {
+    fn from(mode: LineEndingsMode) -> Self {
+        match mode {
+            LineEndingsMode::Lf => LineEndings::Lf,
+            LineEndingsMode::Crlf => LineEndings::Crlf,
+            LineEndingsMode::Preserve => LineEndings::Preserve,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FrontmatterMode {
+    Keep,
+    Strip,
+    Merge,
+}
+
+impl From<FrontmatterMode> for Frontmatter {
+    fn from(mode: FrontmatterMode) -> Self {
+        match mode {
+            FrontmatterMode::Keep => Frontmatter::Keep,
+            FrontmatterMode::Strip => Frontmatter::Strip,
+            FrontmatterMode::Merge => Frontmatter::Merge,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ResolutionOrderMode {
+    Partials,
+    PartialsThenCurrent,
+    CurrentThenPartials,
+}
+
+impl From<ResolutionOrderMode> for ResolutionOrder {
+    fn from(mode: ResolutionOrderMode) -> Self {
+        match mode {
+            ResolutionOrderMode::Partials => ResolutionOrder::Partials,
+            ResolutionOrderMode::PartialsThenCurrent => ResolutionOrder::PartialsThenCurrent,
+            ResolutionOrderMode::CurrentThenPartials => ResolutionOrder::CurrentThenPartials,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum InputEncodingMode {
+    #[value(name = "utf-8", alias = "utf8")]
+    Utf8,
+    Latin1,
+    #[value(name = "utf-16", alias = "utf16")]
+    Utf16,
+}
+
+impl From<InputEncodingMode> for InputEncoding {
+    fn from(mode: InputEncodingMode) -> Self {
+        match mode {
+            InputEncodingMode::Utf8 => InputEncoding::Utf8,
+            InputEncodingMode::Latin1 => InputEncoding::Latin1,
+            InputEncodingMode::Utf16 => InputEncoding::Utf16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+enum NormalizeRule {
+    TrailingSpaces,
+    FinalNewline,
+    BlankLines,
+    All,
+}
+
+/// `NAME=VALUE` of `--var`
+fn parse_variable(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((name, value)) if !name.trim().is_empty() => {
+            Ok((name.trim().to_string(), value.to_string()))
+        }
+        _ => Err("expected NAME=VALUE, e.g. target=cloud".to_string()),
+    }
+}
+
+/// Bytes of a `--max-output-size`: a number with an optional B, KB, MB, GB, KiB, MiB or GiB unit
+fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1_000,
+        "mb" => 1_000_000,
+        "gb" => 1_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        _ => return Err("expected a size like 500KB, 2MiB or 1048576".to_string()),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| "expected a size like 500KB, 2MiB or 1048576".to_string())
+}
+
+/// Milliseconds of a duration like `500ms`, `2s` or `1m`; a bare number is milliseconds
+fn parse_duration(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        _ => return Err("expected a duration like 500ms, 2s or 1m".to_string()),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(|| "expected a duration like 500ms, 2s or 1m".to_string())
+}
+
+fn normalization(rules: &[NormalizeRule]) -> Normalization {
+    let enabled = |rule| rules.contains(&rule) || rules.contains(&NormalizeRule::All);
+    Normalization {
+        trailing_spaces: enabled(NormalizeRule::TrailingSpaces),
+        final_newline: enabled(NormalizeRule::FinalNewline),
+        blank_lines: enabled(NormalizeRule::BlankLines),
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum SourceCommentsMode {
+    Insert,
+    Strip,
+}
+
+impl From<SourceCommentsMode> for SourceComments {
+    fn from(mode: SourceCommentsMode) -> Self {
+        match mode {
+            SourceCommentsMode::Insert => SourceComments::Insert,
+            SourceCommentsMode::Strip => SourceComments::Strip,
+        }
+    }
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
 
 #[derive(Parser)]
 #[clap(name = "app_name", version = crate_version!())]
@@ -21,9 +360,9 @@ INCLUDE SYNTAX:
   !include (path/to/file.md)
 
 PATH RESOLUTION:
-  - Paths starting with '../' are resolved relative to the current file
+  - Paths starting with './' or '../' are resolved relative to the current file
   - Absolute paths starting with '/' are used as-is  
-  - Other paths are resolved relative to the partials directory
+  - Other paths are resolved relative to the partials directory (see --resolution-order)
 
 INPUT/OUTPUT VALIDATION:
   - File input requires file output (e.g., input.md → output.md)
@@ -40,30 +379,75 @@ EXAMPLES:
   # Fix code fences without language definitions
   md2md input.md -p partials -o output.md --fix-code-fences=rust
 
-  # Verbose output
-  md2md src-dir -p partials --batch --verbose
+  # Publish every output as <name>.generated.md in one flat directory
+  md2md src-dir -p partials -o output-dir --rename \"{stem}.generated.md\" --flatten
+
+  # Detailed summary; -vv also logs every include resolution
+  md2md src-dir -p partials --batch -v
+
+  # One-line summary, e.g. in CI
+  md2md src-dir -p partials --ci --quiet
+
+  # Validate sources without writing output (e.g. as a pre-commit hook)
+  md2md check src-dir -p partials
+
+  # Visualize which partials each document depends on
+  md2md graph src-dir -p partials --format mermaid
+
+  # Find partials that are never included
+  md2md list-orphans src-dir -p partials
+
+  # Outputs to rebuild after a partial changed (e.g. from `git diff --name-only`)
+  md2md affected partials/header.md -s src-dir -p partials --outputs -o out
+
+  # Scaffold a new project, then build it using the paths from md2md.toml
+  md2md init my-docs && cd my-docs && md2md --ci --force
+
+  # Debug logs written to a file
+  md2md src-dir -p partials --batch --log-level debug --log-file md2md.log
+
+  # Annotate failures in a GitHub Actions run
+  md2md src-dir -p partials -o output-dir --ci --github-annotations
 "
 )]
+#[command(args_conflicts_with_subcommands = true)]
 struct Cli {
-    /// The source file or directory to be processed
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The source file or directory to be processed. Default: `source` from md2md.toml
     #[arg()]
-    input_path: String,
+    input_path: Option<String>,
 
     /// The directory containing the partials. Default: `partials`
-    #[arg(short = 'p', long = "partials-path", default_value = "partials")]
-    partials: String,
+    #[arg(short = 'p', long = "partials-path")]
+    partials: Option<String>,
+
+    /// Output path (file or directory). Default: `out`
+    #[arg(short = 'o', long = "output-path")]
+    output: Option<String>,
 
-    /// Output path (file or directory)
-    #[arg(short = 'o', long = "output-path", default_value = "out")]
-    output: String,
+    /// Write the outputs into this .tar, .tar.gz, .tgz or .zip file instead, under their paths in the output path
+    #[arg(long = "output-archive", value_name = "PATH")]
+    output_archive: Option<PathBuf>,
 
     /// Process directories recursively (batch mode)
     #[arg(short = 'b', long = "batch", action)]
     batch: bool,
 
-    /// Verbose output
-    #[arg(short = 'v', long = "verbose", action)]
-    verbose: bool,
+    /// More output: -v lists every file and include, -vv also logs include resolution
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Only print a one-line summary and errors
+    #[arg(
+        short = 'q',
+        long = "quiet",
+        action,
+        conflicts_with = "verbose",
+        global = true
+    )]
+    quiet: bool,
 
     /// Disable TUI interface (use simple console output)
     #[arg(short = 'c', long = "ci", action)]
@@ -73,6 +457,14 @@ struct Cli {
     #[arg(short = 'f', long = "force", action)]
     force: bool,
 
+    /// Allow the output path to be inside, or to contain, the source or partials path
+    #[arg(long = "allow-overlap", action)]
+    allow_overlap: bool,
+
+    /// Don't lock the output directory against other runs writing to it
+    #[arg(long = "no-lock", action)]
+    no_lock: bool,
+
     /// Fix code fences that don't specify a language by adding a default language
     #[arg(
         long = "fix-code-fences",
@@ -80,28 +472,562 @@ struct Cli {
         default_value = "text"
     )]
     fix_code_fences: Option<String>,
+
+    /// Directive dialect of the sources: `markedpp` resolves includes relative to the including file and adds !toc and !numberedheadings, `mkdocs` adds --8<-- snippets
+    #[arg(long = "compat", value_enum, default_value = "md2md")]
+    compat: CompatMode,
+
+    /// Keep template tags of this engine that aren't md2md variables, e.g. for Hugo or Jekyll sources
+    #[arg(long = "passthrough", value_enum, value_name = "ENGINE")]
+    passthrough: Option<Passthrough>,
+
+    /// Output file name template in batch mode: `{stem}`, `{ext}` and `{name}` come from the source file
+    #[arg(long = "rename", value_name = "TEMPLATE")]
+    rename: Option<String>,
+
+    /// Write all batch outputs directly into the output directory, failing on name collisions
+    #[arg(long = "flatten", action)]
+    flatten: bool,
+
+    /// Output format: `html` renders the resolved Markdown to an `.html` file, `both` writes it next to the `.md`
+    #[arg(long = "emit", value_enum, default_value = "md")]
+    emit: EmitFormat,
+
+    /// HTML page template with `{{ title }}`, `{{ css }}` and `{{ content }}` placeholders
+    #[arg(long = "html-template", value_name = "PATH")]
+    html_template: Option<PathBuf>,
+
+    /// Stylesheet URL linked from every HTML page
+    #[arg(long = "css", value_name = "URL")]
+    css: Option<String>,
+
+    /// Rewrite links to files included into the same output (`./setup.md#install`) as in-document anchors
+    #[arg(long = "bundle-links", action)]
+    bundle_links: bool,
+
+    /// Validate links and heading anchors in the written outputs, failing files with broken links
+    #[arg(long = "check-links", action)]
+    check_links: bool,
+
+    /// Repeated heading anchors, e.g. from a partial included twice: `warn` logs them, `dedupe` adds a unique `{#anchor-1}` id
+    #[arg(long = "duplicate-headings", value_enum, value_name = "MODE")]
+    duplicate_headings: Option<DuplicateHeadingsMode>,
+
+    /// Line breaks of the written files; `preserve` keeps whatever the source and partials use
+    #[arg(long = "line-endings", value_enum, default_value = "preserve")]
+    line_endings: LineEndingsMode,
+
+    /// Whitespace clean-up of the written Markdown, outside code fences (comma-separated rules)
+    #[arg(
+        long = "normalize",
+        value_enum,
+        value_name = "RULES",
+        value_delimiter = ','
+    )]
+    normalize: Vec<NormalizeRule>,
+
+    /// Wrap included partials in `<!-- md2md: begin/end PATH -->` comments; `strip` removes such comments instead
+    #[arg(
+        long = "source-comments",
+        value_enum,
+        value_name = "MODE",
+        num_args = 0..=1,
+        default_missing_value = "insert"
+    )]
+    source_comments: Option<SourceCommentsMode>,
+
+    /// Fail files with a failed include or code snippet, or a `<!-- Failed to include: ... -->` comment, without writing them
+    #[arg(long = "strict")]
+    strict: bool,
+
+    /// Written in place of a failed include or code snippet instead of an HTML comment, e.g. "> Missing content: {path}"; {error} and {code} are filled in too, and "" writes nothing
+    #[arg(long = "on-error-template", value_name = "TEMPLATE")]
+    on_error_template: Option<String>,
+
+    /// Write OUTPUT.map.json next to every Markdown output, mapping its lines to the source and partial lines they come from
+    #[arg(long = "source-map")]
+    source_map: bool,
+
+    /// Line put at the top of every output, e.g. "<!-- GENERATED from {source} by md2md {version} at {time} -->"
+    #[arg(long = "banner", value_name = "TEMPLATE")]
+    banner: Option<String>,
+
+    /// What becomes of each document's frontmatter; `merge` adds generated, source and generator keys
+    #[arg(
+        long = "frontmatter",
+        value_name = "MODE",
+        value_enum,
+        default_value = "keep"
+    )]
+    frontmatter: FrontmatterMode,
+
+    /// Variable available to every partial and `if` condition, as NAME=VALUE. Repeatable
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_variable)]
+    vars: Vec<(String, String)>,
+
+    /// Print a unified diff against the existing outputs instead of writing them
+    #[arg(long = "diff")]
+    diff: bool,
+
+    /// Encoding of the source documents and partials
+    #[arg(
+        long = "input-encoding",
+        value_enum,
+        value_name = "ENCODING",
+        default_value = "utf-8"
+    )]
+    input_encoding: InputEncodingMode,
+
+    /// Fail includes and code snippets outside the source and partials directories, e.g. for untrusted sources
+    #[arg(long = "restrict-includes")]
+    restrict_includes: bool,
+
+    /// Where include paths not starting with `./`, `../` or `/` are looked up
+    #[arg(
+        long = "resolution-order",
+        value_enum,
+        value_name = "ORDER",
+        default_value = "partials"
+    )]
+    resolution_order: ResolutionOrderMode,
+
+    /// Prefer translations of partials in this locale, e.g. `header.de.md` for `de`
+    #[arg(long = "locale", value_name = "LOCALE")]
+    locale: Option<String>,
+
+    /// Spellcheck every resolved document with COMMAND, which reads it on stdin and prints misspelled words, e.g. `hunspell -l`
+    #[arg(long = "spellcheck", value_name = "COMMAND")]
+    spellcheck: Option<String>,
+
+    /// Link the first occurrence of each term of the glossary FILE in every output to its entry
+    #[arg(long = "glossary", value_name = "FILE")]
+    glossary: Option<PathBuf>,
+
+    /// Read an include up to N more times when reading it fails transiently, e.g. on a flaky network filesystem
+    #[arg(long = "retries", value_name = "N", default_value_t = 0)]
+    retries: u32,
+
+    /// Wait before the first retry of an include, e.g. 500ms or 2s; it doubles before each further one
+    #[arg(long = "retry-delay", value_name = "DURATION", default_value = "500ms", value_parser = parse_duration)]
+    retry_delay: u64,
+
+    /// Only process the documents affected by files changed since REF in git, committed or not, e.g. origin/main
+    #[arg(long = "changed-since", value_name = "REF")]
+    changed_since: Option<String>,
+
+    /// Fail any output larger than SIZE instead of writing it, e.g. 500KB or 2MiB
+    #[arg(long = "max-output-size", value_name = "SIZE", value_parser = parse_size)]
+    max_output_size: Option<u64>,
+
+    /// Fail outputs once all of them together would be larger than SIZE, e.g. 20MB
+    #[arg(long = "max-total-output-size", value_name = "SIZE", value_parser = parse_size)]
+    max_total_output_size: Option<u64>,
+
+    /// Keep running after the first pass and process files again when they or their partials change
+    #[arg(long = "watch")]
+    watch: bool,
+
+    /// Colors of the TUI; defaults to `monochrome` when NO_COLOR is set
+    #[arg(long = "theme", value_enum, value_name = "THEME")]
+    theme: Option<ThemeName>,
+
+    /// Name the results of TUI runs are kept under in .md2md/runs, for the next TUI run to compare against; defaults to one per source, partials and output paths
+    #[arg(long = "profile", value_name = "NAME")]
+    profile: Option<String>,
+
+    /// Command run on every output file after writing it; `{}` is replaced with its path
+    #[arg(long = "post-process", value_name = "COMMAND")]
+    post_process: Option<String>,
+
+    /// Order in which the files of a source directory are processed
+    #[arg(long = "order", value_enum, default_value = "name")]
+    order: Order,
+
+    /// Emit GitHub Actions error annotations for failures (auto-enabled when GITHUB_ACTIONS is set)
+    #[arg(long = "github-annotations", action)]
+    github_annotations: bool,
+
+    /// Also write a report of the run, as FORMAT=PATH (json, markdown or html) or a PATH
+    /// whose extension picks the format. Repeatable
+    #[arg(long = "report", value_name = "FORMAT=PATH", value_parser = parse_report_target)]
+    report: Vec<(ReportFormat, PathBuf)>,
+
+    /// Log level for diagnostic output. Default: `error` with -q, `warn`, `info` with -v, `debug` with -vv
+    #[arg(long = "log-level", value_enum, value_name = "LEVEL", global = true)]
+    log_level: Option<LogLevel>,
+
+    /// Write logs to this file instead of the console (the TUI only logs to a file)
+    #[arg(long = "log-file", value_name = "PATH", global = true)]
+    log_file: Option<PathBuf>,
+
+    /// Project configuration file. Default: `md2md.toml` in the working directory, if present
+    #[arg(long = "config", value_name = "PATH", global = true)]
+    config: Option<PathBuf>,
+}
+
+/// How `md2md daemon`, `md2md lsp` and `md2md usage` resolve documents
+#[derive(Args)]
+struct ResolutionArgs {
+    /// The directory containing the partials. Default: `partials`
+    #[arg(short = 'p', long = "partials-path")]
+    partials: Option<String>,
+
+    /// Language assumed for code fences that don't specify one
+    #[arg(
+        long = "fix-code-fences",
+        value_name = "LANGUAGE",
+        default_value = "text"
+    )]
+    fix_code_fences: Option<String>,
+
+    /// Directive dialect of the sources
+    #[arg(long = "compat", value_enum, default_value = "md2md")]
+    compat: CompatMode,
+
+    /// Keep template tags of this engine that aren't md2md variables
+    #[arg(long = "passthrough", value_enum, value_name = "ENGINE")]
+    passthrough: Option<Passthrough>,
+
+    /// Encoding of the source documents and partials
+    #[arg(
+        long = "input-encoding",
+        value_enum,
+        value_name = "ENCODING",
+        default_value = "utf-8"
+    )]
+    input_encoding: InputEncodingMode,
+
+    /// Where include paths without `./`, `../` or a root are looked up
+    #[arg(
+        long = "resolution-order",
+        value_enum,
+        value_name = "ORDER",
+        default_value = "partials"
+    )]
+    resolution_order: ResolutionOrderMode,
+
+    /// Prefer translations of partials in this locale, e.g. `header.de.md` for `de`
+    #[arg(long = "locale", value_name = "LOCALE")]
+    locale: Option<String>,
+
+    /// Variable available to every partial and `if` condition, as NAME=VALUE. Repeatable
+    #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_variable)]
+    vars: Vec<(String, String)>,
+}
+
+impl ResolutionArgs {
+    fn into_options(self, project: &Config) -> DaemonOptions {
+        DaemonOptions {
+            partials_path: self
+                .partials
+                .map(PathBuf::from)
+                .or(project.partials.clone())
+                .unwrap_or_else(|| PathBuf::from("partials")),
+            variables: project
+                .variables
+                .clone()
+                .into_iter()
+                .chain(self.vars)
+                .collect(),
+            fix_code_fences: self.fix_code_fences,
+            compat: self.compat.into(),
+            passthrough: self.passthrough.map(TemplateSyntax::from),
+            input_encoding: self.input_encoding.into(),
+            resolution_order: self.resolution_order.into(),
+            locale: self.locale,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Resolve and validate every directive without writing any output
+    Check {
+        /// The source file or directory to be checked. Default: `source` from md2md.toml
+        input_path: Option<String>,
+
+        /// The directory containing the partials. Default: `partials`
+        #[arg(short = 'p', long = "partials-path")]
+        partials: Option<String>,
+
+        /// Language assumed for code fences that don't specify one
+        #[arg(
+            long = "fix-code-fences",
+            value_name = "LANGUAGE",
+            default_value = "text"
+        )]
+        fix_code_fences: Option<String>,
+
+        /// Directive dialect of the sources
+        #[arg(long = "compat", value_enum, default_value = "md2md")]
+        compat: CompatMode,
+
+        /// Keep template tags of this engine that aren't md2md variables
+        #[arg(long = "passthrough", value_enum, value_name = "ENGINE")]
+        passthrough: Option<Passthrough>,
+
+        /// Encoding of the source documents and partials
+        #[arg(
+            long = "input-encoding",
+            value_enum,
+            value_name = "ENCODING",
+            default_value = "utf-8"
+        )]
+        input_encoding: InputEncodingMode,
+
+        /// Fail includes and code snippets outside the source and partials directories
+        #[arg(long = "restrict-includes")]
+        restrict_includes: bool,
+
+        /// Where include paths without `./`, `../` or a root are looked up
+        #[arg(
+            long = "resolution-order",
+            value_enum,
+            value_name = "ORDER",
+            default_value = "partials"
+        )]
+        resolution_order: ResolutionOrderMode,
+
+        /// Prefer translations of partials in this locale, e.g. `header.de.md` for `de`
+        #[arg(long = "locale", value_name = "LOCALE")]
+        locale: Option<String>,
+
+        /// Variable available to every partial and `if` condition, as NAME=VALUE. Repeatable
+        #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_variable)]
+        vars: Vec<(String, String)>,
+    },
+
+    /// Print the file → partial dependency graph
+    Graph {
+        /// The source file or directory to be analyzed. Default: `source` from md2md.toml
+        input_path: Option<String>,
+
+        /// The directory containing the partials. Default: `partials`
+        #[arg(short = 'p', long = "partials-path")]
+        partials: Option<String>,
+
+        /// Output format of the graph
+        #[arg(long = "format", value_enum, default_value = "dot")]
+        format: GraphFormat,
+
+        /// Encoding of the source documents and partials
+        #[arg(
+            long = "input-encoding",
+            value_enum,
+            value_name = "ENCODING",
+            default_value = "utf-8"
+        )]
+        input_encoding: InputEncodingMode,
+    },
+
+    /// List partials that no source document includes
+    ListOrphans {
+        /// The source file or directory whose includes are scanned. Default: `source` from md2md.toml
+        input_path: Option<String>,
+
+        /// The directory containing the partials. Default: `partials`
+        #[arg(short = 'p', long = "partials-path")]
+        partials: Option<String>,
+
+        /// Output format of the list
+        #[arg(long = "format", value_enum, default_value = "text")]
+        format: ListFormat,
+
+        /// Encoding of the source documents and partials
+        #[arg(
+            long = "input-encoding",
+            value_enum,
+            value_name = "ENCODING",
+            default_value = "utf-8"
+        )]
+        input_encoding: InputEncodingMode,
+    },
+
+    /// List the source documents that depend on the given partials or snippets
+    Affected {
+        /// Changed partials or code snippets
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// The source file or directory whose includes are scanned. Default: `source` from md2md.toml
+        #[arg(short = 's', long = "source-path")]
+        input_path: Option<String>,
+
+        /// The directory containing the partials. Default: `partials`
+        #[arg(short = 'p', long = "partials-path")]
+        partials: Option<String>,
+
+        /// Print the output files that would be regenerated instead of the sources
+        #[arg(long = "outputs", action)]
+        outputs: bool,
+
+        /// Output path used to map sources to outputs. Default: `out`
+        #[arg(short = 'o', long = "output-path")]
+        output: Option<String>,
+
+        /// Output file name template used to map sources to outputs
+        #[arg(long = "rename", value_name = "TEMPLATE")]
+        rename: Option<String>,
+
+        /// Map sources to outputs directly inside the output directory
+        #[arg(long = "flatten", action)]
+        flatten: bool,
+
+        /// Output format of the list
+        #[arg(long = "format", value_enum, default_value = "text")]
+        format: ListFormat,
+
+        /// Encoding of the source documents and partials
+        #[arg(
+            long = "input-encoding",
+            value_enum,
+            value_name = "ENCODING",
+            default_value = "utf-8"
+        )]
+        input_encoding: InputEncodingMode,
+    },
+
+    /// Count how often each partial is included, the bytes it contributes and the documents using it
+    Usage {
+        /// The source file or directory to be analyzed. Default: `source` from md2md.toml
+        input_path: Option<String>,
+
+        #[command(flatten)]
+        resolution: ResolutionArgs,
+
+        /// Fail includes and code snippets outside the source and partials directories
+        #[arg(long = "restrict-includes")]
+        restrict_includes: bool,
+
+        /// Output format of the statistics
+        #[arg(long = "format", value_enum, default_value = "text")]
+        format: ListFormat,
+    },
+
+    /// Print the JSON Schema of processing settings, run summaries, JSON reports or source maps
+    Schema {
+        /// Data the schema describes
+        #[arg(value_enum, default_value = "report")]
+        schema: SchemaType,
+    },
+
+    /// Resolve documents sent as JSON lines over a Unix socket, keeping partials cached between them
+    Daemon {
+        /// Path of the socket to listen at
+        #[arg(long = "socket", value_name = "PATH")]
+        socket: PathBuf,
+
+        #[command(flatten)]
+        resolution: ResolutionArgs,
+    },
+
+    /// Serve diagnostics, go-to-definition, completion and hover for md2md directives to editors over stdio
+    Lsp {
+        #[command(flatten)]
+        resolution: ResolutionArgs,
+    },
+
+    /// Build the generated files defined as [targets.NAME] in md2md.toml: those named, or every one
+    Build {
+        /// Names of the targets to build. Default: every target
+        targets: Vec<String>,
+
+        /// Variable available to every partial, overriding md2md.toml, as NAME=VALUE. Repeatable
+        #[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_variable)]
+        vars: Vec<(String, String)>,
+    },
+
+    /// Create a starter project: md2md.toml, a source and a partials directory with examples
+    Init {
+        /// Directory to create the project in
+        #[arg(default_value = ".")]
+        directory: String,
+
+        /// Overwrite files that already exist
+        #[arg(short = 'f', long = "force", action)]
+        force: bool,
+    },
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    let project = Config::discover(cli.config.as_deref())?;
+
+    let verbosity = Verbosity::from_flags(cli.quiet, cli.verbose);
+
+    if let Some(command) = cli.command {
+        return run_command(
+            command,
+            &project,
+            verbosity,
+            cli.log_level,
+            cli.log_file.as_deref(),
+        );
+    }
 
-    let source_path = Path::new(&cli.input_path);
-    let partials_path = Path::new(&cli.partials);
-    let output_path = Path::new(&cli.output);
+    let (source_path, partials_path) = resolve_paths(cli.input_path, cli.partials, &project);
+    let output_path = cli
+        .output
+        .map(PathBuf::from)
+        .or(project.output.clone())
+        .unwrap_or_else(|| PathBuf::from("out"));
+    let source_path = source_path.as_path();
+    let partials_path = partials_path.as_path();
+    let output_path = output_path.as_path();
 
     // Validate paths
-    if !source_path.exists() {
-        eprintln!("Error: Source path does not exist: {source_path:?}");
+    if let Err(e) = check_inputs(source_path, partials_path) {
+        eprintln!("Error: {e}");
         std::process::exit(1);
     }
+    if let Some(archive) = &cli.output_archive {
+        let conflict = if cli.diff {
+            Some("--diff")
+        } else if cli.watch {
+            Some("--watch")
+        } else {
+            None
+        };
+        if let Some(flag) = conflict {
+            eprintln!("Error: --output-archive can't be combined with {flag}");
+            std::process::exit(1);
+        }
+        if ArchiveFormat::from_path(archive).is_none() {
+            eprintln!(
+                "Error: Output archive must be a .tar, .tar.gz, .tgz or .zip file: {archive:?}"
+            );
+            std::process::exit(1);
+        }
+    }
+    // Nothing is written at the output path in diff mode or into an archive
+    let writes_output = !cli.diff && cli.output_archive.is_none();
 
-    if !partials_path.exists() {
-        eprintln!("Error: Partials path does not exist: {partials_path:?}");
-        std::process::exit(1);
+    // Writing into the inputs can overwrite sources or get picked up as a source next run
+    if writes_output && !cli.allow_overlap {
+        let output_file = if source_path.is_file() && output_path.is_dir() {
+            output_path.join(source_path.file_name().expect("Invalid source filename"))
+        } else {
+            output_path.to_path_buf()
+        };
+        if let Err(e) = check_output(source_path, partials_path, &output_file) {
+            let hint = match e {
+                ConfigError::OutputOverlap(_) => ". Pass --allow-overlap to write there anyway.",
+                _ => "",
+            };
+            eprintln!("Error: {e}{hint}");
+            std::process::exit(1);
+        }
     }
 
     // Validate input/output type matching: file input → file output, directory input → directory output
-    let final_output_path = if source_path.is_file() {
+    let final_output_path = if !writes_output {
+        // Nothing is written there, so there is nothing to create or confirm overwriting
+        if source_path.is_file() && output_path.is_dir() {
+            output_path.join(source_path.file_name().expect("Invalid source filename"))
+        } else {
+            output_path.to_path_buf()
+        }
+    } else if source_path.is_file() {
         // Input is a file, output must be a file path
         validate_file_output(output_path).expect("Failed to validate file output path");
         handle_file_output_logic(source_path, output_path, cli.ci, cli.force)
@@ -115,38 +1041,524 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         std::process::exit(1);
     };
 
+    // Held until the run, or watch session, ends
+    let _lock = if !writes_output || cli.no_lock {
+        None
+    } else {
+        let output_root = if source_path.is_file() {
+            final_output_path
+                .parent()
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .unwrap_or(Path::new("."))
+        } else {
+            final_output_path.as_path()
+        };
+        match OutputLock::acquire(output_root) {
+            Ok(lock) => Some(lock),
+            Err(e) => {
+                eprintln!("Error: {e}. Pass --no-lock to write anyway.");
+                std::process::exit(1);
+            }
+        }
+    };
+
     let config = ProcessingConfig {
         source_path: source_path.to_path_buf(),
         partials_path: partials_path.to_path_buf(),
         output_path: final_output_path,
         batch: cli.batch || source_path.is_dir(),
-        verbose: cli.verbose,
+        verbose: verbosity.is_verbose(),
         fix_code_fences: cli.fix_code_fences,
+        variables: project
+            .variables
+            .clone()
+            .into_iter()
+            .chain(cli.vars)
+            .collect(),
+        order: cli.order.into(),
+        post_process: cli.post_process.or(project.post_process.clone()),
+        naming: OutputNaming {
+            rename: cli.rename,
+            flatten: cli.flatten,
+        },
+        compat: cli.compat.into(),
+        passthrough: cli.passthrough.map(TemplateSyntax::from),
+        emit: cli.emit.into(),
+        html: HtmlOptions {
+            template: cli.html_template,
+            css: cli.css,
+        },
+        bundle_links: cli.bundle_links,
+        check_links: cli.check_links,
+        duplicate_headings: cli.duplicate_headings.map(DuplicateHeadings::from),
+        line_endings: cli.line_endings.into(),
+        normalization: normalization(&cli.normalize),
+        source_comments: cli.source_comments.map(SourceComments::from),
+        banner: cli.banner.or(project.banner.clone()),
+        diff: cli.diff,
+        input_encoding: cli.input_encoding.into(),
+        restrict_includes: cli.restrict_includes,
+        resolution_order: cli.resolution_order.into(),
+        frontmatter: cli.frontmatter.into(),
+        locale: cli.locale,
+        budget: OutputBudget {
+            per_file: cli.max_output_size,
+            total: cli.max_total_output_size,
+        },
+        lint: project.lint.clone(),
+        spellcheck: match cli.spellcheck {
+            Some(command) => Some(SpellcheckConfig {
+                command,
+                dictionaries: project
+                    .spellcheck
+                    .as_ref()
+                    .map(|spellcheck| spellcheck.dictionaries.clone())
+                    .unwrap_or_default(),
+            }),
+            None => project.spellcheck.clone(),
+        },
+        source_map: cli.source_map,
+        strict: cli.strict,
+        error_template: cli.on_error_template,
+        retry: RetryPolicy {
+            retries: cli.retries,
+            delay_ms: cli.retry_delay,
+        },
+        changed_since: cli.changed_since,
+        glossary: cli.glossary.or(project.glossary.clone()),
     };
+    if let Err(e) = check_settings(&config) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+
+    let github_annotations = cli.github_annotations
+        || std::env::var("GITHUB_ACTIONS").is_ok_and(|value| value == "true");
 
     let summary = Arc::new(Mutex::new(ProcessingSummary::new()));
 
-    // Use TUI interface unless disabled or when running in CI/non-interactive environments
-    if !cli.ci && (cli.verbose || atty::is(atty::Stream::Stdout)) {
-        run_tui_mode(config, summary).expect("Failed to run TUI mode");
+    // Use TUI interface unless disabled, quiet, or when running in CI/non-interactive environments
+    let tui_mode = !cli.ci
+        && !verbosity.is_quiet()
+        && (verbosity.is_verbose() || atty::is(atty::Stream::Stdout));
+
+    init_logging(
+        resolve_log_level(cli.log_level, verbosity),
+        cli.log_file.as_deref(),
+        tui_mode,
+    )?;
+
+    let record_path = history::record_path(
+        &cli.profile
+            .unwrap_or_else(|| history::default_profile(&config)),
+    );
+    let targets = RunTargets {
+        archive: cli.output_archive.as_deref(),
+        record_path: &record_path,
+        reports: &cli.report,
+    };
+    if tui_mode {
+        let theme = cli.theme.map_or_else(Theme::from_env, Theme::from);
+        run_tui_mode(config, summary, cli.watch, theme, &targets).expect("Failed to run TUI mode");
     } else {
         // Simple console mode for backwards compatibility
-        run_console_mode(config, summary).expect("Failed to run console mode");
+        run_console_mode(
+            config,
+            summary,
+            verbosity,
+            github_annotations,
+            cli.watch,
+            &targets,
+        )
+        .expect("Failed to run console mode");
     }
 
     Ok(())
 }
 
-/// Validates that the output path is suitable for file output (not a directory)
-fn validate_file_output(output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    // Check if output path looks like a directory (more permissive for files without extensions)
-    let path_str = output_path.as_os_str().to_str().unwrap_or("");
-    let is_directory = output_path.is_dir() || path_str.ends_with('/') || path_str.ends_with('\\');
+/// Runs a subcommand to completion
+fn run_command(
+    command: Command,
+    project: &Config,
+    verbosity: Verbosity,
+    log_level: Option<LogLevel>,
+    log_file: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    init_logging(resolve_log_level(log_level, verbosity), log_file, false)?;
+    match command {
+        Command::Check {
+            input_path,
+            partials,
+            fix_code_fences,
+            compat,
+            passthrough,
+            input_encoding,
+            restrict_includes,
+            resolution_order,
+            locale,
+            vars,
+        } => {
+            let (source_path, partials_path) = resolve_paths(input_path, partials, project);
+            let variables: HashMap<String, String> =
+                project.variables.clone().into_iter().chain(vars).collect();
+            run_check(
+                &source_path,
+                &partials_path,
+                &CheckOptions {
+                    variables: &variables,
+                    fix_code_fences: fix_code_fences.as_deref(),
+                    compat: compat.into(),
+                    passthrough: passthrough.map(TemplateSyntax::from),
+                    input_encoding: input_encoding.into(),
+                    restrict_includes,
+                    resolution_order: resolution_order.into(),
+                    locale: locale.as_deref(),
+                },
+                verbosity,
+            )
+        }
+        Command::Graph {
+            input_path,
+            partials,
+            format,
+            input_encoding,
+        } => {
+            let (source_path, partials_path) = resolve_paths(input_path, partials, project);
+            let graph =
+                DependencyGraph::build(&source_path, &partials_path, input_encoding.into())?;
+            match format {
+                GraphFormat::Dot => print!("{}", graph.to_dot()),
+                GraphFormat::Mermaid => print!("{}", graph.to_mermaid()),
+                GraphFormat::Json => println!("{}", graph.to_json()?),
+            }
+            Ok(())
+        }
+        Command::ListOrphans {
+            input_path,
+            partials,
+            format,
+            input_encoding,
+        } => {
+            let (source_path, partials_path) = resolve_paths(input_path, partials, project);
+            let graph =
+                DependencyGraph::build(&source_path, &partials_path, input_encoding.into())?;
+            let orphans = graph.orphaned_partials(&partials_path)?;
+            match format {
+                ListFormat::Text => {
+                    for orphan in &orphans {
+                        println!("{orphan}");
+                    }
+                }
+                ListFormat::Json => println!("{}", serde_json::to_string_pretty(&orphans)?),
+            }
+            Ok(())
+        }
+        Command::Affected {
+            files,
+            input_path,
+            partials,
+            outputs,
+            output,
+            rename,
+            flatten,
+            format,
+            input_encoding,
+        } => {
+            let (source_path, partials_path) = resolve_paths(input_path, partials, project);
+            let graph =
+                DependencyGraph::build(&source_path, &partials_path, input_encoding.into())?;
+
+            let mut affected: Vec<String> = files
+                .iter()
+                .flat_map(|file| graph.affected_sources(Path::new(file)))
+                .collect::<BTreeSet<String>>()
+                .into_iter()
+                .collect();
+
+            if outputs {
+                let output_path = output
+                    .map(PathBuf::from)
+                    .or(project.output.clone())
+                    .unwrap_or_else(|| PathBuf::from("out"));
+                let naming = OutputNaming { rename, flatten };
+                affected = affected
+                    .iter()
+                    .map(|source| {
+                        if source_path.is_file() {
+                            Ok(output_path.clone())
+                        } else {
+                            calculate_output_path(
+                                Path::new(source),
+                                &normalize_path(&source_path),
+                                &output_path,
+                                &naming,
+                            )
+                        }
+                    })
+                    .map(|path| path.map(|p| p.to_string_lossy().to_string()))
+                    .collect::<Result<_, _>>()?;
+            }
+
+            match format {
+                ListFormat::Text => {
+                    for file in &affected {
+                        println!("{file}");
+                    }
+                }
+                ListFormat::Json => println!("{}", serde_json::to_string_pretty(&affected)?),
+            }
+            Ok(())
+        }
+        Command::Usage {
+            input_path,
+            resolution,
+            restrict_includes,
+            format,
+        } => {
+            let (source_path, _) = resolve_paths(input_path, None, project);
+            let options = resolution.into_options(project);
+            let mut summary = ProcessingSummary::new();
+            md2md::processor::check_files(
+                &source_path,
+                &options.partials_path,
+                &CheckOptions {
+                    variables: &options.variables,
+                    fix_code_fences: options.fix_code_fences.as_deref(),
+                    compat: options.compat,
+                    passthrough: options.passthrough,
+                    input_encoding: options.input_encoding,
+                    restrict_includes,
+                    resolution_order: options.resolution_order,
+                    locale: options.locale.as_deref(),
+                },
+                &mut summary,
+            )?;
+            let usage = partial_usage(&summary);
+            match format {
+                ListFormat::Text => {
+                    for partial in &usage {
+                        let documents: Vec<&str> =
+                            partial.documents.iter().map(String::as_str).collect();
+                        println!(
+                            "{}: {} includes, {} bytes, used by {}",
+                            partial.path,
+                            partial.count,
+                            partial.bytes,
+                            documents.join(", ")
+                        );
+                    }
+                }
+                ListFormat::Json => println!("{}", serde_json::to_string_pretty(&usage)?),
+            }
+            Ok(())
+        }
+        Command::Schema { schema } => {
+            let schema = schema::schema(schema.into());
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            Ok(())
+        }
+        Command::Daemon { socket, resolution } => {
+            let daemon = Daemon::new(resolution.into_options(project));
+            run_daemon(&daemon, &socket, verbosity)
+        }
+        Command::Lsp { resolution } => md2md::lsp::serve_stdio(resolution.into_options(project)),
+        Command::Build { targets, vars } => run_build(project, &targets, &vars, verbosity),
+        Command::Init { directory, force } => {
+            match scaffold::scaffold_project(Path::new(&directory), force) {
+                Ok(created) => {
+                    for path in &created {
+                        println!("Created {}", path.display());
+                    }
+                    println!(
+                        "\nRun `md2md --ci --force` in {directory:?} to build the example project."
+                    );
+                    Ok(())
+                }
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    std::process::exit(1);
+                }
+            }
+        }
+    }
+}
 
-    if is_directory {
+/// Builds the targets `names` of the project, or every one, then prints the summary
+fn run_build(
+    project: &Config,
+    names: &[String],
+    vars: &[(String, String)],
+    verbosity: Verbosity,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let names: Vec<&String> = if names.is_empty() {
+        project.targets.keys().collect()
+    } else {
+        names.iter().collect()
+    };
+    if names.is_empty() {
         eprintln!(
-            "Error: Input is a file, but output path appears to be a directory: {output_path:?}"
+            "Error: No targets to build; add a [targets.NAME] table with a source and an output to {}",
+            config::CONFIG_FILE_NAME
         );
+        std::process::exit(1);
+    }
+
+    let mut summary = ProcessingSummary::new();
+    for name in names {
+        let processor = project
+            .target(name)
+            .map_err(Into::into)
+            .and_then(|builder| {
+                vars.iter()
+                    .fold(builder, |builder, (name, value)| builder.var(name, value))
+                    .build()
+            });
+        let built = match processor.and_then(|processor| processor.run()) {
+            Ok(built) => built,
+            Err(e) => {
+                eprintln!("Error: Target `{name}`: {e}");
+                std::process::exit(1);
+            }
+        };
+        if !verbosity.is_quiet() {
+            let status = if built.get_failed_count() == 0 {
+                "✓"
+            } else {
+                "✗"
+            };
+            println!(
+                "{status} {name} → {}",
+                project.targets[name].output.display()
+            );
+        }
+        summary.diffs.extend(built.diffs);
+        summary.warnings.extend(built.warnings);
+        for result in built.results {
+            summary.add_result(result);
+        }
+    }
+    cli_messages::print_run_summary(&summary, verbosity);
+    if summary.get_failed_count() > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Serves `daemon` at `socket` until a client asks for a shutdown, then removes the socket
+#[cfg(unix)]
+fn run_daemon(
+    daemon: &Daemon,
+    socket: &Path,
+    verbosity: Verbosity,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let listener = md2md::daemon::bind(socket)
+        .map_err(|e| format!("Failed to listen at {}: {e}", socket.display()))?;
+    if !verbosity.is_quiet() {
+        eprintln!("Listening at {}", socket.display());
+    }
+    let served = daemon.serve(&listener);
+    drop(listener);
+    let _ = std::fs::remove_file(socket);
+    Ok(served?)
+}
+
+#[cfg(not(unix))]
+fn run_daemon(
+    _daemon: &Daemon,
+    socket: &Path,
+    _verbosity: Verbosity,
+) -> Result<(), Box<dyn std::error::Error>> {
+    md2md::daemon::bind(socket)?;
+    Ok(())
+}
+
+/// Resolves the source and partials paths from the command line, then md2md.toml, then defaults
+fn resolve_paths(
+    input_path: Option<String>,
+    partials: Option<String>,
+    project: &Config,
+) -> (PathBuf, PathBuf) {
+    let Some(source_path) = input_path.map(PathBuf::from).or(project.source.clone()) else {
+        eprintln!(
+            "Error: No input path given and no `source` set in {}",
+            config::CONFIG_FILE_NAME
+        );
+        std::process::exit(1);
+    };
+    let partials_path = partials
+        .map(PathBuf::from)
+        .or(project.partials.clone())
+        .unwrap_or_else(|| PathBuf::from("partials"));
+
+    (source_path, partials_path)
+}
+
+fn resolve_log_level(log_level: Option<LogLevel>, verbosity: Verbosity) -> LevelFilter {
+    log_level.map(LevelFilter::from).unwrap_or(match verbosity {
+        Verbosity::Quiet => LevelFilter::ERROR,
+        Verbosity::Normal => LevelFilter::WARN,
+        Verbosity::Verbose => LevelFilter::INFO,
+        Verbosity::Debug => LevelFilter::DEBUG,
+    })
+}
+
+/// Validates sources without writing output, exiting non-zero on any problem
+fn run_check(
+    source_path: &Path,
+    partials_path: &Path,
+    check: &CheckOptions,
+    verbosity: Verbosity,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Err(e) = check_inputs(source_path, partials_path) {
+        eprintln!("Error: {e}");
+        std::process::exit(1);
+    }
+
+    let mut summary = ProcessingSummary::new();
+    md2md::processor::check_files(source_path, partials_path, check, &mut summary)
+        .expect("Failed to check files");
+
+    cli_messages::print_run_summary(&summary, verbosity);
+    if summary.get_failed_count() > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Installs the global tracing subscriber.
+///
+/// Logs go to `log_file` when given. Otherwise console mode logs to stderr and
+/// TUI mode discards them so they don't corrupt the interface.
+fn init_logging(
+    level: LevelFilter,
+    log_file: Option<&Path>,
+    tui_mode: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let builder = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_target(false);
+
+    if let Some(log_file) = log_file {
+        let file = File::create(log_file)
+            .map_err(|e| format!("Failed to create log file {log_file:?}: {e}"))?;
+        builder
+            .with_ansi(false)
+            .with_writer(Mutex::new(file))
+            .init();
+    } else if !tui_mode {
+        builder.with_writer(std::io::stderr).init();
+    }
+
+    Ok(())
+}
+
+/// Validates that the output path is suitable for file output (not a directory)
+fn validate_file_output(output_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    // Files without extensions are fine; only explicit directories are rejected
+    if let Err(e) = check_file_output(output_path) {
+        eprintln!("Error: {e}");
         eprintln!("       When processing a single file, output must be a file path.");
         eprintln!("       Example: input.md -> output.md (not input.md -> output-dir/)");
         std::process::exit(1);
@@ -161,18 +1573,8 @@ fn validate_directory_output(
     ci_mode: bool,
     force: bool,
 ) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    // Check if output path looks like a file (ends with path separator or has obvious file extension)
-    let path_str = output_path.as_os_str().to_str().unwrap_or("");
-    let looks_like_file = path_str.ends_with(".md")
-        || path_str.ends_with(".txt")
-        || path_str.ends_with(".html")
-        || path_str.ends_with(".pdf")
-        || (output_path.extension().is_some() && path_str.contains('.'));
-
-    if looks_like_file {
-        eprintln!(
-            "Error: Input is a directory, but output path appears to be a file: {output_path:?}"
-        );
+    if let Err(e) = check_directory_output(output_path) {
+        eprintln!("Error: {e}");
         eprintln!("       When processing a directory, output must be a directory path.");
         eprintln!("       Example: src-dir -> output-dir (not src-dir -> output.md)");
         std::process::exit(1);
@@ -219,11 +1621,7 @@ fn handle_file_output_logic(
     force: bool,
 ) -> Result<PathBuf, Box<dyn std::error::Error>> {
     // At this point, validation has already confirmed output_path is intended as a file
-    // Check if output_path is intended to be a directory (only explicit directory indicators)
-    let path_str = output_path.as_os_str().to_str().unwrap_or("");
-    let is_directory = output_path.is_dir() || path_str.ends_with('/') || path_str.ends_with('\\');
-
-    if is_directory {
+    if names_directory(output_path) {
         if output_path.exists() {
             // Output is an existing directory, use source filename
             let source_filename = source_path.file_name().expect("Invalid source filename");
@@ -339,36 +1737,144 @@ fn handle_file_output_logic(
     }
 }
 
+/// Opens `path` in `$EDITOR` (`vi` when unset) and waits for it to exit
+fn open_in_editor(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().ok_or("EDITOR is empty")?;
+    let status = std::process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| format!("Failed to run `{editor}`: {e}"))?;
+    if !status.success() {
+        return Err(format!("`{editor}` failed with {status}").into());
+    }
+    Ok(())
+}
+
+/// Where a run writes its outputs, record and reports
+struct RunTargets<'a> {
+    /// Writes the outputs into this archive instead of the output path
+    archive: Option<&'a Path>,
+    /// Where a TUI run keeps its results; console runs don't keep any
+    record_path: &'a Path,
+    reports: &'a [(ReportFormat, PathBuf)],
+}
+
+/// Runs `config`, writing the outputs into `archive` when there is one
+fn process(
+    config: &ProcessingConfig,
+    archive: Option<&Path>,
+    summary: &mut ProcessingSummary,
+    events: Option<&mpsc::Sender<ProcessingEvent>>,
+    cancellation: Option<&CancellationToken>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(archive) = archive else {
+        return md2md::processor::process_files(config, summary, events, cancellation);
+    };
+    // Entries are named like the files would be under the output path
+    let root = if config.batch {
+        config.output_path.as_path()
+    } else {
+        config.output_path.parent().unwrap_or(Path::new(""))
+    };
+    let sink = ArchiveSink::create(archive, root)?;
+    if let Err(e) = md2md::processor::process_files_to(config, &sink, summary, events, cancellation)
+    {
+        // A truncated archive would pass for a complete one
+        sink.discard();
+        return Err(e);
+    }
+    sink.finish()?;
+    Ok(())
+}
+
 fn run_tui_mode(
     config: ProcessingConfig,
     summary: Arc<Mutex<ProcessingSummary>>,
+    watch: bool,
+    theme: Theme,
+    targets: &RunTargets,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let RunTargets {
+        archive,
+        record_path,
+        reports,
+    } = *targets;
     // Initialize terminal
     let mut tui = Tui::new().expect("Failed to create TUI instance");
     tui.init().expect("Failed to initialize TUI");
 
     // Create application
     let mut app = App::new(config.clone(), summary.clone());
+    if watch {
+        app.watch = Some(WatchState::default());
+    }
+    app.archive = archive.map(Path::to_path_buf);
+    app.previous_run = RunRecord::load(record_path).unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "Failed to load the previous run's results");
+        None
+    });
 
-    // Start processing in background
-    let processing_summary = summary.clone();
+    // Start processing in background; progress reaches the app as events
+    let (event_sender, processing_events) = mpsc::channel();
+    let cancellation = CancellationToken::new();
     let processing_config = config.clone();
-    std::thread::spawn(move || {
-        let _ = md2md::processor::process_files(
+    let processing_cancellation = cancellation.clone();
+    let processing_archive = app.archive.clone();
+    let processing_thread = std::thread::spawn(move || {
+        // Changes made during the first run are picked up by the first poll
+        let mut watcher = watch
+            .then(|| Watcher::new(&processing_config))
+            .transpose()
+            .unwrap_or_else(|e| {
+                let _ = event_sender.send(ProcessingEvent::Warning(format!(
+                    "Failed to start watching: {e}"
+                )));
+                None
+            });
+        let mut processing_summary = ProcessingSummary::new();
+        if let Err(e) = process(
             &processing_config,
-            &mut processing_summary
-                .lock()
-                .expect("Failed to acquire processing summary lock in background thread"),
-            |_| {}, // No progress callback needed for TUI
-        );
+            processing_archive.as_deref(),
+            &mut processing_summary,
+            Some(&event_sender),
+            Some(&processing_cancellation),
+        ) {
+            tracing::error!(error = %e, "Processing stopped");
+            let _ = event_sender.send(ProcessingEvent::RunFinished {
+                processed: processing_summary.processed_files,
+                failed: processing_summary.get_failed_count(),
+                cancelled: false,
+            });
+        }
 
-        // Mark processing as complete (we'll check this via the completion logic in app)
-        // Note: We don't have mark_complete on ProcessingSummary, so we rely on the app's logic
+        if let Some(watcher) = &mut watcher {
+            while !processing_cancellation.is_cancelled() {
+                std::thread::sleep(POLL_INTERVAL);
+                if let Err(e) = watcher.poll(Some(&event_sender)) {
+                    let _ = event_sender.send(ProcessingEvent::Warning(format!(
+                        "Failed to check for changes: {e}"
+                    )));
+                }
+            }
+        }
     });
 
     // Start event handler
     let events = EventHandler::new(250);
 
+    // Processing events wake the loop like key presses, so every finished file is drawn at once
+    let action_sender = events.sender();
+    std::thread::spawn(move || {
+        for event in processing_events {
+            if action_sender.send(Action::Processing(event)).is_err() {
+                return;
+            }
+        }
+    });
+
     // Main event loop
     loop {
         // Draw UI
@@ -376,7 +1882,7 @@ fn run_tui_mode(
             use md2md::components;
             use ratatui::{
                 layout::{Constraint, Direction, Layout},
-                style::{Color, Style, Stylize},
+                style::Stylize,
                 widgets::{Block, Borders, Tabs},
             };
 
@@ -396,27 +1902,36 @@ fn run_tui_mode(
             // Create tabs widget
             let tabs = Tabs::new(tab_titles)
                 .block(Block::default().borders(Borders::ALL).title("md2md"))
-                .style(Style::default().fg(Color::White))
-                .highlight_style(Style::default().fg(Color::Yellow).bold())
+                .style(theme.text)
+                .highlight_style(theme.key)
                 .select(app.get_tab_index());
             f.render_widget(tabs, chunks[0]);
 
             // Render the active tab content
             match app.get_active_tab() {
                 md2md::app::ActiveTab::Progress => {
-                    components::render_progress(f, &app, chunks[1]);
+                    components::render_progress(f, &app, chunks[1], &theme);
                 }
                 md2md::app::ActiveTab::Files => {
-                    components::render_files(f, &app, chunks[1]);
+                    components::render_files(f, &app, chunks[1], &theme);
+                }
+                md2md::app::ActiveTab::Tree => {
+                    components::render_tree(f, &app, chunks[1], &theme);
                 }
                 md2md::app::ActiveTab::Analysis => {
-                    components::render_analysis(f, &app, chunks[1]);
+                    components::render_analysis(f, &app, chunks[1], &theme);
                 }
                 md2md::app::ActiveTab::Summary => {
-                    components::render_summary(f, &app, chunks[1]);
+                    components::render_summary(f, &app, chunks[1], &theme);
                 }
                 md2md::app::ActiveTab::ErrorSummary => {
-                    components::render_error_summary(f, &app, chunks[1]);
+                    components::render_error_summary(f, &app, chunks[1], &theme);
+                }
+                md2md::app::ActiveTab::Warnings => {
+                    components::render_warnings(f, &app, chunks[1], &theme);
+                }
+                md2md::app::ActiveTab::Watch => {
+                    components::render_watch(f, &app, chunks[1], &theme);
                 }
             }
 
@@ -426,29 +1941,57 @@ fn run_tui_mode(
                 text::{Line, Span},
                 widgets::{Clear, Paragraph},
             };
-            let help_text = vec![Line::from(vec![
-                Span::styled("Keys: ", Style::default().fg(Color::White).bold()),
-                Span::styled("q", Style::default().fg(Color::Yellow).bold()),
-                Span::raw(" Quit | "),
-                Span::styled("Tab", Style::default().fg(Color::Yellow).bold()),
-                Span::raw("/"),
-                Span::styled("←→", Style::default().fg(Color::Yellow).bold()),
-                Span::raw(" Switch tabs | "),
-                Span::styled("↑↓", Style::default().fg(Color::Yellow).bold()),
-                Span::raw("/"),
-                Span::styled("j", Style::default().fg(Color::Yellow).bold()),
-                Span::styled("k", Style::default().fg(Color::Yellow).bold()),
-                Span::raw(" Navigate | "),
-                Span::styled("1-5", Style::default().fg(Color::Yellow).bold()),
-                Span::raw(" Direct tab | "),
-                Span::styled("e", Style::default().fg(Color::Yellow).bold()),
-                Span::raw(" Toggle errors | "),
-                Span::styled("?", Style::default().fg(Color::Yellow).bold()),
-                Span::raw(" Help"),
-            ])];
+            // The save-report prompt and the outcome of the last action take the keys' place
+            let help_text = if let Some(path) = &app.report_path {
+                vec![Line::from(vec![
+                    Span::styled("Save report to: ", theme.text.bold()),
+                    Span::styled(format!("{path}_"), theme.warning),
+                    Span::raw("  Enter saves · Esc cancels · .md for Markdown, JSON otherwise"),
+                ])]
+            } else if let Some(message) = &app.message {
+                vec![Line::from(Span::styled(
+                    message.as_str(),
+                    theme.warning,
+                ))]
+            } else {
+            vec![Line::from(vec![
+                    Span::styled("Keys: ", theme.text.bold()),
+                    Span::styled("q", theme.key),
+                    Span::raw(" Quit | "),
+                    Span::styled("Tab", theme.key),
+                    Span::raw("/"),
+                    Span::styled("←→", theme.key),
+                    Span::raw(" Switch tabs | "),
+                    Span::styled("↑↓", theme.key),
+                    Span::raw("/"),
+                    Span::styled("j", theme.key),
+                    Span::styled("k", theme.key),
+                    Span::raw(" Navigate | "),
+                    Span::styled("PgUp", theme.key),
+                    Span::raw("/"),
+                    Span::styled("PgDn", theme.key),
+                    Span::raw(" Scroll | "),
+                    Span::styled("/", theme.key),
+                    Span::raw(" Filter | "),
+                    Span::styled("p", theme.key),
+                    Span::raw(" Preview | "),
+                    Span::styled("R", theme.key),
+                    Span::raw(" Retry | "),
+                    Span::styled("o", theme.key),
+                    Span::raw(" Edit | "),
+                    Span::styled("s", theme.key),
+                    Span::raw(" Save report | "),
+                    Span::styled("1-8", theme.key),
+                    Span::raw(" Direct tab | "),
+                    Span::styled("e", theme.key),
+                    Span::raw(" Toggle errors | "),
+                    Span::styled("?", theme.key),
+                    Span::raw(" Help"),
+                ])]
+            };
             let help_widget = Paragraph::new(help_text)
                 .block(Block::default().borders(Borders::ALL))
-                .style(Style::default().fg(Color::Gray));
+                .style(theme.muted);
             f.render_widget(help_widget, chunks[2]);
 
             // Show help dialog if help is visible
@@ -476,104 +2019,161 @@ fn run_tui_mode(
                 let detailed_help = vec![
                     Line::from(Span::styled(
                         "md2md - Markdown Processor with Include Directives",
-                        Style::default().fg(Color::Yellow).bold(),
+                        theme.key,
                     )),
                     Line::from(""),
                     Line::from(Span::styled(
                         "KEYBOARD SHORTCUTS:",
-                        Style::default().fg(Color::White).bold(),
+                        theme.text.bold(),
                     )),
                     Line::from(""),
                     Line::from(vec![
-                        Span::styled("  q,        ", Style::default().fg(Color::Yellow).bold()),
+                        Span::styled("  q,        ", theme.key),
                         Span::raw("Quit the application"),
                     ]),
                     Line::from(vec![
                         Span::styled(
                             "  Tab, →        ",
-                            Style::default().fg(Color::Yellow).bold(),
+                            theme.key,
                         ),
                         Span::raw("Next tab"),
                     ]),
                     Line::from(vec![
                         Span::styled(
                             "  Shift+Tab, ←  ",
-                            Style::default().fg(Color::Yellow).bold(),
+                            theme.key,
                         ),
                         Span::raw("Previous tab"),
                     ]),
                     Line::from(vec![
                         Span::styled(
                             "  ↑, k          ",
-                            Style::default().fg(Color::Yellow).bold(),
+                            theme.key,
                         ),
                         Span::raw("Previous file (in Files tab)"),
                     ]),
                     Line::from(vec![
                         Span::styled(
                             "  ↓, j          ",
-                            Style::default().fg(Color::Yellow).bold(),
+                            theme.key,
                         ),
                         Span::raw("Next file (in Files tab)"),
                     ]),
                     Line::from(vec![
                         Span::styled(
-                            "  1-5           ",
-                            Style::default().fg(Color::Yellow).bold(),
+                            "  PgUp, PgDn    ",
+                            theme.key,
+                        ),
+                        Span::raw("Scroll a page (files, analysis and errors)"),
+                    ]),
+                    Line::from(vec![
+                        Span::styled(
+                            "  Home, End     ",
+                            theme.key,
+                        ),
+                        Span::raw("First or last file, top or bottom of the text"),
+                    ]),
+                    Line::from(vec![
+                        Span::styled(
+                            "  /             ",
+                            theme.key,
+                        ),
+                        Span::raw("Filter files by path or include (Enter keeps, Esc clears)"),
+                    ]),
+                    Line::from(vec![
+                        Span::styled(
+                            "  p, Enter      ",
+                            theme.key,
+                        ),
+                        Span::raw("Preview the selected file's output, or show the selected include's details (Esc closes)"),
+                    ]),
+                    Line::from(vec![
+                        Span::styled(
+                            "  R             ",
+                            theme.key,
+                        ),
+                        Span::raw("Process the selected file (Files) or all failed files (Error Summary) again"),
+                    ]),
+                    Line::from(vec![
+                        Span::styled(
+                            "  o             ",
+                            theme.key,
+                        ),
+                        Span::raw("Open the selected file or its missing partial in $EDITOR"),
+                    ]),
+                    Line::from(vec![
+                        Span::styled(
+                            "  s             ",
+                            theme.key,
+                        ),
+                        Span::raw("Save the summary as a JSON, Markdown (.md) or HTML (.html) report"),
+                    ]),
+                    Line::from(vec![
+                        Span::styled(
+                            "  1-8           ",
+                            theme.key,
                         ),
                         Span::raw("Jump directly to tab (1=Progress, 2=Files, etc.)"),
                     ]),
                     Line::from(vec![
                         Span::styled(
                             "  e             ",
-                            Style::default().fg(Color::Yellow).bold(),
+                            theme.key,
                         ),
                         Span::raw("Toggle error details visibility"),
                     ]),
                     Line::from(vec![
                         Span::styled(
                             "  ?             ",
-                            Style::default().fg(Color::Yellow).bold(),
+                            theme.key,
                         ),
                         Span::raw("Toggle this help dialog"),
                     ]),
                     Line::from(vec![
                         Span::styled(
                             "  r             ",
-                            Style::default().fg(Color::Yellow).bold(),
+                            theme.key,
                         ),
                         Span::raw("Refresh (future use)"),
                     ]),
                     Line::from(""),
                     Line::from(Span::styled(
                         "TABS:",
-                        Style::default().fg(Color::White).bold(),
+                        theme.text.bold(),
                     )),
                     Line::from(""),
                     Line::from(vec![
-                        Span::styled("  Progress      ", Style::default().fg(Color::Cyan).bold()),
+                        Span::styled("  Progress      ", theme.accent.bold()),
                         Span::raw("Shows real-time processing progress"),
                     ]),
                     Line::from(vec![
-                        Span::styled("  Files         ", Style::default().fg(Color::Cyan).bold()),
+                        Span::styled("  Files         ", theme.accent.bold()),
                         Span::raw("Lists all processed files with details"),
                     ]),
                     Line::from(vec![
-                        Span::styled("  Analysis      ", Style::default().fg(Color::Cyan).bold()),
+                        Span::styled("  Tree          ", theme.accent.bold()),
+                        Span::raw("Files with their nested includes (Space/Enter expands)"),
+                    ]),
+                    Line::from(vec![
+                        Span::styled("  Analysis      ", theme.accent.bold()),
                         Span::raw("Statistics and error analysis"),
                     ]),
                     Line::from(vec![
-                        Span::styled("  Summary       ", Style::default().fg(Color::Cyan).bold()),
+                        Span::styled("  Summary       ", theme.accent.bold()),
                         Span::raw("Complete processing summary"),
                     ]),
                     Line::from(vec![
-                        Span::styled("  Error Summary ", Style::default().fg(Color::Cyan).bold()),
+                        Span::styled("  Error Summary ", theme.accent.bold()),
                         Span::raw("Detailed error information (if errors exist)"),
                     ]),
+                    Line::from(vec![
+                        Span::styled("  Watch         ", theme.accent.bold()),
+                        Span::raw("Last run of every file and the changed files (with --watch)"),
+                    ]),
                     Line::from(""),
                     Line::from(Span::styled(
                         "Press ? again to close this help",
-                        Style::default().fg(Color::Gray),
+                        theme.muted,
                     )),
                 ];
 
@@ -584,7 +2184,7 @@ fn run_tui_mode(
                             .title(" Help ")
                             .title_alignment(Alignment::Center),
                     )
-                    .style(Style::default().fg(Color::White))
+                    .style(theme.text)
                     .alignment(Alignment::Left);
                 f.render_widget(help_dialog, help_area);
             }
@@ -599,6 +2199,18 @@ fn run_tui_mode(
                     break;
                 }
 
+                // The editor gets the terminal until it exits, then the file is processed again
+                if let Some(request) = app.edit_request.take() {
+                    events.pause();
+                    tui.suspend().expect("Failed to suspend TUI");
+                    if let Err(e) = open_in_editor(&request.path) {
+                        tracing::warn!(path = %request.path.display(), error = %e, "Failed to open editor");
+                    }
+                    tui.resume().expect("Failed to resume TUI");
+                    events.resume();
+                    app.reprocess(vec![request.source]);
+                }
+
                 // Auto-switch to final tab if processing is complete
                 if app.is_processing_complete() && !app.has_switched_to_final_tab() {
                     let summary_guard = summary
@@ -610,7 +2222,9 @@ fn run_tui_mode(
                             .iter()
                             .any(|r| r.includes.iter().any(|i| !i.success));
 
-                    if has_errors {
+                    if app.watch.is_some() {
+                        app.active_tab = ActiveTab::Watch;
+                    } else if has_errors {
                         app.set_active_tab_to_error_summary();
                     } else {
                         app.set_active_tab_to_summary();
@@ -622,6 +2236,24 @@ fn run_tui_mode(
         }
     }
 
+    // Stop background processing so no files are written after the UI is gone
+    cancellation.cancel();
+    if processing_thread.join().is_err() {
+        tracing::error!("Processing thread panicked");
+    }
+    // Retries and watch runs are included, so the next run compares against what was last seen
+    if app.processing_complete {
+        let summary = summary
+            .lock()
+            .expect("Failed to acquire summary lock for saving the run");
+        save_run_record(record_path, &summary);
+        let elapsed = app
+            .completion_time
+            .unwrap_or_else(Instant::now)
+            .duration_since(app.start_time);
+        write_reports(reports, &summary, elapsed);
+    }
+
     // Cleanup
     tui.exit().expect("Failed to exit TUI");
     Ok(())
@@ -630,33 +2262,109 @@ fn run_tui_mode(
 fn run_console_mode(
     config: ProcessingConfig,
     summary: Arc<Mutex<ProcessingSummary>>,
+    verbosity: Verbosity,
+    github_annotations: bool,
+    watch: bool,
+    targets: &RunTargets,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Starting md2md processing...");
-    println!("Source: {:?}", config.source_path);
-    println!("Partials: {:?}", config.partials_path);
-    println!("Output: {:?}", config.output_path);
-    println!();
+    let RunTargets {
+        archive, reports, ..
+    } = *targets;
+    let started = Instant::now();
+    if !verbosity.is_quiet() {
+        println!("Starting md2md processing...");
+        println!("Source: {:?}", config.source_path);
+        println!("Partials: {:?}", config.partials_path);
+        println!("Output: {:?}", archive.unwrap_or(&config.output_path));
+        println!();
+    }
 
-    md2md::processor::process_files(
+    // Progress goes to stderr so the diffs and summary on stdout stay clean to pipe
+    let (event_sender, processing_events) = mpsc::channel();
+    let tty = atty::is(atty::Stream::Stderr);
+    let progress_thread = std::thread::spawn(move || {
+        let mut progress = cli_messages::ConsoleProgress::new(std::io::stderr(), tty);
+        for event in processing_events {
+            progress.update(&event);
+        }
+    });
+    process(
         &config,
+        archive,
         &mut summary
             .lock()
             .expect("Failed to acquire summary lock for console mode processing"),
-        |summary| {
-            if config.verbose
-                && let Some(current) = &summary.current_file
-            {
-                println!("Processing: {current}");
-            }
-        },
+        (!verbosity.is_quiet()).then_some(&event_sender),
+        None,
     )
     .expect("Failed to process files");
+    drop(event_sender);
+    let _ = progress_thread.join();
 
     // Print final summary
     let summary_guard = summary
         .lock()
         .expect("Failed to acquire summary lock for final summary");
-    cli_messages::print_console_summary(&summary_guard, config.verbose);
+    for diff in &summary_guard.diffs {
+        print!("{}", diff.diff);
+    }
+    if github_annotations {
+        cli_messages::print_github_annotations(&summary_guard);
+    }
+    write_reports(reports, &summary_guard, started.elapsed());
+    if !watch {
+        cli_messages::print_run_summary(&summary_guard, verbosity);
+        std::process::exit(cli_messages::exit_code(&summary_guard));
+    }
+    cli_messages::print_run_summary(&summary_guard, verbosity);
+    drop(summary_guard);
 
-    Ok(())
+    let mut watcher = Watcher::new(&config)?;
+    if !verbosity.is_quiet() {
+        println!();
+        println!("Watching for changes (Ctrl-C to stop)...");
+    }
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        let started = Instant::now();
+        let Some(run) = watcher.poll(None)? else {
+            continue;
+        };
+        if !verbosity.is_quiet() {
+            println!();
+            println!("Run #{}: changed {}", run.run, run.changed.join(", "));
+        }
+        for diff in &run.summary.diffs {
+            print!("{}", diff.diff);
+        }
+        if github_annotations {
+            cli_messages::print_github_annotations(&run.summary);
+        }
+        cli_messages::print_run_summary(&run.summary, verbosity);
+        let mut summary_guard = summary
+            .lock()
+            .expect("Failed to acquire summary lock for watch run");
+        summary_guard.replace_results(run.summary);
+        write_reports(reports, &summary_guard, started.elapsed());
+    }
+}
+
+/// Writes the `--report` files of a finished run
+fn write_reports(
+    reports: &[(ReportFormat, PathBuf)],
+    summary: &ProcessingSummary,
+    elapsed: Duration,
+) {
+    for (format, path) in reports {
+        if let Err(e) = report::write_report(path, *format, summary, elapsed) {
+            tracing::error!(path = %path.display(), error = %e, "Failed to write the report");
+        }
+    }
+}
+
+/// Keeps the results of a finished run for the next run of the profile to compare against
+fn save_run_record(path: &Path, summary: &ProcessingSummary) {
+    if let Err(e) = RunRecord::from_summary(summary).save(path) {
+        tracing::warn!(path = %path.display(), error = %e, "Failed to save the run's results");
+    }
 }
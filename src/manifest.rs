@@ -0,0 +1,121 @@
+//! `pkg.*` variables from the project's package manifest, so generated docs show
+//! the current name, version and description
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Manifests looked for, in order; the first one found is read
+pub const MANIFESTS: [&str; 3] = ["Cargo.toml", "package.json", "pyproject.toml"];
+
+/// Fields of a manifest exposed as `pkg.<field>`
+const FIELDS: [&str; 3] = ["name", "version", "description"];
+
+/// `pkg.name`, `pkg.version` and `pkg.description` from the first manifest in `directory`.
+///
+/// Fields missing from `[package]` of a `Cargo.toml` are read from `[workspace.package]`,
+/// and from `[tool.poetry]` for a `pyproject.toml` without `[project]`; fields that still
+/// aren't strings are left out. Without a manifest there are none.
+pub fn package_variables(
+    directory: &Path,
+) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let Some(name) = MANIFESTS
+        .into_iter()
+        .find(|name| directory.join(name).is_file())
+    else {
+        return Ok(HashMap::new());
+    };
+    let path = directory.join(name);
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+    let invalid = |e: &dyn std::fmt::Display| format!("Invalid {}: {e}", path.display());
+
+    // The table holding the fields, and the one to fall back to, e.g. [tool.poetry]
+    let (package, fallback) = match name {
+        "package.json" => {
+            let json: serde_json::Value =
+                serde_json::from_str(&content).map_err(|e| invalid(&e))?;
+            return Ok(fields(|field| {
+                json.get(field)?.as_str().map(str::to_string)
+            }));
+        }
+        "Cargo.toml" => (&["package"][..], &["workspace", "package"][..]),
+        _ => (&["project"][..], &["tool", "poetry"][..]),
+    };
+    let toml: toml::Table = toml::from_str(&content).map_err(|e| invalid(&e))?;
+    let table = |keys: &[&str]| {
+        keys.iter()
+            .try_fold(&toml, |table, key| table.get(*key)?.as_table())
+    };
+    let (package, fallback) = (table(package), table(fallback));
+    Ok(fields(|field| {
+        [package, fallback]
+            .into_iter()
+            .flatten()
+            .find_map(|table| table.get(field)?.as_str().map(str::to_string))
+    }))
+}
+
+fn fields(value: impl Fn(&str) -> Option<String>) -> HashMap<String, String> {
+    FIELDS
+        .into_iter()
+        .filter_map(|field| Some((format!("pkg.{field}"), value(field)?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_package_variables_from_each_manifest() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let dir = temp_dir.path();
+        assert!(
+            package_variables(dir)
+                .expect("Failed to read package variables")
+                .is_empty()
+        );
+
+        fs::write(
+            dir.join("pyproject.toml"),
+            "[tool.poetry]\nname = \"docs\"\nversion = \"0.3.0\"\n",
+        )
+        .expect("Failed to write pyproject.toml");
+        assert_eq!(
+            package_variables(dir).expect("Failed to read pyproject.toml"),
+            HashMap::from([
+                ("pkg.name".to_string(), "docs".to_string()),
+                ("pkg.version".to_string(), "0.3.0".to_string()),
+            ])
+        );
+
+        fs::write(
+            dir.join("package.json"),
+            r#"{"name": "docs-site", "version": "2.0.0", "description": "The site"}"#,
+        )
+        .expect("Failed to write package.json");
+        assert_eq!(
+            package_variables(dir).expect("Failed to read package.json")["pkg.description"],
+            "The site"
+        );
+
+        // A version inherited from the workspace is read from [workspace.package]
+        fs::write(
+            dir.join("Cargo.toml"),
+            "[package]\nname = \"md2md\"\nversion.workspace = true\n\n[workspace.package]\nversion = \"1.4.0\"\n",
+        )
+        .expect("Failed to write Cargo.toml");
+        assert_eq!(
+            package_variables(dir).expect("Failed to read Cargo.toml"),
+            HashMap::from([
+                ("pkg.name".to_string(), "md2md".to_string()),
+                ("pkg.version".to_string(), "1.4.0".to_string()),
+            ])
+        );
+
+        fs::write(dir.join("Cargo.toml"), "[package\n").expect("Failed to write Cargo.toml");
+        assert!(package_variables(dir).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Document-level directives of markedpp, used in `--compat markedpp` mode.
+//!
+//! `!numberedheadings` numbers the headings of the whole document and `!toc`
+//! is replaced with a linked table of contents. Both accept markedpp's options,
+//! e.g. `!toc (level=2 minlevel=1 omit="Changelog;License")`.
+
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Heading {
+    /// 0-indexed line of the heading
+    pub line: usize,
+    pub level: usize,
+    pub text: String,
+}
+
+/// Applies `!numberedheadings` and then `!toc` to a fully resolved document
+pub fn apply_document_directives(content: &str) -> String {
+    static DIRECTIVE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"^!(toc|numberedheadings)(?:\s*\(([^)]*)\))?\s*$")
+            .expect("Failed to compile markedpp directive regex")
+    });
+
+    let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    let mut toc_lines = Vec::new();
+    let mut numbering = None;
+    let mut in_fence = false;
+    for (index, line) in lines.iter().enumerate() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        if let Some(capture) = DIRECTIVE_REGEX.captures(line.trim_end()) {
+            let options = parse_options(capture.get(2).map_or("", |m| m.as_str()));
+            match &capture[1] {
+                "toc" => toc_lines.push((index, options)),
+                _ => numbering = Some((index, options)),
+            }
+        }
+    }
+
+    if let Some((index, options)) = numbering {
+        lines.remove(index);
+        toc_lines = toc_lines
+            .into_iter()
+            .map(|(line, options)| (if line > index { line - 1 } else { line }, options))
+            .collect();
+        number_headings(&mut lines, &options);
+    }
+
+    let headings = find_headings(&lines);
+    for (index, options) in toc_lines.into_iter().rev() {
+        lines[index] = render_toc(&headings, &options);
+    }
+
+    lines.join("\n")
+}
+
+/// GitHub-style anchor of a heading: lowercase, punctuation removed, spaces as dashes
+pub fn slugify(text: &str) -> String {
+    text.trim()
+        .to_lowercase()
+        .chars()
+        .filter_map(|c| match c {
+            ' ' => Some('-'),
+            c if c.is_alphanumeric() || c == '-' || c == '_' => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Anchors of every heading in `content`, in document order
+pub fn heading_anchors(content: &str) -> Vec<String> {
+    let lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    unique_anchors(&find_headings(&lines))
+}
+
+/// Anchors are unique across the document: repeated slugs get a `-1`, `-2`... suffix
+fn unique_anchors(headings: &[Heading]) -> Vec<String> {
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    headings
+        .iter()
+        .map(|heading| {
+            let slug = heading_anchor(&heading.text);
+            let count = seen.entry(slug.clone()).or_insert(0);
+            let anchor = match *count {
+                0 => slug,
+                n => format!("{slug}-{n}"),
+            };
+            *count += 1;
+            anchor
+        })
+        .collect()
+}
+
+/// Anchor of the first heading in `content`
+pub fn first_heading_anchor(content: &str) -> Option<String> {
+    let lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+    find_headings(&lines)
+        .first()
+        .map(|heading| heading_anchor(&heading.text))
+}
+
+/// Anchor of a heading: its explicit `{#id}` attribute, or its slug
+pub(crate) fn heading_anchor(text: &str) -> String {
+    match explicit_id(text) {
+        Some(id) => id.to_string(),
+        None => slugify(text),
+    }
+}
+
+/// The id of a trailing `{#id}` heading attribute
+pub(crate) fn explicit_id(text: &str) -> Option<&str> {
+    let (_, attribute) = text.strip_suffix('}')?.rsplit_once("{#")?;
+    (!attribute.is_empty() && !attribute.contains(char::is_whitespace)).then_some(attribute)
+}
+
+fn is_fence(line: &str) -> bool {
+    let trimmed = line.trim_start();
+    trimmed.starts_with("```") || trimmed.starts_with("~~~")
+}
+
+pub(crate) fn find_headings(lines: &[String]) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut in_fence = false;
+    for (index, line) in lines.iter().enumerate() {
+        if is_fence(line) {
+            in_fence = !in_fence;
+        } else if !in_fence && let Some((level, text)) = parse_heading(line) {
+            headings.push(Heading {
+                line: index,
+                level,
+                text: text.to_string(),
+            });
+        }
+    }
+    headings
+}
+
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let rest = &line[level..];
+    if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with(' ')) {
+        return None;
+    }
+    let text = rest.trim().trim_end_matches('#').trim_end();
+    Some((level, text))
+}
+
+/// Parses `key=value key="quoted value" flag` options
+fn parse_options(options: &str) -> HashMap<String, String> {
+    static OPTION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"([\w-]+)(?:\s*=\s*(?:"([^"]*)"|(\S+)))?"#)
+            .expect("Failed to compile option regex")
+    });
+    OPTION_REGEX
+        .captures_iter(options)
+        .map(|capture| {
+            let value = capture.get(2).or(capture.get(3)).map_or("", |m| m.as_str());
+            (capture[1].to_string(), value.to_string())
+        })
+        .collect()
+}
+
+fn level_range(options: &HashMap<String, String>, default_level: usize) -> (usize, usize) {
+    let option = |name: &str, default: usize| {
+        options
+            .get(name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+            .clamp(1, 6)
+    };
+    (option("minlevel", 1), option("level", default_level))
+}
+
+fn number_headings(lines: &mut [String], options: &HashMap<String, String>) {
+    let (min_level, max_level) = level_range(options, 6);
+    let mut counters = [0usize; 6];
+    for heading in find_headings(lines) {
+        if heading.level < min_level || heading.level > max_level {
+            continue;
+        }
+        let depth = heading.level - min_level;
+        counters[depth] += 1;
+        counters[depth + 1..].fill(0);
+        let number: Vec<String> = counters[..=depth].iter().map(usize::to_string).collect();
+        lines[heading.line] = format!(
+            "{} {}. {}",
+            "#".repeat(heading.level),
+            number.join("."),
+            heading.text
+        );
+    }
+}
+
+fn render_toc(headings: &[Heading], options: &HashMap<String, String>) -> String {
+    let (min_level, max_level) = level_range(options, 3);
+    let omit: Vec<&str> = options
+        .get("omit")
+        .map(|omit| omit.split(';').map(str::trim).collect())
+        .unwrap_or_default();
+    let bullet = if options.contains_key("numbered") {
+        "1."
+    } else {
+        "*"
+    };
+
+    let mut entries = Vec::new();
+    for (heading, anchor) in headings.iter().zip(unique_anchors(headings)) {
+        if heading.level >= min_level
+            && heading.level <= max_level
+            && !omit.contains(&heading.text.as_str())
+        {
+            let indent = "  ".repeat(heading.level - min_level);
+            let text = match explicit_id(&heading.text) {
+                Some(id) => heading.text[..heading.text.len() - id.len() - 3].trim_end(),
+                None => &heading.text,
+            };
+            entries.push(format!("{indent}{bullet} [{text}](#{anchor})"));
+        }
+    }
+
+    format!("<!-- toc -->\n\n{}\n\n<!-- toc! -->", entries.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toc_and_numbered_headings() {
+        let content = "!numberedheadings (minlevel=2)\n\n# Guide\n\n!toc (minlevel=2)\n\n## Setup\n\n```bash\n## not a heading\n```\n\n### Install\n\n## Skip\n\n## Setup";
+
+        let result = apply_document_directives(content);
+
+        assert_eq!(
+            result,
+            "\n# Guide\n\n<!-- toc -->\n\n* [1. Setup](#1-setup)\n  * [1.1. Install](#11-install)\n* [2. Skip](#2-skip)\n* [3. Setup](#3-setup)\n\n<!-- toc! -->\n\n## 1. Setup\n\n```bash\n## not a heading\n```\n\n### 1.1. Install\n\n## 2. Skip\n\n## 3. Setup"
+        );
+    }
+
+    #[test]
+    fn test_toc_anchors_are_unique() {
+        let result = apply_document_directives(
+            "!toc (omit=\"License\")\n\n# Intro\n\n## Usage\n\n# Usage!\n\n# License",
+        );
+
+        assert!(result.starts_with(
+            "<!-- toc -->\n\n* [Intro](#intro)\n  * [Usage](#usage)\n* [Usage!](#usage-1)\n\n<!-- toc! -->"
+        ));
+    }
+}
@@ -0,0 +1,102 @@
+//! MDX (Docusaurus) awareness.
+//!
+//! Directives in `.mdx` files and partials are left untouched inside the leading
+//! frontmatter, ESM blocks (`import`/`export` up to the next blank line) and JSX
+//! tags, so component syntax survives. Markdown children of a component are
+//! still processed, e.g. an `!include` inside a `<TabItem>`.
+
+use regex::Regex;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+
+pub fn is_mdx(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "mdx")
+}
+
+/// Byte ranges of `content` that hold MDX syntax rather than Markdown
+pub fn protected_ranges(content: &str) -> Vec<Range<usize>> {
+    let body_start = frontmatter_end(content).unwrap_or(0);
+    let mut ranges = Vec::new();
+    if body_start > 0 {
+        ranges.push(0..body_start);
+    }
+
+    let mut offset = body_start;
+    let mut in_fence = false;
+    let mut esm_start = None;
+    for line in content[body_start..].split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let trimmed = line.trim_end();
+
+        if let Some(block_start) = esm_start {
+            if trimmed.is_empty() {
+                ranges.push(block_start..start);
+                esm_start = None;
+            }
+        } else if trimmed.trim_start().starts_with("```") || trimmed.trim_start().starts_with("~~~")
+        {
+            in_fence = !in_fence;
+        } else if !in_fence && (trimmed.starts_with("import ") || trimmed.starts_with("export ")) {
+            esm_start = Some(start);
+        }
+    }
+    if let Some(block_start) = esm_start {
+        ranges.push(block_start..content.len());
+    }
+
+    ranges.extend(
+        jsx_tag_ranges(&content[body_start..])
+            .into_iter()
+            .map(|range| range.start + body_start..range.end + body_start),
+    );
+    ranges
+}
+
+/// End of a leading `---` frontmatter block, including its closing line
+pub(crate) fn frontmatter_end(content: &str) -> Option<usize> {
+    let mut lines = content.split_inclusive('\n');
+    let opening = lines.next()?;
+    if opening.trim_end() != "---" {
+        return None;
+    }
+    let mut offset = opening.len();
+    for line in lines {
+        offset += line.len();
+        if line.trim_end() == "---" {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+/// Opening and closing tags, with attributes and `{...}` expressions, possibly spanning lines
+fn jsx_tag_ranges(content: &str) -> Vec<Range<usize>> {
+    static TAG_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"</?[A-Za-z][\w.:-]*(?:\{(?:[^{}]|\{[^{}]*\})*\}|[^<>{}])*>|</?>")
+            .expect("Failed to compile JSX tag regex")
+    });
+    TAG_REGEX.find_iter(content).map(|m| m.range()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protected_ranges_cover_mdx_syntax() {
+        let content = "---\ntitle: !include (x.md)\n---\nimport Tabs from '@theme/Tabs';\nexport const a = \"!include (y.md)\";\n\n<Tabs values={[{label: \"!include (z.md)\"}]}>\n\n!include (body.md)\n\n</Tabs>\n";
+
+        let ranges = protected_ranges(content);
+        let protected = |needle: &str| {
+            let position = content.find(needle).expect("Needle should be in content");
+            ranges.iter().any(|range| range.contains(&position))
+        };
+
+        assert!(protected("!include (x.md)"));
+        assert!(protected("!include (y.md)"));
+        assert!(protected("!include (z.md)"));
+        assert!(!protected("!include (body.md)"));
+    }
+}
@@ -0,0 +1,271 @@
+//! pymdown-extensions snippets, used in `--compat mkdocs` mode.
+//!
+//! Both forms are expanded before any md2md directive, anywhere in the document
+//! (including code fences, so snippets can fill code blocks):
+//!
+//! ```text
+//! --8<-- "header.md"
+//! --8<-- "example.py:3:10"
+//!
+//! --8<--
+//! intro.md
+//! usage.md
+//! --8<--
+//! ```
+//!
+//! Paths are relative to the partials directory, `;--8<--` escapes a marker and
+//! the indentation of a marker is applied to every included line.
+
+use crate::error_code::ErrorCode;
+use crate::include_resolver::ResolveOptions;
+use crate::types::{DirectiveKind, IncludeResult};
+use regex::Regex;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::LazyLock;
+
+const MAX_DEPTH: usize = 5;
+const MARKER: &str = "--8<--";
+
+/// Replaces every snippet marker in `content` of `current_file` with the referenced file.
+///
+/// Snippets are tracked with depths relative to `current_file`.
+pub fn expand_snippets(
+    content: &str,
+    current_file: &Path,
+    includes_tracker: &mut Vec<IncludeResult>,
+    options: &ResolveOptions,
+) -> Result<String, Box<dyn std::error::Error>> {
+    expand_with_depth(
+        content,
+        &current_file.to_string_lossy(),
+        includes_tracker,
+        options,
+        0,
+    )
+}
+
+fn expand_with_depth(
+    content: &str,
+    parent: &str,
+    includes_tracker: &mut Vec<IncludeResult>,
+    options: &ResolveOptions,
+    depth: usize,
+) -> Result<String, Box<dyn std::error::Error>> {
+    if depth > MAX_DEPTH {
+        return Err(ErrorCode::CircularInclude.error(format!(
+            "Maximum snippet depth ({MAX_DEPTH}) exceeded. Possible circular snippets."
+        )));
+    }
+    static INLINE_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r#"^(\s*)(;?)--8<--\s+(["'])(.+?)(["'])\s*$"#)
+            .expect("Failed to compile snippet regex")
+    });
+
+    let mut output = Vec::new();
+    let mut block: Option<String> = None;
+    for line in content.split('\n') {
+        let trimmed = line.trim();
+        if let Some(indent) = &block {
+            if trimmed == MARKER {
+                block = None;
+            } else if !trimmed.is_empty() && !trimmed.starts_with(';') {
+                let indent = indent.clone();
+                output.push(include_snippet(
+                    trimmed,
+                    &indent,
+                    parent,
+                    includes_tracker,
+                    options,
+                    depth,
+                )?);
+            }
+            continue;
+        }
+
+        if trimmed == MARKER {
+            block = Some(line[..line.len() - line.trim_start().len()].to_string());
+        } else if let Some(capture) = INLINE_REGEX.captures(line) {
+            if &capture[2] == ";" {
+                output.push(line.replacen(";--8<--", MARKER, 1));
+            } else {
+                output.push(include_snippet(
+                    &capture[4],
+                    &capture[1],
+                    parent,
+                    includes_tracker,
+                    options,
+                    depth,
+                )?);
+            }
+        } else {
+            output.push(line.to_string());
+        }
+    }
+
+    Ok(output.join("\n"))
+}
+
+/// Reads one snippet, selects its line range and expands the snippets it contains
+fn include_snippet(
+    reference: &str,
+    indent: &str,
+    parent: &str,
+    includes_tracker: &mut Vec<IncludeResult>,
+    options: &ResolveOptions,
+    depth: usize,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let (file, range) = split_line_range(reference);
+    let path = options.partials_path.join(file);
+    let path_display = path.to_string_lossy().to_string();
+
+    let snippet = options
+        .source
+        .read(&path)
+        .map_err(|e| ErrorCode::MissingInclude.error(e.to_string()))
+        .and_then(|content| select_lines(&content, range));
+    let snippet = match snippet {
+        Ok(snippet) => snippet,
+        Err(e) => {
+            tracing::warn!(path = %path_display, error = %e, "Failed to include snippet");
+            includes_tracker.push(IncludeResult {
+                path: path_display,
+                success: false,
+                error_message: Some(e.to_string()),
+                error_code: ErrorCode::of(e.as_ref()),
+                parent: parent.to_string(),
+                depth,
+                title: None,
+                values: HashMap::new(),
+                duration: None,
+                bytes: None,
+                attempts: None,
+                kind: DirectiveKind::Include,
+            });
+            return Ok(format!(
+                "{indent}<!-- Failed to include snippet: {reference} (Error: {e}) -->"
+            ));
+        }
+    };
+
+    includes_tracker.push(IncludeResult {
+        path: path_display.clone(),
+        success: true,
+        error_message: None,
+        error_code: None,
+        parent: parent.to_string(),
+        depth,
+        title: None,
+        values: HashMap::new(),
+        duration: None,
+        bytes: None,
+        attempts: None,
+        kind: DirectiveKind::Include,
+    });
+    let expanded = expand_with_depth(
+        &snippet,
+        &path_display,
+        includes_tracker,
+        options,
+        depth + 1,
+    )?;
+    Ok(expanded
+        .split('\n')
+        .map(|line| {
+            if line.is_empty() {
+                String::new()
+            } else {
+                format!("{indent}{line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Splits `file.md:start:end` into the file and its 1-indexed line range
+fn split_line_range(reference: &str) -> (&str, (Option<usize>, Option<usize>)) {
+    let mut parts = reference.splitn(3, ':');
+    let file = parts.next().unwrap_or(reference);
+    let start = parts.next();
+    let end = parts.next();
+    let is_number =
+        |part: Option<&str>| part.is_none_or(|p| p.is_empty() || p.parse::<usize>().is_ok());
+    if start.is_none() || !is_number(start) || !is_number(end) {
+        return (reference, (None, None));
+    }
+    let parse = |part: Option<&str>| part.and_then(|p| p.parse().ok());
+    (file, (parse(start), parse(end)))
+}
+
+fn select_lines(
+    content: &str,
+    (start, end): (Option<usize>, Option<usize>),
+) -> Result<String, Box<dyn std::error::Error>> {
+    let content = content.strip_suffix('\n').unwrap_or(content);
+    if start.is_none() && end.is_none() {
+        return Ok(content.to_string());
+    }
+
+    let lines: Vec<&str> = content.split('\n').collect();
+    let start = start.unwrap_or(1);
+    let end = end.unwrap_or(lines.len());
+    if start == 0 || start > end || end > lines.len() {
+        return Err(ErrorCode::InvalidSnippetRange.error(format!(
+            "Lines {start}-{end} are outside the snippet ({} lines)",
+            lines.len()
+        )));
+    }
+    Ok(lines[start - 1..end].join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partial_source::MemorySource;
+    use crate::types::Compat;
+    use std::collections::HashMap;
+    use std::path::Path;
+
+    fn expand(content: &str, source: &MemorySource) -> (String, Vec<IncludeResult>) {
+        let variables = HashMap::new();
+        let options = ResolveOptions {
+            compat: Compat::Mkdocs,
+            ..ResolveOptions::new(Path::new(""), &variables, source)
+        };
+        let mut includes = Vec::new();
+        let result = expand_snippets(content, Path::new("docs/index.md"), &mut includes, &options)
+            .expect("Failed to expand snippets");
+        (result, includes)
+    }
+
+    #[test]
+    fn test_expand_inline_and_block_snippets() {
+        let source = MemorySource::new()
+            .with_file("header.md", "# Header\n--8<-- \"nested.md\"\n")
+            .with_file("nested.md", "Nested")
+            .with_file("code.py", "one\ntwo\nthree\nfour");
+
+        let (result, includes) = expand(
+            "--8<-- \"header.md\"\n\n```python\n--8<-- \"code.py:2:3\"\n```\n\n- item\n\n    --8<--\n    nested.md\n    ;skipped.md\n    --8<--\n\n;--8<-- \"header.md\"",
+            &source,
+        );
+
+        assert_eq!(
+            result,
+            "# Header\nNested\n\n```python\ntwo\nthree\n```\n\n- item\n\n    Nested\n\n--8<-- \"header.md\""
+        );
+        assert_eq!(includes.len(), 4);
+        assert!(includes.iter().all(|inc| inc.success));
+    }
+
+    #[test]
+    fn test_missing_snippets_and_bad_ranges_are_recorded() {
+        let source = MemorySource::new().with_file("short.md", "only line");
+
+        let (result, includes) = expand("--8<-- \"missing.md\"\n--8<-- 'short.md:2:5'", &source);
+
+        assert!(result.starts_with("<!-- Failed to include snippet: missing.md"));
+        assert_eq!(includes[0].error_code, Some(ErrorCode::MissingInclude));
+        assert_eq!(includes[1].error_code, Some(ErrorCode::InvalidSnippetRange));
+    }
+}
@@ -0,0 +1,99 @@
+//! Whitespace clean-up of resolved output, so it passes markdownlint's
+//! MD009 (trailing spaces), MD012 (multiple blank lines) and MD047 (final newline).
+//!
+//! Code fences are left as they are.
+
+use crate::types::Normalization;
+use std::borrow::Cow;
+
+/// Applies the enabled rules of `normalization` to `content`
+pub fn normalize(content: &str, normalization: Normalization) -> Cow<'_, str> {
+    if normalization == Normalization::default() {
+        return Cow::Borrowed(content);
+    }
+
+    let mut lines: Vec<Cow<str>> = Vec::new();
+    let mut in_fence = false;
+    for line in content.split('\n') {
+        let is_fence = {
+            let trimmed = line.trim_start();
+            trimmed.starts_with("```") || trimmed.starts_with("~~~")
+        };
+        if in_fence && !is_fence {
+            lines.push(Cow::Borrowed(line));
+            continue;
+        }
+        if is_fence {
+            in_fence = !in_fence;
+        }
+
+        let line = if normalization.trailing_spaces {
+            strip_trailing_whitespace(line)
+        } else {
+            Cow::Borrowed(line)
+        };
+        let previous_blank = lines.last().is_some_and(|last| last.trim().is_empty());
+        if normalization.blank_lines && previous_blank && line.trim().is_empty() {
+            continue;
+        }
+        lines.push(line);
+    }
+
+    let mut result = lines.join("\n");
+    if normalization.final_newline {
+        let newline = if result.contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+        let trimmed_len = result.trim_end_matches(['\n', '\r']).len();
+        result.truncate(trimmed_len);
+        result.push_str(newline);
+    }
+    Cow::Owned(result)
+}
+
+/// Removes trailing whitespace, except a two-space hard line break, keeping a `\r` line ending
+fn strip_trailing_whitespace(line: &str) -> Cow<'_, str> {
+    let (text, ending) = match line.strip_suffix('\r') {
+        Some(text) => (text, "\r"),
+        None => (line, ""),
+    };
+    let stripped = text.trim_end();
+    if stripped.len() == text.len() || (!stripped.is_empty() && &text[stripped.len()..] == "  ") {
+        Cow::Borrowed(line)
+    } else {
+        Cow::Owned(format!("{stripped}{ending}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_rules() {
+        let content = "# Title  \t\n\n\n\nHard  \nbreak\n```\nkeep   \n\n\n```\n\n\n";
+        let all = Normalization {
+            trailing_spaces: true,
+            final_newline: true,
+            blank_lines: true,
+        };
+
+        assert_eq!(
+            normalize(content, all),
+            "# Title\n\nHard  \nbreak\n```\nkeep   \n\n\n```\n"
+        );
+        assert_eq!(
+            normalize(
+                content,
+                Normalization {
+                    final_newline: true,
+                    ..Default::default()
+                }
+            ),
+            "# Title  \t\n\n\n\nHard  \nbreak\n```\nkeep   \n\n\n```\n"
+        );
+        assert_eq!(normalize(content, Normalization::default()), content);
+    }
+}
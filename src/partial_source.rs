@@ -0,0 +1,600 @@
+use crate::encoding::{decode, has_utf16_bom, read_text};
+use crate::git;
+use crate::graph::normalize_path;
+use crate::types::InputEncoding;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Where included partials and code snippets are read from.
+///
+/// Paths are already resolved by the include rules (partials directory, `../`
+/// relative to the including file, absolute) before they reach the source.
+pub trait PartialSource {
+    fn read(&self, path: &Path) -> io::Result<String>;
+
+    /// The first `limit` lines of a file (all of them with `None`), split like [`str::lines`]
+    fn read_lines(&self, path: &Path, limit: Option<usize>) -> io::Result<Vec<String>> {
+        Ok(first_lines(&self.read(path)?, limit))
+    }
+
+    /// Told the files a document includes before they are read one after another,
+    /// for a source that can read them sooner all at once, like [`PrefetchSource`]
+    fn prefetch(&self, _paths: &[PathBuf]) {}
+
+    /// A file as it was at the git revision `rev`, for `!include(…, rev="…")`
+    fn read_at_revision(&self, path: &Path, rev: &str) -> io::Result<String> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "{} can't be read at git revision `{rev}` from this source",
+                path.display()
+            ),
+        ))
+    }
+}
+
+/// Lets a borrowed source be used where one is owned, e.g. as a layer of [`LayeredSource`]
+impl<T: PartialSource + ?Sized> PartialSource for &T {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        (**self).read(path)
+    }
+
+    fn read_lines(&self, path: &Path, limit: Option<usize>) -> io::Result<Vec<String>> {
+        (**self).read_lines(path, limit)
+    }
+
+    fn prefetch(&self, paths: &[PathBuf]) {
+        (**self).prefetch(paths)
+    }
+
+    fn read_at_revision(&self, path: &Path, rev: &str) -> io::Result<String> {
+        (**self).read_at_revision(path, rev)
+    }
+}
+
+fn first_lines(content: &str, limit: Option<usize>) -> Vec<String> {
+    content
+        .lines()
+        .take(limit.unwrap_or(usize::MAX))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Reads partials from disk, relative to `root`
+#[derive(Debug, Clone, Default)]
+pub struct FileSystemSource {
+    root: PathBuf,
+    encoding: InputEncoding,
+}
+
+impl FileSystemSource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            encoding: InputEncoding::default(),
+        }
+    }
+
+    /// Decodes the files in `encoding` instead of UTF-8
+    pub fn with_encoding(mut self, encoding: InputEncoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+}
+
+impl PartialSource for FileSystemSource {
+    /// A UTF-8 byte order mark is dropped, as it would end up in the middle of the output
+    fn read(&self, path: &Path) -> io::Result<String> {
+        let content = read_text(&self.root.join(path), self.encoding)?;
+        Ok(match content.strip_prefix('\u{feff}') {
+            Some(content) => content.to_string(),
+            None => content,
+        })
+    }
+
+    /// Stops reading once `limit` lines are in, so a snippet of a large file doesn't load all of it
+    fn read_lines(&self, path: &Path, limit: Option<usize>) -> io::Result<Vec<String>> {
+        let mut reader = BufReader::new(fs::File::open(self.root.join(path))?);
+        // Only UTF-8 can be decoded line by line
+        if self.encoding != InputEncoding::Utf8 || has_utf16_bom(reader.fill_buf()?) {
+            return Ok(first_lines(&self.read(path)?, limit));
+        }
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        while limit.is_none_or(|limit| lines.len() < limit) {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 {
+                break;
+            }
+            let text = line.strip_suffix('\n').map_or(line.as_str(), |text| {
+                text.strip_suffix('\r').unwrap_or(text)
+            });
+            let text = if lines.is_empty() {
+                text.strip_prefix('\u{feff}').unwrap_or(text)
+            } else {
+                text
+            };
+            lines.push(text.to_string());
+        }
+        Ok(lines)
+    }
+
+    fn read_at_revision(&self, path: &Path, rev: &str) -> io::Result<String> {
+        let bytes = git::read_at_revision(&self.root.join(path), rev)?;
+        let content = decode(bytes, self.encoding)
+            .map_err(|message| io::Error::new(io::ErrorKind::InvalidData, message))?;
+        Ok(match content.strip_prefix('\u{feff}') {
+            Some(content) => content.to_string(),
+            None => content,
+        })
+    }
+}
+
+/// Serves partials from memory, keyed by their path relative to the partials root
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    files: HashMap<PathBuf, String>,
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_file(mut self, path: impl AsRef<Path>, content: impl Into<String>) -> Self {
+        self.insert(path, content);
+        self
+    }
+
+    pub fn insert(&mut self, path: impl AsRef<Path>, content: impl Into<String>) {
+        self.files
+            .insert(normalize_path(path.as_ref()), content.into());
+    }
+}
+
+impl From<HashMap<String, String>> for MemorySource {
+    fn from(files: HashMap<String, String>) -> Self {
+        files
+            .into_iter()
+            .fold(Self::new(), |source, (path, content)| {
+                source.with_file(path, content)
+            })
+    }
+}
+
+impl PartialSource for MemorySource {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.files
+            .get(&normalize_path(path))
+            .cloned()
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("No in-memory partial at {}", path.display()),
+                )
+            })
+    }
+}
+
+/// Only lets `inner` read files inside `roots`, so untrusted sources can't include
+/// `../../../etc/passwd`, absolute paths elsewhere or symlinks pointing out of the roots.
+///
+/// Paths are checked as they are given, relative to the working directory.
+pub struct RestrictedSource<'a> {
+    inner: &'a dyn PartialSource,
+    /// Absolute, without `.` and `..`
+    roots: Vec<PathBuf>,
+    /// With symlinks resolved, for the roots that exist
+    resolved_roots: Vec<PathBuf>,
+}
+
+impl<'a> RestrictedSource<'a> {
+    pub fn new(inner: &'a dyn PartialSource, roots: impl IntoIterator<Item = PathBuf>) -> Self {
+        let roots: Vec<PathBuf> = roots
+            .into_iter()
+            .filter_map(|root| std::path::absolute(root).ok())
+            .map(|root| normalize_path(&root))
+            .collect();
+        let resolved_roots = roots
+            .iter()
+            .filter_map(|root| fs::canonicalize(root).ok())
+            .collect();
+        Self {
+            inner,
+            roots,
+            resolved_roots,
+        }
+    }
+
+    fn check(&self, path: &Path) -> io::Result<()> {
+        let inside = std::path::absolute(path)
+            .is_ok_and(|absolute| {
+                let absolute = normalize_path(&absolute);
+                self.roots.iter().any(|root| absolute.starts_with(root))
+            })
+            // A file that doesn't exist fails to read anyway
+            && fs::canonicalize(path).map_or(true, |resolved| {
+                self.resolved_roots
+                    .iter()
+                    .any(|root| resolved.starts_with(root))
+            });
+        if inside {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!(
+                    "{} is outside the directories includes are restricted to",
+                    path.display()
+                ),
+            ))
+        }
+    }
+}
+
+impl PartialSource for RestrictedSource<'_> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.check(path)?;
+        self.inner.read(path)
+    }
+
+    fn read_lines(&self, path: &Path, limit: Option<usize>) -> io::Result<Vec<String>> {
+        self.check(path)?;
+        self.inner.read_lines(path, limit)
+    }
+
+    fn prefetch(&self, paths: &[PathBuf]) {
+        let allowed: Vec<PathBuf> = paths
+            .iter()
+            .filter(|path| self.check(path).is_ok())
+            .cloned()
+            .collect();
+        self.inner.prefetch(&allowed);
+    }
+
+    fn read_at_revision(&self, path: &Path, rev: &str) -> io::Result<String> {
+        self.check(path)?;
+        self.inner.read_at_revision(path, rev)
+    }
+}
+
+/// Reads each file from the first of its layers that has it, e.g. in-memory
+/// overrides over the partials on disk.
+///
+/// Only a file missing from a layer falls through to the next one; any other
+/// error, like a path outside a [`RestrictedSource`], is returned as it is.
+#[derive(Default)]
+pub struct LayeredSource<'a> {
+    layers: Vec<Box<dyn PartialSource + 'a>>,
+}
+
+impl<'a> LayeredSource<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `source` under the layers added so far
+    pub fn with_layer(mut self, source: impl PartialSource + 'a) -> Self {
+        self.layers.push(Box::new(source));
+        self
+    }
+
+    /// `read` from the first layer that has the file; when none has it, the error is the last one's
+    fn first<T>(
+        &self,
+        path: &Path,
+        read: impl Fn(&dyn PartialSource) -> io::Result<T>,
+    ) -> io::Result<T> {
+        let mut missing = io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No partial source layers to read {}", path.display()),
+        );
+        for layer in &self.layers {
+            match read(layer.as_ref()) {
+                Err(e) if e.kind() == io::ErrorKind::NotFound => missing = e,
+                result => return result,
+            }
+        }
+        Err(missing)
+    }
+}
+
+impl PartialSource for LayeredSource<'_> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.first(path, |layer| layer.read(path))
+    }
+
+    fn read_lines(&self, path: &Path, limit: Option<usize>) -> io::Result<Vec<String>> {
+        self.first(path, |layer| layer.read_lines(path, limit))
+    }
+
+    fn prefetch(&self, paths: &[PathBuf]) {
+        for layer in &self.layers {
+            layer.prefetch(paths);
+        }
+    }
+
+    fn read_at_revision(&self, path: &Path, rev: &str) -> io::Result<String> {
+        self.first(path, |layer| layer.read_at_revision(path, rev))
+    }
+}
+
+/// Fewer files than this are read faster one after another than spread over threads
+pub const PREFETCH_THRESHOLD: usize = 8;
+
+/// Most threads reading files at once
+const PREFETCH_THREADS: usize = 8;
+
+/// Reads the files a document includes on several threads before it is resolved,
+/// hiding the latency of network filesystems where reading one file after another
+/// dominates processing time.
+///
+/// Only the files of the last [`PartialSource::prefetch`] are kept, so content is
+/// never older than the document being resolved. Files that fail to read, and any
+/// file not prefetched, are read from `inner` when asked for.
+#[derive(Debug)]
+pub struct PrefetchSource<S> {
+    inner: S,
+    prefetched: RefCell<HashMap<PathBuf, String>>,
+}
+
+impl<S: PartialSource + Sync> PrefetchSource<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            prefetched: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn get(&self, path: &Path) -> Option<String> {
+        self.prefetched.borrow().get(&normalize_path(path)).cloned()
+    }
+}
+
+impl<S: PartialSource + Sync> PartialSource for PrefetchSource<S> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        match self.get(path) {
+            Some(content) => Ok(content),
+            None => self.inner.read(path),
+        }
+    }
+
+    fn read_lines(&self, path: &Path, limit: Option<usize>) -> io::Result<Vec<String>> {
+        match self.get(path) {
+            Some(content) => Ok(first_lines(&content, limit)),
+            None => self.inner.read_lines(path, limit),
+        }
+    }
+
+    fn prefetch(&self, paths: &[PathBuf]) {
+        self.prefetched.borrow_mut().clear();
+        if paths.len() < PREFETCH_THRESHOLD {
+            return;
+        }
+
+        let (inner, next) = (&self.inner, AtomicUsize::new(0));
+        let read = Mutex::new(HashMap::new());
+        let work = || {
+            while let Some(path) = paths.get(next.fetch_add(1, Ordering::Relaxed)) {
+                if let Ok(content) = inner.read(path) {
+                    read.lock()
+                        .unwrap_or_else(|e| e.into_inner())
+                        .insert(normalize_path(path), content);
+                }
+            }
+        };
+        std::thread::scope(|scope| {
+            for _ in 0..PREFETCH_THREADS.min(paths.len()) {
+                // Without threads the files are simply read when asked for
+                if std::thread::Builder::new()
+                    .spawn_scoped(scope, work)
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        *self.prefetched.borrow_mut() = read.into_inner().unwrap_or_else(|e| e.into_inner());
+    }
+
+    fn read_at_revision(&self, path: &Path, rev: &str) -> io::Result<String> {
+        self.inner.read_at_revision(path, rev)
+    }
+}
+
+/// Reads every file from `inner` as it was at the git revision `rev`, so includes
+/// with a `rev` go through the same restrictions and decoding as any other
+pub struct RevisionSource<'a> {
+    inner: &'a dyn PartialSource,
+    rev: &'a str,
+}
+
+impl<'a> RevisionSource<'a> {
+    pub fn new(inner: &'a dyn PartialSource, rev: &'a str) -> Self {
+        Self { inner, rev }
+    }
+}
+
+impl PartialSource for RevisionSource<'_> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.inner.read_at_revision(path, self.rev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_read_lines_stops_at_limit_and_splits_like_lines() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let content = "\u{feff}one\r\ntwo\n\nfour\r\nfive";
+        fs::write(temp_dir.path().join("code.rs"), content).expect("Failed to write code file");
+        let source = FileSystemSource::new(temp_dir.path());
+        let memory = MemorySource::new().with_file("code.rs", content);
+
+        for limit in [None, Some(0), Some(2), Some(5), Some(10)] {
+            let lines = source
+                .read_lines(Path::new("code.rs"), limit)
+                .expect("Failed to read lines");
+            let expected: Vec<String> = memory
+                .read_lines(Path::new("code.rs"), limit)
+                .expect("Failed to read lines")
+                .into_iter()
+                .map(|line| line.trim_start_matches('\u{feff}').to_string())
+                .collect();
+            assert_eq!(lines, expected, "limit {limit:?}");
+        }
+        assert_eq!(
+            source
+                .read_lines(Path::new("code.rs"), Some(2))
+                .expect("Failed to read lines"),
+            ["one", "two"]
+        );
+    }
+
+    #[test]
+    fn test_restricted_source_rejects_paths_outside_roots() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials = temp_dir.path().join("partials");
+        fs::create_dir_all(partials.join("sub")).expect("Failed to create partials directory");
+        fs::write(partials.join("ok.md"), "ok").expect("Failed to write ok.md");
+        fs::write(temp_dir.path().join("secret.md"), "secret").expect("Failed to write secret.md");
+        let files = FileSystemSource::default();
+        let source = RestrictedSource::new(&files, [partials.clone()]);
+
+        assert_eq!(
+            source
+                .read(&partials.join("ok.md"))
+                .expect("Failed to read"),
+            "ok"
+        );
+        assert_eq!(
+            source
+                .read(&partials.join("sub/../ok.md"))
+                .expect("Failed to read"),
+            "ok"
+        );
+        for outside in [
+            partials.join("../secret.md"),
+            temp_dir.path().join("secret.md"),
+            PathBuf::from("/etc/passwd"),
+        ] {
+            let error = source.read(&outside).expect_err("Outside the roots");
+            assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(temp_dir.path().join("secret.md"), partials.join("link.md"))
+                .expect("Failed to create symlink");
+            assert!(source.read(&partials.join("link.md")).is_err());
+        }
+    }
+
+    #[test]
+    fn test_revision_source_keeps_the_restrictions() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials = temp_dir.path().join("partials");
+        let files = FileSystemSource::default();
+        let restricted = RestrictedSource::new(&files, [partials]);
+        let source = RevisionSource::new(&restricted, "HEAD");
+
+        let error = source
+            .read(&temp_dir.path().join("secret.md"))
+            .expect_err("Outside the roots");
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+
+        let memory = MemorySource::new().with_file("a.md", "a");
+        let error = RevisionSource::new(&memory, "HEAD")
+            .read(Path::new("a.md"))
+            .expect_err("Memory has no revisions");
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn test_layered_source_reads_the_top_layer_that_has_the_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        fs::write(temp_dir.path().join("header.md"), "# Disk").expect("Failed to write header.md");
+        fs::write(temp_dir.path().join("footer.md"), "Footer").expect("Failed to write footer.md");
+        let files = FileSystemSource::new(temp_dir.path());
+        let source = LayeredSource::new()
+            .with_layer(MemorySource::new().with_file("header.md", "# Memory\nDraft"))
+            .with_layer(&files);
+
+        assert_eq!(
+            source
+                .read(Path::new("header.md"))
+                .expect("Failed to read header.md"),
+            "# Memory\nDraft"
+        );
+        assert_eq!(
+            source
+                .read_lines(Path::new("header.md"), Some(1))
+                .expect("Failed to read lines"),
+            ["# Memory"]
+        );
+        assert_eq!(
+            source
+                .read(Path::new("footer.md"))
+                .expect("Failed to read footer.md"),
+            "Footer"
+        );
+        let missing = source
+            .read(Path::new("missing.md"))
+            .expect_err("No layer has missing.md");
+        assert_eq!(missing.kind(), io::ErrorKind::NotFound);
+        assert_eq!(
+            LayeredSource::new()
+                .read(Path::new("header.md"))
+                .expect_err("No layers")
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+
+        // A layer refusing a path doesn't let a lower one serve it
+        let secret = temp_dir.path().join("footer.md");
+        let restricted = RestrictedSource::new(&files, [temp_dir.path().join("partials")]);
+        let source = LayeredSource::new()
+            .with_layer(restricted)
+            .with_layer(&files);
+        assert_eq!(
+            source
+                .read(&secret)
+                .expect_err("Restricted layer should refuse")
+                .kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+
+    #[test]
+    fn test_prefetch_source_keeps_the_last_prefetch() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let paths: Vec<PathBuf> = (0..PREFETCH_THRESHOLD)
+            .map(|index| temp_dir.path().join(format!("part{index}.md")))
+            .collect();
+        for path in &paths {
+            fs::write(path, "before").expect("Failed to write partial");
+        }
+        let source = PrefetchSource::new(FileSystemSource::default());
+
+        source.prefetch(&paths);
+        fs::write(&paths[0], "after").expect("Failed to update partial");
+        assert_eq!(source.read(&paths[0]).expect("Failed to read"), "before");
+
+        // Too few to prefetch, but what the last document prefetched is dropped
+        source.prefetch(&paths[..1]);
+        assert_eq!(source.read(&paths[0]).expect("Failed to read"), "after");
+        assert!(source.read(&temp_dir.path().join("missing.md")).is_err());
+    }
+}
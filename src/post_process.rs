@@ -0,0 +1,85 @@
+use std::path::Path;
+use std::process::Command;
+
+/// Runs a post-processing command on a written output file.
+///
+/// Every `{}` in `command` is replaced with the quoted output path; when there is
+/// none the path is appended. The command runs through the platform shell, so
+/// pipes and quoting work as on the command line.
+pub fn run_post_process(
+    command: &str,
+    output_file: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let path = shell_quote(&output_file.to_string_lossy());
+    let command_line = if command.contains("{}") {
+        command.replace("{}", &path)
+    } else {
+        format!("{command} {path}")
+    };
+
+    let output = shell(&command_line)
+        .output()
+        .map_err(|e| format!("Failed to run `{command_line}`: {e}"))?;
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let status = match output.status.code() {
+        Some(code) => format!("exit code {code}"),
+        None => "a signal".to_string(),
+    };
+    match stderr.trim() {
+        "" => Err(format!("`{command_line}` failed with {status}").into()),
+        stderr => Err(format!("`{command_line}` failed with {status}: {stderr}").into()),
+    }
+}
+
+/// `command_line` run through the platform shell
+#[cfg(windows)]
+pub(crate) fn shell(command_line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(command_line);
+    command
+}
+
+/// `command_line` run through the platform shell
+#[cfg(not(windows))]
+pub(crate) fn shell(command_line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+#[cfg(windows)]
+fn shell_quote(value: &str) -> String {
+    format!("\"{value}\"")
+}
+
+#[cfg(not(windows))]
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_run_post_process_substitutes_output_path() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let output_file = temp_dir.path().join("it's here.md");
+        fs::write(&output_file, "# Title").expect("Failed to write output file");
+
+        run_post_process("echo formatted >> {}", &output_file)
+            .expect("Post-processing should succeed");
+        let content = fs::read_to_string(&output_file).expect("Failed to read output file");
+        assert_eq!(content, "# Titleformatted\n");
+
+        let error = run_post_process("sh -c 'echo broken >&2; exit 3'", &output_file)
+            .expect_err("A failing command should be reported");
+        assert!(error.to_string().contains("exit code 3: broken"));
+    }
+}
@@ -1,301 +1,3694 @@
-use crate::file_handler::{collect_markdown_files, write_file};
-use crate::include_resolver::process_includes_with_validation;
-use crate::types::{FileProcessResult, ProcessingConfig, ProcessingSummary};
+use crate::banner::{banner_segments, build_time, render_banner};
+use crate::config::CONFIG_FILE_NAME;
+use crate::encoding::read_text;
+use crate::error_code::ErrorCode;
+use crate::file_handler::{
+    SourceFiles, collect_markdown_files, collect_source_files, is_markdown, normalize_line_endings,
+    sort_files,
+};
+use crate::frontmatter::apply_frontmatter;
+use crate::git;
+use crate::glossary::Glossary;
+use crate::graph::{DependencyGraph, normalize_path};
+use crate::html::{DEFAULT_TEMPLATE, render_page};
+use crate::include_resolver::{
+    CodeFences, IncludeCache, ResolveOptions, process_includes_with_variables, resolve_includes,
+    resolve_includes_with_origins,
+};
+use crate::links::{find_broken_links, find_broken_output_links};
+use crate::lint::{LintConfig, lint};
+use crate::mdx;
+use crate::normalize::normalize;
+use crate::partial_source::{FileSystemSource, PartialSource, PrefetchSource, RestrictedSource};
+use crate::post_process::run_post_process;
+use crate::sink::{FileSystemSink, OutputSink};
+use crate::source_map::{self, LineOrigin, SourceMap};
+use crate::spellcheck::{SpellcheckConfig, Spellchecker};
+use crate::types::{
+    CancellationToken, Compat, ContentProcessResult, ContentWarning, DuplicateHeadings, Emit,
+    FileOrder, FileProcessResult, FileSizes, Frontmatter, HtmlOptions, IncludeResult,
+    InputEncoding, LineEndings, Normalization, OutputBudget, OutputDiff, OutputNaming,
+    ProcessingConfig, ProcessingEvent, ProcessingSummary, ResolutionOrder, RetryPolicy, RunConfig,
+    SkipReason, SkippedFile, SourceComments, TemplateSyntax, WarningKind,
+};
+use crate::validation::{
+    ConfigError, check_inputs, check_output, check_settings, settings_warnings,
+};
+use crate::warnings::{WarningCollector, find_leftover_directives};
+use regex::Regex;
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, SystemTime};
 
+/// Embeddable md2md processor.
+///
+/// Unlike the command-line interface it never prints or exits the process:
+/// every outcome is reported through the returned [`ProcessingSummary`].
+///
+/// ```no_run
+/// use md2md::Processor;
+///
+/// let summary = Processor::builder()
+///     .source("docs")
+///     .partials("partials")
+///     .output("out")
+///     .var("version", "1.2.0")
+///     .build()?
+///     .run()?;
+/// assert_eq!(summary.get_failed_count(), 0);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct Processor {
+    config: ProcessingConfig,
+    events: Option<Sender<ProcessingEvent>>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl Processor {
+    pub fn builder() -> ProcessorBuilder {
+        ProcessorBuilder::default()
+    }
+
+    pub fn config(&self) -> &ProcessingConfig {
+        &self.config
+    }
+
+    /// Processes every source file and returns the collected results
+    pub fn run(&self) -> Result<ProcessingSummary, Box<dyn std::error::Error>> {
+        self.run_to(&FileSystemSink)
+    }
+
+    /// [`Processor::run`], writing the outputs to `sink` instead of the output path.
+    /// Links are only checked and post-processing only runs when the sink writes to disk.
+    pub fn run_to(
+        &self,
+        sink: &dyn OutputSink,
+    ) -> Result<ProcessingSummary, Box<dyn std::error::Error>> {
+        let mut summary = ProcessingSummary::new();
+        process_files_to(
+            &self.config,
+            sink,
+            &mut summary,
+            self.events.as_ref(),
+            self.cancellation.as_ref(),
+        )?;
+        Ok(summary)
+    }
+
+    /// Validates every source file without writing any output
+    pub fn check(&self) -> Result<ProcessingSummary, Box<dyn std::error::Error>> {
+        let mut summary = ProcessingSummary::new();
+        check_files(
+            &self.config.source_path,
+            &self.config.partials_path,
+            &CheckOptions {
+                variables: &self.config.variables,
+                fix_code_fences: self.config.fix_code_fences.as_deref(),
+                compat: self.config.compat,
+                passthrough: self.config.passthrough,
+                input_encoding: self.config.input_encoding,
+                restrict_includes: self.config.restrict_includes,
+                resolution_order: self.config.resolution_order,
+                locale: self.config.locale.as_deref(),
+            },
+            &mut summary,
+        )?;
+        Ok(summary)
+    }
+}
+
+/// Builder for [`Processor`] and [`ProcessingConfig`]. Only the source path is
+/// required; the partials and output paths default to `partials` and `out` like the CLI.
+/// Building validates the configuration like [`ProcessingConfig::validate`], except
+/// that an output overlapping the inputs is only logged as a warning.
+#[derive(Default)]
+pub struct ProcessorBuilder {
+    source: Option<PathBuf>,
+    partials: Option<PathBuf>,
+    output: Option<PathBuf>,
+    fix_code_fences: Option<String>,
+    variables: HashMap<String, String>,
+    order: FileOrder,
+    post_process: Option<String>,
+    naming: OutputNaming,
+    compat: Compat,
+    passthrough: Option<TemplateSyntax>,
+    emit: Emit,
+    html: HtmlOptions,
+    bundle_links: bool,
+    check_links: bool,
+    duplicate_headings: Option<DuplicateHeadings>,
+    line_endings: LineEndings,
+    normalization: Normalization,
+    source_comments: Option<SourceComments>,
+    banner: Option<String>,
+    diff: bool,
+    input_encoding: InputEncoding,
+    restrict_includes: bool,
+    resolution_order: ResolutionOrder,
+    frontmatter: Frontmatter,
+    locale: Option<String>,
+    budget: OutputBudget,
+    lint: Option<LintConfig>,
+    spellcheck: Option<SpellcheckConfig>,
+    source_map: bool,
+    strict: bool,
+    error_template: Option<String>,
+    retry: RetryPolicy,
+    changed_since: Option<String>,
+    glossary: Option<PathBuf>,
+    events: Option<Sender<ProcessingEvent>>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl ProcessingConfig {
+    pub fn builder() -> ProcessorBuilder {
+        ProcessorBuilder::default()
+    }
+}
+
+impl ProcessorBuilder {
+    /// The source file or directory to process
+    pub fn source(mut self, path: impl Into<PathBuf>) -> Self {
+        self.source = Some(path.into());
+        self
+    }
+
+    /// The directory containing the partials
+    pub fn partials(mut self, path: impl Into<PathBuf>) -> Self {
+        self.partials = Some(path.into());
+        self
+    }
+
+    /// Output file (for a source file) or directory (for a source directory)
+    pub fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.output = Some(path.into());
+        self
+    }
+
+    /// Adds `language` to code fences that don't specify one instead of failing
+    pub fn fix_code_fences(mut self, language: impl Into<String>) -> Self {
+        self.fix_code_fences = Some(language.into());
+        self
+    }
+
+    /// Sets a variable available to every included partial
+    pub fn var(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.variables.insert(name.into(), value.into());
+        self
+    }
+
+    /// Order in which the files of a source directory are processed. Default: by name
+    pub fn order(mut self, order: FileOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Runs `command` on every written output file, e.g. `prettier --write {}`
+    pub fn post_process(mut self, command: impl Into<String>) -> Self {
+        self.post_process = Some(command.into());
+        self
+    }
+
+    /// Names output files from `template`, e.g. `{stem}.generated.md`
+    pub fn rename(mut self, template: impl Into<String>) -> Self {
+        self.naming.rename = Some(template.into());
+        self
+    }
+
+    /// Writes every output directly into the output directory
+    pub fn flatten(mut self, flatten: bool) -> Self {
+        self.naming.flatten = flatten;
+        self
+    }
+
+    /// Directive dialect of the source documents. Default: md2md
+    pub fn compat(mut self, compat: Compat) -> Self {
+        self.compat = compat;
+        self
+    }
+
+    /// Keeps the tags of `syntax` intact instead of treating them as md2md variables
+    pub fn passthrough(mut self, syntax: TemplateSyntax) -> Self {
+        self.passthrough = Some(syntax);
+        self
+    }
+
+    /// Formats written for every processed file. Default: Markdown
+    pub fn emit(mut self, emit: Emit) -> Self {
+        self.emit = emit;
+        self
+    }
+
+    /// Wraps HTML output in the template at `path` instead of the default page
+    pub fn html_template(mut self, path: impl Into<PathBuf>) -> Self {
+        self.html.template = Some(path.into());
+        self
+    }
+
+    /// Links the stylesheet at `href` from every HTML page
+    pub fn css(mut self, href: impl Into<String>) -> Self {
+        self.html.css = Some(href.into());
+        self
+    }
+
+    /// Turns links to included files, like `./setup.md#install`, into anchors of the bundled output
+    pub fn bundle_links(mut self, bundle_links: bool) -> Self {
+        self.bundle_links = bundle_links;
+        self
+    }
+
+    /// Validates the links and anchors of every written Markdown output after the run
+    pub fn check_links(mut self, check_links: bool) -> Self {
+        self.check_links = check_links;
+        self
+    }
+
+    /// Warns about or deduplicates headings whose anchor repeats an earlier one
+    pub fn duplicate_headings(mut self, handling: DuplicateHeadings) -> Self {
+        self.duplicate_headings = Some(handling);
+        self
+    }
+
+    /// Line breaks of the written files. Default: preserved from the source and partials
+    pub fn line_endings(mut self, line_endings: LineEndings) -> Self {
+        self.line_endings = line_endings;
+        self
+    }
+
+    /// Cleans up whitespace of the written Markdown with the enabled rules
+    pub fn normalization(mut self, normalization: Normalization) -> Self {
+        self.normalization = normalization;
+        self
+    }
+
+    /// Wraps included partials in source-map comments, or strips such comments
+    pub fn source_comments(mut self, source_comments: SourceComments) -> Self {
+        self.source_comments = Some(source_comments);
+        self
+    }
+
+    /// Puts a banner at the top of every output, e.g. `<!-- Generated from {source} -->`
+    pub fn banner(mut self, template: impl Into<String>) -> Self {
+        self.banner = Some(template.into());
+        self
+    }
+
+    /// Reports how every output would change as [`ProcessingEvent::OutputDiff`] instead of writing it
+    pub fn diff(mut self, diff: bool) -> Self {
+        self.diff = diff;
+        self
+    }
+
+    /// Encoding the source documents and partials are read in. Default: UTF-8
+    pub fn input_encoding(mut self, encoding: InputEncoding) -> Self {
+        self.input_encoding = encoding;
+        self
+    }
+
+    /// Fails includes and code snippets outside the source and partials directories
+    pub fn restrict_includes(mut self, restrict_includes: bool) -> Self {
+        self.restrict_includes = restrict_includes;
+        self
+    }
+
+    /// Where plain include paths are looked up. Default: the partials directory only
+    pub fn resolution_order(mut self, order: ResolutionOrder) -> Self {
+        self.resolution_order = order;
+        self
+    }
+
+    /// Keeps, strips or adds generated keys to each document's frontmatter. Default: keep
+    pub fn frontmatter(mut self, frontmatter: Frontmatter) -> Self {
+        self.frontmatter = frontmatter;
+        self
+    }
+
+    /// Prefers translations of partials in `locale`, e.g. `header.de.md` over `header.md`
+    pub fn locale(mut self, locale: impl Into<String>) -> Self {
+        self.locale = Some(locale.into());
+        self
+    }
+
+    /// Fails outputs that would go over the per-file or total size budget instead of writing them
+    pub fn budget(mut self, budget: OutputBudget) -> Self {
+        self.budget = budget;
+        self
+    }
+
+    /// Checks every Markdown output against `rules`, reporting problems as warnings
+    pub fn lint(mut self, rules: LintConfig) -> Self {
+        self.lint = Some(rules);
+        self
+    }
+
+    /// Runs the checker of `config` on every resolved document, reporting misspellings as warnings
+    pub fn spellcheck(mut self, config: SpellcheckConfig) -> Self {
+        self.spellcheck = Some(config);
+        self
+    }
+
+    /// Writes a [`SourceMap`] of every Markdown output next to it, as `<output>.map.json`
+    pub fn source_map(mut self, source_map: bool) -> Self {
+        self.source_map = source_map;
+        self
+    }
+
+    /// Fails files with a failed directive, or a `<!-- Failed to include: ... -->`
+    /// comment from anywhere, instead of writing them
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Writes `template`, with `{path}`, `{error}` and `{code}` filled in, in place of
+    /// failed directives instead of an HTML comment; an empty one writes nothing
+    pub fn error_template(mut self, template: impl Into<String>) -> Self {
+        self.error_template = Some(template.into());
+        self
+    }
+
+    /// Only processes the documents affected by files changed since `rev` in git, committed
+    /// or not; the others are skipped as unchanged. Needs the `git` feature
+    pub fn changed_since(mut self, rev: impl Into<String>) -> Self {
+        self.changed_since = Some(rev.into());
+        self
+    }
+
+    /// Links the first occurrence of each term of the glossary file at `path` in every
+    /// output to its entry
+    pub fn glossary(mut self, path: impl Into<PathBuf>) -> Self {
+        self.glossary = Some(path.into());
+        self
+    }
+
+    /// Reads includes again, up to `retries` times, when they fail transiently, e.g. on a
+    /// network filesystem; `delay` is the wait before the first retry and doubles after it
+    pub fn retries(mut self, retries: u32, delay: Duration) -> Self {
+        self.retry = RetryPolicy {
+            retries,
+            delay_ms: delay.as_millis() as u64,
+        };
+        self
+    }
+
+    /// Sends a [`ProcessingEvent`] for every step of a run to `sender`
+    pub fn events(mut self, sender: Sender<ProcessingEvent>) -> Self {
+        self.events = Some(sender);
+        self
+    }
+
+    /// Lets another thread stop a run by cancelling `token`
+    pub fn cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    pub fn build(mut self) -> Result<Processor, Box<dyn std::error::Error>> {
+        let events = self.events.take();
+        let cancellation = self.cancellation.take();
+        Ok(Processor {
+            config: self.build_config()?,
+            events,
+            cancellation,
+        })
+    }
+
+    /// The validated configuration, for running with [`process_files`]
+    pub fn build_config(self) -> Result<ProcessingConfig, ConfigError> {
+        let source_path = self.source.ok_or(ConfigError::MissingSource)?;
+        let partials_path = self.partials.unwrap_or_else(|| PathBuf::from("partials"));
+        let output_path = self.output.unwrap_or_else(|| PathBuf::from("out"));
+        check_inputs(&source_path, &partials_path)?;
+        if !self.diff {
+            match check_output(&source_path, &partials_path, &output_path) {
+                Err(ConfigError::OutputOverlap(overlap)) => tracing::warn!("{overlap}"),
+                result => result?,
+            }
+        }
+
+        let config = ProcessingConfig {
+            batch: source_path.is_dir(),
+            source_path,
+            partials_path,
+            output_path,
+            verbose: false,
+            fix_code_fences: self.fix_code_fences,
+            variables: self.variables,
+            order: self.order,
+            post_process: self.post_process,
+            naming: self.naming,
+            compat: self.compat,
+            passthrough: self.passthrough,
+            emit: self.emit,
+            html: self.html,
+            bundle_links: self.bundle_links,
+            check_links: self.check_links,
+            duplicate_headings: self.duplicate_headings,
+            line_endings: self.line_endings,
+            normalization: self.normalization,
+            source_comments: self.source_comments,
+            banner: self.banner,
+            diff: self.diff,
+            input_encoding: self.input_encoding,
+            restrict_includes: self.restrict_includes,
+            resolution_order: self.resolution_order,
+            frontmatter: self.frontmatter,
+            locale: self.locale,
+            budget: self.budget,
+            lint: self.lint,
+            spellcheck: self.spellcheck,
+            source_map: self.source_map,
+            strict: self.strict,
+            error_template: self.error_template,
+            retry: self.retry,
+            changed_since: self.changed_since,
+            glossary: self.glossary,
+        };
+        check_settings(&config)?;
+        Ok(config)
+    }
+}
+
+/// Processes every source file in `config`, recording the results in `summary`.
+///
+/// Each step is also sent as a [`ProcessingEvent`] to `events`, when given; a
+/// disconnected receiver does not stop the run. Cancelling `cancellation` stops
+/// the run before the next file; the file in progress is still finished.
 pub fn process_files(
     config: &ProcessingConfig,
     summary: &mut ProcessingSummary,
-    progress_callback: impl Fn(&ProcessingSummary),
+    events: Option<&Sender<ProcessingEvent>>,
+    cancellation: Option<&CancellationToken>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let files = collect_markdown_files(&config.source_path)
-        .expect("Failed to collect markdown files from source path");
-    summary.set_total_files(files.len());
+    process_files_to(config, &FileSystemSink, summary, events, cancellation)
+}
+
+/// [`process_files`], writing the outputs to `sink`
+pub fn process_files_to(
+    config: &ProcessingConfig,
+    sink: &dyn OutputSink,
+    summary: &mut ProcessingSummary,
+    events: Option<&Sender<ProcessingEvent>>,
+    cancellation: Option<&CancellationToken>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    process_files_within(
+        config,
+        &source_root(&config.source_path),
+        sink,
+        summary,
+        events,
+        cancellation,
+    )
+}
+
+/// [`process_files_to`], with includes restricted to `source_root` rather than that of `config`
+fn process_files_within(
+    config: &ProcessingConfig,
+    source_root: &Path,
+    sink: &dyn OutputSink,
+    summary: &mut ProcessingSummary,
+    events: Option<&Sender<ProcessingEvent>>,
+    cancellation: Option<&CancellationToken>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    emit(
+        summary,
+        events,
+        ProcessingEvent::RunStarted(RunConfig::from(config)),
+    );
+    let SourceFiles {
+        mut files,
+        mut skipped,
+    } = collect_source_files(&config.source_path)?;
+    if let Some(rev) = &config.changed_since {
+        skipped.extend(take_unchanged(&mut files, config, rev)?);
+    }
+    sort_files(&mut files, config.order);
+    let total = files.len();
+    summary.set_total_files(total);
+    if !skipped.is_empty() {
+        emit(summary, events, ProcessingEvent::FilesSkipped(skipped));
+    }
+    if files.is_empty() {
+        let message = format!(
+            "No markdown files found in {}",
+            config.source_path.display()
+        );
+        tracing::warn!("{message}");
+        emit(summary, events, ProcessingEvent::Warning(message));
+    }
+    for message in settings_warnings(config) {
+        tracing::warn!("{message}");
+        emit(summary, events, ProcessingEvent::Warning(message));
+    }
+    // Both look for the outputs on disk
+    let check_links = config.check_links && sink.on_disk();
+    let post_process = config.post_process.as_deref().filter(|_| sink.on_disk());
+    if !config.diff && !sink.on_disk() {
+        for (set, step) in [
+            (config.check_links, "Links aren't checked"),
+            (config.post_process.is_some(), "Post-processing is skipped"),
+        ] {
+            if set {
+                let message = format!("{step}: the outputs aren't written to disk");
+                tracing::warn!("{message}");
+                emit(summary, events, ProcessingEvent::Warning(message));
+            }
+        }
+    }
+
+    let cache = IncludeCache::new();
+    // Documents with many includes have their partials read on several threads
+    let files_source =
+        PrefetchSource::new(FileSystemSource::default().with_encoding(config.input_encoding));
+    let source = include_source(
+        &files_source,
+        config.restrict_includes,
+        source_root,
+        &config.partials_path,
+    );
+    let options = ResolveOptions {
+        partials_path: &config.partials_path,
+        variables: &config.variables,
+        source: source.as_ref(),
+        compat: config.compat,
+        passthrough: config.passthrough,
+        bundle_links: config.bundle_links,
+        duplicate_headings: config.duplicate_headings,
+        source_comments: config.source_comments,
+        events,
+        cache: Some(&cache),
+        resolution_order: config.resolution_order,
+        locale: config.locale.as_deref(),
+        error_template: config.error_template.as_deref(),
+        retry: config.retry,
+        warnings: None,
+    };
+    let html_template = match &config.html.template {
+        Some(path) if config.emit.html() => fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read HTML template {}: {e}", path.display()))?,
+        _ => DEFAULT_TEMPLATE.to_string(),
+    };
+    let spellchecker = config
+        .spellcheck
+        .as_ref()
+        .map(Spellchecker::new)
+        .transpose()?;
+    let glossary = config.glossary.as_deref().map(Glossary::load).transpose()?;
+    let output = OutputOptions {
+        sink,
+        post_process,
+        emit: config.emit,
+        html_template: &html_template,
+        css: config.html.css.as_deref(),
+        line_endings: config.line_endings,
+        normalization: config.normalization,
+        banner: config.banner.as_deref(),
+        frontmatter: config.frontmatter,
+        time: build_time(),
+        diff: config.diff,
+        budget: config.budget,
+        spent: Cell::new(0),
+        lint: config.lint.as_ref(),
+        spellchecker: spellchecker.as_ref(),
+        glossary: glossary.as_ref(),
+        source_map: config.source_map,
+        strict: config.strict,
+    };
+
+    let mut cancelled = false;
+    // Output path → the source that claimed it, to detect collisions from renaming or flattening
+    let mut claimed_outputs: HashMap<PathBuf, PathBuf> = HashMap::new();
+    // Markdown outputs written in this run, by source file, for `check_links`
+    let mut written_outputs: Vec<(String, PathBuf)> = Vec::new();
+    for (index, file_path) in files.into_iter().enumerate() {
+        if cancellation.is_some_and(CancellationToken::is_cancelled) {
+            tracing::info!(processed = index, total, "Processing cancelled");
+            cancelled = true;
+            break;
+        }
 
-    for file_path in files {
         // Calculate output path
         let output_path = if config.batch {
-            calculate_output_path(&file_path, &config.source_path, &config.output_path)
-                .expect("Failed to calculate output path for file")
+            calculate_output_path(
+                &file_path,
+                &config.source_path,
+                &config.output_path,
+                &config.naming,
+            )
+            .and_then(|output_path| match claimed_outputs.get(&output_path) {
+                Some(other) => Err(ErrorCode::OutputCollision.error(format!(
+                    "{} is already written by {}",
+                    output_path.display(),
+                    other.display()
+                ))),
+                None => {
+                    claimed_outputs.insert(output_path.clone(), file_path.clone());
+                    Ok(output_path)
+                }
+            })
+        } else {
+            Ok(config.output_path.clone())
+        };
+
+        let _span = tracing::info_span!("file", path = %file_path.display()).entered();
+        tracing::info!("Processing file");
+
+        emit(
+            summary,
+            events,
+            ProcessingEvent::FileStarted {
+                path: file_path.to_string_lossy().to_string(),
+                index,
+                total,
+            },
+        );
+
+        // Problems with a single file are recorded in its result so the rest of the batch still runs
+        let mut outputs = FileOutputs::default();
+        let found = WarningCollector::new(&file_path, &config.output_path);
+        let result = match &output_path {
+            Ok(output_path) => process_single_file(
+                &file_path,
+                output_path,
+                config.fix_code_fences.as_deref(),
+                config.input_encoding,
+                &output,
+                &ResolveOptions {
+                    warnings: Some(&found),
+                    ..options
+                },
+                &mut outputs,
+            ),
+            Err(e) => Err(ErrorCode::of(e.as_ref())
+                .unwrap_or(ErrorCode::InvalidOutputPath)
+                .error(format!("Failed to calculate output path: {e}"))),
+        }
+        .unwrap_or_else(|e| {
+            failed_result(
+                &file_path,
+                Vec::new(),
+                ErrorCode::of(e.as_ref()),
+                e.to_string(),
+            )
+        });
+
+        if let (true, Ok(output_path)) = (result.success, &output_path) {
+            tracing::info!(output = %output_path.display(), "File processed");
         } else {
-            config.output_path.clone()
+            tracing::warn!(
+                error = result.error_message.as_deref().unwrap_or("Unknown error"),
+                "File processed with errors"
+            );
+        }
+
+        if check_links {
+            written_outputs.extend(
+                outputs
+                    .written
+                    .into_iter()
+                    .filter(|path| is_markdown(path))
+                    .map(|path| (result.file_path.clone(), path)),
+            );
+        }
+
+        for include in &result.includes {
+            emit(
+                summary,
+                events,
+                ProcessingEvent::IncludeResolved {
+                    file: result.file_path.clone(),
+                    include: include.clone(),
+                },
+            );
+        }
+        for diff in outputs.diffs {
+            emit(summary, events, ProcessingEvent::OutputDiff(diff));
+        }
+        for notice in outputs.notices {
+            emit(summary, events, ProcessingEvent::Warning(notice));
+        }
+        for warning in found.into_warnings().into_iter().chain(outputs.warnings) {
+            tracing::warn!(file = %warning.file, line = warning.line, "{}", warning.message);
+            emit(summary, events, ProcessingEvent::ContentWarning(warning));
+        }
+        emit(summary, events, ProcessingEvent::FileFinished(result));
+    }
+
+    // Outputs link to each other, so links are only checked once every file is written
+    for (file, output_file) in written_outputs {
+        let links = match fs::read_to_string(&output_file) {
+            Ok(content) => find_broken_output_links(&content, &output_file),
+            Err(e) => {
+                tracing::warn!(output = %output_file.display(), error = %e, "Failed to read output for link checking");
+                continue;
+            }
+        };
+        if !links.is_empty() {
+            emit(
+                summary,
+                events,
+                ProcessingEvent::BrokenLinks { file, links },
+            );
+        }
+    }
+
+    tracing::debug!(hits = cache.hits(), "Includes served from the cache");
+    let (processed, failed) = (summary.processed_files, summary.get_failed_count());
+    emit(
+        summary,
+        events,
+        ProcessingEvent::RunFinished {
+            processed,
+            failed,
+            cancelled,
+        },
+    );
+
+    Ok(())
+}
+
+/// Processes one file of a run of `config` again, e.g. after fixing a missing partial.
+///
+/// The file is written where the full run writes it; merge the returned summary
+/// into the run's with [`ProcessingSummary::replace_results`].
+pub fn reprocess_file(
+    config: &ProcessingConfig,
+    file_path: &Path,
+) -> Result<ProcessingSummary, Box<dyn std::error::Error>> {
+    let output_path = if config.batch {
+        calculate_output_path(
+            file_path,
+            &config.source_path,
+            &config.output_path,
+            &config.naming,
+        )?
+    } else {
+        config.output_path.clone()
+    };
+    let file_config = ProcessingConfig {
+        source_path: file_path.to_path_buf(),
+        output_path,
+        batch: false,
+        // The file is known to be affected
+        changed_since: None,
+        ..config.clone()
+    };
+
+    let mut summary = ProcessingSummary::new();
+    process_files_within(
+        &file_config,
+        &source_root(&config.source_path),
+        &FileSystemSink,
+        &mut summary,
+        None,
+        None,
+    )?;
+    Ok(summary)
+}
+
+/// Takes the documents that depend on no file changed since `rev` out of `files`,
+/// following includes and code snippets through the dependency graph.
+///
+/// Changed files outside the source and partials directories that aren't code
+/// snippets are ignored. Every document is kept when a file settings are read from
+/// changed, like md2md.toml or the glossary, or one the graph doesn't know of, like
+/// a translated partial, and documents with a `rev=` include are always kept.
+fn take_unchanged(
+    files: &mut Vec<PathBuf>,
+    config: &ProcessingConfig,
+    rev: &str,
+) -> Result<Vec<SkippedFile>, Box<dyn std::error::Error>> {
+    let changed = git::changed_files(&config.source_path, rev)
+        .map_err(|e| format!("--changed-since {rev}: {e}"))?;
+    let graph = DependencyGraph::build(
+        &config.source_path,
+        &config.partials_path,
+        config.input_encoding,
+    )?;
+
+    let absolute = |path: &Path| normalize_path(&std::path::absolute(path).unwrap_or_default());
+    let roots = [
+        absolute(&source_root(&config.source_path)),
+        absolute(&config.partials_path),
+    ];
+    let snippets: BTreeSet<PathBuf> = graph
+        .edges
+        .iter()
+        .filter(|edge| edge.kind != "include")
+        .map(|edge| absolute(Path::new(&edge.to)))
+        .collect();
+    let settings: Vec<PathBuf> = config
+        .glossary
+        .iter()
+        .chain(&config.html.template)
+        .chain(config.spellcheck.iter().flat_map(|s| &s.dictionaries))
+        .map(|path| absolute(path))
+        .collect();
+
+    let mut affected: BTreeSet<String> = graph
+        .revision_includers
+        .iter()
+        .flat_map(|file| graph.affected_sources(Path::new(file)))
+        .collect();
+    for file in &changed {
+        let path = absolute(file);
+        if path.file_name() == Some(CONFIG_FILE_NAME.as_ref()) || settings.contains(&path) {
+            tracing::info!(rev, file = %file.display(), "Processing every document, as settings changed");
+            return Ok(Vec::new());
+        }
+        if !snippets.contains(&path) && !roots.iter().any(|root| path.starts_with(root)) {
+            continue;
+        }
+        if !graph.contains(file) {
+            tracing::info!(rev, file = %file.display(), "Processing every document, as a changed file isn't in the dependency graph");
+            return Ok(Vec::new());
+        }
+        affected.extend(graph.affected_sources(file));
+    }
+
+    let (kept, unchanged): (Vec<PathBuf>, Vec<PathBuf>) = files
+        .drain(..)
+        .partition(|file| affected.contains(normalize_path(file).to_string_lossy().as_ref()));
+    tracing::info!(
+        rev,
+        changed = changed.len(),
+        affected = kept.len(),
+        unchanged = unchanged.len(),
+        "Processing only the documents affected by changes"
+    );
+    *files = kept;
+    Ok(unchanged
+        .iter()
+        .map(|file| SkippedFile {
+            path: file.to_string_lossy().to_string(),
+            reason: SkipReason::Unchanged,
+        })
+        .collect())
+}
+
+/// The directory of the source documents: `source_path` itself, or the directory of a single file
+fn source_root(source_path: &Path) -> PathBuf {
+    if source_path.is_dir() {
+        return source_path.to_path_buf();
+    }
+    match source_path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Where includes are read from: `files`, limited to the source and partials directories
+/// with `restrict_includes`
+fn include_source<'a>(
+    files: &'a dyn PartialSource,
+    restrict_includes: bool,
+    source_root: &Path,
+    partials_path: &Path,
+) -> Box<dyn PartialSource + 'a> {
+    if restrict_includes {
+        Box::new(RestrictedSource::new(
+            files,
+            [source_root.to_path_buf(), partials_path.to_path_buf()],
+        ))
+    } else {
+        Box::new(files)
+    }
+}
+
+/// Records `event` in the summary and forwards it to the event channel
+fn emit(
+    summary: &mut ProcessingSummary,
+    events: Option<&Sender<ProcessingEvent>>,
+    event: ProcessingEvent,
+) {
+    summary.apply(&event);
+    if let Some(sender) = events {
+        let _ = sender.send(event);
+    }
+}
+
+/// Stands in for the file name of in-memory content, which has no location:
+/// `!include (x.md)` and `!codesnippet (x.rs)` both resolve to `x.md`/`x.rs` in `source`
+const IN_MEMORY_FILE: &str = "<string>";
+
+/// Resolves every directive in `content`, reading partials and code snippets from `source`.
+///
+/// ```
+/// use md2md::partial_source::MemorySource;
+/// use md2md::processor::process_string;
+///
+/// let partials = MemorySource::new().with_file("header.md", "# Hello");
+/// let result = process_string("!include (header.md)\n\nBody", &partials)?;
+/// assert_eq!(result.content, "# Hello\n\nBody");
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn process_string(
+    content: &str,
+    source: &dyn PartialSource,
+) -> Result<ContentProcessResult, Box<dyn std::error::Error>> {
+    let mut includes = Vec::new();
+    let content = process_includes_with_variables(
+        content,
+        Path::new(IN_MEMORY_FILE),
+        Path::new(""),
+        &mut includes,
+        None,
+        &HashMap::new(),
+        source,
+    )?;
+    Ok(ContentProcessResult { content, includes })
+}
+
+/// How [`check_files`] resolves the sources
+#[derive(Debug, Clone, Copy)]
+pub struct CheckOptions<'a> {
+    /// Variables for `{% name %}` substitution and `if` conditions, as in a build
+    pub variables: &'a HashMap<String, String>,
+    /// Language added to code fences that don't specify one; without it they fail
+    pub fix_code_fences: Option<&'a str>,
+    pub compat: Compat,
+    pub passthrough: Option<TemplateSyntax>,
+    pub input_encoding: InputEncoding,
+    /// Fails includes and code snippets outside the source and partials directories
+    pub restrict_includes: bool,
+    pub resolution_order: ResolutionOrder,
+    pub locale: Option<&'a str>,
+}
+
+static NO_VARIABLES: LazyLock<HashMap<String, String>> = LazyLock::new(HashMap::new);
+
+impl Default for CheckOptions<'_> {
+    fn default() -> Self {
+        Self {
+            variables: &NO_VARIABLES,
+            fix_code_fences: None,
+            compat: Compat::default(),
+            passthrough: None,
+            input_encoding: InputEncoding::default(),
+            restrict_includes: false,
+            resolution_order: ResolutionOrder::default(),
+            locale: None,
+        }
+    }
+}
+
+/// Validates every markdown file under `source_path` without writing any output.
+///
+/// Each file has its directives resolved, its code fences validated and its
+/// relative links checked against the source tree.
+pub fn check_files(
+    source_path: &Path,
+    partials_path: &Path,
+    check: &CheckOptions,
+    summary: &mut ProcessingSummary,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let files = collect_markdown_files(source_path)?;
+    summary.set_total_files(files.len());
+    let cache = IncludeCache::new();
+    let files_source = FileSystemSource::default().with_encoding(check.input_encoding);
+    let source = include_source(
+        &files_source,
+        check.restrict_includes,
+        &source_root(source_path),
+        partials_path,
+    );
+
+    for file_path in files {
+        let _span = tracing::info_span!("check", path = %file_path.display()).entered();
+        summary.set_current_file(file_path.to_string_lossy().to_string());
+
+        let content = match read_text(&file_path, check.input_encoding) {
+            Ok(content) => content,
+            Err(e) => {
+                summary.add_result(failed_result(
+                    &file_path,
+                    Vec::new(),
+                    Some(ErrorCode::UnreadableSource),
+                    format!("Failed to read source file: {e}"),
+                ));
+                continue;
+            }
+        };
+        let mut includes_tracker = Vec::new();
+
+        let options = ResolveOptions {
+            compat: check.compat,
+            passthrough: check.passthrough,
+            cache: Some(&cache),
+            resolution_order: check.resolution_order,
+            locale: check.locale,
+            ..ResolveOptions::new(partials_path, check.variables, source.as_ref())
+        };
+        let result = match resolve_includes(
+            &content,
+            &file_path,
+            &mut includes_tracker,
+            check.fix_code_fences,
+            &options,
+        ) {
+            Ok(processed_content) => {
+                let base_dir = file_path.parent().unwrap_or(Path::new("."));
+                let broken_links = find_broken_links(&processed_content, base_dir);
+                let mut result = build_result(&file_path, includes_tracker);
+
+                result.add_broken_links(&broken_links);
+                result
+            }
+            Err(e) => failed_result(
+                &file_path,
+                includes_tracker,
+                ErrorCode::of(e.as_ref()),
+                format!("Failed to process includes: {e}"),
+            ),
+        };
+
+        summary.add_result(result);
+    }
+
+    Ok(())
+}
+
+/// What is written for each processed file
+struct OutputOptions<'a> {
+    sink: &'a dyn OutputSink,
+    post_process: Option<&'a str>,
+    emit: Emit,
+    html_template: &'a str,
+    css: Option<&'a str>,
+    line_endings: LineEndings,
+    normalization: Normalization,
+    banner: Option<&'a str>,
+    frontmatter: Frontmatter,
+    /// Run time for the banner's `{time}` and merged frontmatter
+    time: SystemTime,
+    /// Diffs against the existing files instead of writing
+    diff: bool,
+    budget: OutputBudget,
+    /// Bytes of the outputs so far, against the total budget
+    spent: Cell<u64>,
+    lint: Option<&'a LintConfig>,
+    spellchecker: Option<&'a Spellchecker>,
+    /// Links the first occurrence of each of its terms in Markdown and HTML outputs
+    glossary: Option<&'a Glossary>,
+    /// Writes `<output>.map.json` next to each Markdown output
+    source_map: bool,
+    /// Files with failed directives or error comments fail without being written
+    strict: bool,
+}
+
+impl Default for OutputOptions<'_> {
+    fn default() -> Self {
+        OutputOptions {
+            sink: &FileSystemSink,
+            post_process: None,
+            emit: Emit::Markdown,
+            html_template: DEFAULT_TEMPLATE,
+            css: None,
+            line_endings: LineEndings::Preserve,
+            normalization: Normalization::default(),
+            banner: None,
+            frontmatter: Frontmatter::Keep,
+            time: SystemTime::UNIX_EPOCH,
+            diff: false,
+            budget: OutputBudget::default(),
+            lint: None,
+            spellchecker: None,
+            glossary: None,
+            spent: Cell::new(0),
+            source_map: false,
+            strict: false,
+        }
+    }
+}
+
+impl OutputOptions<'_> {
+    /// Writes `content` of `source_file` in every requested format, or diffs it in diff mode.
+    /// `origins` are those of the lines of `content`, for the source map.
+    fn write(
+        &self,
+        source_file: &Path,
+        output_file: &Path,
+        content: &str,
+        origins: &[Option<LineOrigin>],
+        outputs: &mut FileOutputs,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let resolved = content;
+        let content = apply_frontmatter(content, self.frontmatter, source_file, self.time);
+        let content = normalize(&content, self.normalization);
+        let content = self
+            .glossary
+            .map_or(Cow::Borrowed(content.as_ref()), |glossary| {
+                glossary.link_terms(&content)
+            });
+        let banner = self
+            .banner
+            .map(|template| render_banner(template, source_file, self.time));
+        // Only copied to put a banner in; every check below reads this one string
+        let written = match &banner {
+            Some(banner) => Cow::Owned(banner_segments(&content, banner).concat()),
+            None => Cow::Borrowed(content.as_ref()),
+        };
+        // Directives resolution couldn't parse would ship as text
+        let leftovers = find_leftover_directives(&written, mdx::is_mdx(source_file));
+        outputs.warnings.extend(
+            leftovers
+                .into_iter()
+                .map(|(line, directive)| ContentWarning {
+                    source: source_file.to_string_lossy().to_string(),
+                    file: output_file.to_string_lossy().to_string(),
+                    line,
+                    kind: WarningKind::LeftoverDirective,
+                    message: format!(
+                        "`{directive}` was left in the output unexpanded; check its syntax"
+                    ),
+                }),
+        );
+        if self.emit.markdown() {
+            self.put(source_file, output_file, &[&written], outputs)?;
+            if self.source_map && !self.diff {
+                let mut origins = source_map::carry_origins(resolved, origins, &written);
+                // Nothing follows the last line break
+                origins.truncate(written.lines().count());
+                let map = SourceMap::new(output_file, source_file, &origins);
+                let map_file = source_map::map_path(output_file);
+                let json = serde_json::to_string_pretty(&map)?;
+                // Counted against the budget, but not as the size of the document
+                self.spend(&map_file, json.len() as u64 + 1)?;
+                self.sink
+                    .write(&map_file, &[&json, "\n"], LineEndings::Preserve)?;
+                outputs.written.push(map_file);
+            }
+            if let Some(rules) = self.lint {
+                let issues = lint(&written, rules);
+                outputs
+                    .warnings
+                    .extend(issues.into_iter().map(|issue| ContentWarning {
+                        source: source_file.to_string_lossy().to_string(),
+                        file: output_file.to_string_lossy().to_string(),
+                        line: issue.line,
+                        kind: WarningKind::Lint,
+                        message: format!("{}: {}", issue.rule.as_str(), issue.message),
+                    }));
+            }
+        }
+        if self.emit.html() {
+            let html_file = output_file.with_extension("html");
+            let title = output_file
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            self.put(
+                source_file,
+                &html_file,
+                &[&render_page(&written, self.html_template, self.css, &title)],
+                outputs,
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Writes one output file from its `segments`, or records how it would change in diff mode
+    fn put(
+        &self,
+        source_file: &Path,
+        output_file: &Path,
+        segments: &[&str],
+        outputs: &mut FileOutputs,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.spend(output_file, segments.iter().map(|s| s.len() as u64).sum())?;
+        if !self.diff {
+            self.sink.write(output_file, segments, self.line_endings)?;
+            outputs.written.push(output_file.to_path_buf());
+            outputs.bytes += segments.iter().map(|s| s.len() as u64).sum::<u64>();
+            return Ok(());
+        }
+
+        let content = segments.concat();
+        let content = normalize_line_endings(&content, self.line_endings);
+
+        // A missing output diffs as empty, so the whole file shows as added
+        let existing = match fs::read_to_string(output_file) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {e}", output_file.display()).into()),
+        };
+        let diff = unified_diff(&existing, &content, output_file);
+        if !diff.is_empty() {
+            outputs.diffs.push(OutputDiff {
+                file_path: source_file.to_string_lossy().to_string(),
+                output_path: output_file.to_string_lossy().to_string(),
+                diff,
+            });
+        }
+        Ok(())
+    }
+
+    /// Counts `size` bytes of `output_file` against the budget, failing when they go over it
+    fn spend(&self, output_file: &Path, size: u64) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(limit) = self.budget.per_file
+            && size > limit
+        {
+            return Err(ErrorCode::OutputTooLarge.error(format!(
+                "{} would be {size} bytes, over the limit of {limit} bytes per output",
+                output_file.display()
+            )));
+        }
+        let total = self.spent.get() + size;
+        if let Some(limit) = self.budget.total
+            && total > limit
+        {
+            return Err(ErrorCode::OutputTooLarge.error(format!(
+                "{} would bring the outputs to {total} bytes, over the total limit of {limit} bytes",
+                output_file.display()
+            )));
+        }
+        self.spent.set(total);
+        Ok(())
+    }
+}
+
+/// What processing one file produced
+#[derive(Default)]
+struct FileOutputs {
+    written: Vec<PathBuf>,
+    /// Bytes of the written outputs, before line endings are converted
+    bytes: u64,
+    /// Changes to existing outputs, in diff mode
+    diffs: Vec<OutputDiff>,
+    /// Problems the lint rules and the spellchecker found
+    warnings: Vec<ContentWarning>,
+    /// Problems with the run rather than the file, e.g. a failing spellchecker
+    notices: Vec<String>,
+}
+
+/// Unified diff from `old` to `new` content of `path`; empty when they are equal
+pub fn unified_diff(old: &str, new: &str, path: &Path) -> String {
+    let path = path.to_string_lossy();
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&format!("a/{path}"), &format!("b/{path}"))
+        .to_string()
+}
+
+/// Resolves and writes one file; what it writes or would change is added to `outputs`
+fn process_single_file(
+    source_file: &Path,
+    output_file: &Path,
+    fix_code_fences: Option<&str>,
+    input_encoding: InputEncoding,
+    output: &OutputOptions,
+    options: &ResolveOptions,
+    outputs: &mut FileOutputs,
+) -> Result<FileProcessResult, Box<dyn std::error::Error>> {
+    let content = match read_text(source_file, input_encoding) {
+        Ok(content) => content,
+        Err(e) => {
+            return Ok(failed_result(
+                source_file,
+                Vec::new(),
+                Some(ErrorCode::UnreadableSource),
+                format!("Failed to read source file: {e}"),
+            ));
+        }
+    };
+    let mut includes_tracker = Vec::new();
+
+    let resolved = if output.source_map {
+        resolve_includes_with_origins(
+            &content,
+            source_file,
+            &mut includes_tracker,
+            fix_code_fences,
+            options,
+        )
+    } else {
+        resolve_includes(
+            &content,
+            source_file,
+            &mut includes_tracker,
+            fix_code_fences,
+            options,
+        )
+        .map(|content| (content, Vec::new()))
+    };
+    match resolved {
+        Ok((processed_content, origins)) => {
+            if output.strict {
+                let result = build_result(source_file, includes_tracker.clone());
+                if !result.success {
+                    return Ok(FileProcessResult {
+                        error_message: result
+                            .error_message
+                            .map(|message| format!("Not written in strict mode. {message}")),
+                        ..result
+                    });
+                }
+                if let Some((line, comment)) = first_error_comment(&processed_content) {
+                    return Ok(failed_result(
+                        source_file,
+                        includes_tracker,
+                        None,
+                        format!("Not written in strict mode: line {line} has `{comment}`"),
+                    ));
+                }
+            }
+            if let Err(e) = output.write(
+                source_file,
+                output_file,
+                &processed_content,
+                &origins,
+                outputs,
+            ) {
+                return Ok(failed_result(
+                    source_file,
+                    includes_tracker,
+                    Some(ErrorCode::of(e.as_ref()).unwrap_or(ErrorCode::OutputWriteFailed)),
+                    format!("Failed to write output: {e}"),
+                ));
+            }
+            if let Some(checker) = output.spellchecker {
+                spellcheck(
+                    checker,
+                    source_file,
+                    &content,
+                    &processed_content,
+                    &includes_tracker,
+                    options.source,
+                    outputs,
+                );
+            }
+            if let Some(command) = output.post_process
+                && let Err(e) = outputs
+                    .written
+                    .iter()
+                    .filter(|file| !source_map::is_map_path(file))
+                    .try_for_each(|file| run_post_process(command, file))
+            {
+                return Ok(failed_result(
+                    source_file,
+                    includes_tracker,
+                    Some(ErrorCode::PostProcessFailed),
+                    format!("Post-processing failed: {e}"),
+                ));
+            }
+            let mut result = build_result(source_file, includes_tracker);
+            if !outputs.written.is_empty() {
+                result.sizes = Some(FileSizes {
+                    source: content.len() as u64,
+                    output: outputs.bytes,
+                });
+            }
+            Ok(result)
+        }
+        Err(e) => Ok(failed_result(
+            source_file,
+            includes_tracker,
+            ErrorCode::of(e.as_ref()),
+            format!("Failed to process includes: {e}"),
+        )),
+    }
+}
+
+/// The first comment outside code that md2md writes in place of a directive it
+/// failed to expand, with its 1-based line. It may come from a partial that was
+/// itself generated.
+fn first_error_comment(content: &str) -> Option<(usize, &str)> {
+    static ERROR_COMMENT_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"<!-- Failed to (?:include|parse|process)\b[\s\S]*?-->")
+            .expect("Failed to compile error comment regex")
+    });
+    let fences = CodeFences::new(content);
+    ERROR_COMMENT_REGEX
+        .find_iter(content)
+        .find(|comment| !fences.contain(content, comment.start()))
+        .map(|comment| {
+            let line = content[..comment.start()].matches('\n').count() + 1;
+            (line, comment.as_str())
+        })
+}
+
+/// Runs `checker` on the `processed` form of `source_file`, adding what it finds to `outputs`
+fn spellcheck(
+    checker: &Spellchecker,
+    source_file: &Path,
+    source: &str,
+    processed: &str,
+    includes: &[IncludeResult],
+    partials: &dyn PartialSource,
+    outputs: &mut FileOutputs,
+) {
+    let mut files = vec![(source_file.to_path_buf(), source.to_string())];
+    for include in includes.iter().filter(|include| include.success) {
+        let path = PathBuf::from(&include.path);
+        if files.iter().any(|(file, _)| *file == path) {
+            continue;
+        }
+        if let Ok(content) = partials.read(&path) {
+            files.push((path, content));
+        }
+    }
+    match checker.check(processed, &files) {
+        Ok(misspellings) => {
+            outputs
+                .warnings
+                .extend(misspellings.into_iter().map(|misspelling| ContentWarning {
+                    source: source_file.to_string_lossy().to_string(),
+                    file: misspelling.file.to_string_lossy().to_string(),
+                    line: misspelling.line.unwrap_or(1),
+                    kind: WarningKind::Misspelling,
+                    message: match misspelling.line {
+                        Some(_) => format!("`{}` may be misspelled", misspelling.word),
+                        None => format!(
+                            "`{}` may be misspelled; it comes from a variable or generated text",
+                            misspelling.word
+                        ),
+                    },
+                }));
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "Spellcheck failed");
+            outputs.notices.push(format!(
+                "Spellcheck of {} failed: {e}",
+                source_file.display()
+            ));
+        }
+    }
+}
+
+fn failed_result(
+    source_file: &Path,
+    includes_tracker: Vec<IncludeResult>,
+    error_code: Option<ErrorCode>,
+    error_message: String,
+) -> FileProcessResult {
+    FileProcessResult {
+        file_path: source_file.to_string_lossy().to_string(),
+        success: false,
+        includes: includes_tracker,
+        error_message: Some(error_message),
+        error_code,
+        sizes: None,
+    }
+}
+
+/// Builds the result for a file whose includes were resolved; the file fails if any include failed
+fn build_result(source_file: &Path, includes_tracker: Vec<IncludeResult>) -> FileProcessResult {
+    let failed_includes: Vec<String> = includes_tracker
+        .iter()
+        .filter(|inc| !inc.success)
+        .map(|inc| {
+            if let Some(error) = inc.formatted_error() {
+                format!("  • {} ({})", inc.path, error)
+            } else {
+                format!("  • {}", inc.path)
+            }
+        })
+        .collect();
+    // The file is reported under the code of its first failure
+    let error_code = includes_tracker
+        .iter()
+        .find(|inc| !inc.success)
+        .and_then(|inc| inc.error_code);
+
+    FileProcessResult {
+        file_path: source_file.to_string_lossy().to_string(),
+        success: failed_includes.is_empty(),
+        includes: includes_tracker,
+        error_message: if failed_includes.is_empty() {
+            None
+        } else {
+            Some(format!(
+                "File contains failed includes:\n{}",
+                failed_includes.join("\n")
+            ))
+        },
+        error_code,
+        sizes: None,
+    }
+}
+
+/// Maps a source file in batch mode to its location under `output_root`
+pub fn calculate_output_path(
+    file_path: &Path,
+    source_root: &Path,
+    output_root: &Path,
+    naming: &OutputNaming,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let relative_path = file_path.strip_prefix(source_root).map_err(|_| {
+        format!(
+            "{} is not inside the source directory {}",
+            file_path.display(),
+            source_root.display()
+        )
+    })?;
+
+    let file_name = match &naming.rename {
+        Some(template) => {
+            let name = render_output_name(template, relative_path);
+            let name_path = Path::new(&name);
+            if name.is_empty()
+                || !name_path
+                    .components()
+                    .all(|c| matches!(c, std::path::Component::Normal(_)))
+            {
+                return Err(format!(
+                    "Rename template {template:?} gives {name:?} for {}, which is not a relative path",
+                    relative_path.display()
+                )
+                .into());
+            }
+            PathBuf::from(name)
+        }
+        None => PathBuf::from(relative_path.file_name().unwrap_or_default()),
+    };
+
+    match relative_path.parent() {
+        Some(parent) if !naming.flatten => Ok(output_root.join(parent).join(file_name)),
+        _ => Ok(output_root.join(file_name)),
+    }
+}
+
+/// Fills `{stem}`, `{ext}` and `{name}` in a rename template from the source file
+fn render_output_name(template: &str, relative_path: &Path) -> String {
+    let part =
+        |value: Option<&std::ffi::OsStr>| value.unwrap_or_default().to_string_lossy().into_owned();
+    template
+        .replace("{stem}", &part(relative_path.file_stem()))
+        .replace("{ext}", &part(relative_path.extension()))
+        .replace("{name}", &part(relative_path.file_name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::partial_source::MemorySource;
+    use crate::sink::MemorySink;
+    use crate::types::{SkipReason, SkippedFile};
+    use std::fs;
+    use std::sync::mpsc;
+    use tempfile::TempDir;
+
+    fn process_file(
+        source_file: &Path,
+        partials_dir: &Path,
+        output_file: &Path,
+    ) -> FileProcessResult {
+        let source = FileSystemSource::default();
+        let variables = HashMap::new();
+        let options = ResolveOptions::new(partials_dir, &variables, &source);
+        process_single_file(
+            source_file,
+            output_file,
+            None,
+            InputEncoding::default(),
+            &OutputOptions::default(),
+            &options,
+            &mut FileOutputs::default(),
+        )
+        .expect("Failed to process single file")
+    }
+
+    #[test]
+    fn test_calculate_output_path() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_root = temp_dir.path().join("src");
+        let output_root = temp_dir.path().join("output");
+        let file_path = source_root.join("docs").join("readme.md");
+
+        let result =
+            calculate_output_path(&file_path, &source_root, &output_root, &Default::default())
+                .expect("Failed to calculate output path");
+        assert_eq!(result, output_root.join("docs").join("readme.md"));
+    }
+
+    #[test]
+    fn test_calculate_output_path_with_rename_and_flatten() {
+        let source_root = Path::new("src");
+        let output_root = Path::new("output");
+        let file_path = source_root.join("docs").join("readme.md");
+
+        let renamed = OutputNaming {
+            rename: Some("{stem}.generated.{ext}".to_string()),
+            flatten: false,
+        };
+        let result = calculate_output_path(&file_path, source_root, output_root, &renamed)
+            .expect("Failed to calculate output path");
+        assert_eq!(result, output_root.join("docs").join("readme.generated.md"));
+
+        let flattened = OutputNaming {
+            rename: None,
+            flatten: true,
+        };
+        let result = calculate_output_path(&file_path, source_root, output_root, &flattened)
+            .expect("Failed to calculate output path");
+        assert_eq!(result, output_root.join("readme.md"));
+
+        let escaping = OutputNaming {
+            rename: Some("../{name}".to_string()),
+            flatten: false,
+        };
+        assert!(calculate_output_path(&file_path, source_root, output_root, &escaping).is_err());
+    }
+
+    #[test]
+    fn test_flatten_reports_output_collisions() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(source_dir.join("a")).expect("Failed to create source directory");
+        fs::create_dir_all(source_dir.join("b")).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(source_dir.join("a").join("index.md"), "# A").expect("Failed to write a");
+        fs::write(source_dir.join("b").join("index.md"), "# B").expect("Failed to write b");
+
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .flatten(true)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        assert_eq!(
+            summary.results[1].error_code,
+            Some(ErrorCode::OutputCollision)
+        );
+        let output =
+            fs::read_to_string(output_dir.join("index.md")).expect("Failed to read output file");
+        assert_eq!(output, "# A");
+    }
+
+    #[test]
+    fn test_process_single_file_success() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        // Create source file
+        let source_file = temp_dir.path().join("source.md");
+        fs::write(&source_file, "# Title\n\nContent here.").expect("Failed to write source file");
+
+        // Create output path
+        let output_file = temp_dir.path().join("output.md");
+
+        let result = process_file(&source_file, &partials_dir, &output_file);
+
+        assert!(result.success);
+        assert_eq!(result.includes.len(), 0);
+        assert!(result.error_message.is_none());
+        assert!(output_file.exists());
+
+        let output_content = fs::read_to_string(&output_file).expect("Failed to read output file");
+        assert_eq!(output_content, "# Title\n\nContent here.");
+    }
+
+    #[test]
+    fn test_process_single_file_with_includes() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        // Create partial file
+        fs::write(partials_dir.join("header.md"), "# Welcome").expect("Failed to write header.md");
+
+        // Create source file with include
+        let source_file = temp_dir.path().join("source.md");
+        fs::write(&source_file, "!include (header.md)\n\nMain content.")
+            .expect("Failed to write source file");
+
+        // Create output path
+        let output_file = temp_dir.path().join("output.md");
+
+        let result = process_file(&source_file, &partials_dir, &output_file);
+
+        assert!(result.success);
+        assert_eq!(result.includes.len(), 1);
+        assert!(result.includes[0].success);
+        assert!(result.error_message.is_none());
+
+        let output_content = fs::read_to_string(&output_file).expect("Failed to read output file");
+        assert_eq!(output_content, "# Welcome\n\nMain content.");
+    }
+
+    #[test]
+    fn test_process_single_file_with_failed_include() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        // Create source file with missing include
+        let source_file = temp_dir.path().join("source.md");
+        fs::write(&source_file, "!include (missing.md)\n\nMain content.")
+            .expect("Failed to write source file");
+
+        // Create output path
+        let output_file = temp_dir.path().join("output.md");
+
+        let result = process_file(&source_file, &partials_dir, &output_file);
+
+        assert!(!result.success); // Should fail due to missing include
+        assert_eq!(result.includes.len(), 1);
+        assert!(!result.includes[0].success);
+        assert!(result.error_message.is_some());
+        assert!(
+            result
+                .error_message
+                .as_ref()
+                .expect("Error message should be present for failed includes")
+                .contains("failed includes")
+        );
+        assert_eq!(result.error_code, Some(ErrorCode::MissingInclude));
+        assert_eq!(
+            result.includes[0].error_code,
+            Some(ErrorCode::MissingInclude)
+        );
+        assert!(
+            result
+                .formatted_error()
+                .is_some_and(|e| e.starts_with("[E001] File contains failed includes"))
+        );
+
+        // File should still be written with error comments
+        assert!(output_file.exists());
+        let output_content = fs::read_to_string(&output_file).expect("Failed to read output file");
+        assert!(output_content.contains("<!-- Failed to include"));
+        assert!(output_content.contains("Main content."));
+    }
+
+    #[test]
+    fn test_circular_include_has_error_code() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("a.md"), "!include (b.md)").expect("Failed to write a.md");
+        fs::write(partials_dir.join("b.md"), "!include (a.md)").expect("Failed to write b.md");
+
+        let source_file = temp_dir.path().join("source.md");
+        fs::write(&source_file, "!include (a.md)").expect("Failed to write source file");
+
+        let result = process_file(
+            &source_file,
+            &partials_dir,
+            &temp_dir.path().join("output.md"),
+        );
+
+        assert!(!result.success);
+        assert_eq!(result.error_code, Some(ErrorCode::CircularInclude));
+    }
+
+    #[test]
+    fn test_check_files_writes_nothing_and_reports_problems() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        fs::write(partials_dir.join("header.md"), "# Header").expect("Failed to write header.md");
+        fs::write(
+            source_dir.join("good.md"),
+            "!include (header.md)\n\n[Bad](bad.md)",
+        )
+        .expect("Failed to write good.md");
+        fs::write(source_dir.join("bad.md"), "!include (missing.md)")
+            .expect("Failed to write bad.md");
+
+        let mut summary = ProcessingSummary::new();
+        check_files(
+            &source_dir,
+            &partials_dir,
+            &CheckOptions::default(),
+            &mut summary,
+        )
+        .expect("Failed to check files");
+
+        assert_eq!(summary.results.len(), 2);
+        assert_eq!(summary.get_success_count(), 1);
+        let bad = summary
+            .results
+            .iter()
+            .find(|r| r.file_path.ends_with("bad.md"))
+            .expect("bad.md should have a result");
+        assert!(!bad.success);
+
+        // Only the source files exist: nothing was written
+        let entries = fs::read_dir(&source_dir)
+            .expect("Failed to read source directory")
+            .count();
+        assert_eq!(entries, 2);
+    }
+
+    #[test]
+    fn test_check_files_resolves_conditional_includes_with_variables() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("cloud.md"), "!include (gone.md)")
+            .expect("Failed to write cloud.md");
+
+        let source_file = temp_dir.path().join("doc.md");
+        fs::write(
+            &source_file,
+            "# Doc\n\n!include (cloud.md, if=\"edition == 'cloud'\")",
+        )
+        .expect("Failed to write doc.md");
+
+        let variables = HashMap::from([("edition".to_string(), "cloud".to_string())]);
+        let mut summary = ProcessingSummary::new();
+        check_files(
+            &source_file,
+            &partials_dir,
+            &CheckOptions {
+                variables: &variables,
+                ..CheckOptions::default()
+            },
+            &mut summary,
+        )
+        .expect("Failed to check files");
+
+        assert_eq!(summary.get_failed_count(), 1);
+        let error = summary.results[0]
+            .error_message
+            .as_ref()
+            .expect("Missing nested include should produce an error message");
+        assert!(error.contains("gone.md"));
+    }
+
+    #[test]
+    fn test_check_files_fails_on_undefined_variable() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(
+            partials_dir.join("title.md"),
+            "# {% product %} {% missing %}",
+        )
+        .expect("Failed to write title.md");
+
+        let source_file = temp_dir.path().join("doc.md");
+        fs::write(&source_file, "!include (title.md)").expect("Failed to write doc.md");
+
+        let variables = HashMap::from([("product".to_string(), "Acme".to_string())]);
+        let mut summary = ProcessingSummary::new();
+        check_files(
+            &source_file,
+            &partials_dir,
+            &CheckOptions {
+                variables: &variables,
+                ..CheckOptions::default()
+            },
+            &mut summary,
+        )
+        .expect("Failed to check files");
+
+        assert_eq!(summary.get_failed_count(), 1);
+        let error = summary.results[0]
+            .error_message
+            .as_ref()
+            .expect("Undefined variable should produce an error message");
+        assert!(error.contains("Variable 'missing' not found"));
+    }
+
+    #[test]
+    fn test_check_files_reports_broken_links() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        let source_file = temp_dir.path().join("doc.md");
+        fs::write(&source_file, "# Doc\n\nSee [the guide](guide.md).")
+            .expect("Failed to write doc.md");
+
+        let mut summary = ProcessingSummary::new();
+        check_files(
+            &source_file,
+            &partials_dir,
+            &CheckOptions::default(),
+            &mut summary,
+        )
+        .expect("Failed to check files");
+
+        assert_eq!(summary.get_failed_count(), 1);
+        let error = summary.results[0]
+            .error_message
+            .as_ref()
+            .expect("Broken link should produce an error message");
+        assert!(error.contains("line 3: guide.md"));
+    }
+
+    #[test]
+    fn test_process_files_single_file() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        // Create source file
+        let source_file = temp_dir.path().join("source.md");
+        fs::write(&source_file, "# Test Document").expect("Failed to write source file");
+
+        // Create output file path
+        let output_file = temp_dir.path().join("output.md");
+
+        let config = ProcessingConfig {
+            source_path: source_file.clone(),
+            partials_path: partials_dir,
+            output_path: output_file.clone(),
+            batch: false,
+            verbose: false,
+            fix_code_fences: None,
+            variables: HashMap::new(),
+            order: FileOrder::default(),
+            post_process: None,
+            naming: OutputNaming::default(),
+            compat: Compat::default(),
+            passthrough: None,
+            emit: Emit::default(),
+            html: HtmlOptions::default(),
+            bundle_links: false,
+            check_links: false,
+            duplicate_headings: None,
+            line_endings: LineEndings::default(),
+            normalization: Normalization::default(),
+            source_comments: None,
+            banner: None,
+            diff: false,
+            input_encoding: InputEncoding::default(),
+            restrict_includes: false,
+            resolution_order: ResolutionOrder::default(),
+            frontmatter: Frontmatter::default(),
+            locale: None,
+            budget: OutputBudget::default(),
+            lint: None,
+            spellcheck: None,
+            source_map: false,
+            strict: false,
+            error_template: None,
+            retry: RetryPolicy::default(),
+            changed_since: None,
+            glossary: None,
+        };
+
+        let mut summary = ProcessingSummary::new();
+
+        let (sender, receiver) = mpsc::channel();
+        process_files(&config, &mut summary, Some(&sender), None).expect("Failed to process files");
+
+        assert_eq!(summary.results.len(), 1);
+        assert!(summary.results[0].success);
+        assert!(output_file.exists());
+        assert!(summary.complete);
+        assert!(summary.started_at <= summary.finished_at);
+        assert_eq!(
+            summary.config.as_ref().map(|c| &c.source_path),
+            Some(&source_file)
+        );
+
+        drop(sender);
+        let events: Vec<ProcessingEvent> = receiver.iter().collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                ProcessingEvent::RunStarted(_),
+                ProcessingEvent::FileStarted {
+                    index: 0,
+                    total: 1,
+                    ..
+                },
+                ProcessingEvent::FileFinished(result),
+                ProcessingEvent::RunFinished {
+                    processed: 1,
+                    failed: 0,
+                    cancelled: false,
+                },
+            ] if result.success
+        ));
+
+        let mut rebuilt = ProcessingSummary::new();
+        for event in &events {
+            rebuilt.apply(event);
+        }
+        assert_eq!(rebuilt.processed_files, summary.processed_files);
+        assert_eq!(rebuilt.total_files, summary.total_files);
+        assert!(rebuilt.complete);
+        assert_eq!(rebuilt.config, summary.config);
+    }
+
+    #[test]
+    fn test_process_files_batch_mode() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        // Create multiple source files
+        fs::write(source_dir.join("doc1.md"), "# Document 1").expect("Failed to write doc1.md");
+        fs::write(source_dir.join("doc2.md"), "# Document 2").expect("Failed to write doc2.md");
+
+        // Create subdirectory with file
+        let sub_dir = source_dir.join("subdir");
+        fs::create_dir(&sub_dir).expect("Failed to create subdirectory");
+        fs::write(sub_dir.join("doc3.md"), "# Document 3").expect("Failed to write doc3.md");
+        fs::write(source_dir.join("logo.png"), "PNG").expect("Failed to write logo.png");
+
+        let config = ProcessingConfig {
+            source_path: source_dir.clone(),
+            partials_path: partials_dir,
+            output_path: output_dir.clone(),
+            batch: true,
+            verbose: false,
+            fix_code_fences: None,
+            variables: HashMap::new(),
+            order: FileOrder::default(),
+            post_process: None,
+            naming: OutputNaming::default(),
+            compat: Compat::default(),
+            passthrough: None,
+            emit: Emit::default(),
+            html: HtmlOptions::default(),
+            bundle_links: false,
+            check_links: false,
+            duplicate_headings: None,
+            line_endings: LineEndings::default(),
+            normalization: Normalization::default(),
+            source_comments: None,
+            banner: None,
+            diff: false,
+            input_encoding: InputEncoding::default(),
+            restrict_includes: false,
+            resolution_order: ResolutionOrder::default(),
+            frontmatter: Frontmatter::default(),
+            locale: None,
+            budget: OutputBudget::default(),
+            lint: None,
+            spellcheck: None,
+            source_map: false,
+            strict: false,
+            error_template: None,
+            retry: RetryPolicy::default(),
+            changed_since: None,
+            glossary: None,
+        };
+
+        let mut summary = ProcessingSummary::new();
+
+        process_files(&config, &mut summary, None, None).expect("Failed to process files");
+
+        assert_eq!(summary.results.len(), 3);
+        assert!(summary.results.iter().all(|r| r.success));
+        assert_eq!(
+            summary.skipped,
+            vec![SkippedFile {
+                path: source_dir.join("logo.png").to_string_lossy().to_string(),
+                reason: SkipReason::NotMarkdown,
+            }]
+        );
+
+        // Check output files exist in correct structure
+        assert!(output_dir.join("doc1.md").exists());
+        assert!(output_dir.join("doc2.md").exists());
+        assert!(output_dir.join("subdir").join("doc3.md").exists());
+    }
+
+    #[test]
+    fn test_process_files_records_failures_and_continues() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        fs::write(partials_dir.join("loop.md"), "!include (loop.md)")
+            .expect("Failed to write loop.md");
+        fs::write(source_dir.join("a-binary.md"), [0xff, 0xfe, 0x00])
+            .expect("Failed to write a-binary.md");
+        fs::write(source_dir.join("b-circular.md"), "!include (loop.md)")
+            .expect("Failed to write b-circular.md");
+        fs::write(source_dir.join("c-good.md"), "# Good").expect("Failed to write c-good.md");
+
+        let config = ProcessingConfig {
+            source_path: source_dir,
+            partials_path: partials_dir,
+            output_path: output_dir.clone(),
+            batch: true,
+            verbose: false,
+            fix_code_fences: None,
+            variables: HashMap::new(),
+            order: FileOrder::default(),
+            post_process: None,
+            naming: OutputNaming::default(),
+            compat: Compat::default(),
+            passthrough: None,
+            emit: Emit::default(),
+            html: HtmlOptions::default(),
+            bundle_links: false,
+            check_links: false,
+            duplicate_headings: None,
+            line_endings: LineEndings::default(),
+            normalization: Normalization::default(),
+            source_comments: None,
+            banner: None,
+            diff: false,
+            input_encoding: InputEncoding::default(),
+            restrict_includes: false,
+            resolution_order: ResolutionOrder::default(),
+            frontmatter: Frontmatter::default(),
+            locale: None,
+            budget: OutputBudget::default(),
+            lint: None,
+            spellcheck: None,
+            source_map: false,
+            strict: false,
+            error_template: None,
+            retry: RetryPolicy::default(),
+            changed_since: None,
+            glossary: None,
+        };
+
+        let mut summary = ProcessingSummary::new();
+        process_files(&config, &mut summary, None, None).expect("Failed to process files");
+
+        assert_eq!(summary.processed_files, 3);
+        assert_eq!(summary.get_success_count(), 1);
+        assert_eq!(summary.get_failed_count(), 2);
+
+        let error_for = |name: &str| {
+            summary
+                .results
+                .iter()
+                .find(|r| r.file_path.ends_with(name))
+                .and_then(|r| r.error_message.clone())
+                .unwrap_or_default()
+        };
+        assert!(error_for("a-binary.md").contains("Failed to read source file"));
+        assert!(error_for("b-circular.md").contains("Maximum include depth"));
+        assert!(output_dir.join("c-good.md").exists());
+    }
+
+    #[test]
+    fn test_process_files_stops_when_cancelled() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::write(source_dir.join("doc1.md"), "# Document 1").expect("Failed to write doc1.md");
+        fs::write(source_dir.join("doc2.md"), "# Document 2").expect("Failed to write doc2.md");
+
+        let config = ProcessingConfig {
+            source_path: source_dir,
+            partials_path: temp_dir.path().to_path_buf(),
+            output_path: output_dir.clone(),
+            batch: true,
+            verbose: false,
+            fix_code_fences: None,
+            variables: HashMap::new(),
+            order: FileOrder::default(),
+            post_process: None,
+            naming: OutputNaming::default(),
+            compat: Compat::default(),
+            passthrough: None,
+            emit: Emit::default(),
+            html: HtmlOptions::default(),
+            bundle_links: false,
+            check_links: false,
+            duplicate_headings: None,
+            line_endings: LineEndings::default(),
+            normalization: Normalization::default(),
+            source_comments: None,
+            banner: None,
+            diff: false,
+            input_encoding: InputEncoding::default(),
+            restrict_includes: false,
+            resolution_order: ResolutionOrder::default(),
+            frontmatter: Frontmatter::default(),
+            locale: None,
+            budget: OutputBudget::default(),
+            lint: None,
+            spellcheck: None,
+            source_map: false,
+            strict: false,
+            error_template: None,
+            retry: RetryPolicy::default(),
+            changed_since: None,
+            glossary: None,
+        };
+
+        let cancellation = CancellationToken::new();
+        cancellation.clone().cancel();
+
+        let (sender, receiver) = mpsc::channel();
+        let mut summary = ProcessingSummary::new();
+        process_files(&config, &mut summary, Some(&sender), Some(&cancellation))
+            .expect("Failed to process files");
+
+        assert_eq!(summary.total_files, 2);
+        assert_eq!(summary.processed_files, 0);
+        assert!(!output_dir.exists());
+        assert!(matches!(
+            receiver.try_iter().last(),
+            Some(ProcessingEvent::RunFinished {
+                processed: 0,
+                cancelled: true,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_processor_builder_runs_with_variables() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        fs::write(
+            partials_dir.join("version.md"),
+            "{% project %} v{% version %}",
+        )
+        .expect("Failed to write partial");
+        fs::write(
+            source_dir.join("doc.md"),
+            "!include (version.md, values=[project=\"md2md\"])",
+        )
+        .expect("Failed to write source file");
+
+        let (sender, receiver) = mpsc::channel();
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .var("version", "1.2.0")
+            .var("project", "overridden")
+            .events(sender)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        let resolved: Vec<IncludeResult> = receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                ProcessingEvent::IncludeResolved { include, .. } => Some(include),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(resolved.len(), 1);
+        assert!(resolved[0].success);
+        let output =
+            fs::read_to_string(output_dir.join("doc.md")).expect("Failed to read output file");
+        assert_eq!(output, "md2md v1.2.0");
+    }
+
+    #[test]
+    fn test_processor_builder_checks_with_variables() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+
+        fs::write(partials_dir.join("cloud.md"), "!include (gone.md)")
+            .expect("Failed to write partial");
+        fs::write(
+            source_dir.join("doc.md"),
+            "!include (cloud.md, if=\"edition == 'cloud'\")",
+        )
+        .expect("Failed to write source file");
+
+        let check = |edition: &str| {
+            Processor::builder()
+                .source(&source_dir)
+                .partials(&partials_dir)
+                .var("edition", edition)
+                .build()
+                .expect("Failed to build processor")
+                .check()
+                .expect("Failed to check files")
+        };
+
+        assert_eq!(check("server").get_failed_count(), 0);
+        assert_eq!(check("cloud").get_failed_count(), 1);
+    }
+
+    #[test]
+    fn test_run_to_memory_sink_leaves_disk_alone() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(source_dir.join("guide")).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("note.md"), "Note").expect("Failed to write partial");
+        fs::write(source_dir.join("index.md"), "# Home\n!include (note.md)\n")
+            .expect("Failed to write source file");
+        fs::write(source_dir.join("guide/setup.md"), "[Home](../missing.md)\n")
+            .expect("Failed to write source file");
+
+        let (sender, receiver) = mpsc::channel();
+        let sink = MemorySink::new();
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .emit(Emit::Both)
+            .line_endings(LineEndings::Crlf)
+            .check_links(true)
+            .events(sender)
+            .build()
+            .expect("Failed to build processor")
+            .run_to(&sink)
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 2);
+        assert!(!output_dir.exists());
+        assert_eq!(
+            sink.get(output_dir.join("index.md")).as_deref(),
+            Some("# Home\r\nNote\r\n")
+        );
+        assert_eq!(
+            sink.paths(),
+            vec![
+                output_dir.join("guide/setup.html"),
+                output_dir.join("guide/setup.md"),
+                output_dir.join("index.html"),
+                output_dir.join("index.md"),
+            ]
+        );
+        // Links are checked on disk, where nothing was written
+        let events: Vec<ProcessingEvent> = receiver.try_iter().collect();
+        assert!(
+            !events
+                .iter()
+                .any(|event| matches!(event, ProcessingEvent::BrokenLinks { .. }))
+        );
+        assert!(events.iter().any(|event| matches!(
+            event,
+            ProcessingEvent::Warning(message) if message.starts_with("Links aren't checked")
+        )));
+    }
+
+    #[test]
+    fn test_include_progress_is_reported_per_document_directive() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::write(temp_dir.path().join("header.md"), "!include(logo.md)")
+            .expect("Failed to write header.md");
+        fs::write(temp_dir.path().join("logo.md"), "(logo)").expect("Failed to write logo.md");
+        fs::write(
+            source_dir.join("doc.md"),
+            "!include(header.md, title=\"Header\")\n\n```text\n!include(skipped.md)\n```\n\n!include(missing.md)",
+        )
+        .expect("Failed to write doc.md");
+
+        let (sender, receiver) = mpsc::channel();
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(temp_dir.path())
+            .output(temp_dir.path().join("output"))
+            .events(sender)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        let progress: Vec<(String, usize, usize)> = receiver
+            .try_iter()
+            .filter_map(|event| match event {
+                ProcessingEvent::IncludeStarted {
+                    path, index, total, ..
+                } => Some((path, index, total)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            progress,
+            vec![
+                ("header.md".to_string(), 0, 2),
+                ("missing.md".to_string(), 1, 2),
+            ]
+        );
+        assert!(summary.current_include.is_none());
+    }
+
+    #[test]
+    fn test_markedpp_compat_resolves_includes_next_to_the_document() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(source_dir.join("guide")).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(source_dir.join("guide").join("setup.md"), "## Setup")
+            .expect("Failed to write setup.md");
+        fs::write(partials_dir.join("footer.md"), "## Footer").expect("Failed to write footer.md");
+        fs::write(
+            source_dir.join("guide").join("index.md"),
+            "# Guide\n\n!toc\n\n!include(setup.md)\n\n!include(footer.md)",
+        )
+        .expect("Failed to write index.md");
+
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .compat(Compat::Markedpp)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_failed_count(), 0);
+        let output = fs::read_to_string(output_dir.join("guide").join("index.md"))
+            .expect("Failed to read output file");
+        assert_eq!(
+            output,
+            "# Guide\n\n<!-- toc -->\n\n* [Guide](#guide)\n  * [Setup](#setup)\n  * [Footer](#footer)\n\n<!-- toc! -->\n\n## Setup\n\n## Footer"
+        );
+    }
+
+    #[test]
+    fn test_mdx_sources_keep_component_syntax() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(
+            partials_dir.join("install.mdx"),
+            "<Admonition type=\"note\">Requires Node</Admonition>",
+        )
+        .expect("Failed to write install.mdx");
+        let document = "---\ndescription: Uses !include (nothing.md)\n---\nimport Tabs from '@theme/Tabs';\n\n<Tabs defaultValue=\"!include (nothing.md)\">\n\n!include (install.mdx)\n\n</Tabs>\n";
+        fs::write(source_dir.join("guide.mdx"), document).expect("Failed to write guide.mdx");
+
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        assert_eq!(summary.get_total_includes(), 1);
+        let output =
+            fs::read_to_string(output_dir.join("guide.mdx")).expect("Failed to read guide.mdx");
+        assert_eq!(
+            output,
+            document.replace(
+                "!include (install.mdx)",
+                "<Admonition type=\"note\">Requires Node</Admonition>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_bundle_links_point_to_included_sections() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(
+            partials_dir.join("intro.md"),
+            "## Intro\n\nFirst [install](./install.md#linux), then [use it](usage.md).",
+        )
+        .expect("Failed to write intro.md");
+        fs::write(partials_dir.join("install.md"), "## Install\n\n### Linux")
+            .expect("Failed to write install.md");
+        fs::write(
+            source_dir.join("handbook.md"),
+            "# Handbook\n\n!include (intro.md)\n\n!include (install.md)",
+        )
+        .expect("Failed to write handbook.md");
+
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .bundle_links(true)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        let output =
+            fs::read_to_string(output_dir.join("handbook.md")).expect("Failed to read handbook.md");
+        assert_eq!(
+            output,
+            "# Handbook\n\n## Intro\n\nFirst [install](#linux), then [use it](usage.md).\n\n## Install\n\n### Linux"
+        );
+    }
+
+    #[test]
+    fn test_source_map_points_output_lines_at_their_sources() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(
+            partials_dir.join("intro.md"),
+            "<!-- md2md:vars who=\"you\" -->\n## Intro\n\nHello {% who %}\n",
+        )
+        .expect("Failed to write intro.md");
+        fs::write(source_dir.join("code.py"), "a = 1\nb = 2\nc = 3\n")
+            .expect("Failed to write code.py");
+        fs::write(
+            source_dir.join("doc.md"),
+            "# Doc\n\n!include(intro.md, title=\"Welcome\")\n\n!codesnippet(code.py, lang=\"python\", start=2)\nEnd\n",
+        )
+        .expect("Failed to write doc.md");
+
+        let sink = MemorySink::new();
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .banner("<!-- generated -->")
+            .source_map(true)
+            .build()
+            .expect("Failed to build processor")
+            .run_to(&sink)
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        let output = sink
+            .get(output_dir.join("doc.md"))
+            .expect("doc.md should be written");
+        let map: SourceMap = serde_json::from_str(
+            &sink
+                .get(output_dir.join("doc.md.map.json"))
+                .expect("doc.md.map.json should be written"),
+        )
+        .expect("Failed to parse source map");
+        let doc = source_dir.join("doc.md").to_string_lossy().to_string();
+        let intro = partials_dir.join("intro.md").to_string_lossy().to_string();
+        let code = source_dir.join("code.py").to_string_lossy().to_string();
+        let origins: Vec<_> = output
+            .lines()
+            .enumerate()
+            .map(|(index, line)| {
+                let origin = map.origin(index + 1);
+                (line, origin.map(|origin| (origin.file, origin.line)))
+            })
+            .collect();
+        assert_eq!(
+            origins,
+            vec![
+                ("<!-- generated -->", None),
+                ("# Doc", Some((doc.clone(), 1))),
+                ("", Some((doc.clone(), 2))),
+                ("# Welcome", Some((doc.clone(), 3))),
+                ("", Some((intro.clone(), 1))),
+                ("## Intro", Some((intro.clone(), 2))),
+                ("", Some((intro.clone(), 3))),
+                ("Hello you", Some((intro, 4))),
+                // The rest of the directive's line
+                ("", Some((doc.clone(), 3))),
+                ("", Some((doc.clone(), 4))),
+                ("```python", Some((doc.clone(), 5))),
+                ("b = 2", Some((code.clone(), 2))),
+                ("c = 3", Some((code, 3))),
+                ("```", Some((doc.clone(), 5))),
+                ("End", Some((doc, 6))),
+            ]
+        );
+        assert_eq!(map.ranges.last().map(|range| range.output_end), Some(15));
+    }
+
+    #[test]
+    fn test_summaries_record_bytes_read_and_written() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("big.md"), "x".repeat(90)).expect("Failed to write big.md");
+        fs::write(source_dir.join("big.md"), "!include(big.md)").expect("Failed to write big.md");
+        fs::write(source_dir.join("plain.md"), "Just text").expect("Failed to write plain.md");
+        fs::write(source_dir.join("broken.md"), "!include(missing.md)")
+            .expect("Failed to write broken.md");
+
+        let sink = MemorySink::new();
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .strict(true)
+            .build()
+            .expect("Failed to build processor")
+            .run_to(&sink)
+            .expect("Failed to run processor");
+
+        let sizes = |name: &str| {
+            summary
+                .results
+                .iter()
+                .find(|r| r.file_path.ends_with(name))
+                .and_then(|r| r.sizes)
+        };
+        assert_eq!(
+            sizes("big.md"),
+            Some(FileSizes {
+                source: 16,
+                output: 90
+            })
+        );
+        assert_eq!(sizes("broken.md"), None);
+
+        let stats = summary.get_size_stats();
+        assert_eq!(stats.bytes_read, 16 + 9);
+        assert_eq!(stats.bytes_written, 90 + 9);
+        assert_eq!(
+            stats.largest_output,
+            Some((source_dir.join("big.md").to_string_lossy().to_string(), 90))
+        );
+        // 90 / 16 and 9 / 9, averaged
+        assert_eq!(stats.average_expansion, Some((5.625 + 1.0) / 2.0));
+    }
+
+    /// Commits everything in `directory`, making it a repository first if needed
+    #[cfg(feature = "git")]
+    fn commit_all(directory: &Path) {
+        let repository = git2::Repository::open(directory)
+            .or_else(|_| git2::Repository::init(directory))
+            .expect("Failed to open repository");
+        let mut index = repository.index().expect("Failed to open index");
+        index
+            .add_all(["*"], git2::IndexAddOption::DEFAULT, None)
+            .expect("Failed to stage files");
+        index.write().expect("Failed to write index");
+        let tree_id = index.write_tree().expect("Failed to write tree");
+        let tree = repository.find_tree(tree_id).expect("Failed to find tree");
+        let signature =
+            git2::Signature::now("md2md", "md2md@example.com").expect("Failed to create signature");
+        let parent = repository
+            .head()
+            .ok()
+            .and_then(|head| head.peel_to_commit().ok());
+        repository
+            .commit(
+                Some("HEAD"),
+                &signature,
+                &signature,
+                "Docs",
+                &tree,
+                &parent.iter().collect::<Vec<_>>(),
+            )
+            .expect("Failed to commit");
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_changed_since_processes_only_affected_documents() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(source_dir.join("plain.md"), "# Plain").expect("Failed to write plain.md");
+        fs::write(source_dir.join("uses.md"), "!include(note.md)")
+            .expect("Failed to write uses.md");
+        fs::write(partials_dir.join("note.md"), "Old note").expect("Failed to write note.md");
+
+        commit_all(temp_dir.path());
+
+        // A changed partial and a new, untracked document
+        fs::write(partials_dir.join("note.md"), "New note").expect("Failed to update note.md");
+        fs::write(source_dir.join("new.md"), "# New").expect("Failed to write new.md");
+
+        let sink = MemorySink::new();
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(temp_dir.path().join("output"))
+            .changed_since("HEAD")
+            .build()
+            .expect("Failed to build processor")
+            .run_to(&sink)
+            .expect("Failed to run processor");
+
+        let processed: Vec<_> = summary
+            .results
+            .iter()
+            .map(|r| Path::new(&r.file_path).file_name().map(|n| n.to_owned()))
+            .collect();
+        assert_eq!(processed.len(), 2, "{processed:?}");
+        assert!(processed.contains(&Some("new.md".into())));
+        assert!(processed.contains(&Some("uses.md".into())));
+        assert_eq!(
+            summary.get_skipped_by_reason(),
+            vec![(SkipReason::Unchanged, 1)]
+        );
+        assert!(summary.skipped[0].path.ends_with("plain.md"));
+    }
+
+    #[cfg(feature = "git")]
+    #[test]
+    fn test_changed_since_processes_everything_for_changes_it_cant_place() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(source_dir.join("plain.md"), "# Plain").expect("Failed to write plain.md");
+        fs::write(source_dir.join("uses.md"), "!include(note.md)")
+            .expect("Failed to write uses.md");
+        fs::write(
+            source_dir.join("pinned.md"),
+            "!include(note.md, rev=\"HEAD\")",
+        )
+        .expect("Failed to write pinned.md");
+        fs::write(partials_dir.join("note.md"), "Note").expect("Failed to write note.md");
+        commit_all(temp_dir.path());
+
+        let processed = || {
+            let summary = Processor::builder()
+                .source(&source_dir)
+                .partials(&partials_dir)
+                .output(temp_dir.path().join("output"))
+                .changed_since("HEAD")
+                .build()
+                .expect("Failed to build processor")
+                .run_to(&MemorySink::new())
+                .expect("Failed to run processor");
+            let mut names: Vec<_> = summary
+                .results
+                .iter()
+                .filter_map(|r| Path::new(&r.file_path).file_name())
+                .map(|name| name.to_string_lossy().to_string())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Documents with a rev= include can change with nothing else
+        assert_eq!(processed(), ["pinned.md"]);
+        fs::write(temp_dir.path().join("notes.txt"), "Unrelated").expect("Failed to write");
+        assert_eq!(processed(), ["pinned.md"]);
+
+        let everything = ["pinned.md", "plain.md", "uses.md"];
+        // A translation the graph doesn't know of
+        fs::write(partials_dir.join("note.fr.md"), "Note").expect("Failed to write note.fr.md");
+        assert_eq!(processed(), everything);
+        commit_all(temp_dir.path());
+
+        fs::write(temp_dir.path().join(CONFIG_FILE_NAME), "").expect("Failed to write config");
+        assert_eq!(processed(), everything);
+    }
+
+    #[test]
+    fn test_check_links_validates_written_outputs() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("usage.md"), "## Usage").expect("Failed to write usage.md");
+        fs::write(
+            source_dir.join("a.md"),
+            "# A\n\n!include (usage.md)\n\n[Usage](#usage) [B](b.md#setup)",
+        )
+        .expect("Failed to write a.md");
+        fs::write(
+            source_dir.join("b.md"),
+            "# B\n\n[A](a.md#install) [C](c.md)",
+        )
+        .expect("Failed to write b.md");
+
+        let (sender, receiver) = mpsc::channel();
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .check_links(true)
+            .events(sender)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_failed_count(), 2);
+        let messages: Vec<String> = summary
+            .results
+            .iter()
+            .map(|r| r.formatted_error().expect("File should have an error"))
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "[E050] File contains broken links:\n  • line 5: b.md#setup",
+                "[E050] File contains broken links:\n  • line 3: a.md#install\n  • line 3: c.md",
+            ]
+        );
+        assert!(output_dir.join("b.md").exists());
+
+        let events: Vec<ProcessingEvent> = receiver.try_iter().collect();
+        assert!(matches!(
+            events[events.len() - 2],
+            ProcessingEvent::BrokenLinks { .. }
+        ));
+    }
+
+    #[test]
+    fn test_duplicate_headings_are_deduplicated() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("setup.md"), "### Setup").expect("Failed to write setup.md");
+        fs::write(
+            source_dir.join("guide.md"),
+            "!toc\n\n## Linux\n\n!include (setup.md)\n\n## macOS\n\n!include (setup.md)",
+        )
+        .expect("Failed to write guide.md");
+
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .compat(Compat::Markedpp)
+            .duplicate_headings(DuplicateHeadings::Dedupe)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        let output =
+            fs::read_to_string(output_dir.join("guide.md")).expect("Failed to read guide.md");
+        assert_eq!(
+            output,
+            "<!-- toc -->\n\n  * [Linux](#linux)\n    * [Setup](#setup)\n  * [macOS](#macos)\n    * [Setup](#setup-1)\n\n<!-- toc! -->\n\n## Linux\n\n### Setup\n\n## macOS\n\n### Setup {#setup-1}"
+        );
+    }
+
+    #[test]
+    fn test_line_endings_are_normalized_and_partial_boms_dropped() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(
+            partials_dir.join("windows.md"),
+            "\u{feff}## Windows\r\n\r\nText\r\n",
+        )
+        .expect("Failed to write windows.md");
+        fs::write(
+            source_dir.join("guide.md"),
+            "# Guide\n\n!include (windows.md)\nEnd",
+        )
+        .expect("Failed to write guide.md");
+
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .line_endings(LineEndings::Crlf)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        let output =
+            fs::read_to_string(output_dir.join("guide.md")).expect("Failed to read guide.md");
+        assert_eq!(output, "# Guide\r\n\r\n## Windows\r\n\r\nText\r\n\r\nEnd");
+    }
+
+    #[test]
+    fn test_undecodable_sources_fail_alone_and_other_encodings_are_read() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("cafe.md"), b"Caf\xe9").expect("Failed to write cafe.md");
+        fs::write(
+            source_dir.join("a.md"),
+            b"# R\xe9sum\xe9\n\n!include (cafe.md)\n",
+        )
+        .expect("Failed to write a.md");
+        fs::write(source_dir.join("b.md"), b"\x89PNG\r\n\x1a\n\0\0").expect("Failed to write b.md");
+        fs::write(source_dir.join("c.md"), "# Plain\n").expect("Failed to write c.md");
+        let run = |encoding| {
+            Processor::builder()
+                .source(&source_dir)
+                .partials(&partials_dir)
+                .output(&output_dir)
+                .input_encoding(encoding)
+                .build()
+                .expect("Failed to build processor")
+                .run()
+                .expect("Failed to run processor")
+        };
+
+        let summary = run(InputEncoding::Utf8);
+        assert_eq!(summary.get_success_count(), 1);
+        let errors: Vec<(&str, Option<ErrorCode>)> = summary
+            .results
+            .iter()
+            .filter(|result| !result.success)
+            .map(|result| {
+                (
+                    result.error_message.as_deref().unwrap_or(""),
+                    result.error_code,
+                )
+            })
+            .collect();
+        assert!(errors[0].0.contains("not valid UTF-8"));
+        assert!(errors[1].0.contains("binary file"));
+        assert!(
+            errors
+                .iter()
+                .all(|(_, code)| *code == Some(ErrorCode::UnreadableSource))
+        );
+
+        let summary = run(InputEncoding::Latin1);
+        assert!(summary.results[0].success);
+        assert_eq!(
+            fs::read_to_string(output_dir.join("a.md")).expect("Failed to read a.md"),
+            "# Résumé\n\nCafé\n"
+        );
+    }
+
+    #[test]
+    fn test_restrict_includes_fails_includes_outside_the_roots() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        fs::create_dir_all(source_dir.join("guide")).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("ok.md"), "Ok").expect("Failed to write ok.md");
+        fs::write(source_dir.join("shared.md"), "Shared").expect("Failed to write shared.md");
+        let secret = temp_dir.path().join("secret.md");
+        fs::write(&secret, "Secret").expect("Failed to write secret.md");
+        fs::write(
+            source_dir.join("guide").join("a.md"),
+            format!(
+                "!include (ok.md)\n!include (../shared.md)\n!include (../../secret.md)\n!include ({})\n!codesnippet (../../secret.md)\n",
+                secret.display()
+            ),
+        )
+        .expect("Failed to write a.md");
+        let processor = |restrict| {
+            Processor::builder()
+                .source(&source_dir)
+                .partials(&partials_dir)
+                .output(temp_dir.path().join("output"))
+                .restrict_includes(restrict)
+                .build()
+                .expect("Failed to build processor")
         };
 
-        summary.set_current_file(file_path.to_string_lossy().to_string());
-        progress_callback(summary);
+        let summary = processor(false).check().expect("Failed to check files");
+        assert!(
+            summary.results[0]
+                .includes
+                .iter()
+                .all(|include| include.success)
+        );
 
-        let result = process_single_file(
-            &file_path,
-            &config.partials_path,
-            &output_path,
-            config.fix_code_fences.as_deref(),
+        for summary in [
+            processor(true).run().expect("Failed to run processor"),
+            processor(true).check().expect("Failed to check files"),
+        ] {
+            let includes = &summary.results[0].includes;
+            let allowed: Vec<bool> = includes.iter().map(|include| include.success).collect();
+            assert_eq!(allowed, [true, true, false, false, false]);
+            assert!(
+                includes[2]
+                    .error_message
+                    .as_deref()
+                    .is_some_and(|message| message.contains("outside the directories"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_banner_is_put_after_frontmatter() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(source_dir.join("guide")).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(
+            source_dir.join("guide").join("a.md"),
+            "---\ntitle: A\n---\n# A",
         )
-        .expect("Failed to process single file");
-        summary.add_result(result);
+        .expect("Failed to write a.md");
 
-        progress_callback(summary);
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .banner("<!-- Generated from {source}; do not edit -->")
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        let output =
+            fs::read_to_string(output_dir.join("guide").join("a.md")).expect("Failed to read a.md");
+        assert_eq!(
+            output,
+            format!(
+                "---\ntitle: A\n---\n<!-- Generated from {}; do not edit -->\n# A",
+                source_dir.join("guide").join("a.md").display()
+            )
+        );
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_glossary_terms_are_linked_in_each_output() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("intro.md"), "A partial is a partial.")
+            .expect("Failed to write intro.md");
+        for name in ["a.md", "b.md"] {
+            fs::write(source_dir.join(name), "!include(intro.md)")
+                .expect("Failed to write source file");
+        }
+        let glossary = temp_dir.path().join("glossary.toml");
+        fs::write(
+            &glossary,
+            "[terms]
+partial = \"/glossary/#partial\"\n",
+        )
+        .expect("Failed to write glossary");
 
-fn process_single_file(
-    source_file: &Path,
-    partials_path: &Path,
-    output_file: &Path,
-    fix_code_fences: Option<&str>,
-) -> Result<FileProcessResult, Box<dyn std::error::Error>> {
-    let content = fs::read_to_string(source_file).expect("Failed to read source file content");
-    let mut includes_tracker = Vec::new();
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .glossary(&glossary)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
 
-    match process_includes_with_validation(
-        &content,
-        source_file,
-        partials_path,
-        &mut includes_tracker,
-        fix_code_fences,
-    ) {
-        Ok(processed_content) => {
-            match write_file(output_file, &processed_content) {
-                Ok(_) => {
-                    // Check if any includes failed
-                    let has_failed_includes = includes_tracker.iter().any(|inc| !inc.success);
-
-                    Ok(FileProcessResult {
-                        file_path: source_file.to_string_lossy().to_string(),
-                        success: !has_failed_includes, // File fails if any include fails
-                        includes: includes_tracker.clone(),
-                        error_message: if has_failed_includes {
-                            let failed_includes: Vec<String> = includes_tracker
-                                .iter()
-                                .filter(|inc| !inc.success)
-                                .map(|inc| {
-                                    if let Some(ref error) = inc.error_message {
-                                        format!("  • {} ({})", inc.path, error)
-                                    } else {
-                                        format!("  • {}", inc.path)
-                                    }
-                                })
-                                .collect();
-
-                            Some(format!(
-                                "File contains failed includes:\n{}",
-                                failed_includes.join("\n")
-                            ))
-                        } else {
-                            None
-                        },
-                    })
-                }
-                Err(e) => Ok(FileProcessResult {
-                    file_path: source_file.to_string_lossy().to_string(),
-                    success: false,
-                    includes: includes_tracker.clone(),
-                    error_message: Some(format!("Failed to write output: {e}")),
-                }),
-            }
+        assert_eq!(summary.get_success_count(), 2);
+        for name in ["a.md", "b.md"] {
+            assert_eq!(
+                fs::read_to_string(output_dir.join(name)).expect("Failed to read output"),
+                "A [partial](/glossary/#partial) is a partial."
+            );
         }
-        Err(e) => Ok(FileProcessResult {
-            file_path: source_file.to_string_lossy().to_string(),
-            success: false,
-            includes: includes_tracker,
-            error_message: Some(format!("Failed to process includes: {e}")),
-        }),
     }
-}
-
-fn calculate_output_path(
-    file_path: &Path,
-    source_root: &Path,
-    output_root: &Path,
-) -> Result<PathBuf, Box<dyn std::error::Error>> {
-    let relative_path = file_path
-        .strip_prefix(source_root)
-        .expect("Failed to strip source root prefix from file path");
-    Ok(output_root.join(relative_path))
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use tempfile::TempDir;
 
     #[test]
-    fn test_calculate_output_path() {
+    fn test_frontmatter_is_stripped_before_the_banner() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
-        let source_root = temp_dir.path().join("src");
-        let output_root = temp_dir.path().join("output");
-        let file_path = source_root.join("docs").join("readme.md");
+        let source_file = temp_dir.path().join("a.md");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_file = temp_dir.path().join("out.md");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(&source_file, "---\ntitle: A\n---\n\n# A").expect("Failed to write a.md");
 
-        let result = calculate_output_path(&file_path, &source_root, &output_root)
-            .expect("Failed to calculate output path");
-        assert_eq!(result, output_root.join("docs").join("readme.md"));
+        let summary = Processor::builder()
+            .source(&source_file)
+            .partials(&partials_dir)
+            .output(&output_file)
+            .banner("<!-- generated -->")
+            .frontmatter(Frontmatter::Strip)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        assert_eq!(
+            fs::read_to_string(&output_file).expect("Failed to read output"),
+            "<!-- generated -->\n# A"
+        );
     }
 
     #[test]
-    fn test_process_single_file_success() {
+    fn test_reprocess_file_replaces_its_result() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
         let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(source_dir.join("guide")).expect("Failed to create source directory");
         fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(source_dir.join("a.md"), "# A").expect("Failed to write a.md");
+        fs::write(
+            source_dir.join("guide").join("b.md"),
+            "# B\n\n!include (later.md)",
+        )
+        .expect("Failed to write b.md");
 
-        // Create source file
-        let source_file = temp_dir.path().join("source.md");
-        fs::write(&source_file, "# Title\n\nContent here.").expect("Failed to write source file");
-
-        // Create output path
-        let output_file = temp_dir.path().join("output.md");
-
-        let result = process_single_file(&source_file, &partials_dir, &output_file, None)
-            .expect("Failed to process single file");
+        let processor = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .build()
+            .expect("Failed to build processor");
+        let mut summary = processor.run().expect("Failed to run processor");
+        assert_eq!(summary.get_failed_count(), 1);
 
-        assert!(result.success);
-        assert_eq!(result.includes.len(), 0);
-        assert!(result.error_message.is_none());
-        assert!(output_file.exists());
+        fs::write(partials_dir.join("later.md"), "Fixed").expect("Failed to write later.md");
+        let failed = summary.results[1].file_path.clone();
+        let rerun = reprocess_file(processor.config(), Path::new(&failed))
+            .expect("Failed to reprocess file");
+        summary.replace_results(rerun);
 
-        let output_content = fs::read_to_string(&output_file).expect("Failed to read output file");
-        assert_eq!(output_content, "# Title\n\nContent here.");
+        assert_eq!(summary.results.len(), 2);
+        assert_eq!(summary.processed_files, 2);
+        assert_eq!(summary.get_failed_count(), 0);
+        assert_eq!(
+            fs::read_to_string(output_dir.join("guide").join("b.md")).expect("Failed to read b.md"),
+            "# B\n\nFixed"
+        );
     }
 
     #[test]
-    fn test_process_single_file_with_includes() {
+    fn test_diff_reports_changes_without_writing() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
         let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
         fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::create_dir_all(&output_dir).expect("Failed to create output directory");
+        fs::write(partials_dir.join("intro.md"), "Updated intro")
+            .expect("Failed to write intro.md");
+        fs::write(source_dir.join("a.md"), "# A\n\n!include (intro.md)\n")
+            .expect("Failed to write a.md");
+        fs::write(source_dir.join("b.md"), "# B\n").expect("Failed to write b.md");
+        fs::write(source_dir.join("c.md"), "# C\n").expect("Failed to write c.md");
+        fs::write(output_dir.join("a.md"), "# A\n\nOld intro\n").expect("Failed to write output");
+        fs::write(output_dir.join("b.md"), "# B\n").expect("Failed to write output");
 
-        // Create partial file
-        fs::write(partials_dir.join("header.md"), "# Welcome").expect("Failed to write header.md");
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .diff(true)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
 
-        // Create source file with include
-        let source_file = temp_dir.path().join("source.md");
-        fs::write(&source_file, "!include (header.md)\n\nMain content.")
-            .expect("Failed to write source file");
+        assert_eq!(summary.get_success_count(), 3);
+        assert_eq!(summary.diffs.len(), 2);
+        assert_eq!(
+            summary.diffs[0].diff,
+            format!(
+                "--- a/{0}\n+++ b/{0}\n@@ -1,3 +1,3 @@\n # A\n \n-Old intro\n+Updated intro\n",
+                output_dir.join("a.md").display()
+            )
+        );
+        // A missing output shows as added; an unchanged one has no diff
+        assert!(summary.diffs[1].output_path.ends_with("c.md"));
+        assert!(summary.diffs[1].diff.contains("+# C"));
 
-        // Create output path
-        let output_file = temp_dir.path().join("output.md");
+        assert_eq!(
+            fs::read_to_string(output_dir.join("a.md")).expect("Failed to read output"),
+            "# A\n\nOld intro\n"
+        );
+        assert!(!output_dir.join("c.md").exists());
+    }
 
-        let result = process_single_file(&source_file, &partials_dir, &output_file, None)
-            .expect("Failed to process single file");
+    #[test]
+    fn test_emit_html_with_template_and_css() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        let template = temp_dir.path().join("page.html");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("intro.md"), "Some *intro*").expect("Failed to write intro.md");
+        fs::write(
+            source_dir.join("guide.md"),
+            "# Guide\n\n!include (intro.md)",
+        )
+        .expect("Failed to write guide.md");
+        fs::write(
+            &template,
+            "<title>{{ title }}</title>{{ css }}<main>{{ content }}</main>",
+        )
+        .expect("Failed to write template");
 
-        assert!(result.success);
-        assert_eq!(result.includes.len(), 1);
-        assert!(result.includes[0].success);
-        assert!(result.error_message.is_none());
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .emit(Emit::Both)
+            .html_template(&template)
+            .css("/site.css")
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
 
-        let output_content = fs::read_to_string(&output_file).expect("Failed to read output file");
-        assert_eq!(output_content, "# Welcome\n\nMain content.");
+        assert_eq!(summary.get_success_count(), 1);
+        let markdown =
+            fs::read_to_string(output_dir.join("guide.md")).expect("Failed to read guide.md");
+        assert_eq!(markdown, "# Guide\n\nSome *intro*");
+        let html =
+            fs::read_to_string(output_dir.join("guide.html")).expect("Failed to read guide.html");
+        assert_eq!(
+            html,
+            "<title>Guide</title><link rel=\"stylesheet\" href=\"/site.css\"><main><h1>Guide</h1>\n<p>Some <em>intro</em></p>\n</main>"
+        );
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_process_single_file_with_failed_include() {
+    fn test_post_process_runs_per_output_and_records_failures() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
         let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
         fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(source_dir.join("a.md"), "# A").expect("Failed to write a.md");
+        fs::write(source_dir.join("broken.md"), "# Broken").expect("Failed to write broken.md");
 
-        // Create source file with missing include
-        let source_file = temp_dir.path().join("source.md");
-        fs::write(&source_file, "!include (missing.md)\n\nMain content.")
-            .expect("Failed to write source file");
-
-        // Create output path
-        let output_file = temp_dir.path().join("output.md");
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .post_process("grep -q Broken {} && exit 1; echo formatted >> {}")
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
 
-        let result = process_single_file(&source_file, &partials_dir, &output_file, None)
-            .expect("Failed to process single file");
+        assert_eq!(summary.get_success_count(), 1);
+        let output = fs::read_to_string(output_dir.join("a.md")).expect("Failed to read a.md");
+        assert_eq!(output, "# Aformatted\n");
 
-        assert!(!result.success); // Should fail due to missing include
-        assert_eq!(result.includes.len(), 1);
-        assert!(!result.includes[0].success);
-        assert!(result.error_message.is_some());
+        let failed = &summary.results[1];
+        assert!(!failed.success);
+        assert_eq!(failed.error_code, Some(ErrorCode::PostProcessFailed));
         assert!(
-            result
+            failed
                 .error_message
-                .as_ref()
-                .expect("Error message should be present for failed includes")
-                .contains("failed includes")
+                .as_deref()
+                .is_some_and(|e| e.starts_with("Post-processing failed"))
         );
-
-        // File should still be written with error comments
-        assert!(output_file.exists());
-        let output_content = fs::read_to_string(&output_file).expect("Failed to read output file");
-        assert!(output_content.contains("<!-- Failed to include"));
-        assert!(output_content.contains("Main content."));
     }
 
     #[test]
-    fn test_process_files_single_file() {
+    fn test_outputs_over_the_budget_fail_without_being_written() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
         let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
         fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("generated.md"), "x".repeat(200))
+            .expect("Failed to write generated.md");
+        fs::write(source_dir.join("a.md"), "# A").expect("Failed to write a.md");
+        fs::write(source_dir.join("big.md"), "!include(generated.md)")
+            .expect("Failed to write big.md");
+        fs::write(source_dir.join("c.md"), "# C").expect("Failed to write c.md");
+        fs::write(source_dir.join("d.md"), "# Deeper").expect("Failed to write d.md");
 
-        // Create source file
-        let source_file = temp_dir.path().join("source.md");
-        fs::write(&source_file, "# Test Document").expect("Failed to write source file");
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .budget(OutputBudget {
+                per_file: Some(100),
+                total: Some(10),
+            })
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
 
-        // Create output file path
-        let output_file = temp_dir.path().join("output.md");
+        let failed: Vec<&str> = summary
+            .results
+            .iter()
+            .filter(|result| !result.success)
+            .map(|result| {
+                assert_eq!(result.error_code, Some(ErrorCode::OutputTooLarge));
+                Path::new(&result.file_path)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default()
+            })
+            .collect();
+        // big.md is over the per-file limit, and d.md would take the total from 6 bytes to 14
+        assert_eq!(failed, ["big.md", "d.md"]);
+        assert!(output_dir.join("a.md").exists());
+        assert!(output_dir.join("c.md").exists());
+        assert!(!output_dir.join("big.md").exists());
+        assert!(!output_dir.join("d.md").exists());
 
-        let config = ProcessingConfig {
-            source_path: source_file.clone(),
-            partials_path: partials_dir,
-            output_path: output_file.clone(),
-            batch: false,
-            verbose: false,
-            fix_code_fences: None,
-        };
+        // Source maps count too
+        let sink = MemorySink::new();
+        let summary = Processor::builder()
+            .source(source_dir.join("a.md"))
+            .partials(&partials_dir)
+            .output(output_dir.join("a.md"))
+            .source_map(true)
+            .budget(OutputBudget {
+                per_file: None,
+                total: Some(50),
+            })
+            .build()
+            .expect("Failed to build processor")
+            .run_to(&sink)
+            .expect("Failed to run processor");
+        assert_eq!(
+            summary.results[0].error_code,
+            Some(ErrorCode::OutputTooLarge)
+        );
+        assert!(sink.get(output_dir.join("a.md.map.json")).is_none());
+    }
 
-        let mut summary = ProcessingSummary::new();
+    #[cfg(unix)]
+    #[test]
+    fn test_spellcheck_reports_misspellings_in_their_partials() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("setup.md"), "Then recieve it.")
+            .expect("Failed to write setup.md");
+        fs::write(source_dir.join("guide.md"), "# Guide\n\n!include(setup.md)")
+            .expect("Failed to write guide.md");
 
-        process_files(&config, &mut summary, |_| {
-            // Progress callback was called
-        })
-        .expect("Failed to process files");
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .spellcheck(SpellcheckConfig {
+                command: "tr -cs 'A-Za-z' '\\n' | grep -x recieve".to_string(),
+                dictionaries: Vec::new(),
+            })
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
 
-        assert_eq!(summary.results.len(), 1);
-        assert!(summary.results[0].success);
-        assert!(output_file.exists());
+        assert_eq!(summary.get_success_count(), 1);
+        assert_eq!(summary.warnings.len(), 1);
+        let warning = &summary.warnings[0];
+        assert_eq!(warning.kind, WarningKind::Misspelling);
+        assert_eq!(
+            warning.file,
+            partials_dir.join("setup.md").to_string_lossy().to_string()
+        );
+        assert_eq!(warning.line, 1);
+        assert_eq!(warning.message, "`recieve` may be misspelled");
     }
 
     #[test]
-    fn test_process_files_batch_mode() {
+    fn test_lint_warnings_point_at_the_output() {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
         let source_dir = temp_dir.path().join("source");
         let partials_dir = temp_dir.path().join("partials");
         let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("deep.md"), "### Deep").expect("Failed to write deep.md");
+        fs::write(source_dir.join("guide.md"), "# Guide\n\n!include(deep.md)")
+            .expect("Failed to write guide.md");
+
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .banner("<!-- Generated -->")
+            .lint(LintConfig::default())
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        assert_eq!(summary.warnings.len(), 1);
+        let warning = &summary.warnings[0];
+        assert_eq!(warning.kind, WarningKind::Lint);
+        assert_eq!(
+            warning.file,
+            output_dir.join("guide.md").to_string_lossy().to_string()
+        );
+        // Lines are counted in the output, after the banner
+        assert_eq!(warning.line, 4);
+        assert_eq!(
+            warning.message,
+            "heading-increment: Heading jumps from H1 to H3"
+        );
+    }
 
+    #[test]
+    fn test_directives_left_in_the_output_are_warned_about() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
         fs::create_dir_all(&source_dir).expect("Failed to create source directory");
         fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(partials_dir.join("intro.md"), "Intro\n\n!include outro.md")
+            .expect("Failed to write intro.md");
+        fs::write(
+            source_dir.join("guide.md"),
+            "# Guide\n\n!include(intro.md)\n\n```md\n!include(example.md)\n```",
+        )
+        .expect("Failed to write guide.md");
 
-        // Create multiple source files
-        fs::write(source_dir.join("doc1.md"), "# Document 1").expect("Failed to write doc1.md");
-        fs::write(source_dir.join("doc2.md"), "# Document 2").expect("Failed to write doc2.md");
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .build()
+            .expect("Failed to build processor")
+            .run()
+            .expect("Failed to run processor");
 
-        // Create subdirectory with file
-        let sub_dir = source_dir.join("subdir");
-        fs::create_dir(&sub_dir).expect("Failed to create subdirectory");
-        fs::write(sub_dir.join("doc3.md"), "# Document 3").expect("Failed to write doc3.md");
+        assert_eq!(summary.get_success_count(), 1);
+        assert_eq!(summary.warnings.len(), 1);
+        let warning = &summary.warnings[0];
+        assert_eq!(warning.kind, WarningKind::LeftoverDirective);
+        assert_eq!(
+            warning.file,
+            output_dir.join("guide.md").to_string_lossy().to_string()
+        );
+        assert_eq!(warning.line, 5);
+        assert_eq!(
+            warning.message,
+            "`!include outro.md` was left in the output unexpanded; check its syntax"
+        );
+    }
 
-        let config = ProcessingConfig {
-            source_path: source_dir.clone(),
-            partials_path: partials_dir,
-            output_path: output_dir.clone(),
-            batch: true,
-            verbose: false,
-            fix_code_fences: None,
+    #[test]
+    fn test_strict_mode_fails_files_with_failed_directives_unwritten() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        let partials_dir = temp_dir.path().join("partials");
+        let output_dir = temp_dir.path().join("output");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::create_dir_all(&partials_dir).expect("Failed to create partials directory");
+        fs::write(
+            partials_dir.join("generated.md"),
+            "Intro\n<!-- Failed to include: old.md (Error: not found) -->",
+        )
+        .expect("Failed to write generated.md");
+        fs::write(source_dir.join("missing.md"), "# A\n\n!include(nowhere.md)")
+            .expect("Failed to write missing.md");
+        fs::write(
+            source_dir.join("carried.md"),
+            "# B\n\n!include(generated.md)",
+        )
+        .expect("Failed to write carried.md");
+        fs::write(
+            source_dir.join("quoted.md"),
+            "# C\n\n```md\n<!-- Failed to include: x.md (Error: not found) -->\n```",
+        )
+        .expect("Failed to write quoted.md");
+
+        let sink = MemorySink::new();
+        let summary = Processor::builder()
+            .source(&source_dir)
+            .partials(&partials_dir)
+            .output(&output_dir)
+            .strict(true)
+            .build()
+            .expect("Failed to build processor")
+            .run_to(&sink)
+            .expect("Failed to run processor");
+
+        assert_eq!(summary.get_success_count(), 1);
+        assert_eq!(sink.paths(), vec![output_dir.join("quoted.md")]);
+        let error = |name: &str| {
+            summary
+                .results
+                .iter()
+                .find(|result| result.file_path.ends_with(name))
+                .and_then(|result| result.error_message.clone())
+                .unwrap_or_default()
         };
+        assert!(
+            error("missing.md")
+                .starts_with("Not written in strict mode. File contains failed includes")
+        );
+        assert_eq!(
+            error("carried.md"),
+            "Not written in strict mode: line 4 has `<!-- Failed to include: old.md (Error: not found) -->`"
+        );
+    }
 
-        let mut summary = ProcessingSummary::new();
+    #[test]
+    fn test_processor_builder_validates_paths() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
 
-        process_files(&config, &mut summary, |_| {}).expect("Failed to process files");
+        let missing_source = Processor::builder().build();
+        assert!(missing_source.is_err());
 
-        assert_eq!(summary.results.len(), 3);
-        assert!(summary.results.iter().all(|r| r.success));
+        let missing_partials = Processor::builder()
+            .source(temp_dir.path())
+            .partials(temp_dir.path().join("nope"))
+            .build();
+        assert!(
+            missing_partials
+                .err()
+                .is_some_and(|e| e.to_string().contains("Partials path does not exist"))
+        );
+    }
 
-        // Check output files exist in correct structure
-        assert!(output_dir.join("doc1.md").exists());
-        assert!(output_dir.join("doc2.md").exists());
-        assert!(output_dir.join("subdir").join("doc3.md").exists());
+    #[test]
+    fn test_process_string_prefetches_includes_on_other_threads() {
+        /// Records the thread of every read
+        struct Recording {
+            inner: MemorySource,
+            reads: std::sync::Mutex<Vec<(PathBuf, std::thread::ThreadId)>>,
+        }
+        impl PartialSource for Recording {
+            fn read(&self, path: &Path) -> std::io::Result<String> {
+                self.reads
+                    .lock()
+                    .expect("Failed to lock reads")
+                    .push((path.to_path_buf(), std::thread::current().id()));
+                self.inner.read(path)
+            }
+        }
+
+        let mut partials = MemorySource::new();
+        let mut content = String::new();
+        for index in 0..10 {
+            partials.insert(format!("part{index}.md"), format!("Part {index}"));
+            content.push_str(&format!("!include (part{index}.md)\n\n"));
+        }
+        content.push_str("!include (part0.md)\n\n!include (missing.md)");
+        let expected = process_string(&content, &partials).expect("Failed to process string");
+
+        let source = PrefetchSource::new(Recording {
+            inner: partials,
+            reads: Default::default(),
+        });
+        let result = process_string(&content, &source).expect("Failed to process string");
+        assert_eq!(result.content, expected.content);
+        let outcomes = |includes: &[IncludeResult]| -> Vec<(String, bool)> {
+            includes
+                .iter()
+                .map(|include| (include.path.clone(), include.success))
+                .collect()
+        };
+        assert_eq!(outcomes(&result.includes), outcomes(&expected.includes));
+
+        // Each partial was read once, ahead of resolution; only the missing one is read again
+        let reads = source
+            .into_inner()
+            .reads
+            .into_inner()
+            .expect("Failed to lock reads");
+        let on_this_thread: Vec<&PathBuf> = reads
+            .iter()
+            .filter(|(_, thread)| *thread == std::thread::current().id())
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(on_this_thread, [&PathBuf::from("missing.md")]);
+        assert_eq!(reads.len(), 12);
+    }
+
+    #[test]
+    fn test_process_string_with_memory_source() {
+        let partials = MemorySource::from(HashMap::from([
+            (
+                "header.md".to_string(),
+                "# {% heading || \"Default\" %}\n\n!include (nested/logo.md)".to_string(),
+            ),
+            ("nested/logo.md".to_string(), "LOGO".to_string()),
+            ("main.rs".to_string(), "fn main() {}".to_string()),
+        ]));
+
+        let result = process_string(
+            "!include (header.md, values=[heading=\"Docs\"])\n\n!codesnippet (main.rs, lang=\"rust\")\n\n!include (missing.md)",
+            &partials,
+        )
+        .expect("Failed to process string");
+
+        assert!(
+            result
+                .content
+                .starts_with("# Docs\n\nLOGO\n\n```rust\nfn main() {}\n```")
+        );
+        assert!(
+            result
+                .content
+                .contains("<!-- Failed to include: missing.md")
+        );
+        assert_eq!(result.includes.len(), 4);
+        assert!(!result.is_success());
+    }
+
+    #[test]
+    fn test_process_string_with_file_system_source() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        fs::write(temp_dir.path().join("footer.md"), "Footer").expect("Failed to write footer");
+
+        let result = process_string(
+            "Body\n\n!include (footer.md)",
+            &FileSystemSource::new(temp_dir.path()),
+        )
+        .expect("Failed to process string");
+
+        assert_eq!(result.content, "Body\n\nFooter");
+        assert!(result.is_success());
     }
 }
@@ -0,0 +1,562 @@
+//! Reports of a processing run, saved to a file after the run
+
+use crate::banner::utc_timestamp;
+use crate::html::{DEFAULT_TEMPLATE, render_page};
+use crate::types::{DirectiveCount, ProcessingSummary, SizeStats};
+use crate::usage::{PartialFailures, PartialUsage, heat_bar, partial_failures, partial_usage};
+use schemars::{JsonSchema, Schema, schema_for};
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    /// Markdown for `.md` and `.markdown` paths, HTML for `.html` and `.htm`, JSON otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("md" | "markdown") => ReportFormat::Markdown,
+            Some("html" | "htm") => ReportFormat::Html,
+            _ => ReportFormat::Json,
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(ReportFormat::Json),
+            "markdown" | "md" => Some(ReportFormat::Markdown),
+            "html" => Some(ReportFormat::Html),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a `--report` value: `FORMAT=PATH`, or a bare `PATH` whose extension picks the format
+pub fn parse_report_target(value: &str) -> Result<(ReportFormat, PathBuf), String> {
+    if let Some((name, path)) = value.split_once('=')
+        && let Some(format) = ReportFormat::from_name(name)
+    {
+        if path.is_empty() {
+            return Err(format!("missing path after `{name}=`"));
+        }
+        return Ok((format, PathBuf::from(path)));
+    }
+    if value.is_empty() {
+        return Err("expected FORMAT=PATH, e.g. markdown=report.md".to_string());
+    }
+    let path = PathBuf::from(value);
+    Ok((ReportFormat::from_path(&path), path))
+}
+
+#[derive(Debug, Serialize, JsonSchema)]
+struct Totals {
+    total: usize,
+    succeeded: usize,
+    failed: usize,
+}
+
+/// A JSON report: the summary of a run with its totals and partial usage
+#[derive(Debug, Serialize, JsonSchema)]
+struct Report<'a> {
+    generated_at: String,
+    elapsed_seconds: f64,
+    files: Totals,
+    includes: Totals,
+    /// Includes by directive, e.g. `include` or `codesnippet`
+    directives: Vec<DirectiveCount>,
+    sizes: SizeStats,
+    partial_usage: Vec<PartialUsage>,
+    /// Partials whose inclusion failed, the one failing the most documents first
+    partial_failures: Vec<PartialFailures>,
+    #[serde(flatten)]
+    summary: &'a ProcessingSummary,
+}
+
+/// JSON Schema of JSON reports
+pub fn report_schema() -> Schema {
+    schema_for!(Report<'static>)
+}
+
+/// Default file name of a report generated at `time`, e.g. `md2md-report-2025-01-31T09-30-00Z.json`
+pub fn default_report_path(time: SystemTime) -> String {
+    format!(
+        "md2md-report-{}.json",
+        utc_timestamp(time).replace(':', "-")
+    )
+}
+
+/// Renders the report of a run that took `elapsed`, generated at `time`
+pub fn render_report(
+    summary: &ProcessingSummary,
+    elapsed: Duration,
+    time: SystemTime,
+    format: ReportFormat,
+) -> Result<String, Box<dyn std::error::Error>> {
+    let report = Report {
+        generated_at: utc_timestamp(time),
+        elapsed_seconds: elapsed.as_secs_f64(),
+        files: Totals {
+            total: summary.results.len(),
+            succeeded: summary.get_success_count(),
+            failed: summary.get_failed_count(),
+        },
+        includes: Totals {
+            total: summary.get_total_includes(),
+            succeeded: summary.get_successful_includes(),
+            failed: summary.get_failed_includes(),
+        },
+        directives: summary.get_directives_by_kind(),
+        sizes: summary.get_size_stats(),
+        partial_usage: partial_usage(summary),
+        partial_failures: partial_failures(summary),
+        summary,
+    };
+    match format {
+        ReportFormat::Json => Ok(serde_json::to_string_pretty(&report)? + "\n"),
+        ReportFormat::Markdown => Ok(markdown_report(&report)),
+        ReportFormat::Html => Ok(render_page(
+            &markdown_report(&report),
+            DEFAULT_TEMPLATE,
+            None,
+            "md2md report",
+        )),
+    }
+}
+
+/// Writes the report of a run to `path` in `format`
+pub fn write_report(
+    path: &Path,
+    format: ReportFormat,
+    summary: &ProcessingSummary,
+    elapsed: Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let report = render_report(summary, elapsed, SystemTime::now(), format)?;
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, report)?;
+    Ok(())
+}
+
+fn markdown_report(report: &Report) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# md2md report\n");
+    let _ = writeln!(
+        out,
+        "Generated {} in {:.2}s by md2md {}.\n",
+        report.generated_at, report.elapsed_seconds, report.summary.version
+    );
+    if let Some(config) = &report.summary.config {
+        let _ = writeln!(
+            out,
+            "Source `{}`, partials `{}`, output `{}`.\n",
+            config.source_path.display(),
+            config.partials_path.display(),
+            config.output_path.display()
+        );
+    }
+    if !report.summary.complete {
+        let _ = writeln!(out, "> The run hadn't finished; results are partial.\n");
+    }
+    let _ = writeln!(out, "| | Total | Succeeded | Failed |");
+    let _ = writeln!(out, "|---|---|---|---|");
+    for (name, totals) in [("Files", &report.files), ("Includes", &report.includes)] {
+        let _ = writeln!(
+            out,
+            "| {name} | {} | {} | {} |",
+            totals.total, totals.succeeded, totals.failed
+        );
+    }
+    for count in &report.directives {
+        let _ = writeln!(
+            out,
+            "| └─ `!{}` | {} | {} | {} |",
+            count.kind.as_str(),
+            count.total,
+            count.total - count.failed,
+            count.failed
+        );
+    }
+
+    if let Some((file, bytes)) = &report.sizes.largest_output {
+        let _ = write!(
+            out,
+            "\nRead {} bytes of sources and wrote {} bytes. The largest output, from `{file}`, has {bytes} bytes",
+            report.sizes.bytes_read, report.sizes.bytes_written
+        );
+        match report.sizes.average_expansion {
+            Some(ratio) => {
+                let _ = writeln!(out, "; outputs are {ratio:.2}× their source on average.");
+            }
+            None => out.push_str(".\n"),
+        }
+    }
+
+    let _ = writeln!(out, "\n## Files\n");
+    let _ = writeln!(out, "| Status | File | Includes | Error |");
+    let _ = writeln!(out, "|---|---|---|---|");
+    for result in &report.summary.results {
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} |",
+            if result.success { "✓" } else { "✗" },
+            table_cell(&result.file_path),
+            result.includes.len(),
+            table_cell(&result.formatted_error().unwrap_or_default())
+        );
+    }
+
+    if !report.summary.skipped.is_empty() {
+        let _ = writeln!(out, "\n## Skipped files\n");
+        let _ = writeln!(out, "| File | Reason |");
+        let _ = writeln!(out, "|---|---|");
+        for file in &report.summary.skipped {
+            let _ = writeln!(
+                out,
+                "| {} | {} |",
+                table_cell(&file.path),
+                file.reason.as_str()
+            );
+        }
+    }
+
+    let includes: Vec<_> = report
+        .summary
+        .results
+        .iter()
+        .flat_map(|result| result.includes.iter().map(move |include| (result, include)))
+        .collect();
+    if !includes.is_empty() {
+        let _ = writeln!(out, "\n## Includes\n");
+        let _ = writeln!(out, "| Status | File | Include | Depth | Time |");
+        let _ = writeln!(out, "|---|---|---|---|---|");
+        for (result, include) in includes {
+            let time = include.duration.map_or_else(
+                || "–".to_string(),
+                |duration| format!("{:.1} ms", duration.as_secs_f64() * 1000.0),
+            );
+            let time = match include.attempts {
+                Some(attempts) => format!("{time}, {attempts} attempts"),
+                None => time,
+            };
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {time} |",
+                if include.success { "✓" } else { "✗" },
+                table_cell(&result.file_path),
+                table_cell(&include.path),
+                include.depth
+            );
+        }
+    }
+
+    if !report.partial_usage.is_empty() {
+        let _ = writeln!(out, "\n## Partial usage\n");
+        let _ = writeln!(out, "| Partial | Includes | Bytes | Documents |");
+        let _ = writeln!(out, "|---|---|---|---|");
+        for usage in &report.partial_usage {
+            let documents: Vec<&str> = usage.documents.iter().map(String::as_str).collect();
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                table_cell(&usage.path),
+                usage.count,
+                usage.bytes,
+                table_cell(&documents.join(", "))
+            );
+        }
+    }
+
+    if let Some(worst) = report.partial_failures.first() {
+        let most = worst.documents.len();
+        let _ = writeln!(out, "\n## Failures by partial\n");
+        let _ = writeln!(out, "| Partial | Failed documents | | Failed includes |");
+        let _ = writeln!(out, "|---|---|---|---|");
+        for failures in &report.partial_failures {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} |",
+                table_cell(&failures.path),
+                failures.documents.len(),
+                heat_bar(failures.documents.len(), most, 20),
+                failures.count
+            );
+        }
+    }
+
+    let failed: Vec<_> = report
+        .summary
+        .results
+        .iter()
+        .flat_map(|result| {
+            result
+                .includes
+                .iter()
+                .filter(|include| !include.success)
+                .map(move |include| (result, include))
+        })
+        .collect();
+    if !failed.is_empty() {
+        let _ = writeln!(out, "\n## Failed includes\n");
+        for (result, include) in failed {
+            let _ = write!(out, "- `{}` → `{}`", result.file_path, include.path);
+            match include.formatted_error() {
+                Some(error) => {
+                    let _ = writeln!(out, ": {error}");
+                }
+                None => out.push('\n'),
+            }
+        }
+    }
+
+    if !report.summary.warnings.is_empty() {
+        let _ = writeln!(out, "\n## Warnings\n");
+        for warning in &report.summary.warnings {
+            let _ = writeln!(
+                out,
+                "- `{}:{}` ({}): {}",
+                warning.file,
+                warning.line,
+                warning.kind.as_str(),
+                warning.message
+            );
+        }
+    }
+    out
+}
+
+/// Keeps a value on one line of its Markdown table cell
+fn table_cell(value: &str) -> String {
+    value.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error_code::ErrorCode;
+    use crate::types::{
+        DirectiveKind, FileProcessResult, IncludeResult, ProcessingEvent, RunConfig, SkipReason,
+        SkippedFile,
+    };
+    use std::collections::{BTreeMap, HashMap};
+    use std::time::UNIX_EPOCH;
+
+    fn summary() -> ProcessingSummary {
+        let mut summary = ProcessingSummary::new();
+        summary.add_result(FileProcessResult {
+            file_path: "docs/a.md".to_string(),
+            success: true,
+            includes: Vec::new(),
+            error_message: None,
+            error_code: None,
+            sizes: None,
+        });
+        summary.add_result(FileProcessResult {
+            file_path: "docs/b.md".to_string(),
+            success: false,
+            includes: vec![IncludeResult {
+                path: "missing.md".to_string(),
+                success: false,
+                error_message: Some("File not found".to_string()),
+                error_code: Some(ErrorCode::MissingInclude),
+                parent: "docs/b.md".to_string(),
+                depth: 0,
+                title: None,
+                values: HashMap::new(),
+                duration: Some(Duration::from_micros(2500)),
+                bytes: None,
+                attempts: None,
+                kind: DirectiveKind::Include,
+            }],
+            error_message: None,
+            error_code: None,
+            sizes: None,
+        });
+        summary.skipped.push(SkippedFile {
+            path: "docs/logo.png".to_string(),
+            reason: SkipReason::NotMarkdown,
+        });
+        summary
+    }
+
+    #[test]
+    fn test_json_report_has_totals_and_results() {
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_210_096);
+        let report = render_report(
+            &summary(),
+            Duration::from_millis(1500),
+            time,
+            ReportFormat::Json,
+        )
+        .expect("Failed to render report");
+        let json: serde_json::Value = serde_json::from_str(&report).expect("Invalid JSON");
+
+        assert_eq!(json["generated_at"], "2024-02-29T12:34:56Z");
+        assert_eq!(json["elapsed_seconds"], 1.5);
+        assert_eq!(json["files"]["failed"], 1);
+        assert_eq!(json["includes"]["total"], 1);
+        assert_eq!(json["results"][1]["file_path"], "docs/b.md");
+        assert_eq!(json["skipped"][0]["reason"], "not-markdown");
+        assert_eq!(json["partial_failures"][0]["path"], "missing.md");
+        assert_eq!(json["partial_failures"][0]["documents"][0], "docs/b.md");
+        assert_eq!(json["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["complete"], false);
+        assert!(json["started_at"].is_null());
+    }
+
+    #[test]
+    fn test_markdown_report_lists_files_and_failed_includes() {
+        let report = render_report(
+            &summary(),
+            Duration::from_secs(2),
+            UNIX_EPOCH,
+            ReportFormat::Markdown,
+        )
+        .expect("Failed to render report");
+
+        assert!(report.starts_with("# md2md report\n"));
+        assert!(report.contains("| Files | 2 | 1 | 1 |"));
+        assert!(report.contains("| ✓ | docs/a.md | 0 |  |"));
+        assert!(report.contains("- `docs/b.md` → `missing.md`: "));
+        assert!(report.contains(&format!("| missing.md | 1 | {} | 1 |", "█".repeat(20))));
+        assert!(report.contains("| ✗ | docs/b.md | missing.md | 0 | 2.5 ms |"));
+        assert!(report.contains(
+            "## Skipped files\n\n| File | Reason |\n|---|---|\n| docs/logo.png | not Markdown |"
+        ));
+    }
+
+    #[test]
+    fn test_reports_record_the_run() {
+        let mut summary = summary();
+        let unfinished =
+            render_report(&summary, Duration::ZERO, UNIX_EPOCH, ReportFormat::Markdown)
+                .expect("Failed to render report");
+        assert!(unfinished.contains("results are partial"));
+
+        summary.apply(&ProcessingEvent::RunStarted(RunConfig {
+            source_path: PathBuf::from("docs"),
+            partials_path: PathBuf::from("partials"),
+            output_path: PathBuf::from("out"),
+            batch: true,
+            diff: false,
+            locale: None,
+            variables: BTreeMap::from([("name".to_string(), "md2md".to_string())]),
+        }));
+        summary.apply(&ProcessingEvent::RunFinished {
+            processed: 2,
+            failed: 1,
+            cancelled: false,
+        });
+
+        let markdown = render_report(&summary, Duration::ZERO, UNIX_EPOCH, ReportFormat::Markdown)
+            .expect("Failed to render report");
+        assert!(markdown.contains(&format!("by md2md {}.", env!("CARGO_PKG_VERSION"))));
+        assert!(markdown.contains("Source `docs`, partials `partials`, output `out`."));
+        assert!(!markdown.contains("results are partial"));
+
+        let json = render_report(&summary, Duration::ZERO, UNIX_EPOCH, ReportFormat::Json)
+            .expect("Failed to render report");
+        let json: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+        assert_eq!(json["complete"], true);
+        assert!(
+            json["started_at"]
+                .as_str()
+                .is_some_and(|t| t.ends_with('Z'))
+        );
+        assert_eq!(json["config"]["variables"]["name"], "md2md");
+    }
+
+    #[test]
+    fn test_reports_include_partial_usage() {
+        let mut summary = summary();
+        summary.add_result(FileProcessResult {
+            file_path: "docs/c.md".to_string(),
+            success: true,
+            includes: vec![IncludeResult {
+                path: "header.md".to_string(),
+                success: true,
+                error_message: None,
+                error_code: None,
+                parent: "docs/c.md".to_string(),
+                depth: 0,
+                title: None,
+                values: HashMap::new(),
+                duration: None,
+                bytes: Some(42),
+                attempts: None,
+                kind: DirectiveKind::Include,
+            }],
+            error_message: None,
+            error_code: None,
+            sizes: None,
+        });
+
+        let json = render_report(&summary, Duration::ZERO, UNIX_EPOCH, ReportFormat::Json)
+            .expect("Failed to render report");
+        let json: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+        assert_eq!(json["partial_usage"][0]["path"], "header.md");
+        assert_eq!(json["partial_usage"][0]["bytes"], 42);
+        assert_eq!(json["partial_usage"][0]["documents"][0], "docs/c.md");
+
+        let markdown = render_report(&summary, Duration::ZERO, UNIX_EPOCH, ReportFormat::Markdown)
+            .expect("Failed to render report");
+        let usage = markdown
+            .split("## Partial usage")
+            .nth(1)
+            .and_then(|section| section.split("\n## ").next())
+            .expect("Report should have a partial usage section");
+        assert!(usage.contains("| header.md | 1 | 42 | docs/c.md |"));
+        assert!(!usage.contains("\n| missing.md |"));
+    }
+
+    #[test]
+    fn test_html_report_renders_the_markdown_tables() {
+        let report = render_report(
+            &summary(),
+            Duration::from_secs(2),
+            UNIX_EPOCH,
+            ReportFormat::Html,
+        )
+        .expect("Failed to render report");
+
+        assert!(report.starts_with("<!DOCTYPE html>"));
+        assert!(report.contains("<title>md2md report</title>"));
+        assert!(report.contains("<td>docs/a.md</td>"));
+    }
+
+    #[test]
+    fn test_report_format_and_default_path() {
+        assert_eq!(
+            ReportFormat::from_path(Path::new("out/report.md")),
+            ReportFormat::Markdown
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("report.json")),
+            ReportFormat::Json
+        );
+        assert_eq!(
+            ReportFormat::from_path(Path::new("report.html")),
+            ReportFormat::Html
+        );
+        assert_eq!(
+            parse_report_target("markdown=out/report.txt"),
+            Ok((ReportFormat::Markdown, PathBuf::from("out/report.txt")))
+        );
+        assert_eq!(
+            parse_report_target("report.html"),
+            Ok((ReportFormat::Html, PathBuf::from("report.html")))
+        );
+        assert!(parse_report_target("html=").is_err());
+        assert_eq!(
+            default_report_path(UNIX_EPOCH),
+            "md2md-report-1970-01-01T00-00-00Z.json"
+        );
+    }
+}
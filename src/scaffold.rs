@@ -0,0 +1,183 @@
+use crate::config::CONFIG_FILE_NAME;
+use crate::file_handler::write_file;
+use crate::ignore::IGNORE_FILE_NAME;
+use std::path::{Path, PathBuf};
+
+const CONFIG_TEMPLATE: &str = r#"# md2md project configuration.
+# Command-line arguments take precedence over these values.
+source = "docs"
+partials = "partials"
+output = "out"
+"#;
+
+const INDEX_TEMPLATE: &str = r#"# My Documentation
+
+!include (greeting.md, values=[name="md2md"])
+
+## Example code
+
+!codesnippet (snippets/hello.rs, lang="rust")
+"#;
+
+const GREETING_TEMPLATE: &str = r#"Welcome to {% name || "your project" %}! This paragraph lives in `partials/greeting.md`
+and is included by every document that needs it.
+"#;
+
+const SNIPPET_TEMPLATE: &str = r#"fn main() {
+    println!("Hello from a code snippet!");
+}
+"#;
+
+const IGNORE_TEMPLATE: &str = r#"# Paths listed here are not processed (gitignore syntax, relative to this directory)
+drafts/
+"#;
+
+/// Files created by [`scaffold_project`], relative to the project root
+pub fn scaffold_files() -> Vec<(PathBuf, &'static str)> {
+    vec![
+        (PathBuf::from(CONFIG_FILE_NAME), CONFIG_TEMPLATE),
+        (PathBuf::from("docs").join("index.md"), INDEX_TEMPLATE),
+        (
+            PathBuf::from("docs").join(IGNORE_FILE_NAME),
+            IGNORE_TEMPLATE,
+        ),
+        (
+            PathBuf::from("docs").join("snippets").join("hello.rs"),
+            SNIPPET_TEMPLATE,
+        ),
+        (
+            PathBuf::from("partials").join("greeting.md"),
+            GREETING_TEMPLATE,
+        ),
+    ]
+}
+
+/// Creates a starter md2md project in `root` and returns the created paths.
+///
+/// Nothing is written when any of the files already exists, unless `force` is set.
+pub fn scaffold_project(
+    root: &Path,
+    force: bool,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error>> {
+    let files: Vec<(PathBuf, &str)> = scaffold_files()
+        .into_iter()
+        .map(|(path, content)| (root.join(path), content))
+        .collect();
+
+    if !force {
+        let existing: Vec<String> = files
+            .iter()
+            .filter(|(path, _)| path.exists())
+            .map(|(path, _)| path.display().to_string())
+            .collect();
+        if !existing.is_empty() {
+            return Err(format!(
+                "Refusing to overwrite existing files (use --force): {}",
+                existing.join(", ")
+            )
+            .into());
+        }
+    }
+
+    let mut created = Vec::new();
+    for (path, content) in files {
+        write_file(&path, content)?;
+        created.push(path);
+    }
+
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::processor::process_files;
+    use crate::types::{
+        Compat, Emit, FileOrder, Frontmatter, HtmlOptions, InputEncoding, LineEndings,
+        Normalization, OutputBudget, OutputNaming, ProcessingConfig, ProcessingSummary,
+        ResolutionOrder, RetryPolicy,
+    };
+    use std::collections::HashMap;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_scaffold_project_produces_working_setup() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let root = temp_dir.path();
+
+        let created = scaffold_project(root, false).expect("Failed to scaffold project");
+        assert_eq!(created.len(), scaffold_files().len());
+        assert!(created.iter().all(|path| path.exists()));
+
+        let config = Config::load(&root.join(CONFIG_FILE_NAME)).expect("Failed to load config");
+        let processing_config = ProcessingConfig {
+            source_path: root.join(config.source.expect("Config should set source")),
+            partials_path: root.join(config.partials.expect("Config should set partials")),
+            output_path: root.join(config.output.expect("Config should set output")),
+            batch: true,
+            verbose: false,
+            fix_code_fences: None,
+            variables: HashMap::new(),
+            order: FileOrder::default(),
+            post_process: None,
+            naming: OutputNaming::default(),
+            compat: Compat::default(),
+            passthrough: None,
+            emit: Emit::default(),
+            html: HtmlOptions::default(),
+            bundle_links: false,
+            check_links: false,
+            duplicate_headings: None,
+            line_endings: LineEndings::default(),
+            normalization: Normalization::default(),
+            source_comments: None,
+            banner: None,
+            diff: false,
+            input_encoding: InputEncoding::default(),
+            restrict_includes: false,
+            resolution_order: ResolutionOrder::default(),
+            frontmatter: Frontmatter::default(),
+            locale: None,
+            budget: OutputBudget::default(),
+            lint: None,
+            spellcheck: None,
+            source_map: false,
+            strict: false,
+            error_template: None,
+            retry: RetryPolicy::default(),
+            changed_since: None,
+            glossary: None,
+        };
+
+        let mut summary = ProcessingSummary::new();
+        process_files(&processing_config, &mut summary, None, None)
+            .expect("Failed to process files");
+
+        assert_eq!(summary.get_success_count(), 1);
+        assert_eq!(summary.get_total_includes(), 2);
+        let output = fs::read_to_string(root.join("out").join("index.md"))
+            .expect("Failed to read generated output");
+        assert!(output.contains("Welcome to md2md!"));
+        assert!(output.contains("```rust\nfn main() {"));
+    }
+
+    #[test]
+    fn test_scaffold_project_refuses_to_overwrite() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let root = temp_dir.path();
+        fs::write(root.join(CONFIG_FILE_NAME), "# mine").expect("Failed to write config");
+
+        let error = scaffold_project(root, false).expect_err("Existing files should be kept");
+        assert!(error.to_string().contains("Refusing to overwrite"));
+        assert_eq!(
+            fs::read_to_string(root.join(CONFIG_FILE_NAME)).expect("Failed to read config"),
+            "# mine"
+        );
+        assert!(!root.join("docs").exists());
+
+        scaffold_project(root, true).expect("Force should overwrite");
+        assert!(root.join("docs").join("index.md").exists());
+    }
+}
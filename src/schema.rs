@@ -0,0 +1,125 @@
+//! JSON Schemas of the data md2md reads and writes, printed by `md2md schema`
+
+use crate::report::report_schema;
+use crate::source_map::SourceMap;
+use crate::types::{ProcessingConfig, ProcessingSummary};
+use schemars::{Schema, schema_for};
+
+/// Data a schema describes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaKind {
+    /// [`ProcessingConfig`], the settings of a run
+    Config,
+    /// [`ProcessingSummary`], the outcome of a run
+    Summary,
+    /// JSON reports written with `--report`: the summary with totals and partial usage
+    Report,
+    /// [`SourceMap`]s written with `--source-map`
+    SourceMap,
+}
+
+pub fn schema(kind: SchemaKind) -> Schema {
+    match kind {
+        SchemaKind::Config => schema_for!(ProcessingConfig),
+        SchemaKind::Summary => schema_for!(ProcessingSummary),
+        SchemaKind::Report => report_schema(),
+        SchemaKind::SourceMap => schema_for!(SourceMap),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Emit, FileProcessResult, InputEncoding, ProcessingEvent, RunConfig};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_schemas_describe_the_serialized_data() {
+        let config = schema(SchemaKind::Config);
+        let required = config
+            .get("required")
+            .and_then(|required| required.as_array())
+            .expect("Config schema should list required fields");
+        assert_eq!(required.len(), 3);
+        assert!(required.contains(&"source_path".into()));
+        assert_eq!(config.get("additionalProperties"), Some(&false.into()));
+
+        let report = schema(SchemaKind::Report);
+        let properties = report
+            .get("properties")
+            .expect("Report schema should have properties");
+        for property in [
+            "generated_at",
+            "files",
+            "partial_usage",
+            "results",
+            "version",
+        ] {
+            assert!(properties.get(property).is_some(), "missing {property}");
+        }
+        assert!(
+            schema(SchemaKind::Summary)
+                .get("properties")
+                .is_some_and(|properties| properties.get("current_file").is_none())
+        );
+        assert!(
+            schema(SchemaKind::SourceMap)
+                .get("properties")
+                .is_some_and(|properties| properties.get("ranges").is_some())
+        );
+    }
+
+    #[test]
+    fn test_summary_round_trips_through_json() {
+        let mut summary = ProcessingSummary::new();
+        summary.apply(&ProcessingEvent::RunStarted(RunConfig {
+            source_path: PathBuf::from("docs"),
+            partials_path: PathBuf::from("partials"),
+            output_path: PathBuf::from("out"),
+            batch: true,
+            diff: false,
+            locale: None,
+            variables: Default::default(),
+        }));
+        summary.add_result(FileProcessResult {
+            file_path: "docs/a.md".to_string(),
+            success: true,
+            includes: Vec::new(),
+            error_message: None,
+            error_code: None,
+            sizes: None,
+        });
+        summary.mark_complete();
+
+        let json = serde_json::to_string(&summary).expect("Failed to serialize summary");
+        let read: ProcessingSummary =
+            serde_json::from_str(&json).expect("Failed to deserialize summary");
+        assert_eq!(read.results.len(), 1);
+        assert_eq!(read.config, summary.config);
+        assert!(read.complete);
+        // Timestamps are kept to the second
+        assert_eq!(
+            read.started_at.map(crate::banner::utc_timestamp),
+            summary.started_at.map(crate::banner::utc_timestamp)
+        );
+    }
+
+    #[test]
+    fn test_config_loads_from_toml_with_defaults() {
+        let config: ProcessingConfig = toml::from_str(
+            "source_path = \"docs\"\npartials_path = \"partials\"\noutput_path = \"out\"\n\
+             emit = \"md\"\ninput_encoding = \"utf-16\"\n\n[budget]\nper_file = 1000\n",
+        )
+        .expect("Failed to load config");
+        assert_eq!(config.emit, Emit::Markdown);
+        assert_eq!(config.input_encoding, InputEncoding::Utf16);
+        assert_eq!(config.budget.per_file, Some(1000));
+        assert!(!config.batch);
+
+        let error = toml::from_str::<ProcessingConfig>(
+            "source_path = \"docs\"\npartials_path = \"partials\"\noutput_path = \"out\"\nbtach = true\n",
+        )
+        .expect_err("Unknown fields should be rejected");
+        assert!(error.to_string().contains("unknown field `btach`"));
+    }
+}
@@ -0,0 +1,139 @@
+//! Where the outputs of a run are written: to disk, to memory or to stdout
+
+use crate::file_handler::{write_segments, write_segments_to};
+use crate::graph::normalize_path;
+use crate::types::LineEndings;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Receives every output file of a run.
+///
+/// An output is given as segments to write one after another, so a large document
+/// isn't copied whole; line breaks are converted to `line_endings` on the way.
+pub trait OutputSink {
+    fn write(
+        &self,
+        path: &Path,
+        segments: &[&str],
+        line_endings: LineEndings,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Whether outputs end up on disk at their paths, where link checking and
+    /// post-processing commands look for them
+    fn on_disk(&self) -> bool {
+        false
+    }
+}
+
+/// Writes outputs to their paths, creating missing directories
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FileSystemSink;
+
+impl OutputSink for FileSystemSink {
+    fn write(
+        &self,
+        path: &Path,
+        segments: &[&str],
+        line_endings: LineEndings,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        write_segments(path, segments, line_endings)
+    }
+
+    fn on_disk(&self) -> bool {
+        true
+    }
+}
+
+/// Keeps outputs in memory by path, for tests and embedders that don't want files
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    files: Mutex<BTreeMap<PathBuf, String>>,
+}
+
+impl MemorySink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The output written to `path`, if any
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<String> {
+        self.lock().get(&normalize_path(path.as_ref())).cloned()
+    }
+
+    /// Paths of the outputs written so far, sorted
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.lock().keys().cloned().collect()
+    }
+
+    pub fn into_files(self) -> BTreeMap<PathBuf, String> {
+        self.files.into_inner().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, String>> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl OutputSink for MemorySink {
+    fn write(
+        &self,
+        path: &Path,
+        segments: &[&str],
+        line_endings: LineEndings,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let mut content = Vec::new();
+        write_segments_to(&mut content, segments, line_endings)?;
+        self.lock()
+            .insert(normalize_path(path), String::from_utf8(content)?);
+        Ok(())
+    }
+}
+
+/// Prints outputs to stdout one after another, e.g. to pipe a single document
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdoutSink;
+
+impl OutputSink for StdoutSink {
+    fn write(
+        &self,
+        _path: &Path,
+        segments: &[&str],
+        line_endings: LineEndings,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let stdout = io::stdout().lock();
+        write_segments_to(io::BufWriter::new(stdout), segments, line_endings)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_sinks_write_the_same_content() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let path = temp_dir.path().join("nested/doc.md");
+        let segments = ["# Title\r", "\nText\n", "More"];
+
+        FileSystemSink
+            .write(&path, &segments, LineEndings::Crlf)
+            .expect("Failed to write to disk");
+        let memory = MemorySink::new();
+        memory
+            .write(
+                &temp_dir.path().join("nested/../nested/doc.md"),
+                &segments,
+                LineEndings::Crlf,
+            )
+            .expect("Failed to write to memory");
+
+        let written = std::fs::read_to_string(&path).expect("Failed to read output");
+        assert_eq!(written, "# Title\r\nText\r\nMore");
+        assert_eq!(memory.get(&path), Some(written));
+        assert_eq!(memory.into_files().len(), 1);
+    }
+}
@@ -0,0 +1,297 @@
+//! Source maps: the source document, partial or code file, and line, that each
+//! line of an output comes from.
+//!
+//! While a document is resolved for a source map, every included block is
+//! wrapped in invisible markers naming its file and first line. They are taken
+//! out before anything else sees the content, leaving the origin of each line;
+//! the steps that rewrite the content later carry the origins along with a line diff.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use similar::{Algorithm, DiffOp, capture_diff_slices};
+use std::path::{Path, PathBuf};
+
+/// Where the source map of `output_file` is written: next to it, as `<output>.map.json`
+pub fn map_path(output_file: &Path) -> PathBuf {
+    let mut map_file = output_file.as_os_str().to_owned();
+    map_file.push(".map.json");
+    PathBuf::from(map_file)
+}
+
+/// Whether `path` is where [`map_path`] writes a source map
+pub fn is_map_path(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(".map.json")
+}
+
+/// Where lines of an output come from, written next to it as `<output>.map.json`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SourceMap {
+    /// The output file
+    pub output: String,
+    /// The source document it was generated from
+    pub source: String,
+    /// Ranges of output lines, in order; lines md2md generated, like a banner, have none
+    pub ranges: Vec<SourceRange>,
+}
+
+/// Consecutive output lines that come from consecutive lines of one file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SourceRange {
+    /// First output line, 1-based
+    pub output_start: usize,
+    /// Last output line, inclusive
+    pub output_end: usize,
+    /// The source document, or the partial or code file the lines were included from
+    pub file: String,
+    /// Line of `file` that `output_start` comes from
+    pub line: usize,
+}
+
+/// The file and line one line of resolved content comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineOrigin {
+    pub file: String,
+    pub line: usize,
+}
+
+/// The origin of each line of some content; lines md2md generated have none
+pub type LineOrigins = Vec<Option<LineOrigin>>;
+
+impl SourceMap {
+    /// The map of `output`, whose lines come from `origins`
+    pub fn new(output: &Path, source: &Path, origins: &[Option<LineOrigin>]) -> Self {
+        let mut ranges: Vec<SourceRange> = Vec::new();
+        for (index, origin) in origins.iter().enumerate() {
+            let Some(origin) = origin else {
+                continue;
+            };
+            let output_line = index + 1;
+            match ranges.last_mut() {
+                Some(range)
+                    if range.output_end + 1 == output_line
+                        && range.file == origin.file
+                        && range.line + (output_line - range.output_start) == origin.line =>
+                {
+                    range.output_end = output_line;
+                }
+                _ => ranges.push(SourceRange {
+                    output_start: output_line,
+                    output_end: output_line,
+                    file: origin.file.clone(),
+                    line: origin.line,
+                }),
+            }
+        }
+        Self {
+            output: output.to_string_lossy().to_string(),
+            source: source.to_string_lossy().to_string(),
+            ranges,
+        }
+    }
+
+    /// Where output line `line` comes from, if it wasn't generated
+    pub fn origin(&self, line: usize) -> Option<LineOrigin> {
+        self.ranges
+            .iter()
+            .find(|range| (range.output_start..=range.output_end).contains(&line))
+            .map(|range| LineOrigin {
+                file: range.file.clone(),
+                line: range.line + (line - range.output_start),
+            })
+    }
+}
+
+const BEGIN: char = '\u{E000}';
+const SEPARATOR: char = '\u{E001}';
+const END: char = '\u{E002}';
+const CLOSE: char = '\u{E003}';
+
+/// Starts a block included from `file`, whose first line is line `line` of it.
+/// Lines before the first, such as an include's title, count as the includer's.
+pub(crate) fn begin_marker(file: &Path, line: i64) -> String {
+    format!("{BEGIN}{line}{SEPARATOR}{}{CLOSE}", file.to_string_lossy())
+}
+
+/// Ends the innermost included block; the includer continues on its line `line`
+pub(crate) fn end_marker(line: i64) -> String {
+    format!("{END}{line}{CLOSE}")
+}
+
+/// Takes the markers out of `content` resolved from `file`, returning it with the
+/// origin of each of its lines
+pub(crate) fn extract_origins(content: &str, file: &Path) -> (String, LineOrigins) {
+    let mut text = String::with_capacity(content.len());
+    let mut origins = Vec::new();
+    // Files being included, innermost last, with the line of each the output is at
+    let mut stack: Vec<(String, i64)> = vec![(file.to_string_lossy().to_string(), 1)];
+    let mut line_origin: Option<Option<LineOrigin>> = None;
+    let origin = |stack: &[(String, i64)]| {
+        stack
+            .iter()
+            .rev()
+            .find(|(_, line)| *line >= 1)
+            .map(|(file, line)| LineOrigin {
+                file: file.clone(),
+                line: *line as usize,
+            })
+    };
+
+    let mut rest = content;
+    while let Some(c) = rest.chars().next() {
+        if (c == BEGIN || c == END)
+            && let Some(close) = rest.find(CLOSE)
+        {
+            let marker = &rest[c.len_utf8()..close];
+            rest = &rest[close + CLOSE.len_utf8()..];
+            if c == BEGIN {
+                let (line, path) = marker.split_once(SEPARATOR).unwrap_or((marker, ""));
+                stack.push((path.to_string(), line.parse().unwrap_or(0)));
+            } else {
+                if stack.len() > 1 {
+                    stack.pop();
+                }
+                if let Some(top) = stack.last_mut() {
+                    top.1 = marker.parse().unwrap_or(top.1);
+                }
+            }
+            continue;
+        }
+        rest = &rest[c.len_utf8()..];
+        let origin = line_origin.get_or_insert_with(|| origin(&stack));
+        if c == '\n' {
+            origins.push(origin.take());
+            line_origin = None;
+            if let Some(top) = stack.last_mut() {
+                top.1 += 1;
+            }
+        }
+        text.push(c);
+    }
+    origins.push(line_origin.unwrap_or_else(|| origin(&stack)));
+    (text, origins)
+}
+
+/// The origins of the lines of `to`, a rewrite of `from` whose lines come from `origins`.
+/// Lines the rewrite added have none; edited lines keep those they replace.
+pub(crate) fn carry_origins(from: &str, origins: &[Option<LineOrigin>], to: &str) -> LineOrigins {
+    if from == to {
+        return origins.to_vec();
+    }
+    let old: Vec<&str> = from.split('\n').collect();
+    let new: Vec<&str> = to.split('\n').collect();
+    let mut carried = vec![None; new.len()];
+    for op in capture_diff_slices(Algorithm::Myers, &old, &new) {
+        match op {
+            DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } => {
+                for offset in 0..len {
+                    carried[new_index + offset] =
+                        origins.get(old_index + offset).cloned().flatten();
+                }
+            }
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => {
+                for offset in 0..new_len {
+                    let old_line = old_index + offset.min(old_len - 1);
+                    carried[new_index + offset] = origins.get(old_line).cloned().flatten();
+                }
+            }
+            DiffOp::Insert { .. } | DiffOp::Delete { .. } => {}
+        }
+    }
+    carried
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_origins_follows_nested_and_inline_blocks() {
+        // doc.md is `# Doc`, ``, `!include(intro.md)`, ``, `See !include(inline.md) too`, `End`;
+        // intro.md has a two-line defaults block, then `## Intro`, ``, `Text`, `!include(nested.md)`
+        let content = format!(
+            "# Doc\n\n{}## Intro\n\nText\n{}Nested{}{}\n\nSee {}here{} too\nEnd",
+            begin_marker(Path::new("partials/intro.md"), 3),
+            begin_marker(Path::new("partials/nested.md"), 1),
+            end_marker(6),
+            end_marker(3),
+            begin_marker(Path::new("partials/inline.md"), 1),
+            end_marker(5),
+        );
+        let (text, origins) = extract_origins(&content, Path::new("doc.md"));
+        assert_eq!(
+            text,
+            "# Doc\n\n## Intro\n\nText\nNested\n\nSee here too\nEnd"
+        );
+        let at = |file: &str, line| {
+            Some(LineOrigin {
+                file: file.to_string(),
+                line,
+            })
+        };
+        assert_eq!(
+            origins,
+            vec![
+                at("doc.md", 1),
+                at("doc.md", 2),
+                at("partials/intro.md", 3),
+                at("partials/intro.md", 4),
+                at("partials/intro.md", 5),
+                at("partials/nested.md", 1),
+                at("doc.md", 4),
+                at("doc.md", 5),
+                at("doc.md", 6),
+            ]
+        );
+
+        let map = SourceMap::new(Path::new("out/doc.md"), Path::new("doc.md"), &origins);
+        let ranges: Vec<_> = map
+            .ranges
+            .iter()
+            .map(|r| (r.output_start, r.output_end, r.file.as_str(), r.line))
+            .collect();
+        assert_eq!(
+            ranges,
+            vec![
+                (1, 2, "doc.md", 1),
+                (3, 5, "partials/intro.md", 3),
+                (6, 6, "partials/nested.md", 1),
+                (7, 9, "doc.md", 4),
+            ]
+        );
+        assert_eq!(map.origin(4), at("partials/intro.md", 4));
+        assert_eq!(map.origin(10), None);
+    }
+
+    #[test]
+    fn test_carry_origins_through_rewrites() {
+        let origins: Vec<_> = (1..=3)
+            .map(|line| {
+                Some(LineOrigin {
+                    file: "doc.md".to_string(),
+                    line,
+                })
+            })
+            .collect();
+        let carried = carry_origins(
+            "one\ntwo  \nthree",
+            &origins,
+            "<!-- banner -->\none\ntwo\nthree",
+        );
+        let lines: Vec<_> = carried
+            .iter()
+            .map(|origin| origin.as_ref().map(|origin| origin.line))
+            .collect();
+        assert_eq!(lines, vec![None, Some(1), Some(2), Some(3)]);
+    }
+}
@@ -0,0 +1,236 @@
+//! Spellchecking of resolved documents with an external checker.
+//!
+//! The checker reads the document on stdin and prints every misspelled word on
+//! a line of its own, as `hunspell -l`, `aspell list` and `enchant-2 -l` do.
+//! Words in the project dictionaries are accepted; the rest are reported in the
+//! source file or partial they were written in, where the fix belongs.
+
+use crate::post_process::shell;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::sync::LazyLock;
+
+/// A word as written: a run of word characters, or several joined by apostrophes
+/// or hyphens, like `don't` or `e-mail`
+static WORD_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\w+(?:['’-]\w+)*").expect("Invalid word regex"));
+
+/// `[spellcheck]` of `md2md.toml`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SpellcheckConfig {
+    /// Checker run through the shell, e.g. `hunspell -l -d en_US`
+    pub command: String,
+    /// Files of accepted words, one per line; `#` starts a comment
+    #[serde(default)]
+    pub dictionaries: Vec<PathBuf>,
+}
+
+/// A word the checker doesn't know, where it was written
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Misspelling {
+    pub word: String,
+    pub file: PathBuf,
+    /// 1-based line; `None` when the word isn't in any of the files, e.g. it came from a variable
+    pub line: Option<usize>,
+}
+
+/// A checker command with the words its project accepts
+#[derive(Debug, Clone)]
+pub struct Spellchecker {
+    command: String,
+    /// Lowercase, so accepted words match in any case
+    accepted: HashSet<String>,
+}
+
+impl Spellchecker {
+    /// Reads the dictionaries of `config`
+    pub fn new(config: &SpellcheckConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut accepted = HashSet::new();
+        for path in &config.dictionaries {
+            let content = fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read dictionary {}: {e}", path.display()))?;
+            accepted.extend(
+                content
+                    .lines()
+                    .map(|line| line.split('#').next().unwrap_or_default().trim())
+                    .filter(|word| !word.is_empty())
+                    .map(str::to_lowercase),
+            );
+        }
+        Ok(Self {
+            command: config.command.clone(),
+            accepted,
+        })
+    }
+
+    /// Misspellings in `content`, the resolved form of `files` (path and content,
+    /// the source file first), located in the files they were written in
+    pub fn check(
+        &self,
+        content: &str,
+        files: &[(PathBuf, String)],
+    ) -> Result<Vec<Misspelling>, Box<dyn std::error::Error>> {
+        let misspelled = self.misspelled_words(content)?;
+        let positions = word_positions(files);
+        let mut misspellings = Vec::new();
+        for word in misspelled {
+            match positions.get(word.as_str()) {
+                Some(positions) => {
+                    misspellings.extend(positions.iter().map(|&(file, line)| Misspelling {
+                        word: word.clone(),
+                        file: files[file].0.clone(),
+                        line: Some(line),
+                    }))
+                }
+                None => {
+                    if let Some((source, _)) = files.first() {
+                        misspellings.push(Misspelling {
+                            word,
+                            file: source.clone(),
+                            line: None,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(misspellings)
+    }
+
+    /// Distinct words the checker reports for `content`, minus accepted ones
+    fn misspelled_words(&self, content: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut child = shell(&self.command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Failed to run `{}`: {e}", self.command))?;
+        // Written from another thread, so a checker answering as it reads can't fill its pipe and stall
+        let mut stdin = child.stdin.take().expect("Failed to open checker stdin");
+        let input = content.to_string();
+        let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+        let output = child
+            .wait_with_output()
+            .map_err(|e| format!("Failed to run `{}`: {e}", self.command))?;
+        let _ = writer.join();
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(format!("`{}` failed: {}", self.command, stderr.trim()).into());
+        }
+
+        let mut seen = HashSet::new();
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .filter(|word| !word.is_empty() && !self.accepted.contains(&word.to_lowercase()))
+            .filter(|word| seen.insert(word.to_string()))
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Every word of `files`, joined ones along with their parts, with the index of each
+/// file and the 1-based line it is on, in the order they are written
+fn word_positions(files: &[(PathBuf, String)]) -> HashMap<&str, Vec<(usize, usize)>> {
+    let mut positions: HashMap<&str, Vec<(usize, usize)>> = HashMap::new();
+    for (file, (_, content)) in files.iter().enumerate() {
+        for (index, line) in content.lines().enumerate() {
+            for token in WORD_REGEX.find_iter(line) {
+                let token = token.as_str();
+                let parts = token.split(|c: char| !c.is_alphanumeric() && c != '_');
+                let words = std::iter::once(token).chain(parts.filter(|part| *part != token));
+                for word in words {
+                    let found = positions.entry(word).or_default();
+                    if found.last() != Some(&(file, index + 1)) {
+                        found.push((file, index + 1));
+                    }
+                }
+            }
+        }
+    }
+    positions
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_misspellings_are_located_in_their_files() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let dictionary = temp_dir.path().join("words.txt");
+        fs::write(&dictionary, "# Project words\nMd2md\n").expect("Failed to write dictionary");
+        let checker = Spellchecker::new(&SpellcheckConfig {
+            // Knows every word but these
+            command: "tr -cs 'A-Za-z0-9' '\\n' | grep -x -e teh -e recieve -e md2md -e Kubernetees"
+                .to_string(),
+            dictionaries: vec![dictionary],
+        })
+        .expect("Failed to create spellchecker");
+
+        let files = [
+            (
+                PathBuf::from("doc.md"),
+                "# Guide\n\n!include(setup.md)".to_string(),
+            ),
+            (
+                PathBuf::from("partials/setup.md"),
+                "Run md2md.\n\nThen recieve teh output.".to_string(),
+            ),
+        ];
+        let content = "# Guide\n\nRun md2md.\n\nThen recieve teh output on Kubernetees.";
+        let misspellings = checker
+            .check(content, &files)
+            .expect("Failed to spellcheck");
+
+        let found: Vec<(&str, &str, Option<usize>)> = misspellings
+            .iter()
+            .map(|m| (m.word.as_str(), m.file.to_str().unwrap_or_default(), m.line))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("recieve", "partials/setup.md", Some(3)),
+                ("teh", "partials/setup.md", Some(3)),
+                ("Kubernetees", "doc.md", None),
+            ]
+        );
+
+        let failing = Spellchecker::new(&SpellcheckConfig {
+            command: "echo broken >&2; exit 2".to_string(),
+            dictionaries: Vec::new(),
+        })
+        .expect("Failed to create spellchecker");
+        let error = failing
+            .check(content, &files)
+            .expect_err("A failing checker should be reported");
+        assert!(error.to_string().contains("broken"));
+    }
+
+    #[test]
+    fn test_word_positions_keep_joined_words_and_their_parts() {
+        let files = [
+            (
+                PathBuf::from("a.md"),
+                "Don't e-mail
+teh teh"
+                    .to_string(),
+            ),
+            (PathBuf::from("b.md"), "e-mail".to_string()),
+        ];
+        let positions = word_positions(&files);
+        assert_eq!(positions["Don't"], [(0, 1)]);
+        assert_eq!(positions["Don"], [(0, 1)]);
+        assert_eq!(positions["e-mail"], [(0, 1), (1, 1)]);
+        assert_eq!(positions["mail"], [(0, 1), (1, 1)]);
+        // Once per line
+        assert_eq!(positions["teh"], [(0, 2)]);
+    }
+}
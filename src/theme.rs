@@ -0,0 +1,205 @@
+use ratatui::style::{Color, Modifier, Style};
+
+/// Styles of the TUI, by what they mark rather than by color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// Headings and labels
+    pub text: Style,
+    /// Hints, placeholders and secondary details
+    pub muted: Style,
+    pub success: Style,
+    pub error: Style,
+    /// Error messages and states that need attention
+    pub warning: Style,
+    /// Counts, durations and section names
+    pub accent: Style,
+    /// File and include paths
+    pub path: Style,
+    pub progress: Style,
+    /// Key names in the footer and help
+    pub key: Style,
+    /// Selected file that was processed successfully
+    pub selected_success: Style,
+    /// Selected file that failed
+    pub selected_error: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            text: Style::new().fg(Color::White),
+            muted: Style::new().fg(Color::Gray),
+            success: Style::new().fg(Color::Green),
+            error: Style::new().fg(Color::Red),
+            warning: Style::new().fg(Color::Yellow),
+            accent: Style::new().fg(Color::Cyan),
+            path: Style::new().fg(Color::Magenta),
+            progress: Style::new().fg(Color::Blue),
+            key: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            selected_success: Style::new().bg(Color::Green).fg(Color::Black),
+            selected_error: Style::new().bg(Color::Red).fg(Color::White),
+        }
+    }
+}
+
+impl Theme {
+    /// Dark text for terminals with a light background
+    pub fn light() -> Self {
+        Self {
+            text: Style::new().fg(Color::Black),
+            muted: Style::new().fg(Color::DarkGray),
+            success: Style::new().fg(Color::Green),
+            error: Style::new().fg(Color::Red),
+            warning: Style::new().fg(Color::Indexed(130)),
+            accent: Style::new().fg(Color::Blue),
+            path: Style::new().fg(Color::Magenta),
+            progress: Style::new().fg(Color::Blue),
+            key: Style::new().fg(Color::Blue).add_modifier(Modifier::BOLD),
+            selected_success: Style::new().bg(Color::Green).fg(Color::White),
+            selected_error: Style::new().bg(Color::Red).fg(Color::White),
+        }
+    }
+
+    /// Bright, bold colors
+    pub fn high_contrast() -> Self {
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        Self {
+            text: bold.fg(Color::White),
+            muted: Style::new().fg(Color::White),
+            success: bold.fg(Color::LightGreen),
+            error: bold.fg(Color::LightRed),
+            warning: bold.fg(Color::LightYellow),
+            accent: bold.fg(Color::LightCyan),
+            path: bold.fg(Color::LightMagenta),
+            progress: Style::new().fg(Color::LightBlue),
+            key: bold.fg(Color::LightYellow),
+            selected_success: bold.bg(Color::White).fg(Color::Black),
+            selected_error: bold.bg(Color::LightRed).fg(Color::Black),
+        }
+    }
+
+    /// No colors, only text attributes; used when `NO_COLOR` is set
+    pub fn monochrome() -> Self {
+        let plain = Style::new();
+        let bold = plain.add_modifier(Modifier::BOLD);
+        Self {
+            text: bold,
+            muted: plain.add_modifier(Modifier::DIM),
+            success: plain,
+            error: bold,
+            warning: plain.add_modifier(Modifier::ITALIC),
+            accent: plain,
+            path: plain.add_modifier(Modifier::UNDERLINED),
+            progress: plain,
+            key: bold,
+            selected_success: plain.add_modifier(Modifier::REVERSED),
+            selected_error: bold.add_modifier(Modifier::REVERSED),
+        }
+    }
+
+    /// The monochrome theme when the `NO_COLOR` environment variable is set, the default otherwise
+    pub fn from_env() -> Self {
+        if std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            Self::monochrome()
+        } else {
+            Self::default()
+        }
+    }
+
+    /// Style of a success or failure
+    pub fn status(&self, success: bool) -> Style {
+        if success { self.success } else { self.error }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::{App, WatchState};
+    use crate::components;
+    use crate::history::RunRecord;
+    use crate::processor::Processor;
+    use crate::types::IncludeProgress;
+    use ratatui::{Terminal, backend::TestBackend};
+    use std::fs;
+    use std::sync::{Arc, Mutex};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_monochrome_theme_has_no_colors() {
+        let theme = Theme::monochrome();
+        for style in [
+            theme.text,
+            theme.muted,
+            theme.success,
+            theme.error,
+            theme.warning,
+            theme.accent,
+            theme.path,
+            theme.progress,
+            theme.key,
+            theme.selected_success,
+            theme.selected_error,
+        ] {
+            assert_eq!(style.fg, None);
+            assert_eq!(style.bg, None);
+        }
+        assert_ne!(theme.success, theme.error);
+        assert_ne!(theme.selected_success, theme.selected_error);
+    }
+
+    #[test]
+    fn test_monochrome_tabs_render_without_colors() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let source_dir = temp_dir.path().join("source");
+        fs::create_dir_all(&source_dir).expect("Failed to create source directory");
+        fs::write(source_dir.join("a.md"), "# A").expect("Failed to write a.md");
+        fs::write(source_dir.join("b.md"), "!include (missing.md)").expect("Failed to write b.md");
+        let processor = Processor::builder()
+            .source(&source_dir)
+            .partials(temp_dir.path())
+            .output(temp_dir.path().join("output"))
+            .build()
+            .expect("Failed to build processor");
+        let mut summary = processor.run().expect("Failed to run processor");
+        summary.current_include = Some(IncludeProgress {
+            path: "header.md".to_string(),
+            index: 3,
+            total: 7,
+        });
+        let mut app = App::new(processor.config().clone(), Arc::new(Mutex::new(summary)));
+        app.watch = Some(WatchState::default());
+        app.previous_run = Some(RunRecord::default());
+        app.mark_processing_complete();
+        // The Files tab with the detail popup of b.md's missing include
+        app.selected_file_index = 1;
+        app.selected_include = Some(0);
+        app.include_details = true;
+
+        let theme = Theme::monochrome();
+        let mut terminal =
+            Terminal::new(TestBackend::new(100, 30)).expect("Failed to create terminal");
+        let renders: [fn(&mut ratatui::Frame, &App, ratatui::layout::Rect, &Theme); 8] = [
+            components::render_progress,
+            components::render_files,
+            components::render_tree,
+            components::render_analysis,
+            components::render_summary,
+            components::render_error_summary,
+            components::render_warnings,
+            components::render_watch,
+        ];
+        for render in renders {
+            terminal
+                .draw(|f| render(f, &app, f.area(), &theme))
+                .expect("Failed to draw");
+            let buffer = terminal.backend().buffer();
+            assert!(
+                buffer
+                    .content()
+                    .iter()
+                    .all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset)
+            );
+        }
+    }
+}
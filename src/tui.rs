@@ -60,6 +60,20 @@ impl Tui {
         Ok(())
     }
 
+    /// Hands the terminal back to the shell, e.g. to run an editor, until [`Tui::resume`].
+    pub fn suspend(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.exit()
+    }
+
+    /// Takes the terminal over again after [`Tui::suspend`].
+    pub fn resume(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, EnableMouseCapture)?;
+        self.terminal.hide_cursor()?;
+        self.terminal.clear()?;
+        Ok(())
+    }
+
     /// Resets the terminal interface.
     ///
     /// This function is also used for the panic hook to revert
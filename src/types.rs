@@ -1,11 +1,105 @@
-use std::collections::HashMap;
+use crate::banner::utc_timestamp;
+use crate::error_code::{ErrorCategory, ErrorCode, format_error};
+use crate::links::BrokenLink;
+use crate::lint::LintConfig;
+use crate::spellcheck::SpellcheckConfig;
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, SystemTime};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IncludeResult {
     pub path: String,
     pub success: bool,
     pub error_message: Option<String>,
+    pub error_code: Option<ErrorCode>,
+    /// File containing the directive: the source document or another partial
+    pub parent: String,
+    /// Nesting level; includes of the source document are at depth 0
+    pub depth: usize,
+    /// `title` parameter of the directive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    /// `values` parameter of the directive
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub values: HashMap<String, String>,
+    /// Time spent resolving it, nested includes included; not measured on wasm32
+    #[serde(skip)]
+    pub duration: Option<Duration>,
+    /// Size of the content it put in the output, nested includes included
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<usize>,
+    /// Times the file was read, when transient failures made it take more than one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attempts: Option<u32>,
+    #[serde(default)]
+    pub kind: DirectiveKind,
+}
+
+/// Directive an [`IncludeResult`] comes from
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum DirectiveKind {
+    /// `!include`, or the include syntax of `--compat` dialects
+    #[default]
+    Include,
+    /// `!codesnippet`
+    #[serde(rename = "codesnippet")]
+    CodeSnippet,
+    /// `!docsnippet`
+    #[serde(rename = "docsnippet")]
+    DocSnippet,
+}
+
+impl DirectiveKind {
+    pub const ALL: [DirectiveKind; 3] = [
+        DirectiveKind::Include,
+        DirectiveKind::CodeSnippet,
+        DirectiveKind::DocSnippet,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            DirectiveKind::Include => "include",
+            DirectiveKind::CodeSnippet => "codesnippet",
+            DirectiveKind::DocSnippet => "docsnippet",
+        }
+    }
+}
+
+/// The failures of a run in one [`ErrorCategory`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct ErrorCategoryCount {
+    pub category: ErrorCategory,
+    pub count: usize,
+    /// Paths that failed and how often, most frequent first
+    pub paths: Vec<(String, usize)>,
+}
+
+/// How many directives of one kind a run resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct DirectiveCount {
+    pub kind: DirectiveKind,
+    pub total: usize,
+    pub failed: usize,
 }
 
 #[derive(Debug, Clone)]
@@ -13,6 +107,10 @@ pub struct IncludeParameters {
     pub title: Option<String>,
     pub title_level: Option<u8>,
     pub values: HashMap<String, String>,
+    /// `if` condition; the include is left out when it doesn't hold
+    pub condition: Option<String>,
+    /// Git revision to read the file at, e.g. a release tag
+    pub rev: Option<String>,
 }
 
 impl Default for IncludeParameters {
@@ -21,6 +119,8 @@ impl Default for IncludeParameters {
             title: None,
             title_level: Some(1),
             values: HashMap::new(),
+            condition: None,
+            rev: None,
         }
     }
 }
@@ -32,20 +132,386 @@ pub struct CodeSnippetParameters {
     pub end: Option<usize>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
+pub struct DocSnippetParameters {
+    /// Item whose documentation is embedded, e.g. `Processor::run`; the module's without one
+    pub item: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FileProcessResult {
     pub file_path: String,
     pub success: bool,
     pub includes: Vec<IncludeResult>,
     pub error_message: Option<String>,
+    pub error_code: Option<ErrorCode>,
+    /// Sizes of the source and its outputs, for files that were written
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sizes: Option<FileSizes>,
+}
+
+/// Bytes read from a source document and generated from it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct FileSizes {
+    pub source: u64,
+    /// Every output of the file, e.g. both the Markdown and the HTML
+    pub output: u64,
+}
+
+/// Sizes across a run, to spot includes blowing up outputs
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
+pub struct SizeStats {
+    /// Bytes of the source documents written
+    pub bytes_read: u64,
+    pub bytes_written: u64,
+    /// The source document with the largest output, and the bytes of that output
+    pub largest_output: Option<(String, u64)>,
+    /// Output size over source size, averaged over the files written
+    pub average_expansion: Option<f64>,
+}
+
+impl IncludeResult {
+    /// The error message prefixed with its error code, for reporters
+    pub fn formatted_error(&self) -> Option<String> {
+        self.error_message
+            .as_deref()
+            .map(|message| format_error(self.error_code, message))
+    }
+}
+
+impl FileProcessResult {
+    /// The error message prefixed with its error code, for reporters
+    pub fn formatted_error(&self) -> Option<String> {
+        self.error_message
+            .as_deref()
+            .map(|message| format_error(self.error_code, message))
+    }
+
+    /// Fails the file with its broken links listed after any existing error
+    pub fn add_broken_links(&mut self, broken_links: &[BrokenLink]) {
+        if broken_links.is_empty() {
+            return;
+        }
+        let details: Vec<String> = broken_links
+            .iter()
+            .map(|link| format!("  • line {}: {}", link.line, link.target))
+            .collect();
+        let links_message = format!("File contains broken links:\n{}", details.join("\n"));
+
+        self.success = false;
+        self.error_code = self.error_code.or(Some(ErrorCode::BrokenLink));
+        self.error_message = Some(match self.error_message.take() {
+            Some(existing) => format!("{existing}\n{links_message}"),
+            None => links_message,
+        });
+    }
+
+    /// Paths from this file down to its include at `index`, through the partials that include it
+    pub fn include_chain(&self, index: usize) -> Vec<&str> {
+        let Some(include) = self.includes.get(index) else {
+            return Vec::new();
+        };
+        let mut chain = vec![include.path.as_str()];
+        let (mut parent, mut depth) = (include.parent.as_str(), include.depth);
+        // A partial is tracked before the includes nested in it
+        for earlier in self.includes[..index].iter().rev() {
+            if depth == 0 {
+                break;
+            }
+            if earlier.depth + 1 == depth && earlier.path == parent {
+                chain.push(&earlier.path);
+                (parent, depth) = (&earlier.parent, earlier.depth);
+            }
+        }
+        chain.push(&self.file_path);
+        chain.reverse();
+        chain
+    }
+}
+
+/// Result of processing in-memory content with [`crate::processor::process_string`]
+#[derive(Debug, Serialize)]
+pub struct ContentProcessResult {
+    pub content: String,
+    pub includes: Vec<IncludeResult>,
+}
+
+impl ContentProcessResult {
+    /// Whether every directive was resolved
+    pub fn is_success(&self) -> bool {
+        self.includes.iter().all(|inc| inc.success)
+    }
+}
+
+/// Progress of a processing run, in the order it happens
+#[derive(Debug, Clone)]
+pub enum ProcessingEvent {
+    /// A run of `config` is starting, before any file
+    RunStarted(RunConfig),
+    /// A file is about to be processed; `index` is 0-based out of `total`
+    FileStarted {
+        path: String,
+        index: usize,
+        total: usize,
+    },
+    /// A directive of `file` is about to be resolved; `index` is 0-based out of the
+    /// file's `total` directives, and nested includes resolve as part of it
+    IncludeStarted {
+        file: String,
+        path: String,
+        index: usize,
+        total: usize,
+    },
+    /// A directive in `file` was resolved, successfully or not
+    IncludeResolved {
+        file: String,
+        include: IncludeResult,
+    },
+    FileFinished(FileProcessResult),
+    /// An output of a file would change; nothing is written in diff mode
+    OutputDiff(OutputDiff),
+    /// Links in the written output of the finished `file` that don't resolve
+    BrokenLinks {
+        file: String,
+        links: Vec<BrokenLink>,
+    },
+    /// Files in the source path that won't be processed, sent before the first file
+    FilesSkipped(Vec<SkippedFile>),
+    /// A problem that doesn't belong to any single file's result
+    Warning(String),
+    RunFinished {
+        processed: usize,
+        failed: usize,
+        /// The run stopped early because its [`CancellationToken`] was cancelled
+        cancelled: bool,
+    },
+    /// Watched files changed; the `affected` source files are processed again as `run`
+    FilesChanged {
+        run: usize,
+        paths: Vec<String>,
+        affected: Vec<String>,
+    },
+    /// Something in the finished `source` file or its partials that was handled but deserves attention
+    ContentWarning(ContentWarning),
+    /// Results of a watch mode run, in place of the files' earlier ones
+    Rerun {
+        run: usize,
+        results: Vec<FileProcessResult>,
+        diffs: Vec<OutputDiff>,
+        warnings: Vec<ContentWarning>,
+    },
+}
+
+/// Stops a processing run between files; clones share the same flag
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// What a [`ContentWarning`] is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningKind {
+    /// A code fence without a language was given the `--fix-code-fences` one
+    FenceFixed,
+    /// A variable wasn't set, so its `||` default was used
+    DefaultValue,
+    /// A directive uses syntax that is kept for compatibility only
+    DeprecatedSyntax,
+    /// An include reads a file from the output directory, likely generated by an earlier run
+    IncludesOutput,
+    /// A partial has no translation into the locale, so the untranslated one was included
+    LocaleFallback,
+    /// An H1 of the assembled document repeats an earlier one, e.g. from a partial included twice
+    DuplicateTitle,
+    /// A rule under `[lint]` found a problem in an output
+    Lint,
+    /// The spellchecker doesn't know a word, and no project dictionary has it
+    Misspelling,
+    /// A directive was left in the output as text, e.g. because of a typo in its syntax
+    LeftoverDirective,
+}
+
+impl WarningKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            WarningKind::FenceFixed => "fence fixed",
+            WarningKind::DefaultValue => "default value",
+            WarningKind::DeprecatedSyntax => "deprecated syntax",
+            WarningKind::IncludesOutput => "includes output",
+            WarningKind::LocaleFallback => "locale fallback",
+            WarningKind::DuplicateTitle => "duplicate title",
+            WarningKind::Lint => "lint",
+            WarningKind::Misspelling => "misspelling",
+            WarningKind::LeftoverDirective => "leftover directive",
+        }
+    }
+}
+
+/// Content that was processed but deserves attention; warnings never fail a file
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ContentWarning {
+    /// Source file being processed
+    pub source: String,
+    /// File the warning is about: the source file, one of its partials or its output
+    pub file: String,
+    /// 1-based line in `file`
+    pub line: usize,
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+/// Changes a run would make to an existing output, in diff mode
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OutputDiff {
+    /// Source file the output is generated from
+    pub file_path: String,
+    pub output_path: String,
+    /// Unified diff from the existing output to the processed content
+    pub diff: String,
+}
+
+/// Why a file in the source path wasn't processed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum SkipReason {
+    /// Matched by `.md2mdignore`
+    Ignored,
+    /// Not a `.md` or `.mdx` file
+    NotMarkdown,
+    /// Depends on nothing changed since the `--changed-since` revision
+    Unchanged,
+}
+
+impl SkipReason {
+    pub const ALL: [SkipReason; 3] = [
+        SkipReason::Ignored,
+        SkipReason::NotMarkdown,
+        SkipReason::Unchanged,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SkipReason::Ignored => "ignored by .md2mdignore",
+            SkipReason::NotMarkdown => "not Markdown",
+            SkipReason::Unchanged => "unchanged since --changed-since",
+        }
+    }
+}
+
+/// A file in the source path that wasn't processed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: SkipReason,
+}
+
+/// The settings a run was started with, as recorded in its summary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RunConfig {
+    pub source_path: PathBuf,
+    pub partials_path: PathBuf,
+    pub output_path: PathBuf,
+    pub batch: bool,
+    pub diff: bool,
+    pub locale: Option<String>,
+    /// Sorted, so reports of the same configuration are identical
+    pub variables: BTreeMap<String, String>,
+}
+
+impl From<&ProcessingConfig> for RunConfig {
+    fn from(config: &ProcessingConfig) -> Self {
+        Self {
+            source_path: config.source_path.clone(),
+            partials_path: config.partials_path.clone(),
+            output_path: config.output_path.clone(),
+            batch: config.batch,
+            diff: config.diff,
+            locale: config.locale.clone(),
+            variables: config.variables.clone().into_iter().collect(),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ProcessingSummary {
+    /// Version of md2md that made the run
+    pub version: String,
+    #[serde(with = "timestamp")]
+    #[schemars(with = "Option<String>")]
+    pub started_at: Option<SystemTime>,
+    #[serde(with = "timestamp")]
+    #[schemars(with = "Option<String>")]
+    pub finished_at: Option<SystemTime>,
+    /// Set by [`ProcessingSummary::mark_complete`] once the run has finished, cancelled or not
+    pub complete: bool,
+    /// Settings of the run, once it has started
+    pub config: Option<RunConfig>,
     pub results: Vec<FileProcessResult>,
+    /// Files in the source path that weren't processed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<SkippedFile>,
+    /// Outputs that would change, in diff mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub diffs: Vec<OutputDiff>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<ContentWarning>,
     pub total_files: usize,
     pub processed_files: usize,
+    /// Progress state only, left out of serialized reports so they stay diffable
+    #[serde(skip)]
     pub current_file: Option<String>,
+    /// Directive of the current file being resolved
+    #[serde(skip)]
+    pub current_include: Option<IncludeProgress>,
+}
+
+/// Directive being resolved, as of the last [`ProcessingEvent::IncludeStarted`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IncludeProgress {
+    pub path: String,
+    /// 0-based, out of `total`
+    pub index: usize,
+    pub total: usize,
+}
+
+/// Timestamps as UTC ISO 8601, e.g. `2025-01-31T09:30:00Z`
+mod timestamp {
+    use super::*;
+    use crate::banner::parse_utc_timestamp;
+
+    pub fn serialize<S: Serializer>(
+        time: &Option<SystemTime>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => serializer.serialize_str(&utc_timestamp(*time)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<SystemTime>, D::Error> {
+        Option::<String>::deserialize(deserializer)?
+            .map(|text| {
+                parse_utc_timestamp(&text)
+                    .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp `{text}`")))
+            })
+            .transpose()
+    }
 }
 
 impl Default for ProcessingSummary {
@@ -57,10 +523,19 @@ impl Default for ProcessingSummary {
 impl ProcessingSummary {
     pub fn new() -> Self {
         Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: None,
+            finished_at: None,
+            complete: false,
+            config: None,
             results: Vec::new(),
+            skipped: Vec::new(),
+            diffs: Vec::new(),
+            warnings: Vec::new(),
             total_files: 0,
             processed_files: 0,
             current_file: None,
+            current_include: None,
         }
     }
 
@@ -72,11 +547,185 @@ impl ProcessingSummary {
         self.current_file = Some(file);
     }
 
+    /// Updates the summary with a processing event, so a summary can be rebuilt from
+    /// the events received over a channel
+    pub fn apply(&mut self, event: &ProcessingEvent) {
+        match event {
+            ProcessingEvent::RunStarted(config) => {
+                self.started_at = Some(SystemTime::now());
+                self.config = Some(config.clone());
+            }
+            ProcessingEvent::RunFinished { .. } => self.mark_complete(),
+            ProcessingEvent::FileStarted { path, total, .. } => {
+                self.set_total_files(*total);
+                self.set_current_file(path.clone());
+                self.current_include = None;
+            }
+            ProcessingEvent::IncludeStarted {
+                path, index, total, ..
+            } => {
+                self.current_include = Some(IncludeProgress {
+                    path: path.clone(),
+                    index: *index,
+                    total: *total,
+                });
+            }
+            ProcessingEvent::FileFinished(result) => {
+                self.current_include = None;
+                self.add_result(result.clone());
+            }
+            ProcessingEvent::FilesSkipped(files) => self.skipped = files.clone(),
+            ProcessingEvent::OutputDiff(diff) => self.diffs.push(diff.clone()),
+            ProcessingEvent::ContentWarning(warning) => self.warnings.push(warning.clone()),
+            ProcessingEvent::BrokenLinks { file, links } => {
+                if let Some(result) = self.results.iter_mut().find(|r| &r.file_path == file) {
+                    result.add_broken_links(links);
+                }
+            }
+            ProcessingEvent::Rerun {
+                results,
+                diffs,
+                warnings,
+                ..
+            } => {
+                self.replace_results(ProcessingSummary {
+                    results: results.clone(),
+                    diffs: diffs.clone(),
+                    warnings: warnings.clone(),
+                    ..ProcessingSummary::new()
+                });
+            }
+            ProcessingEvent::IncludeResolved { .. }
+            | ProcessingEvent::Warning(_)
+            | ProcessingEvent::FilesChanged { .. } => {}
+        }
+    }
+
+    /// Records that the run is over; later calls keep the first finishing time
+    pub fn mark_complete(&mut self) {
+        if !self.complete {
+            self.complete = true;
+            self.finished_at = Some(SystemTime::now());
+        }
+    }
+
+    /// Time from the start of the run to its end, or to now while it is running
+    pub fn elapsed(&self) -> Option<Duration> {
+        let started = self.started_at?;
+        let until = self.finished_at.unwrap_or_else(SystemTime::now);
+        Some(until.duration_since(started).unwrap_or_default())
+    }
+
     pub fn add_result(&mut self, result: FileProcessResult) {
         self.processed_files += 1;
         self.results.push(result);
     }
 
+    /// Puts the results, diffs and warnings of files processed again in place of their earlier ones
+    pub fn replace_results(&mut self, rerun: ProcessingSummary) {
+        for result in rerun.results {
+            self.diffs.retain(|diff| diff.file_path != result.file_path);
+            self.warnings
+                .retain(|warning| warning.source != result.file_path);
+            match self
+                .results
+                .iter_mut()
+                .find(|r| r.file_path == result.file_path)
+            {
+                Some(existing) => *existing = result,
+                None => self.add_result(result),
+            }
+        }
+        self.diffs.extend(rerun.diffs);
+        self.warnings.extend(rerun.warnings);
+    }
+
+    /// How many files were skipped for each reason, leaving out reasons with none
+    pub fn get_skipped_by_reason(&self) -> Vec<(SkipReason, usize)> {
+        SkipReason::ALL
+            .into_iter()
+            .map(|reason| {
+                let count = self.skipped.iter().filter(|f| f.reason == reason).count();
+                (reason, count)
+            })
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// Failures grouped by category, most frequent first, each with the paths that
+    /// fail most often. A failed include counts at its partial; a file that failed
+    /// for another reason counts at the file.
+    pub fn get_error_categories(&self) -> Vec<ErrorCategoryCount> {
+        let mut categories: BTreeMap<ErrorCategory, BTreeMap<&str, usize>> = BTreeMap::new();
+        for result in &self.results {
+            let mut failed_includes = result.includes.iter().filter(|i| !i.success).peekable();
+            if failed_includes.peek().is_none() {
+                if !result.success {
+                    *categories
+                        .entry(ErrorCategory::of(result.error_code))
+                        .or_default()
+                        .entry(&result.file_path)
+                        .or_default() += 1;
+                }
+                continue;
+            }
+            for include in failed_includes {
+                *categories
+                    .entry(ErrorCategory::of(include.error_code))
+                    .or_default()
+                    .entry(&include.path)
+                    .or_default() += 1;
+            }
+        }
+        let mut counts: Vec<ErrorCategoryCount> = categories
+            .into_iter()
+            .map(|(category, paths)| {
+                let mut paths: Vec<(String, usize)> = paths
+                    .into_iter()
+                    .map(|(path, count)| (path.to_string(), count))
+                    .collect();
+                // Stable, so paths failing equally often stay sorted by name
+                paths.sort_by_key(|(_, count)| Reverse(*count));
+                ErrorCategoryCount {
+                    category,
+                    count: paths.iter().map(|(_, count)| count).sum(),
+                    paths,
+                }
+            })
+            .collect();
+        counts.sort_by_key(|c| Reverse(c.count));
+        counts
+    }
+
+    /// Bytes read and written by the files written so far
+    pub fn get_size_stats(&self) -> SizeStats {
+        let mut stats = SizeStats::default();
+        let mut expansions = Vec::new();
+        for result in &self.results {
+            let Some(sizes) = result.sizes else {
+                continue;
+            };
+            stats.bytes_read += sizes.source;
+            stats.bytes_written += sizes.output;
+            if stats
+                .largest_output
+                .as_ref()
+                .is_none_or(|(_, bytes)| sizes.output > *bytes)
+            {
+                stats.largest_output = Some((result.file_path.clone(), sizes.output));
+            }
+            // An empty source has no meaningful ratio
+            if sizes.source > 0 {
+                expansions.push(sizes.output as f64 / sizes.source as f64);
+            }
+        }
+        if !expansions.is_empty() {
+            stats.average_expansion =
+                Some(expansions.iter().sum::<f64>() / expansions.len() as f64);
+        }
+        stats
+    }
+
     pub fn get_success_count(&self) -> usize {
         self.results.iter().filter(|r| r.success).count()
     }
@@ -101,6 +750,29 @@ impl ProcessingSummary {
         self.get_total_includes() - self.get_successful_includes()
     }
 
+    /// How many directives of each kind were resolved and failed, leaving out kinds with none
+    pub fn get_directives_by_kind(&self) -> Vec<DirectiveCount> {
+        DirectiveKind::ALL
+            .into_iter()
+            .map(|kind| {
+                let directives = self
+                    .results
+                    .iter()
+                    .flat_map(|r| &r.includes)
+                    .filter(|i| i.kind == kind);
+                let (total, failed) = directives.fold((0, 0), |(total, failed), i| {
+                    (total + 1, failed + usize::from(!i.success))
+                });
+                DirectiveCount {
+                    kind,
+                    total,
+                    failed,
+                }
+            })
+            .filter(|count| count.total > 0)
+            .collect()
+    }
+
     pub fn get_progress_percentage(&self) -> f64 {
         if self.total_files == 0 {
             0.0
@@ -110,14 +782,276 @@ impl ProcessingSummary {
     }
 }
 
-#[derive(Debug, Clone)]
+/// Order in which the files of a source directory are processed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileOrder {
+    /// Lexicographic by path
+    #[default]
+    Name,
+    /// Least recently modified first, by path for equal times
+    Mtime,
+}
+
+/// Directive dialect accepted in source documents
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compat {
+    #[default]
+    Md2md,
+    /// markedpp: includes resolve relative to the including file, plus `!toc` and `!numberedheadings`
+    Markedpp,
+    /// MkDocs: pymdown-extensions `--8<--` snippets, alongside md2md directives
+    Mkdocs,
+}
+
+/// Where plain include paths, neither absolute nor starting with `./` or `../`, are
+/// looked up; `./` and `../` are always relative to the including file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResolutionOrder {
+    /// The partials directory only
+    #[default]
+    Partials,
+    /// The partials directory, then the including file's directory
+    PartialsThenCurrent,
+    /// The including file's directory, then the partials directory
+    CurrentThenPartials,
+}
+
+/// What becomes of a source document's own `---` frontmatter in its output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Frontmatter {
+    /// Written as it is
+    #[default]
+    Keep,
+    /// Left out, with the blank lines after it
+    Strip,
+    /// Kept, with the build time, source path and md2md version added
+    Merge,
+}
+
+/// Template engine whose tags are passed through untouched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum TemplateSyntax {
+    /// Jinja (also Nunjucks, Twig): `{% raw %}` blocks and `{# comments #}` are opaque
+    Jinja,
+    /// Liquid (Jekyll, Shopify): `{% raw %}` and `{% comment %}` blocks are opaque
+    Liquid,
+}
+
+/// What to do with headings whose anchor repeats an earlier heading's
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum DuplicateHeadings {
+    /// Logs a warning for every repeated anchor
+    Warn,
+    /// Gives repeated headings a unique `{#anchor-1}` id attribute
+    Dedupe,
+}
+
+/// How batch mode names output files
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputNaming {
+    /// Output file name template; `{stem}`, `{ext}` and `{name}` come from the source file
+    pub rename: Option<String>,
+    /// Writes every output directly into the output directory instead of mirroring the source tree
+    pub flatten: bool,
+}
+
+/// Formats written for every processed file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum Emit {
+    #[default]
+    #[serde(alias = "md")]
+    Markdown,
+    /// HTML rendered from the resolved Markdown, written with an `.html` extension
+    Html,
+    /// Both the Markdown and the HTML output
+    Both,
+}
+
+impl Emit {
+    pub fn markdown(self) -> bool {
+        matches!(self, Emit::Markdown | Emit::Both)
+    }
+
+    pub fn html(self) -> bool {
+        matches!(self, Emit::Html | Emit::Both)
+    }
+}
+
+/// Source-map comments around included content
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum SourceComments {
+    /// Wraps every included partial in `<!-- md2md: begin path -->` / `<!-- md2md: end path -->`
+    Insert,
+    /// Removes such comments, e.g. ones carried in from already generated partials
+    Strip,
+}
+
+/// Line breaks of written output files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineEndings {
+    /// Whatever the source and partials use, possibly mixed
+    #[default]
+    Preserve,
+    Lf,
+    Crlf,
+}
+
+/// Text encoding of source documents and partials
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputEncoding {
+    /// UTF-8; files starting with a UTF-16 byte order mark are read as UTF-16
+    #[default]
+    #[serde(rename = "utf-8", alias = "utf8")]
+    Utf8,
+    /// ISO-8859-1
+    Latin1,
+    /// UTF-16, little-endian unless a byte order mark says otherwise
+    #[serde(rename = "utf-16", alias = "utf16")]
+    Utf16,
+}
+
+/// Opt-in whitespace clean-up of written Markdown, outside code fences
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct Normalization {
+    /// Strips trailing whitespace, keeping two-space hard line breaks
+    pub trailing_spaces: bool,
+    /// Ends the file with exactly one newline
+    pub final_newline: bool,
+    /// Collapses consecutive blank lines into one
+    pub blank_lines: bool,
+}
+
+/// Limits, in bytes, on what a run writes, to catch includes pulling in far more than intended
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputBudget {
+    /// Largest any single output file may be
+    pub per_file: Option<u64>,
+    /// Largest all outputs of the run may be together
+    pub total: Option<u64>,
+}
+
+/// Retries of include reads that fail transiently, e.g. on a flaky network filesystem.
+/// Missing files are never retried.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Reads after the first failed one; none by default
+    pub retries: u32,
+    /// Wait before the first retry, in milliseconds; it doubles before each further one
+    pub delay_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: 0,
+            delay_ms: 500,
+        }
+    }
+}
+
+/// Page around the HTML output
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct HtmlOptions {
+    /// Template file with `{{ title }}`, `{{ css }}` and `{{ content }}` placeholders
+    pub template: Option<PathBuf>,
+    /// Stylesheet URL linked from every page
+    pub css: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct ProcessingConfig {
     pub source_path: PathBuf,
     pub partials_path: PathBuf,
     pub output_path: PathBuf,
+    #[serde(default)]
     pub batch: bool,
+    #[serde(default)]
     pub verbose: bool,
     pub fix_code_fences: Option<String>,
+    /// Variables available to every included partial
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    #[serde(default)]
+    pub order: FileOrder,
+    /// Command run on every written output file; `{}` is replaced with its path
+    pub post_process: Option<String>,
+    #[serde(default)]
+    pub naming: OutputNaming,
+    #[serde(default)]
+    pub compat: Compat,
+    /// Template engine whose tags survive variable substitution
+    pub passthrough: Option<TemplateSyntax>,
+    #[serde(default)]
+    pub emit: Emit,
+    #[serde(default)]
+    pub html: HtmlOptions,
+    /// Rewrites links to files included into the same output as in-document anchors
+    #[serde(default)]
+    pub bundle_links: bool,
+    /// Validates links and anchors in the written outputs once every file is processed
+    #[serde(default)]
+    pub check_links: bool,
+    pub duplicate_headings: Option<DuplicateHeadings>,
+    #[serde(default)]
+    pub line_endings: LineEndings,
+    #[serde(default)]
+    pub normalization: Normalization,
+    pub source_comments: Option<SourceComments>,
+    /// Line put at the top of every output; `{source}`, `{time}` and `{version}` are filled in
+    pub banner: Option<String>,
+    /// Compares the processed content with the existing outputs instead of writing them
+    #[serde(default)]
+    pub diff: bool,
+    /// Encoding the source documents and partials are read in
+    #[serde(default)]
+    pub input_encoding: InputEncoding,
+    /// Fails includes and code snippets outside the source and partials directories
+    #[serde(default)]
+    pub restrict_includes: bool,
+    #[serde(default)]
+    pub resolution_order: ResolutionOrder,
+    #[serde(default)]
+    pub frontmatter: Frontmatter,
+    /// Locale whose translations of partials, e.g. `header.de.md`, are preferred
+    pub locale: Option<String>,
+    /// Outputs that would go over it fail instead of being written
+    #[serde(default)]
+    pub budget: OutputBudget,
+    /// Rules checked on every Markdown output, reported as warnings
+    pub lint: Option<LintConfig>,
+    /// Checker run on every resolved document, reporting misspellings as warnings
+    pub spellcheck: Option<SpellcheckConfig>,
+    /// Writes `<output>.map.json` next to every Markdown output, telling where its lines come from
+    #[serde(default)]
+    pub source_map: bool,
+    /// Fails files with a failed directive or an error comment instead of writing them
+    #[serde(default)]
+    pub strict: bool,
+    /// Written in place of a failed directive, with `{path}`, `{error}` and `{code}` filled
+    /// in; an empty one writes nothing. Default: an HTML comment describing the failure
+    pub error_template: Option<String>,
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    /// Only processes the documents affected by files changed since this git revision
+    pub changed_since: Option<String>,
+    /// Glossary file whose terms are linked to their entries where they first occur in an output
+    pub glossary: Option<PathBuf>,
 }
 
 #[cfg(test)]
@@ -130,6 +1064,15 @@ mod tests {
             path: "test.md".to_string(),
             success: true,
             error_message: None,
+            error_code: None,
+            parent: "docs/index.md".to_string(),
+            depth: 0,
+            title: None,
+            values: HashMap::new(),
+            duration: None,
+            bytes: None,
+            attempts: None,
+            kind: DirectiveKind::Include,
         };
 
         assert_eq!(result.path, "test.md");
@@ -143,6 +1086,15 @@ mod tests {
             path: "missing.md".to_string(),
             success: false,
             error_message: Some("File not found".to_string()),
+            error_code: None,
+            parent: "docs/index.md".to_string(),
+            depth: 0,
+            title: None,
+            values: HashMap::new(),
+            duration: None,
+            bytes: None,
+            attempts: None,
+            kind: DirectiveKind::Include,
         };
 
         assert_eq!(result.path, "missing.md");
@@ -156,6 +1108,15 @@ mod tests {
             path: "header.md".to_string(),
             success: true,
             error_message: None,
+            error_code: None,
+            parent: "docs/index.md".to_string(),
+            depth: 0,
+            title: None,
+            values: HashMap::new(),
+            duration: None,
+            bytes: None,
+            attempts: None,
+            kind: DirectiveKind::Include,
         }];
 
         let result = FileProcessResult {
@@ -163,6 +1124,8 @@ mod tests {
             success: true,
             includes,
             error_message: None,
+            error_code: None,
+            sizes: None,
         };
 
         assert_eq!(result.file_path, "test.md");
@@ -171,6 +1134,45 @@ mod tests {
         assert!(result.error_message.is_none());
     }
 
+    #[test]
+    fn test_include_chain_follows_parents() {
+        let include = |path: &str, parent: &str, depth| IncludeResult {
+            path: path.to_string(),
+            success: true,
+            error_message: None,
+            error_code: None,
+            parent: parent.to_string(),
+            depth,
+            title: None,
+            values: HashMap::new(),
+            duration: None,
+            bytes: None,
+            attempts: None,
+            kind: DirectiveKind::Include,
+        };
+        let result = FileProcessResult {
+            file_path: "guide.md".to_string(),
+            success: true,
+            includes: vec![
+                include("header.md", "guide.md", 0),
+                include("logo.md", "header.md", 1),
+                include("intro.md", "guide.md", 0),
+                include("logo.md", "intro.md", 1),
+                include("badge.md", "logo.md", 2),
+            ],
+            error_message: None,
+            error_code: None,
+            sizes: None,
+        };
+
+        assert_eq!(result.include_chain(0), vec!["guide.md", "header.md"]);
+        assert_eq!(
+            result.include_chain(4),
+            vec!["guide.md", "intro.md", "logo.md", "badge.md"]
+        );
+        assert!(result.include_chain(5).is_empty());
+    }
+
     #[test]
     fn test_processing_summary_new() {
         let summary = ProcessingSummary::new();
@@ -199,8 +1201,19 @@ mod tests {
                 path: "header.md".to_string(),
                 success: true,
                 error_message: None,
+                error_code: None,
+                parent: "docs/index.md".to_string(),
+                depth: 0,
+                title: None,
+                values: HashMap::new(),
+                duration: None,
+                bytes: None,
+                attempts: None,
+                kind: DirectiveKind::Include,
             }],
             error_message: None,
+            error_code: None,
+            sizes: None,
         };
         summary.add_result(result1);
 
@@ -209,11 +1222,22 @@ mod tests {
             file_path: "test2.md".to_string(),
             success: false,
             includes: vec![IncludeResult {
-                path: "missing.md".to_string(),
+                path: "missing.rs".to_string(),
                 success: false,
                 error_message: Some("File not found".to_string()),
+                error_code: None,
+                parent: "docs/index.md".to_string(),
+                depth: 0,
+                title: None,
+                values: HashMap::new(),
+                duration: None,
+                bytes: None,
+                attempts: None,
+                kind: DirectiveKind::CodeSnippet,
             }],
             error_message: Some("Processing failed".to_string()),
+            error_code: None,
+            sizes: None,
         };
         summary.add_result(result2);
 
@@ -224,9 +1248,91 @@ mod tests {
         assert_eq!(summary.get_total_includes(), 2);
         assert_eq!(summary.get_successful_includes(), 1);
         assert_eq!(summary.get_failed_includes(), 1);
+        assert_eq!(
+            summary.get_directives_by_kind(),
+            vec![
+                DirectiveCount {
+                    kind: DirectiveKind::Include,
+                    total: 1,
+                    failed: 0,
+                },
+                DirectiveCount {
+                    kind: DirectiveKind::CodeSnippet,
+                    total: 1,
+                    failed: 1,
+                },
+            ]
+        );
         assert_eq!(summary.get_progress_percentage(), 66.66666666666666);
     }
 
+    #[test]
+    fn test_error_categories_group_failures_by_path() {
+        let include = |path: &str, code: Option<ErrorCode>| IncludeResult {
+            path: path.to_string(),
+            success: code.is_none(),
+            error_message: code.map(|_| "failed".to_string()),
+            error_code: code,
+            parent: "doc.md".to_string(),
+            depth: 0,
+            title: None,
+            values: HashMap::new(),
+            duration: None,
+            bytes: None,
+            attempts: None,
+            kind: DirectiveKind::Include,
+        };
+        let file =
+            |path: &str, includes: Vec<IncludeResult>, code: Option<ErrorCode>| FileProcessResult {
+                file_path: path.to_string(),
+                success: code.is_none() && includes.iter().all(|i| i.success),
+                includes,
+                error_message: None,
+                error_code: code,
+                sizes: None,
+            };
+
+        let mut summary = ProcessingSummary::new();
+        for name in ["a.md", "b.md", "c.md"] {
+            summary.add_result(file(
+                name,
+                vec![
+                    include("header.md", None),
+                    include("gone.md", Some(ErrorCode::MissingInclude)),
+                ],
+                Some(ErrorCode::MissingInclude),
+            ));
+        }
+        summary.add_result(file(
+            "d.md",
+            vec![include("old.md", Some(ErrorCode::MissingInclude))],
+            Some(ErrorCode::MissingInclude),
+        ));
+        summary.add_result(file("e.md", vec![], Some(ErrorCode::UnclosedFence)));
+        summary.add_result(file("f.md", vec![], None));
+
+        let categories: Vec<_> = summary
+            .get_error_categories()
+            .into_iter()
+            .map(|c| (c.category, c.count, c.paths))
+            .collect();
+        assert_eq!(
+            categories,
+            vec![
+                (
+                    ErrorCategory::FileNotFound,
+                    4,
+                    vec![("gone.md".to_string(), 3), ("old.md".to_string(), 1)]
+                ),
+                (
+                    ErrorCategory::FenceInvalid,
+                    1,
+                    vec![("e.md".to_string(), 1)]
+                ),
+            ]
+        );
+    }
+
     #[test]
     fn test_processing_summary_empty_progress() {
         let summary = ProcessingSummary::new();
@@ -243,6 +1349,8 @@ mod tests {
             success: true,
             includes: vec![],
             error_message: None,
+            error_code: None,
+            sizes: None,
         };
         summary.add_result(result1);
 
@@ -251,6 +1359,8 @@ mod tests {
             success: true,
             includes: vec![],
             error_message: None,
+            error_code: None,
+            sizes: None,
         };
         summary.add_result(result2);
 
@@ -266,6 +1376,36 @@ mod tests {
             batch: true,
             verbose: false,
             fix_code_fences: Some("text".to_string()),
+            variables: HashMap::new(),
+            order: FileOrder::Name,
+            post_process: None,
+            naming: OutputNaming::default(),
+            compat: Compat::Md2md,
+            passthrough: None,
+            emit: Emit::default(),
+            html: HtmlOptions::default(),
+            bundle_links: false,
+            check_links: false,
+            duplicate_headings: None,
+            line_endings: LineEndings::default(),
+            normalization: Normalization::default(),
+            source_comments: None,
+            banner: None,
+            diff: false,
+            input_encoding: InputEncoding::default(),
+            restrict_includes: false,
+            resolution_order: ResolutionOrder::default(),
+            frontmatter: Frontmatter::default(),
+            locale: None,
+            budget: OutputBudget::default(),
+            lint: None,
+            spellcheck: None,
+            source_map: false,
+            strict: false,
+            error_template: None,
+            retry: RetryPolicy::default(),
+            changed_since: None,
+            glossary: None,
         };
 
         assert_eq!(config.source_path, PathBuf::from("/source"));
@@ -0,0 +1,195 @@
+//! How often each partial is included across a run, and how often it fails
+
+use crate::types::ProcessingSummary;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Inclusions of one partial or code snippet
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct PartialUsage {
+    pub path: String,
+    /// Successful inclusions, nested ones included
+    pub count: usize,
+    /// Bytes its inclusions put in the outputs
+    pub bytes: usize,
+    /// Source documents whose output contains it
+    pub documents: BTreeSet<String>,
+}
+
+/// Usage of every partial included in `summary`, most included first
+pub fn partial_usage(summary: &ProcessingSummary) -> Vec<PartialUsage> {
+    let mut usage: BTreeMap<&str, PartialUsage> = BTreeMap::new();
+    for result in &summary.results {
+        for include in result.includes.iter().filter(|include| include.success) {
+            let entry = usage
+                .entry(include.path.as_str())
+                .or_insert_with(|| PartialUsage {
+                    path: include.path.clone(),
+                    ..PartialUsage::default()
+                });
+            entry.count += 1;
+            entry.bytes += include.bytes.unwrap_or(0);
+            entry.documents.insert(result.file_path.clone());
+        }
+    }
+    let mut usage: Vec<PartialUsage> = usage.into_values().collect();
+    // Stable, so equally used partials stay in path order
+    usage.sort_by_key(|partial| std::cmp::Reverse(partial.count));
+    usage
+}
+
+/// A bar of up to `width` blocks, as long relative to `width` as `value` is to `max`
+pub fn heat_bar(value: usize, max: usize, width: usize) -> String {
+    if max == 0 {
+        return String::new();
+    }
+    "█".repeat((value * width).div_ceil(max))
+}
+
+/// Failed inclusions of one partial or code snippet
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct PartialFailures {
+    pub path: String,
+    /// Failed inclusions, nested ones included
+    pub count: usize,
+    /// Source documents that failed because of it
+    pub documents: BTreeSet<String>,
+}
+
+/// Partials whose inclusion failed in `summary`, the one failing the most documents first
+pub fn partial_failures(summary: &ProcessingSummary) -> Vec<PartialFailures> {
+    let mut failures: BTreeMap<&str, PartialFailures> = BTreeMap::new();
+    for result in &summary.results {
+        for include in result.includes.iter().filter(|include| !include.success) {
+            let entry = failures
+                .entry(include.path.as_str())
+                .or_insert_with(|| PartialFailures {
+                    path: include.path.clone(),
+                    ..PartialFailures::default()
+                });
+            entry.count += 1;
+            entry.documents.insert(result.file_path.clone());
+        }
+    }
+    let mut failures: Vec<PartialFailures> = failures.into_values().collect();
+    failures.sort_by_key(|partial| std::cmp::Reverse((partial.documents.len(), partial.count)));
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{DirectiveKind, FileProcessResult, IncludeResult};
+    use std::collections::HashMap;
+
+    fn include(path: &str, success: bool, bytes: usize) -> IncludeResult {
+        IncludeResult {
+            path: path.to_string(),
+            success,
+            error_message: None,
+            error_code: None,
+            parent: "doc.md".to_string(),
+            depth: 0,
+            title: None,
+            values: HashMap::new(),
+            duration: None,
+            bytes: success.then_some(bytes),
+            attempts: None,
+            kind: DirectiveKind::Include,
+        }
+    }
+
+    #[test]
+    fn test_partial_usage_counts_bytes_and_documents() {
+        let mut summary = ProcessingSummary::new();
+        for (file, includes) in [
+            (
+                "a.md",
+                vec![
+                    include("header.md", true, 10),
+                    include("header.md", true, 10),
+                ],
+            ),
+            (
+                "b.md",
+                vec![
+                    include("footer.md", true, 4),
+                    include("header.md", true, 12),
+                    include("missing.md", false, 0),
+                ],
+            ),
+        ] {
+            summary.add_result(FileProcessResult {
+                file_path: file.to_string(),
+                success: true,
+                includes,
+                error_message: None,
+                error_code: None,
+                sizes: None,
+            });
+        }
+
+        let usage = partial_usage(&summary);
+        assert_eq!(
+            usage,
+            vec![
+                PartialUsage {
+                    path: "header.md".to_string(),
+                    count: 3,
+                    bytes: 32,
+                    documents: BTreeSet::from(["a.md".to_string(), "b.md".to_string()]),
+                },
+                PartialUsage {
+                    path: "footer.md".to_string(),
+                    count: 1,
+                    bytes: 4,
+                    documents: BTreeSet::from(["b.md".to_string()]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_partial_failures_rank_partials_by_failed_documents() {
+        let mut summary = ProcessingSummary::new();
+        for (file, includes) in [
+            (
+                "a.md",
+                vec![
+                    include("old.md", false, 0),
+                    include("old.md", false, 0),
+                    include("header.md", true, 10),
+                ],
+            ),
+            ("b.md", vec![include("gone.md", false, 0)]),
+            ("c.md", vec![include("gone.md", false, 0)]),
+        ] {
+            summary.add_result(FileProcessResult {
+                file_path: file.to_string(),
+                success: false,
+                includes,
+                error_message: None,
+                error_code: None,
+                sizes: None,
+            });
+        }
+
+        let failures = partial_failures(&summary);
+        assert_eq!(
+            failures,
+            vec![
+                PartialFailures {
+                    path: "gone.md".to_string(),
+                    count: 2,
+                    documents: BTreeSet::from(["b.md".to_string(), "c.md".to_string()]),
+                },
+                PartialFailures {
+                    path: "old.md".to_string(),
+                    count: 2,
+                    documents: BTreeSet::from(["a.md".to_string()]),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Checks of a [`ProcessingConfig`] before it is run, shared by the CLI and
+//! [`ProcessorBuilder`](crate::processor::ProcessorBuilder)
+
+use crate::file_handler::output_overlap;
+use crate::types::ProcessingConfig;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Why a [`ProcessingConfig`] can't be run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The builder was given no source path
+    MissingSource,
+    SourceNotFound(PathBuf),
+    /// The source path exists but is neither a file nor a directory
+    UnsupportedSource(PathBuf),
+    PartialsNotFound(PathBuf),
+    PartialsNotDirectory(PathBuf),
+    /// The output would overwrite or be read as an input
+    OutputOverlap(String),
+    /// A single source file would be written over an existing directory
+    OutputIsDirectory(PathBuf),
+    /// A source directory would be written into an existing file
+    OutputIsFile(PathBuf),
+    HtmlTemplateNotFound(PathBuf),
+    DictionaryNotFound(PathBuf),
+    /// Settings that contradict each other
+    Conflict(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingSource => write!(f, "No source path given"),
+            ConfigError::SourceNotFound(path) => {
+                write!(f, "Source path does not exist: {path:?}")
+            }
+            ConfigError::UnsupportedSource(path) => {
+                write!(f, "Input path is neither a file nor a directory: {path:?}")
+            }
+            ConfigError::PartialsNotFound(path) => {
+                write!(f, "Partials path does not exist: {path:?}")
+            }
+            ConfigError::PartialsNotDirectory(path) => {
+                write!(f, "Partials path is not a directory: {path:?}")
+            }
+            ConfigError::OutputOverlap(overlap) => write!(f, "{overlap}"),
+            ConfigError::OutputIsDirectory(path) => write!(
+                f,
+                "Input is a file, but output path appears to be a directory: {path:?}"
+            ),
+            ConfigError::OutputIsFile(path) => write!(
+                f,
+                "Input is a directory, but output path appears to be a file: {path:?}"
+            ),
+            ConfigError::HtmlTemplateNotFound(path) => {
+                write!(f, "HTML template does not exist: {path:?}")
+            }
+            ConfigError::DictionaryNotFound(path) => {
+                write!(f, "Spellcheck dictionary does not exist: {path:?}")
+            }
+            ConfigError::Conflict(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ProcessingConfig {
+    /// Checks that the inputs exist, that the output doesn't overlap them unless
+    /// nothing is written, and that the settings make sense together
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        check_inputs(&self.source_path, &self.partials_path)?;
+        if !self.diff {
+            check_output(&self.source_path, &self.partials_path, &self.output_path)?;
+        }
+        check_settings(self)
+    }
+}
+
+/// The source path is a file or directory and the partials path a directory
+pub fn check_inputs(source_path: &Path, partials_path: &Path) -> Result<(), ConfigError> {
+    if !source_path.exists() {
+        return Err(ConfigError::SourceNotFound(source_path.to_path_buf()));
+    }
+    if !source_path.is_file() && !source_path.is_dir() {
+        return Err(ConfigError::UnsupportedSource(source_path.to_path_buf()));
+    }
+    if !partials_path.exists() {
+        return Err(ConfigError::PartialsNotFound(partials_path.to_path_buf()));
+    }
+    if !partials_path.is_dir() {
+        return Err(ConfigError::PartialsNotDirectory(
+            partials_path.to_path_buf(),
+        ));
+    }
+    Ok(())
+}
+
+/// The output is of the source's kind and neither overwrites nor contains the inputs
+pub fn check_output(
+    source_path: &Path,
+    partials_path: &Path,
+    output_path: &Path,
+) -> Result<(), ConfigError> {
+    if source_path.is_file() && output_path.is_dir() {
+        return Err(ConfigError::OutputIsDirectory(output_path.to_path_buf()));
+    }
+    if source_path.is_dir() && output_path.is_file() {
+        return Err(ConfigError::OutputIsFile(output_path.to_path_buf()));
+    }
+    match output_overlap(source_path, partials_path, output_path) {
+        Some(overlap) => Err(ConfigError::OutputOverlap(overlap)),
+        None => Ok(()),
+    }
+}
+
+/// Files the settings name exist, and no setting is contradicted or made pointless by another
+pub fn check_settings(config: &ProcessingConfig) -> Result<(), ConfigError> {
+    if let Some(template) = &config.html.template
+        && !template.is_file()
+    {
+        return Err(ConfigError::HtmlTemplateNotFound(template.clone()));
+    }
+    if let Some(dictionary) = config
+        .spellcheck
+        .iter()
+        .flat_map(|spellcheck| &spellcheck.dictionaries)
+        .find(|dictionary| !dictionary.is_file())
+    {
+        return Err(ConfigError::DictionaryNotFound(dictionary.clone()));
+    }
+
+    if let Some(lint) = &config.lint
+        && lint.line_length
+        && lint.max_line_length == 0
+    {
+        return Err(ConfigError::Conflict(
+            "`max_line_length` is 0, so every line would be reported".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Settings that won't take effect, which a run warns about but still does
+pub fn settings_warnings(config: &ProcessingConfig) -> Vec<String> {
+    let mut warnings = Vec::new();
+    if !config.emit.html() && (config.html.template.is_some() || config.html.css.is_some()) {
+        warnings.push("An HTML template or stylesheet is set, but no HTML is emitted".to_string());
+    }
+    warnings
+}
+
+/// Whether `output_path` names a directory: an existing one, or one ending in a separator
+pub fn names_directory(output_path: &Path) -> bool {
+    let path = output_path.as_os_str().to_string_lossy();
+    output_path.is_dir() || path.ends_with('/') || path.ends_with('\\')
+}
+
+/// A single source file is written to a file, not a directory
+pub fn check_file_output(output_path: &Path) -> Result<(), ConfigError> {
+    if names_directory(output_path) {
+        return Err(ConfigError::OutputIsDirectory(output_path.to_path_buf()));
+    }
+    Ok(())
+}
+
+/// A source directory is written to a directory, not a path that looks like a file
+pub fn check_directory_output(output_path: &Path) -> Result<(), ConfigError> {
+    if output_path.is_file() || output_path.extension().is_some() {
+        return Err(ConfigError::OutputIsFile(output_path.to_path_buf()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint::LintConfig;
+    use crate::spellcheck::SpellcheckConfig;
+    use crate::types::Emit;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_builder_validates_the_config() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let root = temp_dir.path();
+        let docs = root.join("docs");
+        let partials = root.join("partials");
+        fs::create_dir_all(&docs).expect("Failed to create docs");
+        fs::create_dir_all(&partials).expect("Failed to create partials");
+        fs::write(docs.join("a.md"), "# A").expect("Failed to write a.md");
+        let builder = || {
+            ProcessingConfig::builder()
+                .source(&docs)
+                .partials(&partials)
+                .output(root.join("out"))
+        };
+
+        let config = builder().build_config().expect("Failed to build config");
+        assert!(config.batch);
+        assert_eq!(config.validate(), Ok(()));
+
+        let error = |builder: crate::processor::ProcessorBuilder| {
+            builder
+                .build_config()
+                .expect_err("Invalid config should be rejected")
+        };
+        assert_eq!(
+            error(ProcessingConfig::builder()),
+            ConfigError::MissingSource
+        );
+        assert_eq!(
+            error(builder().source(root.join("missing"))),
+            ConfigError::SourceNotFound(root.join("missing"))
+        );
+        assert_eq!(
+            error(builder().partials(docs.join("a.md"))),
+            ConfigError::PartialsNotDirectory(docs.join("a.md"))
+        );
+        assert_eq!(
+            error(builder().source(docs.join("a.md")).output(root)),
+            ConfigError::OutputIsDirectory(root.to_path_buf())
+        );
+        // Only warned about by the builder
+        let overlapping = builder()
+            .output(docs.join("out"))
+            .build_config()
+            .expect("Overlapping outputs should only be warned about");
+        assert!(matches!(
+            overlapping.validate(),
+            Err(ConfigError::OutputOverlap(_))
+        ));
+
+        assert_eq!(
+            error(
+                builder()
+                    .html_template(root.join("page.html"))
+                    .emit(Emit::Html)
+            ),
+            ConfigError::HtmlTemplateNotFound(root.join("page.html"))
+        );
+        let unused_css = builder()
+            .css("style.css")
+            .build_config()
+            .expect("A stylesheet without HTML should only be warned about");
+        assert_eq!(settings_warnings(&unused_css).len(), 1);
+        assert!(settings_warnings(&config).is_empty());
+        assert!(matches!(
+            error(builder().lint(LintConfig {
+                max_line_length: 0,
+                ..LintConfig::default()
+            })),
+            ConfigError::Conflict(_)
+        ));
+        assert_eq!(
+            error(builder().spellcheck(SpellcheckConfig {
+                command: "hunspell -l".to_string(),
+                dictionaries: vec![root.join("words.txt")],
+            })),
+            ConfigError::DictionaryNotFound(root.join("words.txt"))
+        );
+    }
+}
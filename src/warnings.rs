@@ -0,0 +1,595 @@
+//! Warnings about content that processed fine but deserves attention
+
+use crate::file_handler::resolve_lenient;
+use crate::include_resolver::{CodeFences, opaque_ranges};
+use crate::mdx;
+use crate::types::{Compat, ContentWarning, TemplateSyntax, WarningKind};
+use regex::Regex;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// Warnings of one document, collected by include resolution as it goes through
+/// the document and its partials; see [`crate::include_resolver::ResolveOptions::warnings`].
+///
+/// Missing or unreadable files aren't warned about; they are reported as errors by
+/// processing itself. A warning is only reported once, however often the partial it
+/// is in gets included.
+#[derive(Debug)]
+pub struct WarningCollector {
+    source: String,
+    /// Resolved, so it compares with resolved include paths
+    output_path: PathBuf,
+    /// H1s of the assembled document so far, with where each first appeared
+    titles: RefCell<HashMap<String, (PathBuf, usize)>>,
+    warnings: RefCell<Vec<ContentWarning>>,
+    /// Everything found so far in order, so a partial served from the cache can
+    /// report what was found in it again
+    findings: RefCell<Vec<Finding>>,
+    /// Partials scanned for default values, with the values they were included with
+    scanned: RefCell<HashMap<ScanKey, Vec<Finding>>>,
+}
+
+type ScanKey = (PathBuf, BTreeMap<String, String>);
+
+/// A warning or an H1, found in `file` at the 1-based `line`
+#[derive(Debug, Clone)]
+pub(crate) enum Finding {
+    Warning {
+        file: PathBuf,
+        line: usize,
+        kind: WarningKind,
+        message: String,
+    },
+    Title {
+        file: PathBuf,
+        line: usize,
+        text: String,
+    },
+}
+
+impl WarningCollector {
+    /// Collects the warnings of `source_file`, whose outputs go to `output_path`
+    pub fn new(source_file: &Path, output_path: &Path) -> Self {
+        Self {
+            source: source_file.to_string_lossy().to_string(),
+            output_path: resolve_lenient(output_path),
+            titles: RefCell::new(HashMap::new()),
+            warnings: RefCell::new(Vec::new()),
+            findings: RefCell::new(Vec::new()),
+            scanned: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// The warnings found, in the order they were
+    pub fn into_warnings(self) -> Vec<ContentWarning> {
+        self.warnings.into_inner()
+    }
+
+    /// How many findings there are so far, to get those that come after with [`Self::since`]
+    pub(crate) fn mark(&self) -> usize {
+        self.findings.borrow().len()
+    }
+
+    pub(crate) fn since(&self, mark: usize) -> Vec<Finding> {
+        self.findings.borrow()[mark..].to_vec()
+    }
+
+    /// Reports `findings` again, as found by an earlier resolution of the same partial
+    pub(crate) fn replay(&self, findings: &[Finding]) {
+        for finding in findings {
+            match finding {
+                Finding::Warning {
+                    file,
+                    line,
+                    kind,
+                    message,
+                } => self.warn(file, *line, *kind, message.clone()),
+                Finding::Title { file, line, text } => self.title(file, *line, text),
+            }
+        }
+    }
+
+    pub(crate) fn warn(&self, file: &Path, line: usize, kind: WarningKind, message: String) {
+        self.findings.borrow_mut().push(Finding::Warning {
+            file: file.to_path_buf(),
+            line,
+            kind,
+            message: message.clone(),
+        });
+        self.push(file, line, kind, message);
+    }
+
+    fn push(&self, file: &Path, line: usize, kind: WarningKind, message: String) {
+        let file = file.to_string_lossy().to_string();
+        let mut warnings = self.warnings.borrow_mut();
+        let repeated = warnings.iter().any(|warning| {
+            warning.file == file
+                && warning.line == line
+                && warning.kind == kind
+                && warning.message == message
+        });
+        if !repeated {
+            warnings.push(ContentWarning {
+                source: self.source.clone(),
+                file,
+                line,
+                kind,
+                message,
+            });
+        }
+    }
+
+    /// Opening fences without a language, which `--fix-code-fences` fills in
+    pub(crate) fn fixed_fences(&self, file: &Path, content: &str, language: &str) {
+        let mut open: Option<usize> = None;
+        for (index, line) in content.lines().enumerate() {
+            let trimmed = line.trim_start();
+            let marker = trimmed.chars().take_while(|&c| c == '`').count();
+            if marker < 3 {
+                continue;
+            }
+            let indent = line.len() - trimmed.len();
+            match open {
+                None => {
+                    if trimmed[marker..].trim().is_empty() {
+                        self.warn(
+                            file,
+                            index + 1,
+                            WarningKind::FenceFixed,
+                            format!("Code fence without a language was set to `{language}`"),
+                        );
+                    }
+                    open = Some(indent);
+                }
+                Some(open_indent) if open_indent == indent => open = None,
+                Some(_) => {}
+            }
+        }
+    }
+
+    /// `!include (…)` with a space before `(`; markedpp itself writes it that way,
+    /// so the space is only deprecated in md2md syntax
+    pub(crate) fn directive_syntax(
+        &self,
+        file: &Path,
+        line: usize,
+        directive: &str,
+        compat: Compat,
+    ) {
+        static SPACED_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r"^!include\s+\(").expect("Failed to compile spaced directive regex")
+        });
+        if compat != Compat::Markedpp && SPACED_REGEX.is_match(directive) {
+            self.warn(
+                file,
+                line,
+                WarningKind::DeprecatedSyntax,
+                format!("`{directive}` has a space before `(`; write `!include(…)`"),
+            );
+        }
+    }
+
+    /// An include of `path` in `file` that was read from `include_path`: one under
+    /// the output directory, or an untranslated one when there is a `locale`
+    pub(crate) fn include(
+        &self,
+        file: &Path,
+        line: usize,
+        path: &str,
+        include_path: &Path,
+        locale: Option<&str>,
+    ) {
+        if let Some(locale) = locale
+            && !include_path
+                .file_stem()
+                .is_some_and(|stem| stem.to_string_lossy().ends_with(&format!(".{locale}")))
+        {
+            self.warn(
+                file,
+                line,
+                WarningKind::LocaleFallback,
+                format!("`{path}` has no `{locale}` translation; included untranslated"),
+            );
+        }
+        if resolve_lenient(include_path).starts_with(&self.output_path) {
+            self.warn(
+                file,
+                line,
+                WarningKind::IncludesOutput,
+                format!(
+                    "`{path}` is in the output directory; generated content would be processed twice"
+                ),
+            );
+        }
+    }
+
+    /// Records an H1 of the assembled document, warning when an earlier one has the same text
+    pub(crate) fn title(&self, file: &Path, line: usize, text: &str) {
+        self.findings.borrow_mut().push(Finding::Title {
+            file: file.to_path_buf(),
+            line,
+            text: text.to_string(),
+        });
+        self.include_title(file, line, text);
+    }
+
+    /// Records the H1 title an include gives its partial. It isn't one of the
+    /// partial's findings, as the cache keeps the title with the resolved partial.
+    pub(crate) fn include_title(&self, file: &Path, line: usize, text: &str) {
+        let first = self.titles.borrow().get(text).cloned();
+        match first {
+            Some((first_file, first_line)) => {
+                let message = format!(
+                    "H1 `{text}` repeats the one at {}:{first_line}",
+                    first_file.display()
+                );
+                self.push(file, line, WarningKind::DuplicateTitle, message);
+            }
+            None => {
+                self.titles
+                    .borrow_mut()
+                    .insert(text.to_string(), (file.to_path_buf(), line));
+            }
+        }
+    }
+
+    /// `{% name || "default" %}` placeholders in `content`, a partial included with
+    /// `values`, whose variable is not set. A partial included again with the same
+    /// values isn't scanned again; what the first scan found is reported instead.
+    pub(crate) fn default_values(
+        &self,
+        file: &Path,
+        content: &str,
+        values: &HashMap<String, String>,
+        passthrough: Option<TemplateSyntax>,
+    ) {
+        let key = (
+            file.to_path_buf(),
+            values
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+        );
+        let scanned = self.scanned.borrow().get(&key).cloned();
+        if let Some(findings) = scanned {
+            self.replay(&findings);
+            return;
+        }
+        let mark = self.mark();
+        self.scan_default_values(file, content, values, passthrough);
+        let findings = self.since(mark);
+        self.scanned.borrow_mut().insert(key, findings);
+    }
+
+    fn scan_default_values(
+        &self,
+        file: &Path,
+        content: &str,
+        values: &HashMap<String, String>,
+        passthrough: Option<TemplateSyntax>,
+    ) {
+        static VAR_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+            Regex::new(r#"\{%\s*(\w+(?:\.\w+)*)\s*\|\|\s*"([^"]+)"\s*%\}"#)
+                .expect("Failed to compile variable regex")
+        });
+        let opaque = passthrough.map_or_else(Vec::new, |syntax| opaque_ranges(content, syntax));
+        for capture in VAR_REGEX.captures_iter(content) {
+            let placeholder = capture.get(0).expect("Failed to get full match");
+            let name = &capture[1];
+            if values.contains_key(name)
+                || opaque
+                    .iter()
+                    .any(|range| range.contains(&placeholder.start()))
+            {
+                continue;
+            }
+            self.warn(
+                file,
+                line_of(content, placeholder.start()),
+                WarningKind::DefaultValue,
+                format!("`{name}` is not set; using its default \"{}\"", &capture[2]),
+            );
+        }
+    }
+}
+
+/// Directives still in `content`, an output, outside code, with their 1-based line.
+///
+/// Resolution skips a directive it can't parse, e.g. `!include intro.md` without
+/// parentheses, so it would otherwise ship as literal text.
+pub fn find_leftover_directives(content: &str, mdx: bool) -> Vec<(usize, String)> {
+    static LEFTOVER_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"!(?:include|codesnippet|docsnippet)\b[^)\n]*\)?")
+            .expect("Failed to compile leftover directive regex")
+    });
+    let fences = CodeFences::new(content);
+    let protected = if mdx {
+        mdx::protected_ranges(content)
+    } else {
+        Vec::new()
+    };
+    LEFTOVER_REGEX
+        .find_iter(content)
+        .filter(|directive| {
+            !fences.contain(content, directive.start())
+                && !protected
+                    .iter()
+                    .any(|range| range.contains(&directive.start()))
+        })
+        .map(|directive| {
+            (
+                line_of(content, directive.start()),
+                directive.as_str().trim_end().to_string(),
+            )
+        })
+        .collect()
+}
+
+/// 1-based line of the byte `position` in `content`
+fn line_of(content: &str, position: usize) -> usize {
+    content[..position].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::include_resolver::{IncludeCache, ResolveOptions, resolve_includes};
+    use crate::partial_source::FileSystemSource;
+    use std::fs;
+    use tempfile::TempDir;
+
+    /// The warnings resolving `source_file` with `options` finds
+    fn find_warnings(
+        source_file: &Path,
+        fix_code_fences: Option<&str>,
+        output_path: &Path,
+        options: &ResolveOptions,
+    ) -> Vec<ContentWarning> {
+        let content = fs::read_to_string(source_file).expect("Failed to read source file");
+        let warnings = WarningCollector::new(source_file, output_path);
+        let options = ResolveOptions {
+            warnings: Some(&warnings),
+            ..*options
+        };
+        resolve_includes(
+            &content,
+            source_file,
+            &mut Vec::new(),
+            fix_code_fences,
+            &options,
+        )
+        .expect("Failed to resolve includes");
+        warnings.into_warnings()
+    }
+
+    #[test]
+    fn test_find_warnings_in_source_and_partials() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials).expect("Failed to create partials directory");
+        fs::write(
+            partials.join("intro.md"),
+            "Hello {% name || \"World\" %} from {% place || \"here\" %}\n\n!include(nested.md)",
+        )
+        .expect("Failed to write intro.md");
+        fs::write(partials.join("nested.md"), "Made by {% author || \"us\" %}")
+            .expect("Failed to write nested.md");
+        let source = temp_dir.path().join("doc.md");
+        fs::write(
+            &source,
+            "# Doc\n\n```\ncode\n```\n\n```\n!include (ignored.md)\n```\n\n!include (intro.md, values=[place=\"there\"])",
+        )
+        .expect("Failed to write doc.md");
+
+        let variables = HashMap::new();
+        let files = FileSystemSource::default();
+        let options = ResolveOptions::new(&partials, &variables, &files);
+        let warnings = find_warnings(
+            &source,
+            Some("text"),
+            &temp_dir.path().join("out"),
+            &options,
+        );
+        let found: Vec<_> = warnings
+            .iter()
+            .map(|w| {
+                let file = Path::new(&w.file).file_name().expect("Missing file name");
+                (w.kind, file.to_string_lossy().to_string(), w.line)
+            })
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                (WarningKind::FenceFixed, "doc.md".to_string(), 3),
+                (WarningKind::FenceFixed, "doc.md".to_string(), 7),
+                (WarningKind::DeprecatedSyntax, "doc.md".to_string(), 11),
+                (WarningKind::DefaultValue, "intro.md".to_string(), 1),
+                // Inherits `place` from the include of intro.md, so its variables are filled in
+                (WarningKind::DefaultValue, "nested.md".to_string(), 1),
+            ]
+        );
+        assert!(warnings[3].message.contains("`name`"));
+        assert!(
+            warnings
+                .iter()
+                .all(|w| w.source == source.to_string_lossy())
+        );
+    }
+
+    #[test]
+    fn test_find_leftover_directives_outside_code() {
+        let content = "# Doc\n\n!include intro.md\n\n```\n!include(example.md)\n```\n\nUse `!codesnippet(x.rs)` or !codesnippet(x.rs lang=\"rust\")\n\nThe !includes list";
+        assert_eq!(
+            find_leftover_directives(content, false),
+            vec![
+                (3, "!include intro.md".to_string()),
+                (9, "!codesnippet(x.rs lang=\"rust\")".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_warnings_for_includes_of_outputs() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials = temp_dir.path().join("partials");
+        let output = temp_dir.path().join("out");
+        fs::create_dir_all(&partials).expect("Failed to create partials directory");
+        fs::create_dir_all(&output).expect("Failed to create output directory");
+        fs::write(partials.join("intro.md"), "Intro").expect("Failed to write intro.md");
+        fs::write(output.join("doc.md"), "Generated").expect("Failed to write output");
+        let source = temp_dir.path().join("doc.md");
+        fs::write(&source, "!include(intro.md)\n\n!include(./out/doc.md)")
+            .expect("Failed to write doc.md");
+
+        let variables = HashMap::new();
+        let files = FileSystemSource::default();
+        let options = ResolveOptions::new(&partials, &variables, &files);
+        let warnings = find_warnings(&source, None, &output, &options);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::IncludesOutput);
+        assert_eq!(warnings[0].line, 3);
+        assert!(warnings[0].message.contains("`./out/doc.md`"));
+    }
+
+    #[test]
+    fn test_find_warnings_for_duplicate_titles() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials).expect("Failed to create partials directory");
+        fs::write(partials.join("setup.md"), "# Setup\n\nRun it.")
+            .expect("Failed to write setup.md");
+        fs::write(partials.join("product.md"), "# {% product %}")
+            .expect("Failed to write product.md");
+        fs::write(partials.join("intro.md"), "Hi").expect("Failed to write intro.md");
+        let source = temp_dir.path().join("doc.md");
+        fs::write(
+            &source,
+            "# Guide\n\n!include(setup.md)\n\n!include(product.md, values=[product=\"A\"])\n\n!include(product.md, values=[product=\"B\"])\n\n!include(setup.md)\n\n!include(intro.md, title=\"Guide\")",
+        )
+        .expect("Failed to write doc.md");
+
+        let variables = HashMap::new();
+        let files = FileSystemSource::default();
+        let options = ResolveOptions::new(&partials, &variables, &files);
+        let warnings = find_warnings(&source, None, &temp_dir.path().join("out"), &options);
+
+        let found: Vec<(WarningKind, String, usize)> = warnings
+            .iter()
+            .map(|w| {
+                let file = Path::new(&w.file)
+                    .file_name()
+                    .expect("Failed to get file name")
+                    .to_string_lossy()
+                    .to_string();
+                (w.kind, file, w.line)
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (WarningKind::DuplicateTitle, "setup.md".to_string(), 1),
+                (WarningKind::DuplicateTitle, "doc.md".to_string(), 11),
+            ]
+        );
+        assert!(
+            warnings[0]
+                .message
+                .starts_with("H1 `Setup` repeats the one at")
+        );
+        assert!(warnings[0].message.ends_with("setup.md:1"));
+    }
+
+    #[test]
+    fn test_find_warnings_for_locale_fallbacks() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials).expect("Failed to create partials directory");
+        fs::write(partials.join("header.md"), "Welcome").expect("Failed to write header.md");
+        fs::write(partials.join("header.de.md"), "Willkommen")
+            .expect("Failed to write header.de.md");
+        fs::write(partials.join("footer.md"), "Goodbye").expect("Failed to write footer.md");
+        let source = temp_dir.path().join("doc.md");
+        fs::write(&source, "!include(header.md)\n\n!include(footer.md)")
+            .expect("Failed to write doc.md");
+
+        let variables = HashMap::new();
+        let files = FileSystemSource::default();
+        let options = ResolveOptions {
+            locale: Some("de"),
+            ..ResolveOptions::new(&partials, &variables, &files)
+        };
+        let warnings = find_warnings(&source, None, &temp_dir.path().join("out"), &options);
+
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::LocaleFallback);
+        assert_eq!(warnings[0].line, 3);
+        assert!(
+            warnings[0]
+                .message
+                .contains("`footer.md` has no `de` translation")
+        );
+    }
+
+    #[test]
+    fn test_find_warnings_of_partials_served_from_the_cache() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let partials = temp_dir.path().join("partials");
+        fs::create_dir_all(&partials).expect("Failed to create partials directory");
+        fs::write(
+            partials.join("setup.md"),
+            "# Setup\n\nBy {% author || \"us\" %}",
+        )
+        .expect("Failed to write setup.md");
+        let first = temp_dir.path().join("first.md");
+        let second = temp_dir.path().join("second.md");
+        fs::write(&first, "!include(setup.md)").expect("Failed to write first.md");
+        fs::write(&second, "# Setup\n\n!include(setup.md)").expect("Failed to write second.md");
+
+        let files = FileSystemSource::default();
+        let variables = HashMap::from([("product".to_string(), "md2md".to_string())]);
+        let cache = IncludeCache::new();
+        let options = ResolveOptions {
+            cache: Some(&cache),
+            ..ResolveOptions::new(&partials, &variables, &files)
+        };
+        let output = temp_dir.path().join("out");
+        let first_warnings = find_warnings(&first, None, &output, &options);
+        let second_warnings = find_warnings(&second, None, &output, &options);
+
+        assert_eq!(cache.hits(), 1);
+        let found = |warnings: &[ContentWarning]| -> Vec<(WarningKind, usize)> {
+            warnings.iter().map(|w| (w.kind, w.line)).collect()
+        };
+        assert_eq!(found(&first_warnings), vec![(WarningKind::DefaultValue, 3)]);
+        // The partial's warnings and titles come with it from the cache
+        assert_eq!(
+            found(&second_warnings),
+            vec![
+                (WarningKind::DefaultValue, 3),
+                (WarningKind::DuplicateTitle, 1)
+            ]
+        );
+        assert!(second_warnings[1].file.ends_with("setup.md"));
+    }
+
+    #[test]
+    fn test_partials_are_scanned_once_per_values() {
+        let warnings = WarningCollector::new(Path::new("doc.md"), Path::new("out"));
+        let file = Path::new("partials/intro.md");
+        let content = "Hi {% name || \"you\" %}";
+        let values = HashMap::from([("place".to_string(), "here".to_string())]);
+        for _ in 0..3 {
+            warnings.default_values(file, content, &values, None);
+        }
+        warnings.default_values(file, content, &HashMap::new(), None);
+
+        assert_eq!(warnings.scanned.borrow().len(), 2);
+        // Findings are logged for every include, so cached partials carry them
+        assert_eq!(warnings.mark(), 4);
+        assert_eq!(warnings.into_warnings().len(), 1);
+    }
+}
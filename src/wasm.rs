@@ -0,0 +1,22 @@
+//! JavaScript bindings, built with `--features wasm --target wasm32-unknown-unknown`.
+//!
+//! Partials are passed in from JavaScript, so nothing touches the filesystem.
+
+use crate::partial_source::MemorySource;
+use crate::processor::process_string;
+use std::collections::HashMap;
+use wasm_bindgen::prelude::*;
+
+/// Resolves every directive in `content`.
+///
+/// `partials_json` is a JSON object mapping partial and code snippet paths to
+/// their contents, e.g. `{"header.md": "# Hello"}`. Returns the processed
+/// content and the outcome of each directive as JSON:
+/// `{"content": "...", "includes": [{"path": "...", "success": true, "error_message": null}]}`
+#[wasm_bindgen(js_name = processMarkdown)]
+pub fn process_markdown(content: &str, partials_json: &str) -> Result<String, JsError> {
+    let partials: HashMap<String, String> = serde_json::from_str(partials_json)?;
+    let result = process_string(content, &MemorySource::from(partials))
+        .map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(serde_json::to_string(&result)?)
+}
@@ -0,0 +1,283 @@
+use crate::file_handler::collect_markdown_files;
+use crate::graph::{DependencyGraph, normalize_path};
+use crate::processor::reprocess_file;
+use crate::types::{ProcessingConfig, ProcessingEvent, ProcessingSummary};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, SystemTime};
+
+/// How often watch mode looks for changed files
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Modification time and size of a watched file
+type Stamp = (Option<SystemTime>, u64);
+
+/// Source files processed again after watched files changed
+#[derive(Debug)]
+pub struct WatchRun {
+    /// 1-based; the run before watching started is run 0
+    pub run: usize,
+    /// Files added, modified or removed since the previous poll
+    pub changed: Vec<String>,
+    pub summary: ProcessingSummary,
+}
+
+/// Polls the files a run of `config` reads and processes the affected source files again.
+///
+/// Watched are the markdown files under the source and partials paths plus every
+/// partial and code snippet they reference; outputs are never watched, so writing
+/// them doesn't trigger another run.
+#[derive(Debug)]
+pub struct Watcher {
+    config: ProcessingConfig,
+    graph: DependencyGraph,
+    stamps: HashMap<PathBuf, Stamp>,
+    runs: usize,
+}
+
+impl Watcher {
+    /// Starts watching from the current state of the files
+    pub fn new(config: &ProcessingConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let graph = DependencyGraph::build(
+            &config.source_path,
+            &config.partials_path,
+            config.input_encoding,
+        )?;
+        let stamps = stamps(config, &graph);
+        Ok(Self {
+            config: config.clone(),
+            graph,
+            stamps,
+            runs: 0,
+        })
+    }
+
+    /// Processes the source files affected by changes since the last poll, if any.
+    ///
+    /// Every poll that finds changes is a run, even when no source file depends on
+    /// the changed files. A [`ProcessingEvent::FilesChanged`] is sent before the
+    /// files are processed and a [`ProcessingEvent::Rerun`] after.
+    pub fn poll(
+        &mut self,
+        events: Option<&Sender<ProcessingEvent>>,
+    ) -> Result<Option<WatchRun>, Box<dyn std::error::Error>> {
+        let current = stamps(&self.config, &self.graph);
+        let mut changed: Vec<PathBuf> = current
+            .iter()
+            .filter(|(path, stamp)| self.stamps.get(*path) != Some(stamp))
+            .map(|(path, _)| path.clone())
+            .chain(
+                self.stamps
+                    .keys()
+                    .filter(|path| !current.contains_key(*path))
+                    .cloned(),
+            )
+            .collect();
+        if changed.is_empty() {
+            return Ok(None);
+        }
+        changed.sort();
+
+        // The old graph knows what depended on removed files, the new one what
+        // depends on added files
+        let graph = DependencyGraph::build(
+            &self.config.source_path,
+            &self.config.partials_path,
+            self.config.input_encoding,
+        )?;
+        let affected: BTreeSet<String> = changed
+            .iter()
+            .flat_map(|path| {
+                self.graph
+                    .affected_sources(path)
+                    .into_iter()
+                    .chain(graph.affected_sources(path))
+            })
+            .collect();
+        let files: Vec<PathBuf> = collect_markdown_files(&self.config.source_path)?
+            .into_iter()
+            .filter(|file| affected.contains(&display(&normalize_path(file))))
+            .collect();
+        self.stamps = stamps(&self.config, &graph);
+        self.graph = graph;
+        self.runs += 1;
+
+        let run = self.runs;
+        let changed: Vec<String> = changed.iter().map(|path| display(path)).collect();
+        send(
+            events,
+            ProcessingEvent::FilesChanged {
+                run,
+                paths: changed.clone(),
+                affected: files.iter().map(|file| display(file)).collect(),
+            },
+        );
+
+        let mut summary = ProcessingSummary::new();
+        for file in &files {
+            match reprocess_file(&self.config, file) {
+                Ok(rerun) => summary.replace_results(rerun),
+                Err(e) => {
+                    tracing::warn!(file = %file.display(), error = %e, "Failed to process file again");
+                    send(
+                        events,
+                        ProcessingEvent::Warning(format!(
+                            "Failed to process {} again: {e}",
+                            file.display()
+                        )),
+                    );
+                }
+            }
+        }
+        send(
+            events,
+            ProcessingEvent::Rerun {
+                run,
+                results: summary.results.clone(),
+                diffs: summary.diffs.clone(),
+                warnings: summary.warnings.clone(),
+            },
+        );
+
+        Ok(Some(WatchRun {
+            run,
+            changed,
+            summary,
+        }))
+    }
+}
+
+/// Stamps of the watched files that exist, keyed by normalized path
+fn stamps(config: &ProcessingConfig, graph: &DependencyGraph) -> HashMap<PathBuf, Stamp> {
+    let output = normalize_path(&config.output_path);
+    collect_markdown_files(&config.source_path)
+        .unwrap_or_default()
+        .into_iter()
+        .chain(collect_markdown_files(&config.partials_path).unwrap_or_default())
+        .map(|path| normalize_path(&path))
+        .chain(graph.nodes().into_iter().map(PathBuf::from))
+        .filter(|path| !path.starts_with(&output))
+        .filter_map(|path| {
+            let metadata = fs::metadata(&path).ok()?;
+            Some((path, (metadata.modified().ok(), metadata.len())))
+        })
+        .collect()
+}
+
+fn display(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+fn send(events: Option<&Sender<ProcessingEvent>>, event: ProcessingEvent) {
+    if let Some(sender) = events {
+        let _ = sender.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::processor::Processor;
+    use std::sync::mpsc;
+    use tempfile::TempDir;
+
+    fn watched_project(temp_dir: &TempDir) -> ProcessingConfig {
+        let source = temp_dir.path().join("docs");
+        let partials = temp_dir.path().join("partials");
+        fs::create_dir_all(&source).expect("Failed to create source dir");
+        fs::create_dir_all(&partials).expect("Failed to create partials dir");
+        fs::write(source.join("a.md"), "# A\n!include (note.md)\n").expect("Failed to write a.md");
+        fs::write(source.join("b.md"), "# B\n").expect("Failed to write b.md");
+        fs::write(partials.join("note.md"), "Note").expect("Failed to write note.md");
+        fs::write(partials.join("unused.md"), "Unused").expect("Failed to write unused.md");
+
+        // Outputs inside the source must not trigger runs of their own
+        let processor = Processor::builder()
+            .source(source)
+            .partials(partials)
+            .output(temp_dir.path().join("docs/out"))
+            .build()
+            .expect("Failed to build processor");
+        processor.run().expect("Failed to process files");
+        processor.config().clone()
+    }
+
+    #[test]
+    fn test_poll_processes_sources_affected_by_a_partial() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let config = watched_project(&temp_dir);
+        let mut watcher = Watcher::new(&config).expect("Failed to start watching");
+        assert!(watcher.poll(None).expect("Failed to poll").is_none());
+
+        fs::write(config.partials_path.join("note.md"), "Changed note")
+            .expect("Failed to change note.md");
+        let (sender, receiver) = mpsc::channel();
+        let run = watcher
+            .poll(Some(&sender))
+            .expect("Failed to poll")
+            .expect("Change not detected");
+
+        assert_eq!(run.run, 1);
+        assert_eq!(run.changed.len(), 1);
+        assert!(run.changed[0].ends_with("note.md"));
+        assert_eq!(run.summary.results.len(), 1);
+        assert!(run.summary.results[0].file_path.ends_with("a.md"));
+        let output =
+            fs::read_to_string(config.output_path.join("a.md")).expect("Failed to read output");
+        assert!(output.contains("Changed note"));
+
+        let events: Vec<_> = receiver.try_iter().collect();
+        assert!(matches!(
+            &events[..],
+            [
+                ProcessingEvent::FilesChanged { run: 1, affected, .. },
+                ProcessingEvent::Rerun { run: 1, results, .. },
+            ] if affected.len() == 1 && results.len() == 1
+        ));
+
+        // Writing the output didn't count as a change
+        assert!(watcher.poll(None).expect("Failed to poll").is_none());
+    }
+
+    #[test]
+    fn test_poll_counts_changes_no_source_depends_on() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let config = watched_project(&temp_dir);
+        let mut watcher = Watcher::new(&config).expect("Failed to start watching");
+
+        fs::write(config.partials_path.join("unused.md"), "Still unused")
+            .expect("Failed to change unused.md");
+        let run = watcher
+            .poll(None)
+            .expect("Failed to poll")
+            .expect("Change not detected");
+        assert_eq!(run.run, 1);
+        assert!(run.summary.results.is_empty());
+    }
+
+    #[test]
+    fn test_poll_processes_added_sources_and_removed_partials() {
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let config = watched_project(&temp_dir);
+        let mut watcher = Watcher::new(&config).expect("Failed to start watching");
+
+        fs::write(config.source_path.join("c.md"), "# C\n").expect("Failed to write c.md");
+        let run = watcher
+            .poll(None)
+            .expect("Failed to poll")
+            .expect("Added file not detected");
+        assert_eq!(run.summary.results.len(), 1);
+        assert!(run.summary.results[0].file_path.ends_with("c.md"));
+
+        fs::remove_file(config.partials_path.join("note.md")).expect("Failed to remove note.md");
+        let run = watcher
+            .poll(None)
+            .expect("Failed to poll")
+            .expect("Removed file not detected");
+        assert_eq!(run.run, 2);
+        assert_eq!(run.summary.results.len(), 1);
+        assert!(!run.summary.results[0].success);
+    }
+}